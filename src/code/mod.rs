@@ -38,8 +38,35 @@ pub enum OpCode {
     Closure,
     GetFree,
     CurrentClosure,
-    And,
-    Or,
+    GreaterEqual,
+    SetFree,
+    GetLocalCell,
+    GetFreeCell,
+    IterNext,
+    DefineLocal,
+    DefineGlobal,
+    GetGlobalCell,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Power,
+    SetIndex,
+    Slice,
+    Range,
+    Match,
+    CallSpread,
+    Destructure,
+    Template,
+    CallNamed,
+    // records the stack's height as a loop starts, for `LoopUnwind`
+    LoopEnter,
+    // drops what was pushed since the innermost loop started, so that a
+    // `break` or `continue` inside an expression leaves nothing behind
+    LoopUnwind,
+    // forgets the innermost loop, as it has ended
+    LoopExit,
 }
 
 impl Display for OpCode {
@@ -78,8 +105,31 @@ impl Display for OpCode {
                 OpCode::Closure => "OpClosure",
                 OpCode::GetFree => "OpGetFree",
                 OpCode::CurrentClosure => "OpCurrentClosure",
-                OpCode::And => "OpAnd",
-                OpCode::Or => "OpOr",
+                OpCode::GreaterEqual => "OpGreaterEqual",
+                OpCode::SetFree => "OpSetFree",
+                OpCode::GetLocalCell => "OpGetLocalCell",
+                OpCode::GetFreeCell => "OpGetFreeCell",
+                OpCode::IterNext => "OpIterNext",
+                OpCode::DefineLocal => "OpDefineLocal",
+                OpCode::DefineGlobal => "OpDefineGlobal",
+                OpCode::GetGlobalCell => "OpGetGlobalCell",
+                OpCode::BitAnd => "OpBitAnd",
+                OpCode::BitOr => "OpBitOr",
+                OpCode::BitXor => "OpBitXor",
+                OpCode::ShiftLeft => "OpShiftLeft",
+                OpCode::ShiftRight => "OpShiftRight",
+                OpCode::Power => "OpPower",
+                OpCode::SetIndex => "OpSetIndex",
+                OpCode::Slice => "OpSlice",
+                OpCode::Range => "OpRange",
+                OpCode::Match => "OpMatch",
+                OpCode::CallSpread => "OpCallSpread",
+                OpCode::Destructure => "OpDestructure",
+                OpCode::Template => "OpTemplate",
+                OpCode::CallNamed => "OpCallNamed",
+                OpCode::LoopEnter => "OpLoopEnter",
+                OpCode::LoopUnwind => "OpLoopUnwind",
+                OpCode::LoopExit => "OpLoopExit",
             }
         )
     }
@@ -120,8 +170,31 @@ impl TryFrom<u8> for OpCode {
             0x1b => Ok(OpCode::Closure),
             0x1c => Ok(OpCode::GetFree),
             0x1d => Ok(OpCode::CurrentClosure),
-            0x1e => Ok(OpCode::And),
-            0x1f => Ok(OpCode::Or),
+            0x20 => Ok(OpCode::GreaterEqual),
+            0x21 => Ok(OpCode::SetFree),
+            0x22 => Ok(OpCode::GetLocalCell),
+            0x23 => Ok(OpCode::GetFreeCell),
+            0x24 => Ok(OpCode::IterNext),
+            0x25 => Ok(OpCode::DefineLocal),
+            0x26 => Ok(OpCode::DefineGlobal),
+            0x27 => Ok(OpCode::GetGlobalCell),
+            0x28 => Ok(OpCode::BitAnd),
+            0x29 => Ok(OpCode::BitOr),
+            0x2a => Ok(OpCode::BitXor),
+            0x2b => Ok(OpCode::ShiftLeft),
+            0x2c => Ok(OpCode::ShiftRight),
+            0x2d => Ok(OpCode::Power),
+            0x2e => Ok(OpCode::SetIndex),
+            0x2f => Ok(OpCode::Slice),
+            0x30 => Ok(OpCode::Range),
+            0x31 => Ok(OpCode::Match),
+            0x32 => Ok(OpCode::CallSpread),
+            0x33 => Ok(OpCode::Destructure),
+            0x34 => Ok(OpCode::Template),
+            0x35 => Ok(OpCode::CallNamed),
+            0x36 => Ok(OpCode::LoopEnter),
+            0x37 => Ok(OpCode::LoopUnwind),
+            0x38 => Ok(OpCode::LoopExit),
             _ => Err("Invalid OpCode"),
         }
     }
@@ -160,8 +233,31 @@ impl From<OpCode> for u8 {
             OpCode::Closure => 0x1b,
             OpCode::GetFree => 0x1c,
             OpCode::CurrentClosure => 0x1d,
-            OpCode::And => 0x1e,
-            OpCode::Or => 0x1f,
+            OpCode::GreaterEqual => 0x20,
+            OpCode::SetFree => 0x21,
+            OpCode::GetLocalCell => 0x22,
+            OpCode::GetFreeCell => 0x23,
+            OpCode::IterNext => 0x24,
+            OpCode::DefineLocal => 0x25,
+            OpCode::DefineGlobal => 0x26,
+            OpCode::GetGlobalCell => 0x27,
+            OpCode::BitAnd => 0x28,
+            OpCode::BitOr => 0x29,
+            OpCode::BitXor => 0x2a,
+            OpCode::ShiftLeft => 0x2b,
+            OpCode::ShiftRight => 0x2c,
+            OpCode::Power => 0x2d,
+            OpCode::SetIndex => 0x2e,
+            OpCode::Slice => 0x2f,
+            OpCode::Range => 0x30,
+            OpCode::Match => 0x31,
+            OpCode::CallSpread => 0x32,
+            OpCode::Destructure => 0x33,
+            OpCode::Template => 0x34,
+            OpCode::CallNamed => 0x35,
+            OpCode::LoopEnter => 0x36,
+            OpCode::LoopUnwind => 0x37,
+            OpCode::LoopExit => 0x38,
         }
     }
 }
@@ -169,7 +265,7 @@ impl From<OpCode> for u8 {
 pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
     let mut instruction = [0x00; 4];
     match op {
-        OpCode::Closure => {
+        OpCode::Closure | OpCode::Destructure | OpCode::CallNamed => {
             instruction[0] = u8::from(op);
             let operand = (operands[0] as u16).to_be_bytes();
             instruction[1] = operand[0];
@@ -179,8 +275,13 @@ pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
         OpCode::SetLocal
         | OpCode::GetLocal
         | OpCode::Call
+        | OpCode::CallSpread
         | OpCode::GetBuiltin
-        | OpCode::GetFree => {
+        | OpCode::GetFree
+        | OpCode::SetFree
+        | OpCode::GetLocalCell
+        | OpCode::GetFreeCell
+        | OpCode::DefineLocal => {
             instruction[0] = u8::from(op);
             instruction[1] = operands[0] as u8;
         }
@@ -190,7 +291,11 @@ pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
         | OpCode::SetGlobal
         | OpCode::GetGlobal
         | OpCode::Array
-        | OpCode::Hash => {
+        | OpCode::Template
+        | OpCode::Hash
+        | OpCode::IterNext
+        | OpCode::DefineGlobal
+        | OpCode::GetGlobalCell => {
             instruction[0] = u8::from(op);
             let operand = (operands[0] as u16).to_be_bytes();
             instruction[1] = operand[0];
@@ -213,8 +318,20 @@ pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
         | OpCode::ReturnValue
         | OpCode::Return
         | OpCode::CurrentClosure
-        | OpCode::And
-        | OpCode::Or => {
+        | OpCode::GreaterEqual
+        | OpCode::BitAnd
+        | OpCode::BitOr
+        | OpCode::BitXor
+        | OpCode::ShiftLeft
+        | OpCode::ShiftRight
+        | OpCode::Power
+        | OpCode::SetIndex
+        | OpCode::Slice
+        | OpCode::Range
+        | OpCode::Match
+        | OpCode::LoopEnter
+        | OpCode::LoopUnwind
+        | OpCode::LoopExit => {
             instruction[0] = u8::from(op);
         }
     }
@@ -228,7 +345,7 @@ pub fn disassemble(instructions: &Instructions) -> String {
     instructions.chunks_exact(WORD_SIZE).for_each(|word| {
         let op: OpCode = OpCode::try_from(word[0]).expect("Invalid OpCode");
         match op {
-            OpCode::Closure => {
+            OpCode::Closure | OpCode::Destructure | OpCode::CallNamed => {
                 let operand = read_u16(&word[1..=2]);
                 assembly.push_str(&format!(
                     "{:04x} {} {} {}\n",
@@ -238,8 +355,13 @@ pub fn disassemble(instructions: &Instructions) -> String {
             OpCode::SetLocal
             | OpCode::GetLocal
             | OpCode::Call
+            | OpCode::CallSpread
             | OpCode::GetBuiltin
-            | OpCode::GetFree => {
+            | OpCode::GetFree
+            | OpCode::SetFree
+            | OpCode::GetLocalCell
+            | OpCode::GetFreeCell
+            | OpCode::DefineLocal => {
                 assembly.push_str(&format!("{:04x} {} {}\n", address, op, &word[1]))
             }
             OpCode::Constant
@@ -248,7 +370,11 @@ pub fn disassemble(instructions: &Instructions) -> String {
             | OpCode::SetGlobal
             | OpCode::GetGlobal
             | OpCode::Array
-            | OpCode::Hash => {
+            | OpCode::Template
+            | OpCode::Hash
+            | OpCode::IterNext
+            | OpCode::DefineGlobal
+            | OpCode::GetGlobalCell => {
                 let operand = read_u16(&word[1..=2]);
                 assembly.push_str(&format!("{:04x} {} {}\n", address, op, operand))
             }
@@ -269,8 +395,20 @@ pub fn disassemble(instructions: &Instructions) -> String {
             | OpCode::ReturnValue
             | OpCode::Return
             | OpCode::CurrentClosure
-            | OpCode::And
-            | OpCode::Or => assembly.push_str(&format!("{:04x} {}\n", address, op)),
+            | OpCode::GreaterEqual
+            | OpCode::BitAnd
+            | OpCode::BitOr
+            | OpCode::BitXor
+            | OpCode::ShiftLeft
+            | OpCode::ShiftRight
+            | OpCode::Power
+            | OpCode::SetIndex
+            | OpCode::Slice
+            | OpCode::Range
+            | OpCode::Match
+            | OpCode::LoopEnter
+            | OpCode::LoopUnwind
+            | OpCode::LoopExit => assembly.push_str(&format!("{:04x} {}\n", address, op)),
         }
         address += 4;
     });
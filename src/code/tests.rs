@@ -90,6 +90,14 @@ fn test_make_op_greater_than() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_make_op_greater_equal() {
+    let (op, operands) = (OpCode::GreaterEqual, []);
+    let expected: [u8; 4] = [0x20, 0x00, 0x00, 0x00];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn test_make_op_minus() {
     let (op, operands) = (OpCode::Minus, []);
@@ -242,22 +250,6 @@ fn test_make_op_current_closure() {
     assert_eq!(result, expected);
 }
 
-#[test]
-fn test_make_op_and() {
-    let (op, operands) = (OpCode::And, []);
-    let expected: [u8; 4] = [0x1e, 0x00, 0x00, 0x00];
-    let result = make(op, &operands);
-    assert_eq!(result, expected);
-}
-
-#[test]
-fn test_make_op_or() {
-    let (op, operands) = (OpCode::Or, []);
-    let expected: [u8; 4] = [0x1f, 0x00, 0x00, 0x00];
-    let result = make(op, &operands);
-    assert_eq!(result, expected);
-}
-
 #[test]
 fn test_disassemble() {
     let input: Instructions = vec![
@@ -269,7 +261,7 @@ fn test_disassemble() {
         0x00, 0x12, 0xFF, 0xFE, 0x00, 0x13, 0xFF, 0xFE, 0x00, 0x14, 0x00, 0x00, 0x00, 0x15, 0xFE,
         0x00, 0x00, 0x16, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x00, 0x18, 0xFE, 0x00, 0x00, 0x19,
         0xFE, 0x00, 0x00, 0x1a, 0xFE, 0x00, 0x00, 0x1b, 0xFF, 0xFE, 0xFE, 0x1c, 0xFE, 0x00, 0x00,
-        0x1d, 0x00, 0x00, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
+        0x1d, 0x00, 0x00, 0x00,
     ];
     let expected = String::from(
         "\
@@ -304,8 +296,6 @@ fn test_disassemble() {
 0070 OpClosure 65534 254\n\
 0074 OpGetFree 254\n\
 0078 OpCurrentClosure\n\
-007c OpAnd\n\
-0080 OpOr\n\
 ",
     );
     let result = disassemble(&input);
@@ -1,7 +1,12 @@
-use crate::code::{make, Instructions, OpCode, WORD_SIZE};
+use crate::code::{make, read_u16, Instructions, OpCode, WORD_SIZE};
+use crate::evaluator::{module, EvalError};
 use crate::object::{CompiledFunction, Object};
-use crate::parser::ast::{Expression, Infix, Prefix, Program, Statement};
+use crate::parser::ast::{CallArg, Expression, Infix, Prefix, Program, Statement, TemplatePart};
 use crate::symtab::{Symbol, SymbolScope, SymbolTable};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 mod tests;
@@ -15,6 +20,38 @@ pub struct Compiler {
     pub symbol_table: SymbolTable,
     scopes: Vec<Instructions>,
     scope_idx: usize,
+    // the loops enclosing the code being compiled, innermost last
+    loops: Vec<LoopJumps>,
+    // top-level names that will be bound later on, see `compile_hoisted`
+    pending: HashSet<String>,
+    // the constant of every function compiled so far, keyed by its source and
+    // what its names resolved to; kept across REPL lines like the constants
+    pub function_cache: HashMap<String, u32>,
+    // every symbol resolved so far, in order, for the keys of `function_cache`
+    resolved: Vec<Rc<Symbol>>,
+    // every module imported so far, by canonical path; kept across REPL lines
+    // like the constants, as its bindings live on in the globals
+    pub modules: HashMap<PathBuf, Module>,
+    // the names exported so far by the module being compiled
+    exports: Vec<(String, Rc<Symbol>)>,
+}
+
+// a module compiled by its first import, see `compile_use`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    // the constant of the function running the module's code
+    address: u32,
+    // the names the module exports, with the global slots holding them
+    exports: Vec<(String, Rc<Symbol>)>,
+}
+
+// jumps out of a loop's body, patched once their targets are known
+#[derive(Debug, Default, PartialEq)]
+struct LoopJumps {
+    breaks: Vec<u32>,
+    continues: Vec<u32>,
+    // how many values the loop keeps on the stack, which `break` has to pop
+    stack_values: usize,
 }
 
 impl Compiler {
@@ -26,6 +63,12 @@ impl Compiler {
             symbol_table,
             scopes: vec![Instructions::new()],
             scope_idx: 0,
+            loops: vec![],
+            pending: HashSet::new(),
+            function_cache: HashMap::new(),
+            resolved: vec![],
+            modules: HashMap::new(),
+            exports: vec![],
         }
     }
 
@@ -45,6 +88,33 @@ impl Compiler {
         ))
     }
 
+    // like `compile`, but declares every top-level name first, so that
+    // functions can refer to functions and values defined further down; the
+    // statements still run in order, so a function can only be called once
+    // its `let` has run
+    pub fn compile_hoisted(&mut self, program: Program) -> Result<ByteCode, CompilerError> {
+        self.hoist(&program);
+        self.compile(program)
+    }
+
+    // declares the names bound by a program's top-level statements, which
+    // stay pending until their `let` is compiled
+    fn hoist(&mut self, program: &Program) {
+        let Program(statements) = program;
+        for statement in statements {
+            let Some(name) = statement.let_name() else {
+                continue;
+            };
+            if self.symbol_table.resolve(name.to_string()).is_none() {
+                self.pending.insert(name.to_string());
+                match statement.unexported() {
+                    Statement::Const(..) => self.symbol_table.define_constant(name.to_string()),
+                    _ => self.symbol_table.define(name.to_string()),
+                };
+            }
+        }
+    }
+
     fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompilerError> {
         for statement in statements.iter() {
             self.compile_statement(statement)?;
@@ -54,7 +124,11 @@ impl Compiler {
 
     fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompilerError> {
         match statement {
-            Statement::Let(id, val) => self.compile_let(id, val)?,
+            Statement::Let(id, val) => self.compile_let(id, val, false)?,
+            Statement::Const(id, val) => self.compile_let(id, val, true)?,
+            Statement::LetDestructure(names, rest, val) => {
+                self.compile_let_destructure(names, rest.as_ref(), val)?
+            }
             Statement::Return(val) => {
                 self.compile_expression(val)?;
                 self.emit(OpCode::ReturnValue, &[]);
@@ -63,20 +137,223 @@ impl Compiler {
                 self.compile_expression(expression)?;
                 self.emit(OpCode::Pop, &[]);
             }
-            Statement::BlockStatement(statements) => self.compile_block_statement(statements)?,
-            Statement::Assignment(_, _) => todo!(),
+            Statement::Block(statements) => self.compile_block_statement(statements)?,
+            Statement::Assignment(id, val) => self.compile_assignment(id, val)?,
+            Statement::IndexAssignment(collection, index, val) => {
+                self.compile_expression(collection)?;
+                self.compile_expression(index)?;
+                self.compile_expression(val)?;
+                self.emit(OpCode::SetIndex, &[]);
+            }
+            Statement::For(variable, iterable, body) => {
+                self.compile_for_statement(variable, iterable, body)?
+            }
+            Statement::Break => {
+                let innermost = self.loops.last().ok_or(CompilerError::OutsideLoop)?;
+                let stack_values = innermost.stack_values;
+                self.emit(OpCode::LoopUnwind, &[]);
+                for _ in 0..stack_values {
+                    self.emit(OpCode::Pop, &[]);
+                }
+                let jump_pos = self.emit(OpCode::Jump, &[9999_u32]);
+                self.loops.last_mut().unwrap().breaks.push(jump_pos);
+            }
+            Statement::Continue => {
+                if self.loops.is_empty() {
+                    return Err(CompilerError::OutsideLoop);
+                }
+                self.emit(OpCode::LoopUnwind, &[]);
+                let jump_pos = self.emit(OpCode::Jump, &[9999_u32]);
+                let innermost = self.loops.last_mut().ok_or(CompilerError::OutsideLoop)?;
+                innermost.continues.push(jump_pos);
+            }
+            Statement::Use(path) => self.compile_use(path)?,
+            Statement::Export(statement) => self.compile_export(statement)?,
+        }
+        Ok(())
+    }
+
+    // a module is compiled by its first import into a function that runs the
+    // module's code, with its top-level names in global slots of its own, the
+    // first time it is called; every import calls it and then binds the
+    // exported values in the importing scope, as the evaluator does
+    fn compile_use(&mut self, path: &str) -> Result<(), CompilerError> {
+        let key = module::resolve(path)?;
+        let module = match self.modules.get(&key) {
+            Some(module) => module.clone(),
+            None => {
+                let program = module::load(path, &key)?;
+                let module =
+                    module::running_as(key.clone(), || self.compile_module(path, program))?;
+                self.modules.insert(key, module.clone());
+                module
+            }
+        };
+
+        self.emit(OpCode::Closure, &[module.address, 0]);
+        self.emit(OpCode::Call, &[0]);
+        self.emit(OpCode::Pop, &[]);
+        for (name, exported) in module.exports {
+            self.resolved.push(Rc::clone(&exported));
+            self.load_symbol(exported);
+            self.pending.remove(&name);
+            let symbol = self.symbol_table.define(name);
+            self.set_binding(&symbol);
         }
         Ok(())
     }
 
-    fn compile_let(&mut self, id: &Expression, val: &Expression) -> Result<(), CompilerError> {
+    fn compile_module(&mut self, path: &str, program: Program) -> Result<Module, CompilerError> {
+        // the module's names are private to it, but take up global slots after
+        // those already in use
+        let mut table = SymbolTable::new();
+        table.define_all_builtins();
+        table.num_definitions = self.globals().num_definitions;
+        // set once the module has run, so that it only runs once
+        let loaded = table.num_definitions;
+        table.num_definitions += 1;
+
+        let importer = mem::replace(&mut self.symbol_table, table);
+        let loops = mem::take(&mut self.loops);
+        let pending = mem::take(&mut self.pending);
+        let exports = mem::take(&mut self.exports);
+        self.scopes.push(Instructions::new());
+        self.scope_idx += 1;
+
+        let result = self.compile_module_body(program, loaded);
+
+        self.scope_idx -= 1;
+        let instructions = self.scopes.pop().unwrap();
+        let table = mem::replace(&mut self.symbol_table, importer);
+        self.globals().num_definitions = table.num_definitions;
+        self.loops = loops;
+        self.pending = pending;
+        let exports = mem::replace(&mut self.exports, exports);
+        result?;
+
+        let function = CompiledFunction {
+            name: format!("<import \"{path}\">"),
+            ..CompiledFunction::new(instructions, 0, 0)
+        };
+        let address = self.add_constant(Object::CompiledFunc(Rc::new(function)));
+        Ok(Module { address, exports })
+    }
+
+    fn compile_module_body(&mut self, program: Program, loaded: u32) -> Result<(), CompilerError> {
+        self.emit(OpCode::GetGlobal, &[loaded]);
+        let jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
+        self.emit(OpCode::Return, &[]);
+        let start_pos = self.scopes[self.scope_idx].len() as u32;
+        self.change_operand(jump_not_truthy_pos as usize, start_pos)?;
+
+        self.hoist(&program);
+        let Program(statements) = program;
+        self.compile_statements(&statements)?;
+        // only once it has run, so a module that fails runs again the next
+        // time it is imported
+        self.emit(OpCode::True, &[]);
+        self.emit(OpCode::SetGlobal, &[loaded]);
+        self.emit(OpCode::Return, &[]);
+        Ok(())
+    }
+
+    // records the name an `export` applies to, for the modules importing this
+    // one; `export name;` only checks that `name` exists
+    fn compile_export(&mut self, statement: &Statement) -> Result<(), CompilerError> {
+        self.compile_statement(statement)?;
+        let name = match statement {
+            Statement::Expression(Expression::Identifier(name)) => name,
+            statement => match statement.let_name() {
+                Some(name) => name,
+                None => return Ok(()),
+            },
+        };
+        let symbol = self
+            .symbol_table
+            .resolve(name.to_string())
+            .ok_or(CompilerError::UndefinedVariable)?;
+        self.exports.retain(|(exported, _)| exported != name);
+        self.exports.push((name.to_string(), symbol));
+        Ok(())
+    }
+
+    fn compile_let(
+        &mut self,
+        id: &Expression,
+        val: &Expression,
+        constant: bool,
+    ) -> Result<(), CompilerError> {
         if let Expression::Identifier(id) = id {
-            let symbol = self.symbol_table.define(id.to_string());
+            self.pending.remove(id);
+            let symbol = match constant {
+                true => self.symbol_table.define_constant(id.to_string()),
+                false => self.symbol_table.define(id.to_string()),
+            };
+            self.compile_expression(val)?;
+            self.set_binding(&symbol);
+        }
+        Ok(())
+    }
+
+    // the value is destructured onto the stack by `OpDestructure`, with the
+    // first element on top, so the names are set in order and the rest last;
+    // unlike a `let`, the names are only bound once the value is compiled
+    fn compile_let_destructure(
+        &mut self,
+        names: &[String],
+        rest: Option<&String>,
+        val: &Expression,
+    ) -> Result<(), CompilerError> {
+        self.compile_expression(val)?;
+        self.emit(
+            OpCode::Destructure,
+            &[names.len() as u32, u32::from(rest.is_some())],
+        );
+        for name in names.iter().chain(rest) {
+            self.pending.remove(name);
+            let symbol = self.symbol_table.define(name.to_string());
+            self.set_binding(&symbol);
+        }
+        Ok(())
+    }
+
+    // pops the value on top of the stack into a binding made by a `let`
+    fn set_binding(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::BlockGlobal => {
+                self.emit(OpCode::SetGlobal, &[symbol.index])
+            }
+            SymbolScope::Local => self.emit(OpCode::SetLocal, &[symbol.index]),
+            scope => unreachable!("let bindings are never {scope:?}"),
+        };
+    }
+
+    fn compile_assignment(
+        &mut self,
+        id: &Expression,
+        val: &Expression,
+    ) -> Result<(), CompilerError> {
+        if let Expression::Identifier(id) = id {
+            let symbol = match self.symbol_table.resolve(id.to_string()) {
+                Some(symbol) => {
+                    self.resolved.push(Rc::clone(&symbol));
+                    symbol
+                }
+                None => return Err(CompilerError::AssignmentWithoutLet(id.to_string())),
+            };
+            if symbol.constant {
+                return Err(CompilerError::ReassignConstant(id.to_string()));
+            }
             self.compile_expression(val)?;
             match symbol.scope {
-                SymbolScope::Global => self.emit(OpCode::SetGlobal, &[symbol.index]),
+                SymbolScope::Global | SymbolScope::BlockGlobal => {
+                    self.emit(OpCode::SetGlobal, &[symbol.index])
+                }
                 SymbolScope::Local => self.emit(OpCode::SetLocal, &[symbol.index]),
-                _ => todo!(),
+                SymbolScope::Free => self.emit(OpCode::SetFree, &[symbol.index]),
+                SymbolScope::Builtin | SymbolScope::Function => {
+                    return Err(CompilerError::InvalidAssignment)
+                }
             };
         }
         Ok(())
@@ -92,16 +369,28 @@ impl Compiler {
     fn compile_expression(&mut self, expression: &Expression) -> Result<(), CompilerError> {
         match expression {
             Expression::Identifier(id) => match self.symbol_table.resolve(id.to_string()) {
-                Some(binding) => self.load_symbol(binding),
+                // only a function, which may run later on, can read a name
+                // whose `let` hasn't been compiled yet
+                Some(_) if self.pending.contains(id) && self.symbol_table.is_top_level() => {
+                    return Err(CompilerError::UsedBeforeDefinition(id.to_string()));
+                }
+                Some(binding) => {
+                    self.resolved.push(Rc::clone(&binding));
+                    self.load_symbol(binding)
+                }
                 None => {
                     return Err(CompilerError::UndefinedVariable);
                 }
             },
             Expression::Integer(integer) => self.compile_integer_expression(*integer)?,
+            Expression::Float(float) => self.compile_float_expression(*float)?,
             Expression::Prefix(prefix, right) => self.compile_prefix_expression(prefix, right)?,
             Expression::Infix(left, infix, right) => {
                 self.compile_infix_expression(left, infix, right)?
             }
+            Expression::Null => {
+                self.emit(OpCode::Null, &[]);
+            }
             Expression::Boolean(value) => {
                 if *value {
                     self.emit(OpCode::True, &[]);
@@ -112,15 +401,22 @@ impl Compiler {
             Expression::If(condition, consequence, alternative) => {
                 self.compile_if_expression(condition, consequence, alternative)?
             }
+            Expression::Match(value, arms, default) => {
+                self.compile_match_expression(value, arms, default)?
+            }
+            Expression::Macro(..) => return Err(CompilerError::UnexpandedMacro),
             Expression::Function(args, body, name) => {
+                let constants_start = self.constants.len();
+                let resolved_start = self.resolved.len();
                 self.enter_scope();
                 self.symbol_table.define_function_name(name.clone());
-                for arg in args {
+                for (arg, _) in args {
                     match arg {
                         Expression::Identifier(id) => self.symbol_table.define(id.clone()),
                         _ => todo!(),
                     };
                 }
+                self.compile_defaults(args)?;
                 self.compile_statement(body)?;
                 if self.last_instruction_is(OpCode::Pop) {
                     let address = self.scopes[self.scope_idx].len() - WORD_SIZE;
@@ -134,29 +430,76 @@ impl Compiler {
                 let instructions = self.leave_scope();
                 free_symbols
                     .iter()
-                    .for_each(|binding| self.load_symbol(Rc::clone(binding)));
-                let compilted_fn = Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                    instructions,
-                    num_locals,
-                    args.len() as u32,
-                )));
-                let address = self.add_constant(compilted_fn);
+                    .for_each(|binding| self.load_free_symbol(Rc::clone(binding)));
+                // the same source bound to the same name, with every name in
+                // it resolving the same way, compiles to the same function,
+                // whose constant can be shared
+                let key = format!(
+                    "{name} {args:?} {body:?} {:?} {free_symbols:?}",
+                    &self.resolved[resolved_start..]
+                );
+                let address = match self.function_cache.get(&key) {
+                    Some(&address) => {
+                        self.discard_constants(constants_start);
+                        address
+                    }
+                    None => {
+                        // a function that isn't bound with `let` is told apart
+                        // from others by its constant
+                        let name = match name.as_str() {
+                            "" => format!("<anonymous #{}>", self.constants.len()),
+                            name => name.to_string(),
+                        };
+                        let compilted_fn = Object::CompiledFunc(Rc::new(CompiledFunction {
+                            num_defaults: args.iter().filter(|(_, d)| d.is_some()).count() as u32,
+                            parameters: args.iter().map(|(arg, _)| arg.to_string()).collect(),
+                            name,
+                            ..CompiledFunction::new(instructions, num_locals, args.len() as u32)
+                        }));
+                        let address = self.add_constant(compilted_fn);
+                        self.function_cache.insert(key, address);
+                        address
+                    }
+                };
                 self.emit(OpCode::Closure, &[address, free_symbols.len() as u32]);
             }
             Expression::Call(func, args) => {
                 self.compile_expression(func)?;
-                for arg in args {
-                    self.compile_expression(arg)?;
+                if args.iter().any(|arg| matches!(arg, CallArg::Named(..))) {
+                    self.compile_named_arguments(args)?;
+                } else if args.iter().any(|arg| matches!(arg, CallArg::Spread(_))) {
+                    let num_parts = self.compile_argument_parts(args)?;
+                    self.emit(OpCode::CallSpread, &[num_parts]);
+                } else {
+                    for arg in args {
+                        if let CallArg::Expression(arg) = arg {
+                            self.compile_expression(arg)?;
+                        }
+                    }
+                    self.emit(OpCode::Call, &[args.len() as u32]);
                 }
-                self.emit(OpCode::Call, &[args.len() as u32]);
             }
             Expression::String(val) => {
                 let str = Object::String(val.clone());
                 let address = self.add_constant(str);
                 self.emit(OpCode::Constant, &[address]);
             }
+            Expression::StringTemplate(parts) => {
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(text) => {
+                            let address = self.add_constant(Object::String(text.clone()));
+                            self.emit(OpCode::Constant, &[address]);
+                        }
+                        TemplatePart::Expression(expression) => {
+                            self.compile_expression(expression)?
+                        }
+                    }
+                }
+                self.emit(OpCode::Template, &[parts.len() as u32]);
+            }
             Expression::Array(val) => {
-                for exp in val.into_iter() {
+                for exp in val.iter() {
                     self.compile_expression(exp)?;
                 }
                 self.emit(OpCode::Array, &[val.len() as u32]);
@@ -166,6 +509,24 @@ impl Compiler {
                 self.compile_expression(i)?;
                 self.emit(OpCode::Index, &[]);
             }
+            Expression::Range(start, end) => {
+                self.compile_expression(start)?;
+                self.compile_expression(end)?;
+                self.emit(OpCode::Range, &[]);
+            }
+            Expression::Slice(store, start, end) => {
+                self.compile_expression(store)?;
+                // a bound that is left out is null
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.compile_expression(bound)?,
+                        None => {
+                            self.emit(OpCode::Null, &[]);
+                        }
+                    }
+                }
+                self.emit(OpCode::Slice, &[]);
+            }
             Expression::Hash(val) => {
                 for (k, v) in val {
                     self.compile_expression(k)?;
@@ -173,8 +534,93 @@ impl Compiler {
                 }
                 self.emit(OpCode::Hash, &[(val.len() * 2) as u32]);
             }
-            Expression::While(_, _) => todo!(),
+            Expression::While(condition, body) => self.compile_while_expression(condition, body)?,
+        }
+        Ok(())
+    }
+
+    // the arguments before any named ones are pushed as a number of arrays,
+    // which the VM joins together: each run of ordinary arguments is put in
+    // an array, and each spread argument should already be one; returns how
+    // many arrays there are
+    fn compile_argument_parts(&mut self, args: &[CallArg]) -> Result<u32, CompilerError> {
+        let mut num_parts = 0;
+        let mut run_len = 0;
+        for arg in args {
+            match arg {
+                CallArg::Expression(arg) => {
+                    self.compile_expression(arg)?;
+                    run_len += 1;
+                }
+                CallArg::Spread(arg) => {
+                    if run_len > 0 {
+                        self.emit(OpCode::Array, &[run_len]);
+                        num_parts += 1;
+                        run_len = 0;
+                    }
+                    self.compile_expression(arg)?;
+                    num_parts += 1;
+                }
+                CallArg::Named(..) => break,
+            }
+        }
+        if run_len > 0 {
+            self.emit(OpCode::Array, &[run_len]);
+            num_parts += 1;
+        }
+        Ok(num_parts)
+    }
+
+    // the other arguments are pushed as for a spread, followed by the named
+    // ones' values; the VM puts these in order using the names, which are an
+    // array constant
+    fn compile_named_arguments(&mut self, args: &[CallArg]) -> Result<(), CompilerError> {
+        let num_parts = self.compile_argument_parts(args)?;
+        let mut names = vec![];
+        for arg in args {
+            if let CallArg::Named(name, value) = arg {
+                self.compile_expression(value)?;
+                names.push(Rc::new(Object::String(name.clone())));
+            }
+        }
+        let address = self.add_constant(Object::Array(RefCell::new(names)));
+        self.emit(OpCode::CallNamed, &[address, num_parts]);
+        Ok(())
+    }
+
+    // a function with defaults starts with a jump for each number of
+    // arguments it accepts, and the VM starts a call at the one for the number
+    // it was given; each jumps to the code filling in the first default that
+    // wasn't passed, which carries on filling in the rest
+    fn compile_defaults(
+        &mut self,
+        parameters: &[(Expression, Option<Expression>)],
+    ) -> Result<(), CompilerError> {
+        let defaulted: Vec<_> = parameters
+            .iter()
+            .filter_map(|(parameter, default)| Some((parameter, default.as_ref()?)))
+            .collect();
+        if defaulted.is_empty() {
+            return Ok(());
+        }
+
+        let entry_jumps: Vec<_> = (0..=defaulted.len())
+            .map(|_| self.emit(OpCode::Jump, &[9999_u32]))
+            .collect();
+
+        for ((parameter, default), jump_pos) in defaulted.into_iter().zip(&entry_jumps) {
+            let default_pos = self.scopes[self.scope_idx].len() as u32;
+            self.change_operand(*jump_pos as usize, default_pos)?;
+
+            self.compile_expression(default)?;
+            if let Expression::Identifier(id) = parameter {
+                let symbol = self.symbol_table.resolve(id.to_string()).unwrap();
+                self.emit(OpCode::SetLocal, &[symbol.index]);
+            }
         }
+
+        let body_pos = self.scopes[self.scope_idx].len() as u32;
+        self.change_operand(*entry_jumps.last().unwrap() as usize, body_pos)?;
         Ok(())
     }
 
@@ -189,10 +635,7 @@ impl Compiler {
         let jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
 
         self.compile_statement(consequence)?;
-
-        if self.last_instruction_is(OpCode::Pop) {
-            self.remove_last_instruction();
-        }
+        self.keep_block_value();
 
         let jump_pos = self.emit(OpCode::Jump, &[9999_u32]);
 
@@ -203,11 +646,8 @@ impl Compiler {
             self.emit(OpCode::Null, &[]);
         } else {
             let else_block = alternative.as_ref().unwrap();
-            self.compile_statement(&else_block)?;
-
-            if self.last_instruction_is(OpCode::Pop) {
-                self.remove_last_instruction();
-            }
+            self.compile_statement(else_block)?;
+            self.keep_block_value();
         }
 
         let after_consequence_pos = self.scopes[self.scope_idx].len() as u32;
@@ -216,6 +656,174 @@ impl Compiler {
         Ok(())
     }
 
+    // the matched value stays on the stack while the patterns are compared
+    // with it, and is popped before the chosen arm's block runs
+    fn compile_match_expression(
+        &mut self,
+        value: &Expression,
+        arms: &[(Expression, Statement)],
+        default: &Option<Box<Statement>>,
+    ) -> Result<(), CompilerError> {
+        self.compile_expression(value)?;
+
+        let mut jumps_to_end = vec![];
+        for (pattern, block) in arms {
+            self.compile_expression(pattern)?;
+            self.emit(OpCode::Match, &[]);
+            let jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
+
+            self.compile_match_arm(block)?;
+            jumps_to_end.push(self.emit(OpCode::Jump, &[9999_u32]));
+
+            let next_arm_pos = self.scopes[self.scope_idx].len() as u32;
+            self.change_operand(jump_not_truthy_pos as usize, next_arm_pos)?;
+        }
+
+        match default {
+            Some(block) => self.compile_match_arm(block)?,
+            None => {
+                self.emit(OpCode::Pop, &[]);
+                self.emit(OpCode::Null, &[]);
+            }
+        }
+
+        let end_pos = self.scopes[self.scope_idx].len() as u32;
+        for jump_pos in jumps_to_end {
+            self.change_operand(jump_pos as usize, end_pos)?;
+        }
+
+        Ok(())
+    }
+
+    fn compile_match_arm(&mut self, block: &Statement) -> Result<(), CompilerError> {
+        self.emit(OpCode::Pop, &[]);
+        let block_start = self.scopes[self.scope_idx].len();
+        self.compile_statement(block)?;
+        // an empty block must not take the pop of the matched value as its own
+        if self.scopes[self.scope_idx].len() == block_start {
+            self.emit(OpCode::Null, &[]);
+        } else {
+            self.keep_block_value();
+        }
+        Ok(())
+    }
+
+    // leaves the value of the block just compiled on the stack; a block that
+    // does not end in an expression, e.g. with a `let`, has the value null
+    fn keep_block_value(&mut self) {
+        if self.last_instruction_is(OpCode::Pop) {
+            self.remove_last_instruction();
+        } else {
+            self.emit(OpCode::Null, &[]);
+        }
+    }
+
+    fn compile_while_expression(
+        &mut self,
+        condition: &Expression,
+        body: &Statement,
+    ) -> Result<(), CompilerError> {
+        self.emit(OpCode::LoopEnter, &[]);
+        let loop_start_pos = self.scopes[self.scope_idx].len() as u32;
+
+        self.compile_expression(condition)?;
+
+        let jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
+
+        self.loops.push(LoopJumps::default());
+        self.compile_statement(body)?;
+        let jumps = self.loops.pop().unwrap();
+
+        self.emit(OpCode::Jump, &[loop_start_pos]);
+
+        let after_loop_pos = self.scopes[self.scope_idx].len() as u32;
+        self.change_operand(jump_not_truthy_pos as usize, after_loop_pos)?;
+        self.patch_loop_jumps(jumps, loop_start_pos, after_loop_pos)?;
+        self.emit(OpCode::LoopExit, &[]);
+
+        // a loop evaluates to null unless it is exited with `return`
+        self.emit(OpCode::Null, &[]);
+
+        Ok(())
+    }
+
+    // the array and the index of its next element stay on the stack while the
+    // loop runs; the loop's variables are block scoped and are detached from
+    // any closures at the end of each iteration, so that closures created in
+    // different iterations don't share them
+    fn compile_for_statement(
+        &mut self,
+        variable: &Expression,
+        iterable: &Expression,
+        body: &Statement,
+    ) -> Result<(), CompilerError> {
+        self.compile_expression(iterable)?;
+        let start_index = self.add_constant(Object::Integer(0));
+        self.emit(OpCode::Constant, &[start_index]);
+        self.emit(OpCode::LoopEnter, &[]);
+
+        let loop_start_pos = self.scopes[self.scope_idx].len() as u32;
+        let iter_next_pos = self.emit(OpCode::IterNext, &[9999_u32]);
+
+        self.enter_block();
+        if let Expression::Identifier(id) = variable {
+            let symbol = self.symbol_table.define(id.to_string());
+            self.define_symbol(&symbol);
+        }
+
+        self.loops.push(LoopJumps {
+            stack_values: 2,
+            ..Default::default()
+        });
+        self.compile_statement(body)?;
+        let jumps = self.loops.pop().unwrap();
+
+        // `continue` still has to detach the iteration's variables
+        let iteration_end_pos = self.scopes[self.scope_idx].len() as u32;
+        for symbol in self.symbol_table.definitions() {
+            self.emit(OpCode::Null, &[]);
+            self.define_symbol(&symbol);
+        }
+        self.leave_block();
+
+        self.emit(OpCode::Jump, &[loop_start_pos]);
+
+        let after_loop_pos = self.scopes[self.scope_idx].len() as u32;
+        self.change_operand(iter_next_pos as usize, after_loop_pos)?;
+        self.patch_loop_jumps(jumps, iteration_end_pos, after_loop_pos)?;
+        self.emit(OpCode::LoopExit, &[]);
+
+        // like an expression statement, the loop's value is popped; it is null
+        self.emit(OpCode::Null, &[]);
+        self.emit(OpCode::Pop, &[]);
+
+        Ok(())
+    }
+
+    fn patch_loop_jumps(
+        &mut self,
+        jumps: LoopJumps,
+        continue_pos: u32,
+        break_pos: u32,
+    ) -> Result<(), CompilerError> {
+        for jump_pos in jumps.continues {
+            self.change_operand(jump_pos as usize, continue_pos)?;
+        }
+        for jump_pos in jumps.breaks {
+            self.change_operand(jump_pos as usize, break_pos)?;
+        }
+        Ok(())
+    }
+
+    // binds the value on top of the stack to a fresh variable, rather than
+    // assigning to any variable a closure has captured from the same slot
+    fn define_symbol(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            SymbolScope::Local => self.emit(OpCode::DefineLocal, &[symbol.index]),
+            _ => self.emit(OpCode::DefineGlobal, &[symbol.index]),
+        };
+    }
+
     fn compile_prefix_expression(
         &mut self,
         prefix: &Prefix,
@@ -239,13 +847,22 @@ impl Compiler {
         Ok(())
     }
 
+    fn compile_float_expression(&mut self, float: f64) -> Result<(), CompilerError> {
+        let constant_address = self.add_constant(Object::Float(float));
+        self.emit(OpCode::Constant, &[constant_address]);
+        Ok(())
+    }
+
     fn compile_infix_expression(
         &mut self,
         left: &Expression,
         infix: &Infix,
         right: &Expression,
     ) -> Result<(), CompilerError> {
-        if *infix == Infix::LessThan {
+        if *infix == Infix::And || *infix == Infix::Or {
+            return self.compile_logical_expression(left, infix, right);
+        }
+        if *infix == Infix::LessThan || *infix == Infix::LessEqual {
             self.compile_expression(right)?;
             self.compile_expression(left)?;
         } else {
@@ -271,19 +888,75 @@ impl Compiler {
             Infix::LessThan => {
                 self.emit(OpCode::GreaterThan, &[]);
             }
+            Infix::GreaterEqual | Infix::LessEqual => {
+                self.emit(OpCode::GreaterEqual, &[]);
+            }
             Infix::Equal => {
                 self.emit(OpCode::Equal, &[]);
             }
             Infix::NotEqual => {
                 self.emit(OpCode::NotEqual, &[]);
             }
-            Infix::And => {
-                self.emit(OpCode::And, &[]);
+            Infix::BitAnd => {
+                self.emit(OpCode::BitAnd, &[]);
             }
-            Infix::Or => {
-                self.emit(OpCode::Or, &[]);
+            Infix::BitOr => {
+                self.emit(OpCode::BitOr, &[]);
             }
+            Infix::BitXor => {
+                self.emit(OpCode::BitXor, &[]);
+            }
+            Infix::ShiftLeft => {
+                self.emit(OpCode::ShiftLeft, &[]);
+            }
+            Infix::ShiftRight => {
+                self.emit(OpCode::ShiftRight, &[]);
+            }
+            Infix::Power => {
+                self.emit(OpCode::Power, &[]);
+            }
+            Infix::And | Infix::Or => unreachable!("compiled by `compile_logical_expression`"),
+        }
+        Ok(())
+    }
+
+    // the right operand is only evaluated if the left one does not already
+    // decide the result, which is always a boolean
+    fn compile_logical_expression(
+        &mut self,
+        left: &Expression,
+        infix: &Infix,
+        right: &Expression,
+    ) -> Result<(), CompilerError> {
+        self.compile_expression(left)?;
+
+        let mut jumps_to_false = vec![self.emit(OpCode::JumpNotTruthy, &[9999_u32])];
+        let mut jumps_to_end = vec![];
+
+        if *infix == Infix::Or {
+            self.emit(OpCode::True, &[]);
+            jumps_to_end.push(self.emit(OpCode::Jump, &[9999_u32]));
+            let right_pos = self.scopes[self.scope_idx].len() as u32;
+            self.change_operand(jumps_to_false.remove(0) as usize, right_pos)?;
+        }
+
+        self.compile_expression(right)?;
+
+        jumps_to_false.push(self.emit(OpCode::JumpNotTruthy, &[9999_u32]));
+        self.emit(OpCode::True, &[]);
+        jumps_to_end.push(self.emit(OpCode::Jump, &[9999_u32]));
+
+        let false_pos = self.scopes[self.scope_idx].len() as u32;
+        for jump_pos in jumps_to_false {
+            self.change_operand(jump_pos as usize, false_pos)?;
         }
+        self.emit(OpCode::False, &[]);
+
+        let end_pos = self.scopes[self.scope_idx].len() as u32;
+        for jump_pos in jumps_to_end {
+            self.change_operand(jump_pos as usize, end_pos)?;
+        }
+
         Ok(())
     }
 
@@ -292,8 +965,18 @@ impl Compiler {
         (self.constants.len() - 1) as u32
     }
 
+    // drops the constants added from `start` on, which only the code compiled
+    // since then refers to
+    fn discard_constants(&mut self, start: usize) {
+        self.constants.truncate(start);
+        self.function_cache
+            .retain(|_, address| (*address as usize) < start);
+        self.modules
+            .retain(|_, module| (module.address as usize) < start);
+    }
+
     fn last_instruction_is(&self, target_opcode: OpCode) -> bool {
-        if self.scopes[self.scope_idx].len() == 0 {
+        if self.scopes[self.scope_idx].is_empty() {
             return false;
         }
         let last_op_code = self.get_instruction_at(self.scopes[self.scope_idx].len() - WORD_SIZE);
@@ -321,17 +1004,13 @@ impl Compiler {
         address: usize,
         new_instruction: &[u8],
     ) -> Result<(), CompilerError> {
-        let mut i = 0;
-        for _ in 0..WORD_SIZE {
-            self.scopes[self.scope_idx][address + i] = new_instruction[i];
-            i += 1;
-        }
+        self.scopes[self.scope_idx][address..address + WORD_SIZE]
+            .copy_from_slice(&new_instruction[..WORD_SIZE]);
         Ok(())
     }
 
     fn get_instruction_at(&self, idx: usize) -> Result<OpCode, CompilerError> {
-        OpCode::try_from(self.scopes[self.scope_idx][idx] as u8)
-            .map_err(|_| CompilerError::InvalidOpCode)
+        OpCode::try_from(self.scopes[self.scope_idx][idx]).map_err(|_| CompilerError::InvalidOpCode)
     }
 
     fn emit(&mut self, op: OpCode, operands: &[u32]) -> u32 {
@@ -341,6 +1020,15 @@ impl Compiler {
         instruction_address as u32
     }
 
+    // the table of the program's top level, whose slots are the globals
+    fn globals(&mut self) -> &mut SymbolTable {
+        let mut table = &mut self.symbol_table;
+        while table.outer.is_some() {
+            table = table.outer.as_deref_mut().unwrap();
+        }
+        table
+    }
+
     fn enter_scope(&mut self) {
         self.symbol_table = SymbolTable::new_enclosed(self.symbol_table.clone());
         self.scopes.push(Instructions::new());
@@ -353,9 +1041,21 @@ impl Compiler {
         self.scopes.pop().unwrap()
     }
 
+    fn enter_block(&mut self) {
+        let table = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_block(table);
+    }
+
+    fn leave_block(&mut self) {
+        // the enclosing table is moved back rather than cloned, as the block
+        // allocated its slots there
+        let outer = self.symbol_table.outer.take();
+        self.symbol_table = *outer.expect("a block is always enclosed");
+    }
+
     fn load_symbol(&mut self, binding: Rc<Symbol>) {
         match binding.scope {
-            SymbolScope::Global => {
+            SymbolScope::Global | SymbolScope::BlockGlobal => {
                 self.emit(OpCode::GetGlobal, &[binding.index]);
             }
             SymbolScope::Local => {
@@ -372,10 +1072,116 @@ impl Compiler {
             }
         }
     }
+
+    // pushes the variable itself rather than its value, so that a closure
+    // capturing it shares the binding with the scope it was captured from
+    fn load_free_symbol(&mut self, binding: Rc<Symbol>) {
+        match binding.scope {
+            SymbolScope::Local => {
+                self.emit(OpCode::GetLocalCell, &[binding.index]);
+            }
+            SymbolScope::Free => {
+                self.emit(OpCode::GetFreeCell, &[binding.index]);
+            }
+            SymbolScope::BlockGlobal => {
+                self.emit(OpCode::GetGlobalCell, &[binding.index]);
+            }
+            _ => self.load_symbol(binding),
+        }
+    }
+}
+
+// removes every `OpConstant; OpPop` pair, i.e. a constant used as a statement,
+// and moves the targets of jumps to match; a pair is kept if a jump targets
+// its `OpPop`, which then pops a value from elsewhere, or if it holds the last
+// `OpPop`, since the REPL shows the value popped last
+pub fn optimize_instructions(instructions: Instructions) -> Instructions {
+    let words: Vec<&[u8]> = instructions.chunks_exact(WORD_SIZE).collect();
+    let ops: Vec<OpCode> = words
+        .iter()
+        .map(|word| OpCode::try_from(word[0]).expect("Invalid OpCode"))
+        .collect();
+    let is_jump =
+        |op: &OpCode| matches!(op, OpCode::Jump | OpCode::JumpNotTruthy | OpCode::IterNext);
+    let targets: HashSet<usize> = words
+        .iter()
+        .zip(ops.iter())
+        .filter(|(_, op)| is_jump(op))
+        .map(|(word, _)| read_u16(&word[1..=2]) as usize / WORD_SIZE)
+        .collect();
+
+    let last_pop = ops.iter().rposition(|op| *op == OpCode::Pop);
+
+    let mut removed = vec![false; words.len()];
+    let mut i = 0;
+    while i + 1 < words.len() {
+        if ops[i] == OpCode::Constant
+            && ops[i + 1] == OpCode::Pop
+            && !targets.contains(&(i + 1))
+            && Some(i + 1) != last_pop
+        {
+            removed[i] = true;
+            removed[i + 1] = true;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    // the new address of every word, and of the end of the instructions
+    let mut addresses = Vec::with_capacity(words.len() + 1);
+    let mut address = 0;
+    for is_removed in removed.iter() {
+        addresses.push(address as u32);
+        if !is_removed {
+            address += WORD_SIZE;
+        }
+    }
+    addresses.push(address as u32);
+
+    let mut optimized = Instructions::new();
+    for ((word, op), is_removed) in words.into_iter().zip(ops).zip(removed) {
+        if is_removed {
+            continue;
+        }
+        if is_jump(&op) {
+            let target = addresses[read_u16(&word[1..=2]) as usize / WORD_SIZE];
+            optimized.extend_from_slice(&make(op, &[target]));
+        } else {
+            optimized.extend_from_slice(word);
+        }
+    }
+    optimized
 }
 
 #[derive(Debug, PartialEq)]
 pub enum CompilerError {
     InvalidOpCode,
     UndefinedVariable,
+    // assigning to a name that was never bound with `let`
+    AssignmentWithoutLet(String),
+    // assigning to a name bound with `const`
+    ReassignConstant(String),
+    // reading a top-level name at the top level before its `let`, which
+    // only functions defined earlier can refer to
+    UsedBeforeDefinition(String),
+    InvalidAssignment,
+    OutsideLoop,
+    // a module that can't be read or parsed, with its path
+    ImportFailed(String),
+    // the chain of modules that imports itself
+    CircularImport(String),
+    // a macro that isn't bound with a top-level `let`, so is never expanded
+    UnexpandedMacro,
+}
+
+impl From<EvalError> for CompilerError {
+    // finding a module for `import` is shared with the evaluator
+    fn from(error: EvalError) -> Self {
+        match error {
+            EvalError::CircularImport(chain) => CompilerError::CircularImport(chain),
+            EvalError::ImportFailed(message) => CompilerError::ImportFailed(message),
+            other => CompilerError::ImportFailed(format!("{other:?}")),
+        }
+    }
 }
@@ -1,9 +1,10 @@
 #![cfg(test)]
 
 use crate::code::{make, OpCode};
-use crate::compiler::{ByteCode, Compiler, CompilerError};
+use crate::compiler::{optimize_instructions, ByteCode, Compiler, CompilerError};
 use crate::object::{CompiledFunction, Object};
 use crate::parser::Parser;
+use crate::value;
 use std::rc::Rc;
 
 fn parse_and_compile(input: &str) -> (Option<ByteCode>, Option<CompilerError>) {
@@ -53,6 +54,26 @@ fn test_compile_integer_addition() {
     assert_eq!(byte_code, Some(expected));
 }
 
+#[test]
+fn test_compile_float_addition() {
+    let input = "1.5 + 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::Add, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Float(1.5)), Rc::new(Object::Integer(2))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
 #[test]
 fn test_compile_integer_subtraction() {
     let input = "4 - 2";
@@ -199,6 +220,26 @@ fn test_compile_boolean_expression_two() {
     assert_eq!(byte_code, Some(expected));
 }
 
+#[test]
+fn test_compile_less_equal_expression() {
+    let input = "1 <= 2;";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::GreaterEqual, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(2)), Rc::new(Object::Integer(1))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
 #[test]
 fn test_compile_boolean_expression() {
     let input = "1 == 2;";
@@ -284,10 +325,14 @@ fn test_compile_boolean_expression_six() {
     let input = "true && false;";
     let expected = ByteCode(
         vec![
-            make(OpCode::True, &[]),
-            make(OpCode::False, &[]),
-            make(OpCode::And, &[]),
-            make(OpCode::Pop, &[]),
+            make(OpCode::True, &[]),            // 0000
+            make(OpCode::JumpNotTruthy, &[24]), // 0004
+            make(OpCode::False, &[]),           // 0008
+            make(OpCode::JumpNotTruthy, &[24]), // 0012
+            make(OpCode::True, &[]),            // 0016
+            make(OpCode::Jump, &[28]),          // 0020
+            make(OpCode::False, &[]),           // 0024
+            make(OpCode::Pop, &[]),             // 0028
         ]
         .into_iter()
         .flatten()
@@ -304,10 +349,16 @@ fn test_compile_boolean_expression_seven() {
     let input = "true || false;";
     let expected = ByteCode(
         vec![
-            make(OpCode::True, &[]),
-            make(OpCode::False, &[]),
-            make(OpCode::Or, &[]),
-            make(OpCode::Pop, &[]),
+            make(OpCode::True, &[]),            // 0000
+            make(OpCode::JumpNotTruthy, &[16]), // 0004
+            make(OpCode::True, &[]),            // 0008
+            make(OpCode::Jump, &[36]),          // 0012
+            make(OpCode::False, &[]),           // 0016
+            make(OpCode::JumpNotTruthy, &[32]), // 0020
+            make(OpCode::True, &[]),            // 0024
+            make(OpCode::Jump, &[36]),          // 0028
+            make(OpCode::False, &[]),           // 0032
+            make(OpCode::Pop, &[]),             // 0036
         ]
         .into_iter()
         .flatten()
@@ -732,6 +783,53 @@ fn test_compile_index_expression_three() {
     assert_eq!(byte_code, Some(expected));
 }
 
+#[test]
+fn test_compile_slice_expression() {
+    let input = "[1][:2]";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Array, &[1_u32]),
+            make(OpCode::Null, &[]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::Slice, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_index_assignment() {
+    let input = "[1][0] = 2;";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Array, &[1_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::Constant, &[2_u32]),
+            make(OpCode::SetIndex, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::Integer(2)),
+        ],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
 #[test]
 fn test_compile_function_one() {
     let input = "fn() { return 5 + 10; }";
@@ -746,19 +844,22 @@ fn test_compile_function_one() {
         vec![
             Rc::new(Object::Integer(5)),
             Rc::new(Object::Integer(10)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Constant, &[1_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #2>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Constant, &[1_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -780,19 +881,22 @@ fn test_compile_function_two() {
         vec![
             Rc::new(Object::Integer(5)),
             Rc::new(Object::Integer(10)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Constant, &[1_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #2>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Constant, &[1_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -814,19 +918,22 @@ fn test_compile_function_three() {
         vec![
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(2)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Pop, &[]),
-                    make(OpCode::Constant, &[1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #2>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Pop, &[]),
+                        make(OpCode::Constant, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -845,9 +952,10 @@ fn test_compile_function_four() {
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
-        vec![Rc::new(Object::CompiledFunc(Rc::new(
-            CompiledFunction::new(make(OpCode::Return, &[]).to_vec(), 0, 0),
-        )))],
+        vec![Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+            name: String::from("<anonymous #0>"),
+            ..CompiledFunction::new(make(OpCode::Return, &[]).to_vec(), 0, 0)
+        })))],
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -868,17 +976,20 @@ fn test_compile_function_call_one() {
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(24)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #1>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -902,17 +1013,20 @@ fn test_compile_function_call_two() {
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(24)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("noArg"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -938,17 +1052,20 @@ fn() { num };
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(55)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetGlobal, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #1>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetGlobal, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -974,19 +1091,22 @@ fn() {
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(77)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #1>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -1014,23 +1134,26 @@ fn() {
         vec![
             Rc::new(Object::Integer(77)),
             Rc::new(Object::Integer(55)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::Constant, &[1_u32]),
-                    make(OpCode::SetLocal, &[1_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::GetLocal, &[1_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                2,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #2>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[1_u32]),
+                        make(OpCode::SetLocal, &[1_u32]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::GetLocal, &[1_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    2,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -1057,14 +1180,18 @@ arg(24);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![make(OpCode::Return, &[])]
-                    .into_iter()
-                    .flatten()
-                    .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("arg"),
+                parameters: ["a"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![make(OpCode::Return, &[])]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
             Rc::new(Object::Integer(24)),
         ],
     );
@@ -1094,14 +1221,18 @@ arg(1, 2, 3);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![make(OpCode::Return, &[])]
-                    .into_iter()
-                    .flatten()
-                    .collect::<Vec<u8>>(),
-                3,
-                3,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("arg"),
+                parameters: ["a", "b", "c"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![make(OpCode::Return, &[])]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<u8>>(),
+                    3,
+                    3,
+                )
+            }))),
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(2)),
             Rc::new(Object::Integer(3)),
@@ -1131,17 +1262,21 @@ arg(24);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("arg"),
+                parameters: ["a"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
             Rc::new(Object::Integer(24)),
         ],
     );
@@ -1171,21 +1306,25 @@ arg(1, 2, 3);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Pop, &[]),
-                    make(OpCode::GetLocal, &[1_u32]),
-                    make(OpCode::Pop, &[]),
-                    make(OpCode::GetLocal, &[2_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                3,
-                3,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("arg"),
+                parameters: ["a", "b", "c"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Pop, &[]),
+                        make(OpCode::GetLocal, &[1_u32]),
+                        make(OpCode::Pop, &[]),
+                        make(OpCode::GetLocal, &[2_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    3,
+                    3,
+                )
+            }))),
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(2)),
             Rc::new(Object::Integer(3)),
@@ -1237,8 +1376,9 @@ fn() { len([]) };
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
-        vec![Rc::new(Object::CompiledFunc(Rc::new(
-            CompiledFunction::new(
+        vec![Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+            name: String::from("<anonymous #0>"),
+            ..CompiledFunction::new(
                 vec![
                     make(OpCode::GetBuiltin, &[0_u32]),
                     make(OpCode::Array, &[0_u32]),
@@ -1250,8 +1390,8 @@ fn() { len([]) };
                 .collect::<Vec<u8>>(),
                 0,
                 0,
-            ),
-        )))],
+            )
+        })))],
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1276,31 +1416,39 @@ fn(a) {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Closure, &[0_u32, 1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #0>"),
+                parameters: ["b"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetFree, &[0_u32]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #1>"),
+                parameters: ["a"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetLocalCell, &[0_u32]),
+                        make(OpCode::Closure, &[0_u32, 1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -1328,46 +1476,58 @@ fn(a) {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::GetFree, &[1_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Closure, &[0_u32, 2_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Closure, &[1_u32, 1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #0>"),
+                parameters: ["c"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetFree, &[0_u32]),
+                        make(OpCode::GetFree, &[1_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #1>"),
+                parameters: ["b"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetFreeCell, &[0_u32]),
+                        make(OpCode::GetLocalCell, &[0_u32]),
+                        make(OpCode::Closure, &[0_u32, 2_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #2>"),
+                parameters: ["a"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetLocalCell, &[0_u32]),
+                        make(OpCode::Closure, &[1_u32, 1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -1405,54 +1565,63 @@ fn() {
             Rc::new(Object::Integer(66)),
             Rc::new(Object::Integer(77)),
             Rc::new(Object::Integer(88)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[3_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetGlobal, &[0_u32]),
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::GetFree, &[1_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Add, &[]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                0,
-            )))),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[2_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Closure, &[4_u32, 2_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                0,
-            )))),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[1_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Closure, &[5_u32, 1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #4>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[3_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::GetGlobal, &[0_u32]),
+                        make(OpCode::GetFree, &[0_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::GetFree, &[1_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Add, &[]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    0,
+                )
+            }))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #5>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[2_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::GetFreeCell, &[0_u32]),
+                        make(OpCode::GetLocalCell, &[0_u32]),
+                        make(OpCode::Closure, &[4_u32, 2_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    0,
+                )
+            }))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("<anonymous #6>"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[1_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::GetLocalCell, &[0_u32]),
+                        make(OpCode::Closure, &[5_u32, 1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
@@ -1480,21 +1649,25 @@ countdown(1);
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(1)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::CurrentClosure, &[]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Subtract, &[]),
-                    make(OpCode::Call, &[1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("countdown"),
+                parameters: ["x"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::CurrentClosure, &[]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Subtract, &[]),
+                        make(OpCode::Call, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
             Rc::new(Object::Integer(1)),
         ],
     );
@@ -1525,40 +1698,590 @@ wrapper();
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(1)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::CurrentClosure, &[]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Subtract, &[]),
-                    make(OpCode::Call, &[1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("countdown"),
+                parameters: ["x"].map(String::from).to_vec(),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::CurrentClosure, &[]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Subtract, &[]),
+                        make(OpCode::Call, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+            }))),
             Rc::new(Object::Integer(1)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Closure, &[1_u32, 0_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Constant, &[2_u32]),
-                    make(OpCode::Call, &[1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction {
+                name: String::from("wrapper"),
+                ..CompiledFunction::new(
+                    vec![
+                        make(OpCode::Closure, &[1_u32, 0_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[2_u32]),
+                        make(OpCode::Call, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    0,
+                )
+            }))),
         ],
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
     assert_eq!(byte_code, Some(expected));
 }
+
+#[test]
+fn test_compile_while_expression() {
+    let input = "while (true) { 10 };";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::LoopEnter, &[]),
+            make(OpCode::True, &[]),
+            make(OpCode::JumpNotTruthy, &[24_u32]),
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Pop, &[]),
+            make(OpCode::Jump, &[4_u32]),
+            make(OpCode::LoopExit, &[]),
+            make(OpCode::Null, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_while_break_and_continue() {
+    let input = "while (true) { break; continue; };";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::LoopEnter, &[]),           // 0000
+            make(OpCode::True, &[]),                // 0004
+            make(OpCode::JumpNotTruthy, &[32_u32]), // 0008
+            make(OpCode::LoopUnwind, &[]),          // 0012
+            make(OpCode::Jump, &[32_u32]),          // 0016
+            make(OpCode::LoopUnwind, &[]),          // 0020
+            make(OpCode::Jump, &[4_u32]),           // 0024
+            make(OpCode::Jump, &[4_u32]),           // 0028
+            make(OpCode::LoopExit, &[]),            // 0032
+            make(OpCode::Null, &[]),                // 0036
+            make(OpCode::Pop, &[]),                 // 0040
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_for_statement() {
+    let input = "for (x in [1]) { x }";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),     // 0000
+            make(OpCode::Array, &[1_u32]),        // 0004
+            make(OpCode::Constant, &[1_u32]),     // 0008
+            make(OpCode::LoopEnter, &[]),         // 0012
+            make(OpCode::IterNext, &[44_u32]),    // 0016
+            make(OpCode::DefineGlobal, &[0_u32]), // 0020
+            make(OpCode::GetGlobal, &[0_u32]),    // 0024
+            make(OpCode::Pop, &[]),               // 0028
+            make(OpCode::Null, &[]),              // 0032
+            make(OpCode::DefineGlobal, &[0_u32]), // 0036
+            make(OpCode::Jump, &[16_u32]),        // 0040
+            make(OpCode::LoopExit, &[]),          // 0044
+            make(OpCode::Null, &[]),              // 0048
+            make(OpCode::Pop, &[]),               // 0052
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(0))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_for_break_and_continue() {
+    let input = "for (x in []) { continue; break; }";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Array, &[0_u32]),        // 0000
+            make(OpCode::Constant, &[0_u32]),     // 0004
+            make(OpCode::LoopEnter, &[]),         // 0008
+            make(OpCode::IterNext, &[56_u32]),    // 0012
+            make(OpCode::DefineGlobal, &[0_u32]), // 0016
+            make(OpCode::LoopUnwind, &[]),        // 0020
+            make(OpCode::Jump, &[44_u32]),        // 0024
+            make(OpCode::LoopUnwind, &[]),        // 0028
+            make(OpCode::Pop, &[]),               // 0032
+            make(OpCode::Pop, &[]),               // 0036
+            make(OpCode::Jump, &[56_u32]),        // 0040
+            make(OpCode::Null, &[]),              // 0044
+            make(OpCode::DefineGlobal, &[0_u32]), // 0048
+            make(OpCode::Jump, &[12_u32]),        // 0052
+            make(OpCode::LoopExit, &[]),          // 0056
+            make(OpCode::Null, &[]),              // 0060
+            make(OpCode::Pop, &[]),               // 0064
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(0))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_for_statement_variable_not_visible_after_loop() {
+    let input = "for (x in [1]) { let y = x; }; y";
+    let (_, error) = parse_and_compile(input);
+    assert_eq!(error, Some(CompilerError::UndefinedVariable));
+}
+
+#[test]
+fn test_compile_assignment() {
+    let input = "let x = 1; x = 2;";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_assignment_error_if_undefined() {
+    let input = "x = 2;";
+    let expected_error = CompilerError::AssignmentWithoutLet(String::from("x"));
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(byte_code, None);
+}
+
+#[test]
+fn test_compile_compound_assignment_error_if_undefined() {
+    let input = "x += 2;";
+    let expected_error = CompilerError::AssignmentWithoutLet(String::from("x"));
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(byte_code, None);
+}
+
+#[test]
+fn test_compile_assignment_error_if_builtin() {
+    let input = "len = 2;";
+    let expected_error = CompilerError::InvalidAssignment;
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(byte_code, None);
+}
+
+#[test]
+fn test_compile_import_error_if_missing() {
+    let input = r#"import "/nonexistent/lib.mk";"#;
+    let (byte_code, error) = parse_and_compile(input);
+    let expected_error = CompilerError::ImportFailed(String::from("/nonexistent/lib.mk: NotFound"));
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(byte_code, None);
+}
+
+#[test]
+fn test_compile_export_as_the_statement_exported() {
+    let (exported, error) = parse_and_compile("export let x = 1; export x; x");
+    assert_eq!(error, None);
+    let (plain, _) = parse_and_compile("let x = 1; x; x");
+    assert_eq!(exported, plain);
+}
+
+// compiles each input in turn, keeping the state between them like the REPL
+fn compile_lines(inputs: &[&str]) -> Compiler {
+    let mut compiler = Compiler::new();
+    for input in inputs {
+        let ast = Parser::parse_program(input).expect("got a parsing error");
+        let mut next = Compiler::new_with_state(compiler.symbol_table, compiler.constants);
+        next.function_cache = compiler.function_cache;
+        next.modules = compiler.modules;
+        next.compile(ast).expect("got a compiler error");
+        compiler = next;
+    }
+    compiler
+}
+
+fn count_functions(compiler: &Compiler) -> usize {
+    compiler
+        .constants
+        .iter()
+        .filter(|constant| matches!(***constant, Object::CompiledFunc(_)))
+        .count()
+}
+
+#[test]
+fn test_compile_redefined_function_shares_constant() {
+    let input = "let add = fn(a) { fn(b) { a + b + 1 } };";
+    let compiler = compile_lines(&[input, input]);
+    // the literal 1 is only added once as well
+    assert_eq!(compiler.constants.len(), 3);
+    assert_eq!(count_functions(&compiler), 2);
+}
+
+#[test]
+fn test_compile_identical_functions_in_one_program_share_constant() {
+    let compiler = compile_lines(&["let f = fn(x) { x * 2 }; let f = fn(x) { x * 2 };"]);
+    assert_eq!(count_functions(&compiler), 1);
+
+    // a function's name is what profiling tells it apart by
+    let compiler = compile_lines(&["let f = fn(x) { x * 2 }; let g = fn(x) { x * 2 };"]);
+    assert_eq!(count_functions(&compiler), 2);
+}
+
+#[test]
+fn test_compile_function_with_different_free_variable_gets_own_constant() {
+    let compiler = compile_lines(&[
+        "let f = fn(a, b) { fn() { a } };",
+        "let f = fn(a, b) { fn() { b } };",
+    ]);
+    assert_eq!(count_functions(&compiler), 4);
+}
+
+#[test]
+fn test_compile_function_reading_different_global_gets_own_constant() {
+    let compiler = compile_lines(&[
+        "let x = 1; let y = 2;",
+        "let f = fn() { x };",
+        "let f = fn() { y };",
+    ]);
+    assert_eq!(count_functions(&compiler), 2);
+}
+
+fn concat(instructions: &[[u8; 4]]) -> Vec<u8> {
+    instructions.iter().flatten().copied().collect()
+}
+
+#[test]
+fn test_optimize_removes_constant_statements() {
+    let (byte_code, _) = parse_and_compile("1; 2; let x = 3; 4; x");
+    let expected = concat(&[
+        make(OpCode::Constant, &[2]),
+        make(OpCode::SetGlobal, &[0]),
+        make(OpCode::GetGlobal, &[0]),
+        make(OpCode::Pop, &[]),
+    ]);
+    assert_eq!(optimize_instructions(byte_code.unwrap().0), expected);
+}
+
+#[test]
+fn test_optimize_keeps_last_popped_constant() {
+    let (byte_code, _) = parse_and_compile("1; 2");
+    let expected = concat(&[make(OpCode::Constant, &[1]), make(OpCode::Pop, &[])]);
+    assert_eq!(optimize_instructions(byte_code.unwrap().0), expected);
+}
+
+#[test]
+fn test_optimize_keeps_pop_targeted_by_jump() {
+    // the else branch's constant is followed by the pop both branches jump to
+    let (byte_code, _) = parse_and_compile("if (true) { 10 } else { 20 }; 1024");
+    let instructions = byte_code.unwrap().0;
+    assert_eq!(optimize_instructions(instructions.clone()), instructions);
+}
+
+#[test]
+fn test_optimize_moves_jump_targets() {
+    let input = "let x = 0; while (x < 2) { 1; x = x + 1; 2; }; 3";
+    let (byte_code, _) = parse_and_compile(input);
+    let expected = concat(&[
+        make(OpCode::Constant, &[0]),       // 0000
+        make(OpCode::SetGlobal, &[0]),      // 0004
+        make(OpCode::LoopEnter, &[]),       // 0008
+        make(OpCode::Constant, &[1]),       // 0012
+        make(OpCode::GetGlobal, &[0]),      // 0016
+        make(OpCode::GreaterThan, &[]),     // 0020
+        make(OpCode::JumpNotTruthy, &[48]), // 0024
+        make(OpCode::GetGlobal, &[0]),      // 0028
+        make(OpCode::Constant, &[3]),       // 0032
+        make(OpCode::Add, &[]),             // 0036
+        make(OpCode::SetGlobal, &[0]),      // 0040
+        make(OpCode::Jump, &[12]),          // 0044
+        make(OpCode::LoopExit, &[]),        // 0048
+        make(OpCode::Null, &[]),            // 0052
+        make(OpCode::Pop, &[]),             // 0056
+        make(OpCode::Constant, &[5]),       // 0060
+        make(OpCode::Pop, &[]),             // 0064
+    ]);
+    assert_eq!(optimize_instructions(byte_code.unwrap().0), expected);
+}
+
+#[test]
+fn test_compile_bitwise_operators() {
+    let input = "1 & 2 | 3 ^ 4 << 5 >> 6";
+    let expected = ByteCode(
+        concat(&[
+            make(OpCode::Constant, &[0]),
+            make(OpCode::Constant, &[1]),
+            make(OpCode::BitAnd, &[]),
+            make(OpCode::Constant, &[2]),
+            make(OpCode::Constant, &[3]),
+            make(OpCode::Constant, &[4]),
+            make(OpCode::ShiftLeft, &[]),
+            make(OpCode::Constant, &[5]),
+            make(OpCode::ShiftRight, &[]),
+            make(OpCode::BitXor, &[]),
+            make(OpCode::BitOr, &[]),
+            make(OpCode::Pop, &[]),
+        ]),
+        (1..=6).map(|n| Rc::new(Object::Integer(n))).collect(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_if_block_shares_function_scope() {
+    // both branches define their own local in the function's frame
+    let input = "fn() { if (true) { let a = 1; } else { let b = 2; }; a }";
+    let expected_function = CompiledFunction {
+        name: String::from("<anonymous #2>"),
+        ..CompiledFunction::new(
+            concat(&[
+                make(OpCode::True, &[]),            // 0000
+                make(OpCode::JumpNotTruthy, &[24]), // 0004
+                make(OpCode::Constant, &[0]),       // 0008
+                make(OpCode::SetLocal, &[0]),       // 0012
+                make(OpCode::Null, &[]),            // 0016
+                make(OpCode::Jump, &[36]),          // 0020
+                make(OpCode::Constant, &[1]),       // 0024
+                make(OpCode::SetLocal, &[1]),       // 0028
+                make(OpCode::Null, &[]),            // 0032
+                make(OpCode::Pop, &[]),             // 0036
+                make(OpCode::GetLocal, &[0]),       // 0040
+                make(OpCode::ReturnValue, &[]),     // 0044
+            ]),
+            2,
+            0,
+        )
+    };
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(
+        byte_code.unwrap().1[2],
+        Rc::new(Object::CompiledFunc(Rc::new(expected_function)))
+    );
+}
+
+#[test]
+fn test_compile_match() {
+    let input = "match (1) { 2 => 3, _ => 4 }; match (5) { 6 => {} }";
+    let expected = ByteCode(
+        concat(&[
+            make(OpCode::Constant, &[0]),       // 0000
+            make(OpCode::Constant, &[1]),       // 0004
+            make(OpCode::Match, &[]),           // 0008
+            make(OpCode::JumpNotTruthy, &[28]), // 0012
+            make(OpCode::Pop, &[]),             // 0016
+            make(OpCode::Constant, &[2]),       // 0020
+            make(OpCode::Jump, &[36]),          // 0024
+            make(OpCode::Pop, &[]),             // 0028
+            make(OpCode::Constant, &[3]),       // 0032
+            make(OpCode::Pop, &[]),             // 0036
+            make(OpCode::Constant, &[4]),       // 0040
+            make(OpCode::Constant, &[5]),       // 0044
+            make(OpCode::Match, &[]),           // 0048
+            make(OpCode::JumpNotTruthy, &[68]), // 0052
+            make(OpCode::Pop, &[]),             // 0056
+            make(OpCode::Null, &[]),            // 0060
+            make(OpCode::Jump, &[76]),          // 0064
+            make(OpCode::Pop, &[]),             // 0068
+            make(OpCode::Null, &[]),            // 0072
+            make(OpCode::Pop, &[]),             // 0076
+        ]),
+        (1..=6).map(|n| Rc::new(Object::Integer(n))).collect(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_default_parameters() {
+    // a call with one argument starts at 0000 and with two at 0004
+    let input = "fn(a, b = 1) { a }";
+    let expected_function = CompiledFunction {
+        name: String::from("<anonymous #1>"),
+        num_defaults: 1,
+        parameters: ["a", "b"].map(String::from).to_vec(),
+        ..CompiledFunction::new(
+            concat(&[
+                make(OpCode::Jump, &[8]),       // 0000
+                make(OpCode::Jump, &[16]),      // 0004
+                make(OpCode::Constant, &[0]),   // 0008
+                make(OpCode::SetLocal, &[1]),   // 0012
+                make(OpCode::GetLocal, &[0]),   // 0016
+                make(OpCode::ReturnValue, &[]), // 0020
+            ]),
+            2,
+            2,
+        )
+    };
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    let ByteCode(_, constants) = byte_code.unwrap();
+    assert_eq!(
+        constants[1],
+        Rc::new(Object::CompiledFunc(Rc::new(expected_function)))
+    );
+    let Object::CompiledFunc(function) = &*constants[1] else {
+        unreachable!()
+    };
+    assert_eq!(function.entry_point(0), None);
+    assert_eq!(function.entry_point(1), Some(0));
+    assert_eq!(function.entry_point(2), Some(4));
+    assert_eq!(function.entry_point(3), None);
+}
+
+#[test]
+fn test_compile_spread_arguments() {
+    let input = "let f = len; let a = []; f(1, 2, ...a, 3)";
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    let ByteCode(instructions, _) = byte_code.unwrap();
+    let expected = concat(&[
+        make(OpCode::GetBuiltin, &[0]),
+        make(OpCode::SetGlobal, &[0]),
+        make(OpCode::Array, &[0]),
+        make(OpCode::SetGlobal, &[1]),
+        make(OpCode::GetGlobal, &[0]),
+        make(OpCode::Constant, &[0]),
+        make(OpCode::Constant, &[1]),
+        make(OpCode::Array, &[2]),
+        make(OpCode::GetGlobal, &[1]),
+        make(OpCode::Constant, &[2]),
+        make(OpCode::Array, &[1]),
+        make(OpCode::CallSpread, &[3]),
+        make(OpCode::Pop, &[]),
+    ]);
+    assert_eq!(instructions, expected);
+}
+
+#[test]
+fn test_compile_named_arguments() {
+    let input = "let f = len; f(1, a: 2, b: 3)";
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    let ByteCode(instructions, constants) = byte_code.unwrap();
+    let expected = concat(&[
+        make(OpCode::GetBuiltin, &[0]),
+        make(OpCode::SetGlobal, &[0]),
+        make(OpCode::GetGlobal, &[0]),
+        make(OpCode::Constant, &[0]),
+        make(OpCode::Array, &[1]),
+        make(OpCode::Constant, &[1]),
+        make(OpCode::Constant, &[2]),
+        make(OpCode::CallNamed, &[3, 1]),
+        make(OpCode::Pop, &[]),
+    ]);
+    assert_eq!(instructions, expected);
+    assert_eq!(constants[3], Rc::new(value!(["a", "b"])));
+}
+
+#[test]
+fn test_compile_unexpanded_macro() {
+    let (_, error) = parse_and_compile("let f = fn() { macro(x) { x } };");
+    assert_eq!(error, Some(CompilerError::UnexpandedMacro));
+}
+
+#[test]
+fn test_compile_reassign_constant() {
+    let tests = [
+        "const PI = 3; PI = 4;",
+        "const PI = 3; PI += 1;",
+        "let x = 1; const PI = 3; let x = 2; PI = x;",
+        "const PI = 3; let f = fn() { PI = 4; };",
+        "const PI = 3; let f = fn() { fn() { PI = 4; } };",
+        "let f = fn() { const PI = 3; fn() { PI = 4; } };",
+        "if (true) { const PI = 3; PI = 4; }",
+    ];
+    for input in tests {
+        let (_, error) = parse_and_compile(input);
+        assert_eq!(
+            error,
+            Some(CompilerError::ReassignConstant(String::from("PI"))),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_compile_let_destructure() {
+    let input = "let [a, b, ...rest] = [];";
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    let ByteCode(instructions, _) = byte_code.unwrap();
+    let expected = concat(&[
+        make(OpCode::Array, &[0]),
+        make(OpCode::Destructure, &[2, 1]),
+        make(OpCode::SetGlobal, &[0]),
+        make(OpCode::SetGlobal, &[1]),
+        make(OpCode::SetGlobal, &[2]),
+    ]);
+    assert_eq!(instructions, expected);
+}
+
+#[test]
+fn test_compile_string_template() {
+    let input = r#""a{1}b""#;
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    let ByteCode(instructions, constants) = byte_code.unwrap();
+    let expected = concat(&[
+        make(OpCode::Constant, &[0]),
+        make(OpCode::Constant, &[1]),
+        make(OpCode::Constant, &[2]),
+        make(OpCode::Template, &[3]),
+        make(OpCode::Pop, &[]),
+    ]);
+    assert_eq!(instructions, expected);
+    assert_eq!(
+        constants,
+        [
+            Rc::new(Object::String(String::from("a"))),
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::String(String::from("b"))),
+        ]
+    );
+}
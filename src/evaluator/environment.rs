@@ -1,12 +1,19 @@
 use crate::object::Object;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
 pub struct Environment {
     store: HashMap<String, Rc<Object>>,
     outer: Option<Rc<RefCell<Environment>>>,
+    // names that will be bound later on, so that reading one too early can be
+    // told apart from reading a name that doesn't exist
+    pending: HashSet<String>,
+    // names bound in this scope that modules importing it can see
+    exported: HashSet<String>,
+    // names bound in this scope with `const`
+    constants: HashSet<String>,
 }
 
 #[allow(unused)]
@@ -15,6 +22,9 @@ impl Environment {
         Environment {
             store: HashMap::new(),
             outer: None,
+            pending: HashSet::new(),
+            exported: HashSet::new(),
+            constants: HashSet::new(),
         }
     }
 
@@ -22,6 +32,9 @@ impl Environment {
         Rc::new(RefCell::new(Environment {
             store: HashMap::new(),
             outer: Some(outer),
+            pending: HashSet::new(),
+            exported: HashSet::new(),
+            constants: HashSet::new(),
         }))
     }
 
@@ -40,8 +53,64 @@ impl Environment {
         result
     }
 
+    // binds `key` in this scope, shadowing any binding of the same name in an
+    // outer scope
+    pub fn define(&mut self, key: &str, val: Rc<Object>) {
+        self.pending.remove(key);
+        self.constants.remove(key);
+        self.store.insert(key.to_string(), val);
+    }
+
+    // like `define`, but the binding can't be changed with `set` afterwards
+    pub fn define_constant(&mut self, key: &str, val: Rc<Object>) {
+        self.define(key, val);
+        self.constants.insert(key.to_string());
+    }
+
+    // whether the nearest binding of `key` was made with `define_constant`
+    pub fn is_constant(&self, key: &str) -> bool {
+        match self.store.contains_key(key) {
+            true => self.constants.contains(key),
+            false => self
+                .outer
+                .as_ref()
+                .is_some_and(|outer| outer.borrow().is_constant(key)),
+        }
+    }
+
+    // marks `key` as bound later on in this scope
+    pub fn declare(&mut self, key: &str) {
+        if !self.store.contains_key(key) {
+            self.pending.insert(key.to_string());
+        }
+    }
+
+    pub fn is_declared(&self, key: &str) -> bool {
+        self.pending.contains(key)
+            || self
+                .outer
+                .as_ref()
+                .is_some_and(|outer| outer.borrow().is_declared(key))
+    }
+
+    // makes the binding of `key` in this scope visible to importers
+    pub fn export(&mut self, key: &str) {
+        self.exported.insert(key.to_string());
+    }
+
+    // the exported bindings of this scope, with their current values
+    pub fn exports(&self) -> Vec<(String, Rc<Object>)> {
+        self.store
+            .iter()
+            .filter(|(key, _)| self.exported.contains(*key))
+            .map(|(key, val)| (key.to_string(), Rc::clone(val)))
+            .collect()
+    }
+
+    // updates the nearest existing binding of `key`, or defines it in this
+    // scope if there is none
     pub fn set(&mut self, key: &str, val: Rc<Object>) {
-        if self.store.get(key).is_some() {
+        if self.store.contains_key(key) {
             self.store.insert(key.to_string(), Rc::clone(&val));
             return;
         } else if let Some(outer) = &self.outer {
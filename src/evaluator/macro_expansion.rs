@@ -0,0 +1,139 @@
+use super::environment::Environment;
+use super::{bind_arguments, eval_expression, eval_statement, order_named_arguments, EvalError};
+use crate::object::{Function, Object};
+use crate::parser::ast::{CallArg, Expression, Program, Statement};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Macros are expanded before a program is compiled or evaluated, so both
+// backends run the expanded code. A macro's body is always run by the
+// evaluator, with each argument bound to the quoted code it was given, and
+// must return quoted code to put in place of the call.
+
+/// Takes every top-level `let name = macro(...) { ... };` out of `program`,
+/// binding the macro in `env`, where it stays for any later program.
+pub fn define_macros(program: Program, env: &Rc<RefCell<Environment>>) -> Program {
+    let Program(statements) = program;
+    let statements = statements
+        .into_iter()
+        .filter_map(|statement| match statement {
+            Statement::Let(Expression::Identifier(name), Expression::Macro(parameters, body)) => {
+                let parameters = parameters
+                    .into_iter()
+                    .filter_map(|(parameter, default)| match parameter {
+                        Expression::Identifier(id) => Some((id, default)),
+                        _ => None,
+                    })
+                    .collect();
+                let macro_ = Function {
+                    name: String::new(),
+                    parameters,
+                    body: *body,
+                    env: Rc::clone(env),
+                };
+                env.borrow_mut()
+                    .define(&name, Rc::new(Object::Macro(macro_)));
+                None
+            }
+            statement => Some(statement),
+        })
+        .collect();
+    Program(statements)
+}
+
+/// Replaces each call of a macro bound in `env` with the code it returns.
+/// Arguments are expanded before the macro is called, but the code returned
+/// is not expanded again.
+pub fn expand_macros(
+    program: Program,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Program, EvalError> {
+    program.modify(&mut |expression| match expression {
+        Expression::Call(function, args) => match &*function {
+            Expression::Identifier(name) => match env.borrow().get(name) {
+                Some(object) if matches!(*object, Object::Macro(_)) => expand_call(object, args),
+                _ => Ok(Expression::Call(function, args)),
+            },
+            _ => Ok(Expression::Call(function, args)),
+        },
+        expression => Ok(expression),
+    })
+}
+
+fn expand_call(object: Rc<Object>, args: Vec<CallArg>) -> Result<Expression, EvalError> {
+    let Object::Macro(macro_) = &*object else {
+        unreachable!("only macros are expanded")
+    };
+    let mut positional = vec![];
+    let mut named = vec![];
+    for arg in args {
+        match arg {
+            CallArg::Expression(code) => positional.push(Rc::new(Object::Quote(code))),
+            CallArg::Spread(_) => return Err(EvalError::MacroSpread),
+            CallArg::Named(name, code) => named.push((name, Rc::new(Object::Quote(code)))),
+        }
+    }
+    let args = match named.is_empty() {
+        true => positional,
+        false => order_named_arguments(&object, positional, named)?,
+    };
+
+    let env = bind_arguments(macro_, &object, &args)?;
+    let mut result = eval_statement(&macro_.body, env)?;
+    if let Object::Return(value) = &*result {
+        result = Rc::clone(value);
+    }
+
+    match &*result {
+        Object::Quote(code) => Ok(code.clone()),
+        other => Err(EvalError::NotQuoted(other.type_name())),
+    }
+}
+
+// `quote(code)` evaluates to `code` itself, except that each `unquote(value)`
+// in it is evaluated and replaced by code for the resulting value
+pub(super) fn eval_quote(
+    args: &[CallArg],
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let code = single_argument(args)?.clone();
+    let code = code.modify(&mut |expression| match expression {
+        Expression::Call(function, args)
+            if matches!(&*function, Expression::Identifier(name) if name == "unquote") =>
+        {
+            let value = eval_expression(single_argument(&args)?, Rc::clone(&env))?;
+            unquote(&value)
+        }
+        expression => Ok(expression),
+    })?;
+    Ok(Rc::new(Object::Quote(code)))
+}
+
+fn single_argument(args: &[CallArg]) -> Result<&Expression, EvalError> {
+    match args {
+        [CallArg::Expression(code)] => Ok(code),
+        [CallArg::Spread(_)] => Err(EvalError::MacroSpread),
+        [CallArg::Named(name, _)] => Err(EvalError::UnknownArgument(name.clone())),
+        _ => Err(EvalError::IncorrectNumberOfArgs),
+    }
+}
+
+// the code for a literal that evaluates to `value`
+fn unquote(value: &Object) -> Result<Expression, EvalError> {
+    match value {
+        Object::Null => Ok(Expression::Null),
+        Object::Integer(int) => Ok(Expression::Integer(*int)),
+        Object::Float(float) => Ok(Expression::Float(*float)),
+        Object::Boolean(bool) => Ok(Expression::Boolean(*bool)),
+        Object::String(string) => Ok(Expression::String(string.clone())),
+        Object::Quote(code) => Ok(code.clone()),
+        Object::Array(elements) => Ok(Expression::Array(
+            elements
+                .borrow()
+                .iter()
+                .map(|element| unquote(element))
+                .collect::<Result<_, _>>()?,
+        )),
+        other => Err(EvalError::CannotUnquote(other.type_name())),
+    }
+}
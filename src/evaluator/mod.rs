@@ -1,20 +1,39 @@
 use crate::evaluator::environment::Environment;
-use crate::object::builtins::{Builtin, BuiltinError};
-use crate::object::{Function, Hashable, Object};
-use crate::parser::ast::{Expression, Infix, Prefix, Program, Statement};
+use crate::object::builtins::{resolve_index, Builtin, BuiltinError};
+use crate::object::{
+    deep_equal, order_arguments, range, repeat_string, slice, ArgumentError, Function, Hashable,
+    Object,
+};
+use crate::parser::ast::{CallArg, Expression, Infix, Prefix, Program, Statement, TemplatePart};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::ErrorKind;
 use std::rc::Rc;
 
 pub mod environment;
+pub mod macro_expansion;
+pub mod module;
 mod tests;
 
-#[allow(dead_code)]
 pub fn eval(program: Program, env: Rc<RefCell<Environment>>) -> Result<Rc<Object>, EvalError> {
     let Program(statements) = program;
     eval_statements(&statements, env)
 }
 
+// like `eval`, but declares every top-level name first, so that reading one
+// before its `let` has run is reported as such rather than as undefined; the
+// statements still run in order
+pub fn eval_hoisted(
+    program: Program,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let Program(statements) = &program;
+    for name in statements.iter().filter_map(Statement::let_name) {
+        env.borrow_mut().declare(name);
+    }
+    eval(program, env)
+}
+
 fn eval_statements(
     statements: &[Statement],
     env: Rc<RefCell<Environment>>,
@@ -36,19 +55,28 @@ fn eval_statement(
     statement: &Statement,
     env: Rc<RefCell<Environment>>,
 ) -> Result<Rc<Object>, EvalError> {
-    Ok(match statement {
-        Statement::Let(id, val) => {
-            eval_let_statement(id, val, env)?;
-            Rc::new(Object::Null)
-        }
-        Statement::Return(exp) => Rc::new(Object::Return(Rc::clone(&eval_expression(exp, env)?))),
-        Statement::Expression(exp) => eval_expression(exp, env)?,
-        Statement::BlockStatement(statements) => eval_block_statement(statements, env)?,
-        Statement::Assignment(id, val) => {
-            eval_assignment_statement(id, val, env)?;
-            Rc::new(Object::Null)
+    match statement {
+        Statement::Let(..) | Statement::Const(..) | Statement::LetDestructure(..) => {
+            eval_binding_statement(statement, env)?
         }
-    })
+        Statement::Assignment(id, val) => eval_assignment_statement(id, val, env)?,
+        Statement::IndexAssignment(collection, index, val) => {
+            eval_index_assignment_statement(collection, index, val, env)?
+        }
+        Statement::Use(path) => eval_use_statement(path, env)?,
+        Statement::Export(statement) => eval_export_statement(statement, env)?,
+        Statement::Return(exp) => return Ok(Rc::new(Object::Return(eval_expression(exp, env)?))),
+        Statement::Expression(exp) => return eval_expression(exp, env),
+        Statement::Block(statements) => return eval_block_statement(statements, env),
+        Statement::For(variable, iterable, body) => {
+            return eval_for_statement(variable, iterable, body, env)
+        }
+        Statement::Break => return Ok(Rc::new(Object::Break)),
+        Statement::Continue => return Ok(Rc::new(Object::Continue)),
+    }
+    // the statements above that are only run for their effect all share one
+    // null, which keeps this (deeply recursive) function's stack frame small
+    Ok(Rc::new(Object::Null))
 }
 
 fn eval_assignment_statement(
@@ -58,7 +86,10 @@ fn eval_assignment_statement(
 ) -> Result<(), EvalError> {
     if let Expression::Identifier(key) = id {
         if env.borrow().get(key).is_none() {
-            return Err(EvalError::UnrecognisedIdentifier);
+            return Err(EvalError::AssignmentWithoutLet(key.to_string()));
+        }
+        if env.borrow().is_constant(key) {
+            return Err(EvalError::ReassignConstant(key.to_string()));
         }
         let value = eval_expression(val, Rc::clone(&env))?;
         env.borrow_mut().set(key, value);
@@ -66,6 +97,75 @@ fn eval_assignment_statement(
     Ok(())
 }
 
+// changes the collection in place, so every value sharing it sees the change;
+// an array can only be assigned within its bounds (counting back from the end
+// for a negative index), but a hash gains any key
+// that it didn't have
+fn eval_index_assignment_statement(
+    collection: &Expression,
+    index: &Expression,
+    val: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<(), EvalError> {
+    let collection = eval_expression(collection, Rc::clone(&env))?;
+    let index = eval_expression(index, Rc::clone(&env))?;
+    let value = eval_expression(val, env)?;
+
+    match (&*collection, &*index) {
+        (Object::Array(array), Object::Integer(idx)) => {
+            let mut array = array.borrow_mut();
+            let idx = resolve_index(*idx, array.len())?;
+            array[idx] = value;
+        }
+        (Object::Hash(map), index) => {
+            let key = Hashable::try_from(index)
+                .map_err(|type_name| EvalError::UnhashableKey(type_name.to_string()))?;
+            map.borrow_mut().insert(key, value);
+        }
+        _ => return Err(EvalError::IncompatibleTypes),
+    }
+    Ok(())
+}
+
+// binds every name the module exports in the current scope; a module is only
+// run the first time it is imported
+fn eval_use_statement(path: &str, env: Rc<RefCell<Environment>>) -> Result<(), EvalError> {
+    let module = module::import(path)?;
+    for (name, value) in module.borrow().exports() {
+        env.borrow_mut().define(&name, value);
+    }
+    Ok(())
+}
+
+// `export name;` only checks that `name` exists before exporting it
+fn eval_export_statement(
+    statement: &Statement,
+    env: Rc<RefCell<Environment>>,
+) -> Result<(), EvalError> {
+    eval_statement(statement, Rc::clone(&env))?;
+    if let Statement::Let(Expression::Identifier(name), _)
+    | Statement::Const(Expression::Identifier(name), _)
+    | Statement::Expression(Expression::Identifier(name)) = statement
+    {
+        env.borrow_mut().export(name);
+    }
+    Ok(())
+}
+
+// the statements that bind names share one arm in `eval_statement`, as each
+// arm there adds to its stack frame
+fn eval_binding_statement(
+    statement: &Statement,
+    env: Rc<RefCell<Environment>>,
+) -> Result<(), EvalError> {
+    match statement {
+        Statement::Let(id, val) => eval_let_statement(id, val, env),
+        Statement::Const(id, val) => eval_const_statement(id, val, env),
+        Statement::LetDestructure(names, rest, val) => eval_let_destructure(names, rest, val, env),
+        _ => unreachable!("only statements that bind names are evaluated here"),
+    }
+}
+
 fn eval_let_statement(
     id: &Expression,
     val: &Expression,
@@ -73,7 +173,47 @@ fn eval_let_statement(
 ) -> Result<(), EvalError> {
     if let Expression::Identifier(key) = id {
         let value = eval_expression(val, Rc::clone(&env))?;
-        env.borrow_mut().set(key, value);
+        env.borrow_mut().define(key, value);
+    }
+    Ok(())
+}
+
+fn eval_const_statement(
+    id: &Expression,
+    val: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<(), EvalError> {
+    if let Expression::Identifier(key) = id {
+        let value = eval_expression(val, Rc::clone(&env))?;
+        env.borrow_mut().define_constant(key, value);
+    }
+    Ok(())
+}
+
+// binds each name to the array element at its position; any elements left
+// over are ignored, or bound as an array to the rest
+fn eval_let_destructure(
+    names: &[String],
+    rest: &Option<String>,
+    val: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<(), EvalError> {
+    let value = eval_expression(val, Rc::clone(&env))?;
+    let Object::Array(elements) = &*value else {
+        return Err(EvalError::CannotDestructure(value.type_name()));
+    };
+    let elements = elements.borrow();
+    if elements.len() < names.len() {
+        return Err(EvalError::TooFewElements(names.len(), elements.len()));
+    }
+
+    let mut env = env.borrow_mut();
+    for (name, element) in names.iter().zip(elements.iter()) {
+        env.define(name, Rc::clone(element));
+    }
+    if let Some(rest) = rest {
+        let rest_elements = elements[names.len()..].to_vec();
+        env.define(rest, Rc::new(Object::Array(RefCell::new(rest_elements))));
     }
     Ok(())
 }
@@ -86,7 +226,7 @@ fn eval_block_statement(
 
     for statement in statements.iter() {
         result = eval_statement(statement, Rc::clone(&env))?;
-        if let Object::Return(_) = *result {
+        if let Object::Return(_) | Object::Break | Object::Continue = *result {
             break;
         }
     }
@@ -101,46 +241,107 @@ fn eval_expression(
     match expression {
         Expression::Identifier(id) => eval_identifier_expression(id, env),
         Expression::Integer(int) => Ok(Rc::new(Object::Integer(*int))),
+        Expression::Float(float) => Ok(Rc::new(Object::Float(*float))),
         Expression::Prefix(operator, operand) => eval_prefix_expressions(operator, operand, env),
         Expression::Infix(left, infix, right) => eval_infix_expression(left, infix, right, env),
         Expression::Boolean(val) => Ok(Rc::new(Object::Boolean(*val))),
+        Expression::Null => Ok(Rc::new(Object::Null)),
         Expression::If(condition, if_block, else_block) => {
             eval_if_expression(condition, if_block, else_block, env)
         }
-        Expression::Function(parameters, body, _) => {
-            eval_function_expression(parameters, body, env)
+        Expression::Function(parameters, body, name) => {
+            eval_function_expression(name, parameters, body, env)
         }
         Expression::Call(func, args) => eval_function_call_expression(func, args, env),
         Expression::String(string) => Ok(Rc::new(Object::String(string.clone()))),
+        Expression::StringTemplate(parts) => eval_string_template(parts, env),
         Expression::Array(elements) => eval_array_literal(elements, env),
         Expression::Index(exp, index) => eval_index_expression(exp, index, env),
+        Expression::Range(start, end) => eval_range_expression(start, end, env),
+        Expression::Slice(exp, start, end) => {
+            eval_slice_expression(exp, start.as_deref(), end.as_deref(), env)
+        }
         Expression::Hash(pairs) => eval_hash_literal(pairs, env),
         Expression::While(condition, loop_block) => {
             eval_while_expression(condition, loop_block, env)
         }
+        Expression::Match(value, arms, default) => eval_match_expression(value, arms, default, env),
+        Expression::Macro(..) => Err(EvalError::UnexpandedMacro),
     }
 }
 
+// each expression's value is written as it would be by `puts`
+fn eval_string_template(
+    parts: &[TemplatePart],
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let mut string = String::new();
+    for part in parts {
+        match part {
+            TemplatePart::Literal(text) => string.push_str(text),
+            TemplatePart::Expression(expression) => {
+                let value = eval_expression(expression, Rc::clone(&env))?;
+                string.push_str(&value.to_string());
+            }
+        }
+    }
+    Ok(Rc::new(Object::String(string)))
+}
+
 fn eval_while_expression(
     condition: &Expression,
     loop_block: &Statement,
     env: Rc<RefCell<Environment>>,
 ) -> Result<Rc<Object>, EvalError> {
-    let mut result = Rc::new(Object::Null);
-
+    // a loop evaluates to null unless it is exited with `return`
     loop {
-        let check = condition.clone();
-        if !is_truthy(&*eval_expression(&check, Rc::clone(&env))?) {
+        if !is_truthy(&*eval_expression(condition, Rc::clone(&env))?) {
             break;
         }
 
-        result = eval_statement(loop_block, Rc::clone(&env))?;
-        if let Object::Return(_) = &*result {
+        let result = eval_statement(loop_block, Rc::clone(&env))?;
+        match &*result {
+            Object::Return(_) => return Ok(result),
+            Object::Break => break,
+            _ => {}
+        }
+    }
+
+    Ok(Rc::new(Object::Null))
+}
+
+fn eval_for_statement(
+    variable: &Expression,
+    iterable: &Expression,
+    body: &Statement,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let iterable = eval_expression(iterable, Rc::clone(&env))?;
+    let Object::Array(elements) = &*iterable else {
+        return Err(EvalError::NotIterable(iterable.type_name().to_string()));
+    };
+    // each iteration gets its own environment so that closures created in
+    // the body capture that iteration's bindings; the body may assign to the
+    // array, so every iteration reads the next element afresh, as the VM does
+    for i in 0.. {
+        let Some(element) = elements.borrow().get(i).cloned() else {
             break;
+        };
+        let loop_env = Environment::new_enclosed(Rc::clone(&env));
+        if let Expression::Identifier(id) = variable {
+            loop_env.borrow_mut().define(id, element);
+        }
+
+        let result = eval_statement(body, loop_env)?;
+        match &*result {
+            Object::Return(_) => return Ok(result),
+            Object::Break => break,
+            _ => {}
         }
     }
 
-    Ok(result)
+    // like `while`, a loop evaluates to null unless it is exited with `return`
+    Ok(Rc::new(Object::Null))
 }
 
 fn eval_hash_literal(
@@ -153,17 +354,13 @@ fn eval_hash_literal(
         let key = eval_expression(k, Rc::clone(&env))?;
         let value = eval_expression(v, Rc::clone(&env))?;
 
-        let key = match &*key {
-            Object::String(key) => Hashable::String(key.clone()),
-            Object::Integer(key) => Hashable::Integer(*key),
-            Object::Boolean(key) => Hashable::Boolean(*key),
-            _ => return Err(EvalError::IncompatibleTypes),
-        };
+        let key = Hashable::try_from(&*key)
+            .map_err(|type_name| EvalError::UnhashableKey(type_name.to_string()))?;
 
         map.insert(key, value);
     }
 
-    Ok(Rc::new(Object::Hash(map)))
+    Ok(Rc::new(Object::Hash(RefCell::new(map))))
 }
 
 fn eval_index_expression(
@@ -177,34 +374,64 @@ fn eval_index_expression(
     match &*collection {
         Object::Array(array) => match &*index {
             Object::Integer(idx) => {
-                if *idx < 0 || *idx as usize >= array.len() {
-                    return Err(EvalError::IndexOutOfBounds);
-                }
-                // safe to unwrap due to bound check
-                let result = array.get(*idx as usize).unwrap();
-                Ok(Rc::clone(result))
+                let array = array.borrow();
+                let idx = resolve_index(*idx, array.len())?;
+                Ok(Rc::clone(&array[idx]))
             }
             _ => Err(EvalError::IncompatibleTypes),
         },
-        Object::Hash(map) => Ok(match &*index {
-            Object::String(key) => match map.get(&Hashable::String(key.clone())) {
-                Some(object) => Rc::clone(object),
-                None => Rc::new(Object::Null),
-            },
-            Object::Integer(key) => match map.get(&Hashable::Integer(*key)) {
-                Some(object) => Rc::clone(object),
-                None => Rc::new(Object::Null),
-            },
-            Object::Boolean(key) => match map.get(&Hashable::Boolean(*key)) {
+        Object::Hash(map) => {
+            let key = Hashable::try_from(&*index)
+                .map_err(|type_name| EvalError::UnhashableKey(type_name.to_string()))?;
+            Ok(match map.borrow().get(&key) {
                 Some(object) => Rc::clone(object),
                 None => Rc::new(Object::Null),
-            },
-            _ => return Err(EvalError::IncompatibleTypes),
-        }),
+            })
+        }
         _ => Err(EvalError::IncompatibleTypes),
     }
 }
 
+fn eval_range_expression(
+    start: &Expression,
+    end: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let start = eval_expression(start, Rc::clone(&env))?;
+    let end = eval_expression(end, env)?;
+    match (&*start, &*end) {
+        (Object::Integer(start), Object::Integer(end)) => range(*start, *end)
+            .map(Rc::new)
+            .ok_or(EvalError::RangeTooLong(*start, *end)),
+        (start, end) => Err(EvalError::UnsupportedOperands(
+            "..",
+            start.type_name(),
+            end.type_name(),
+        )),
+    }
+}
+
+fn eval_slice_expression(
+    exp: &Expression,
+    start: Option<&Expression>,
+    end: Option<&Expression>,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let collection = eval_expression(exp, Rc::clone(&env))?;
+    // a bound that is left out is null, which slices from the beginning or
+    // to the end
+    let bound = |bound: Option<&Expression>| match bound {
+        Some(bound) => eval_expression(bound, Rc::clone(&env)),
+        None => Ok(Rc::new(Object::Null)),
+    };
+    let start = bound(start)?;
+    let end = bound(end)?;
+
+    slice(&collection, &start, &end)
+        .map(Rc::new)
+        .ok_or(EvalError::IncompatibleTypes)
+}
+
 fn eval_array_literal(
     expressions: &[Expression],
     env: Rc<RefCell<Environment>>,
@@ -216,37 +443,60 @@ fn eval_array_literal(
         array.push(object);
     }
 
-    Ok(Rc::new(Object::Array(array)))
+    Ok(Rc::new(Object::Array(RefCell::new(array))))
 }
 
 fn eval_function_call_expression(
     func: &Expression,
-    args: &[Expression],
+    args: &[CallArg],
     env: Rc<RefCell<Environment>>,
 ) -> Result<Rc<Object>, EvalError> {
+    if matches!(func, Expression::Identifier(name) if name == "quote") {
+        return macro_expansion::eval_quote(args, env);
+    }
+
     let function = eval_expression(func, Rc::clone(&env))?;
-    let arguments: Vec<Rc<Object>> = args
-        .iter()
-        .map(|exp| eval_expression(exp, Rc::clone(&env)))
-        .collect::<Result<Vec<Rc<Object>>, EvalError>>()?;
+    let mut arguments = Vec::with_capacity(args.len());
+    let mut named = vec![];
+    for arg in args {
+        match arg {
+            CallArg::Expression(exp) => arguments.push(eval_expression(exp, Rc::clone(&env))?),
+            CallArg::Spread(exp) => match &*eval_expression(exp, Rc::clone(&env))? {
+                Object::Array(elements) => arguments.extend(elements.borrow().iter().cloned()),
+                other => return Err(EvalError::CannotSpread(other.type_name())),
+            },
+            CallArg::Named(name, exp) => {
+                named.push((name.clone(), eval_expression(exp, Rc::clone(&env))?))
+            }
+        }
+    }
+    if !named.is_empty() {
+        arguments = order_named_arguments(&function, arguments, named)?;
+    }
 
     apply_function(function, &arguments)
 }
 
+// puts each named argument in its parameter's place; a builtin has no
+// parameter names, so can't take any
+fn order_named_arguments(
+    function: &Object,
+    positional: Vec<Rc<Object>>,
+    named: Vec<(String, Rc<Object>)>,
+) -> Result<Vec<Rc<Object>>, EvalError> {
+    let parameters = match function {
+        Object::Function(function) | Object::Macro(function) => &function.parameters,
+        Object::Builtin(_) => return Err(EvalError::UnknownArgument(named[0].0.clone())),
+        _ => return Err(EvalError::NotAFunction),
+    };
+    let parameters = parameters.iter().map(|(name, _)| name.as_str());
+    Ok(order_arguments(parameters, positional, named)?)
+}
+
 fn apply_function(func: Rc<Object>, args: &[Rc<Object>]) -> Result<Rc<Object>, EvalError> {
     match &*func {
         Object::Function(function) => {
-            let extended_env = Environment::new_enclosed(Rc::clone(&function.env));
-
-            if function.parameters.len() != args.len() {
-                return Err(EvalError::IncorrectNumberOfArgs);
-            }
-
-            function
-                .parameters
-                .iter()
-                .zip(args.iter())
-                .for_each(|(p, a)| extended_env.borrow_mut().set(p, Rc::clone(a)));
+            let extended_env = bind_arguments(function, &func, args)?;
 
             let mut result = eval_statement(&function.body, extended_env)?;
 
@@ -256,27 +506,57 @@ fn apply_function(func: Rc<Object>, args: &[Rc<Object>]) -> Result<Rc<Object>, E
 
             Ok(result)
         }
-        Object::Builtin(builtin) => builtin.apply(args).map_err(|e| match e {
-            BuiltinError::IncompatibleTypes => EvalError::IncompatibleTypes,
-            BuiltinError::IncorrectNumberOfArgs => EvalError::IncorrectNumberOfArgs,
+        Object::Builtin(builtin) => builtin.apply(args, &mut |func, args| {
+            apply_function(Rc::clone(func), args)
         }),
         _ => Err(EvalError::NotAFunction),
     }
 }
 
+// returns a new environment for a call to `function`, with its parameters
+// bound to `args`; a default is evaluated on each call that leaves its
+// argument out, and can refer to the parameters before it. A function bound
+// with `let` can always call itself by that name, as in the VM, even once the
+// name is bound to something else where the function was defined
+fn bind_arguments(
+    function: &Function,
+    this: &Rc<Object>,
+    args: &[Rc<Object>],
+) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    if args.len() > function.parameters.len() {
+        return Err(EvalError::IncorrectNumberOfArgs);
+    }
+
+    let env = Environment::new_enclosed(Rc::clone(&function.env));
+    if !function.name.is_empty() {
+        env.borrow_mut().define(&function.name, Rc::clone(this));
+    }
+    for (i, (parameter, default)) in function.parameters.iter().enumerate() {
+        let value = match (args.get(i), default) {
+            (Some(arg), _) => Rc::clone(arg),
+            (None, Some(default)) => eval_expression(default, Rc::clone(&env))?,
+            (None, None) => return Err(EvalError::IncorrectNumberOfArgs),
+        };
+        env.borrow_mut().define(parameter, value);
+    }
+    Ok(env)
+}
+
 fn eval_function_expression(
-    parameters: &[Expression],
+    name: &str,
+    parameters: &[(Expression, Option<Expression>)],
     body: &Statement,
     env: Rc<RefCell<Environment>>,
 ) -> Result<Rc<Object>, EvalError> {
     let mut params = vec![];
-    parameters.iter().for_each(|exp| {
+    parameters.iter().for_each(|(exp, default)| {
         if let Expression::Identifier(id) = exp {
-            params.push(id.to_string());
+            params.push((id.to_string(), default.clone()));
         }
     });
 
     Ok(Rc::new(Object::Function(Function {
+        name: name.to_string(),
         parameters: params,
         body: body.clone(),
         env,
@@ -290,7 +570,10 @@ fn eval_identifier_expression(
     match env.borrow().get(id) {
         Some(object) => Ok(object),
         None => match Builtin::get(id) {
-            None => Err(EvalError::UnrecognisedIdentifier),
+            None if env.borrow().is_declared(id) => {
+                Err(EvalError::UsedBeforeDefinition(id.to_string()))
+            }
+            None => Err(EvalError::UndefinedVariable(id.to_string())),
             Some(object) => Ok(object),
         },
     }
@@ -313,11 +596,35 @@ fn eval_if_expression(
     }
 }
 
+// arms are tried in order, and their patterns are only evaluated until one
+// matches; values of different types never match
+fn eval_match_expression(
+    value: &Expression,
+    arms: &[(Expression, Statement)],
+    default: &Option<Box<Statement>>,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let value = eval_expression(value, Rc::clone(&env))?;
+
+    for (pattern, block) in arms {
+        let pattern = eval_expression(pattern, Rc::clone(&env))?;
+        if deep_equal(&value, &pattern).ok_or(EvalError::NestingTooDeep)? {
+            return eval_statement(block, env);
+        }
+    }
+
+    match default {
+        Some(block) => eval_statement(block, env),
+        None => Ok(Rc::new(Object::Null)),
+    }
+}
+
 fn is_truthy(object: &Object) -> bool {
-    !matches!(
-        object,
-        Object::Boolean(false) | Object::Integer(0) | Object::Null
-    )
+    match object {
+        Object::Boolean(false) | Object::Integer(0) | Object::Null => false,
+        Object::Float(float) => *float != 0.0,
+        _ => true,
+    }
 }
 
 fn eval_infix_expression(
@@ -326,12 +633,26 @@ fn eval_infix_expression(
     right: &Expression,
     env: Rc<RefCell<Environment>>,
 ) -> Result<Rc<Object>, EvalError> {
+    if let Infix::And | Infix::Or = infix {
+        return eval_logical_expression(left, infix, right, env);
+    }
+
     let left_object = eval_expression(left, Rc::clone(&env))?;
     let right_object = eval_expression(right, Rc::clone(&env))?;
 
     Ok(match (&*left_object, infix, &*right_object) {
         (Object::Integer(left_int), _, Object::Integer(right_int)) => {
-            eval_integer_infix_expression(*left_int, infix, *right_int)
+            eval_integer_infix_expression(*left_int, infix, *right_int)?
+        }
+        // mixing integers and floats promotes the integer to a float
+        (Object::Integer(left_int), _, Object::Float(right_float)) => {
+            eval_float_infix_expression(*left_int as f64, infix, *right_float)?
+        }
+        (Object::Float(left_float), _, Object::Integer(right_int)) => {
+            eval_float_infix_expression(*left_float, infix, *right_int as f64)?
+        }
+        (Object::Float(left_float), _, Object::Float(right_float)) => {
+            eval_float_infix_expression(*left_float, infix, *right_float)?
         }
         (Object::Boolean(left_bool), Infix::Equal, Object::Boolean(right_bool)) => {
             Rc::new(Object::Boolean(left_bool == right_bool))
@@ -339,22 +660,100 @@ fn eval_infix_expression(
         (Object::Boolean(left_bool), Infix::NotEqual, Object::Boolean(right_bool)) => {
             Rc::new(Object::Boolean(left_bool != right_bool))
         }
-        (Object::Boolean(left_bool), Infix::And, Object::Boolean(right_bool)) => {
-            Rc::new(Object::Boolean(*left_bool && *right_bool))
+        (Object::Boolean(_), _, Object::Boolean(_)) => return Err(EvalError::UnknownOperator),
+        (Object::Array(a1), Infix::Plus, Object::Array(a2)) => {
+            let elements = [a1.borrow().as_slice(), &a2.borrow()].concat();
+            Rc::new(Object::Array(RefCell::new(elements)))
         }
-        (Object::Boolean(left_bool), Infix::Or, Object::Boolean(right_bool)) => {
-            Rc::new(Object::Boolean(*left_bool || *right_bool))
+        (Object::Array(_), Infix::Plus, other) => {
+            return Err(EvalError::UsePushToAppend(other.type_name()))
+        }
+        (
+            Object::Array(_) | Object::Hash(_),
+            Infix::Equal | Infix::NotEqual,
+            Object::Array(_) | Object::Hash(_),
+        ) => {
+            let equal = deep_equal(&left_object, &right_object).ok_or(EvalError::NestingTooDeep)?;
+            Rc::new(Object::Boolean(equal == (*infix == Infix::Equal)))
         }
-        (Object::Boolean(_), _, Object::Boolean(_)) => return Err(EvalError::UnknownOperator),
         (Object::String(s1), Infix::Plus, Object::String(s2)) => {
-            Rc::new(Object::String(format!("{s1}{s2}")))
+            // allocates the result once at its final size
+            Rc::new(Object::String([s1.as_str(), s2].concat()))
+        }
+        (Object::String(s), Infix::Multiply, Object::Integer(count))
+        | (Object::Integer(count), Infix::Multiply, Object::String(s)) => {
+            match repeat_string(s, *count) {
+                Some(repeated) => Rc::new(Object::String(repeated)),
+                None => return Err(EvalError::InvalidRepeatCount(*count)),
+            }
+        }
+        // compares contents byte by byte, so a prefix sorts first
+        (Object::String(s1), infix, Object::String(s2)) if is_comparison(infix) => {
+            Rc::new(Object::Boolean(match infix {
+                Infix::LessThan => s1 < s2,
+                Infix::GreaterThan => s1 > s2,
+                Infix::LessEqual => s1 <= s2,
+                Infix::GreaterEqual => s1 >= s2,
+                Infix::Equal => s1 == s2,
+                _ => s1 != s2,
+            }))
+        }
+        // any value can be compared with null, which only equals itself
+        (Object::Null, Infix::Equal, _) | (_, Infix::Equal, Object::Null) => {
+            Rc::new(Object::Boolean(left_object == right_object))
+        }
+        (Object::Null, Infix::NotEqual, _) | (_, Infix::NotEqual, Object::Null) => {
+            Rc::new(Object::Boolean(left_object != right_object))
+        }
+        // other values have to be converted explicitly before they can be
+        // concatenated with a string
+        (Object::String(_), _, _) | (_, _, Object::String(_)) => {
+            return Err(EvalError::UnsupportedOperands(
+                infix.symbol(),
+                left_object.type_name(),
+                right_object.type_name(),
+            ))
         }
-        (Object::String(_), _, Object::String(_)) => return Err(EvalError::UnknownOperator),
         _ => return Err(EvalError::IncompatibleTypes),
     })
 }
 
-fn eval_integer_infix_expression(left: i64, infix: &Infix, right: i64) -> Rc<Object> {
+fn is_comparison(infix: &Infix) -> bool {
+    matches!(
+        infix,
+        Infix::LessThan
+            | Infix::GreaterThan
+            | Infix::LessEqual
+            | Infix::GreaterEqual
+            | Infix::Equal
+            | Infix::NotEqual
+    )
+}
+
+// the right operand is only evaluated if the left one does not already decide
+// the result, which is always a boolean
+fn eval_logical_expression(
+    left: &Expression,
+    infix: &Infix,
+    right: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, EvalError> {
+    let left_truthy = is_truthy(&*eval_expression(left, Rc::clone(&env))?);
+
+    let result = match (infix, left_truthy) {
+        (Infix::And, false) => false,
+        (Infix::Or, true) => true,
+        _ => is_truthy(&*eval_expression(right, env)?),
+    };
+
+    Ok(Rc::new(Object::Boolean(result)))
+}
+
+fn eval_integer_infix_expression(
+    left: i64,
+    infix: &Infix,
+    right: i64,
+) -> Result<Rc<Object>, EvalError> {
     let result = match infix {
         Infix::Plus => Object::Integer(left + right),
         Infix::Minus => Object::Integer(left - right),
@@ -362,17 +761,58 @@ fn eval_integer_infix_expression(left: i64, infix: &Infix, right: i64) -> Rc<Obj
         Infix::Divide => Object::Integer(left / right),
         Infix::GreaterThan => Object::Boolean(left > right),
         Infix::LessThan => Object::Boolean(left < right),
+        Infix::GreaterEqual => Object::Boolean(left >= right),
+        Infix::LessEqual => Object::Boolean(left <= right),
         Infix::Equal => Object::Boolean(left == right),
         Infix::NotEqual => Object::Boolean(left != right),
-        Infix::And => {
-            Object::Boolean(is_truthy(&Object::Integer(left)) && is_truthy(&Object::Integer(right)))
-        }
-        Infix::Or => {
-            Object::Boolean(is_truthy(&Object::Integer(left)) || is_truthy(&Object::Integer(right)))
+        Infix::And | Infix::Or => unreachable!("handled by `eval_logical_expression`"),
+        Infix::BitAnd => Object::Integer(left & right),
+        Infix::BitOr => Object::Integer(left | right),
+        Infix::BitXor => Object::Integer(left ^ right),
+        // shifting by 64 or more bits, or by a negative amount, is an error
+        Infix::ShiftLeft => match u32::try_from(right).ok().and_then(|n| left.checked_shl(n)) {
+            Some(shifted) => Object::Integer(shifted),
+            None => return Err(EvalError::InvalidShift(right)),
+        },
+        Infix::ShiftRight => match u32::try_from(right).ok().and_then(|n| left.checked_shr(n)) {
+            Some(shifted) => Object::Integer(shifted),
+            None => return Err(EvalError::InvalidShift(right)),
+        },
+        Infix::Power if right < 0 => return Err(EvalError::NegativeExponent(right)),
+        Infix::Power => match u32::try_from(right).ok().and_then(|n| left.checked_pow(n)) {
+            Some(power) => Object::Integer(power),
+            None => return Err(EvalError::IntegerOverflow),
+        },
+    };
+
+    Ok(Rc::new(result))
+}
+
+fn eval_float_infix_expression(
+    left: f64,
+    infix: &Infix,
+    right: f64,
+) -> Result<Rc<Object>, EvalError> {
+    let result = match infix {
+        Infix::Plus => Object::Float(left + right),
+        Infix::Minus => Object::Float(left - right),
+        Infix::Multiply => Object::Float(left * right),
+        Infix::Divide => Object::Float(left / right),
+        Infix::Power => Object::Float(left.powf(right)),
+        Infix::GreaterThan => Object::Boolean(left > right),
+        Infix::LessThan => Object::Boolean(left < right),
+        Infix::GreaterEqual => Object::Boolean(left >= right),
+        Infix::LessEqual => Object::Boolean(left <= right),
+        Infix::Equal => Object::Boolean(left == right),
+        Infix::NotEqual => Object::Boolean(left != right),
+        Infix::And | Infix::Or => unreachable!("handled by `eval_logical_expression`"),
+        // bitwise operators only apply to integers
+        Infix::BitAnd | Infix::BitOr | Infix::BitXor | Infix::ShiftLeft | Infix::ShiftRight => {
+            return Err(EvalError::IncompatibleTypes)
         }
     };
 
-    Rc::new(result)
+    Ok(Rc::new(result))
 }
 
 fn eval_prefix_expressions(
@@ -390,15 +830,17 @@ fn eval_prefix_expressions(
 fn eval_minus_operator_expression(object: &Object) -> Result<Rc<Object>, EvalError> {
     match object {
         Object::Integer(int) => Ok(Rc::new(Object::Integer(-int))),
+        Object::Float(float) => Ok(Rc::new(Object::Float(-float))),
         _ => Err(EvalError::UnknownOperator),
     }
 }
 
 fn eval_bang_operator_expression(object: &Object) -> Rc<Object> {
-    // false, Null, and 0 are falsy; everything else is truthy
+    // false, Null, 0 and 0.0 are falsy; everything else is truthy
     let result = match object {
         Object::Null => true,
         Object::Integer(int) => *int == 0,
+        Object::Float(float) => *float == 0.0,
         Object::Boolean(val) => !val,
         _ => false,
     };
@@ -410,8 +852,95 @@ fn eval_bang_operator_expression(object: &Object) -> Rc<Object> {
 pub enum EvalError {
     IncompatibleTypes,
     UnknownOperator,
-    UnrecognisedIdentifier,
+    // reading a name that is neither bound nor a builtin
+    UndefinedVariable(String),
+    // assigning to a name that was never bound with `let`
+    AssignmentWithoutLet(String),
+    // assigning to a name bound with `const`
+    ReassignConstant(String),
+    // reading a top-level name before its `let` has run
+    UsedBeforeDefinition(String),
     NotAFunction,
     IncorrectNumberOfArgs,
     IndexOutOfBounds,
+    UnhashableKey(String),
+    NotIterable(String),
+    NestingTooDeep,
+    // shifting by a negative amount or by more than 63 bits
+    InvalidShift(i64),
+    // raising an integer to a negative power, whose result is not an integer
+    NegativeExponent(i64),
+    IntegerOverflow,
+    // adding something other than an array to an array, which is done with
+    // `push`; holds the type of the value being added
+    UsePushToAppend(&'static str),
+    // repeating a string a negative number of times, or so many times that
+    // the result would be unreasonably long
+    InvalidRepeatCount(i64),
+    // an operator that the operand types don't support, e.g. `"a" - "b"`;
+    // holds the operator and the types of its left and right operands
+    UnsupportedOperands(&'static str, &'static str, &'static str),
+    // a range, given by its start and end, with too many integers in it to
+    // build an array of
+    RangeTooLong(i64, i64),
+    // spreading something other than an array into a call's arguments;
+    // holds its type
+    CannotSpread(&'static str),
+    // destructuring something other than an array in a `let`; holds its type
+    CannotDestructure(&'static str),
+    // destructuring an array with fewer elements than there are names; holds
+    // the number of names and of elements
+    TooFewElements(usize, usize),
+    // the name of a type (or hash key type) that `to_json` can't convert
+    NotSerializable(String),
+    // `from_json` was given malformed JSON; holds what was wrong with it
+    InvalidJson(String),
+    // a file given to `import` that couldn't be read or parsed; holds its
+    // path and what went wrong
+    ImportFailed(String),
+    // a builtin such as `puts` failed to write its output
+    Io(ErrorKind),
+    // importing a file that is still being run, as it imports itself directly
+    // or through other modules; holds the chain of paths that led back to it
+    CircularImport(String),
+    // a macro that isn't bound with a top-level `let`, so is never expanded
+    UnexpandedMacro,
+    // a macro returning something other than quoted code; holds its type
+    NotQuoted(&'static str),
+    // unquoting a value that can't be written as a literal; holds its type
+    CannotUnquote(&'static str),
+    // spreading an array into a macro call or `quote`, which take code
+    // rather than values
+    MacroSpread,
+    // a named argument that isn't one of the function's parameters
+    UnknownArgument(String),
+    // a parameter given both by position and by name, or by name twice
+    DuplicateArgument(String),
+    // a parameter without a default left out before one given by name
+    MissingArgument(String),
+}
+
+impl From<ArgumentError> for EvalError {
+    fn from(error: ArgumentError) -> Self {
+        match error {
+            ArgumentError::Unknown(name) => EvalError::UnknownArgument(name),
+            ArgumentError::Duplicate(name) => EvalError::DuplicateArgument(name),
+            ArgumentError::Missing(name) => EvalError::MissingArgument(name),
+        }
+    }
+}
+
+impl From<BuiltinError> for EvalError {
+    fn from(error: BuiltinError) -> Self {
+        match error {
+            BuiltinError::IncompatibleTypes => EvalError::IncompatibleTypes,
+            BuiltinError::IncorrectNumberOfArgs => EvalError::IncorrectNumberOfArgs,
+            BuiltinError::IndexOutOfBounds => EvalError::IndexOutOfBounds,
+            BuiltinError::NestingTooDeep => EvalError::NestingTooDeep,
+            BuiltinError::UnhashableKey(type_name) => EvalError::UnhashableKey(type_name),
+            BuiltinError::NotSerializable(type_name) => EvalError::NotSerializable(type_name),
+            BuiltinError::InvalidJson(message) => EvalError::InvalidJson(message),
+            BuiltinError::Io(kind) => EvalError::Io(kind),
+        }
+    }
 }
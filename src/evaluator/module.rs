@@ -0,0 +1,102 @@
+use crate::evaluator::environment::Environment;
+use crate::evaluator::{eval_hoisted, EvalError};
+use crate::parser::ast::Program;
+use crate::parser::Parser;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+thread_local! {
+    // the top-level environment of every module imported on this thread, by
+    // canonical path, so that importing a file again doesn't run it again
+    static MODULE_CACHE: RefCell<HashMap<PathBuf, Rc<RefCell<Environment>>>> =
+        RefCell::new(HashMap::new());
+    // the canonical paths of the files being run, innermost last: the script,
+    // if there is one, then each module whose import hasn't finished
+    static RUNNING: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the environment holding the top-level bindings of the module at
+/// `path`, running the module first unless it has been imported before.
+pub fn import(path: &str) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    let key = resolve(path)?;
+    if let Some(module) = MODULE_CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return Ok(module);
+    }
+    let program = load(path, &key)?;
+
+    // only cached once it has run, so a module that fails is run again the
+    // next time it is imported
+    let env = Rc::new(RefCell::new(Environment::new()));
+    running_as(key.clone(), || eval_hoisted(program, Rc::clone(&env)))?;
+    MODULE_CACHE.with(|cache| cache.borrow_mut().insert(key, Rc::clone(&env)));
+    Ok(env)
+}
+
+/// The canonical path of the module at `path`, which identifies it however it
+/// is imported. Relative paths are relative to the directory of the file
+/// importing the module, or to the current directory if there is no such file.
+pub fn resolve(path: &str) -> Result<PathBuf, EvalError> {
+    let importer = RUNNING.with(|running| running.borrow().last().cloned());
+    let resolved = match importer.as_ref().and_then(|importer| importer.parent()) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    };
+    fs::canonicalize(resolved).map_err(|e| import_failed(path, &e.kind()))
+}
+
+/// Reads and parses the module that `path` resolved to as `key`, unless it is
+/// still running, which would make importing it a circular import.
+pub fn load(path: &str, key: &Path) -> Result<Program, EvalError> {
+    check_not_running(key)?;
+    let source = fs::read_to_string(key).map_err(|e| import_failed(path, &e.kind()))?;
+    Parser::parse_program(&source).map_err(|errors| import_failed(path, &errors[0]))
+}
+
+fn import_failed(path: &str, e: &dyn std::fmt::Debug) -> EvalError {
+    let importer = RUNNING.with(|running| running.borrow().last().cloned());
+    EvalError::ImportFailed(match importer {
+        Some(importer) => format!("{path} (imported from {}): {e:?}", importer.display()),
+        None => format!("{path}: {e:?}"),
+    })
+}
+
+/// Calls `f` as the script at `path`, so that the modules it imports are
+/// found relative to it, and a module importing it is a circular import.
+pub fn run_script<T>(path: &str, f: impl FnOnce() -> T) -> T {
+    // the script has already been read, so this only fails if it has since
+    // been removed, in which case the path as given will do
+    let key = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    running_as(key, f)
+}
+
+/// Calls `f` as the module at `key`, so that the modules it imports are found
+/// relative to it, and importing it again meanwhile is a circular import.
+pub fn running_as<T>(key: PathBuf, f: impl FnOnce() -> T) -> T {
+    RUNNING.with(|running| running.borrow_mut().push(key));
+    let result = f();
+    RUNNING.with(|running| running.borrow_mut().pop());
+    result
+}
+
+// a file that is still running when it is imported again would wait on
+// itself, so this reports the chain of imports that led back to it
+fn check_not_running(key: &Path) -> Result<(), EvalError> {
+    RUNNING.with(|running| {
+        let running = running.borrow();
+        match running.iter().position(|path| path == key) {
+            Some(start) => {
+                let chain: Vec<_> = running[start..]
+                    .iter()
+                    .map(PathBuf::as_path)
+                    .chain([key])
+                    .map(|path| path.display().to_string())
+                    .collect();
+                Err(EvalError::CircularImport(chain.join(" -> ")))
+            }
+            None => Ok(()),
+        }
+    })
+}
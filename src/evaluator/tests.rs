@@ -1,13 +1,17 @@
 #![cfg(test)]
 
 use crate::evaluator::environment::Environment;
+use crate::evaluator::macro_expansion::{define_macros, expand_macros};
 use crate::evaluator::{eval, EvalError};
+use crate::object::http;
 use crate::object::{Function, Hashable, Object};
-use crate::parser::ast::{Expression, Infix, Statement};
+use crate::parser::ast::{Expression, Infix, Program, Statement};
 use crate::parser::Parser;
+use crate::tests::{capture_output, with_clock, write_module};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::time::Duration;
 
 fn parse_and_eval(input: &str) -> Result<Rc<Object>, EvalError> {
     // PRE: `input` is a well-formed (i.e. parsable) program
@@ -24,6 +28,76 @@ fn test_eval_for_integer_expression() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_eval_for_float_expression() {
+    let input = "2.5";
+    let expected = Rc::new(Object::Float(2.5));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_arithmetic() {
+    let input = "[1.5 + 2.25, 1.5 - 2.0, 1.5 * 2.0, 1.0 / 4.0, -2.5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Float(3.75)),
+        Rc::new(Object::Float(-0.5)),
+        Rc::new(Object::Float(3.0)),
+        Rc::new(Object::Float(0.25)),
+        Rc::new(Object::Float(-2.5)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_mixed_integer_and_float_arithmetic_promotes_to_float() {
+    let input = "[1 + 0.5, 0.5 * 4, 7 / 2.0, 7 / 2]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Float(1.5)),
+        Rc::new(Object::Float(2.0)),
+        Rc::new(Object::Float(3.5)),
+        Rc::new(Object::Integer(3)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_comparison() {
+    let input = "[1.5 < 2, 2 <= 2.0, 0.1 > 0.2, 3.0 >= 3, 1 == 1.0, 1.5 != 1.5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_truthiness() {
+    let input = "[!0.0, !0.5, if (0.0) { 1 } else { 2 }]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Integer(2)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_hash_key_error() {
+    let input = "{1.5: 1}";
+    let expected_error = EvalError::UnhashableKey(String::from("float"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
 #[test]
 fn test_eval_for_boolean_expression() {
     let input = "true";
@@ -168,6 +242,34 @@ fn test_eval_infix_operators_six() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_eval_less_equal_and_greater_equal() {
+    let input = "[5 <= 5, 4 <= 5, 6 <= 5, 5 >= 5, 6 >= 5, 4 >= 5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_comparison_error_if_booleans() {
+    for input in [
+        "true > false",
+        "true < false",
+        "true >= false",
+        "true <= false",
+    ] {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::UnknownOperator, "{input}");
+    }
+}
+
 #[test]
 fn test_eval_infix_error_if_boolean_passed_to_arithmetic_operator() {
     let input = "2 + true";
@@ -338,7 +440,7 @@ fn test_eval_let_statement_binding_four() {
 #[test]
 fn test_eval_let_statement_error_if_identifier_unbound() {
     let input = "foo";
-    let expected_error = EvalError::UnrecognisedIdentifier;
+    let expected_error = EvalError::UndefinedVariable(String::from("foo"));
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -347,8 +449,9 @@ fn test_eval_let_statement_error_if_identifier_unbound() {
 fn test_eval_function_definition() {
     let input = "fn(x) { x + 2 }";
     let expected = Rc::new(Object::Function(Function {
-        parameters: vec![String::from("x")],
-        body: Statement::BlockStatement(vec![Statement::Expression(Expression::Infix(
+        name: String::new(),
+        parameters: vec![(String::from("x"), None)],
+        body: Statement::Block(vec![Statement::Expression(Expression::Infix(
             Box::new(Expression::Identifier(String::from("x"))),
             Infix::Plus,
             Box::new(Expression::Integer(2)),
@@ -459,6 +562,23 @@ return y + 1;";
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_eval_bare_return() {
+    let tests = [
+        ("let f = fn() { return; }; f()", "null"),
+        (
+            "let f = fn(x) { if (x) { return } 1 }; [f(true), f(false)]",
+            "[null, 1]",
+        ),
+        ("return; 1", "null"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
 #[test]
 fn test_eval_recursive_function() {
     let input = "
@@ -485,6 +605,27 @@ fn test_eval_string_expression() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_eval_string_literal_is_not_looked_up() {
+    let tests = [
+        (r#"let x = "hello"; x"#, "hello"),
+        // a string with the same text as a binding is still just the string
+        (r#"let hello = 1; "hello""#, "hello"),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(
+            result,
+            Rc::new(Object::String(String::from(expected))),
+            "{input}"
+        );
+    }
+    assert_eq!(
+        parse_and_eval(r#""x"; x"#).err().unwrap(),
+        EvalError::UndefinedVariable(String::from("x"))
+    );
+}
+
 #[test]
 fn test_eval_string_concatenation() {
     let input = "\"hello\" + \"world\"";
@@ -495,10 +636,26 @@ fn test_eval_string_concatenation() {
 
 #[test]
 fn test_eval_string_concatenation_error_if_unsupported_infix() {
-    let input = "\"hello\" * \"world\"";
-    let expected_error = EvalError::UnknownOperator;
-    let error = parse_and_eval(input).err().unwrap();
-    assert_eq!(error, expected_error);
+    let tests = [
+        ("\"hello\" * \"world\"", "*", "string", "string"),
+        ("\"foo\" - \"bar\"", "-", "string", "string"),
+        ("\"count: \" + 5", "+", "string", "integer"),
+        ("1.5 + \"x\"", "+", "float", "string"),
+        ("\"a\" + [\"b\"]", "+", "string", "array"),
+    ];
+    for (input, operator, left, right) in tests {
+        let expected_error = EvalError::UnsupportedOperands(operator, left, right);
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_string_concatenation_in_loop() {
+    let input = "let s = \"\"; for (x in [\"a\", \"b\", \"c\"]) { s += x; }; s";
+    let expected = Rc::new(Object::String(String::from("abc")));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
 }
 
 #[test]
@@ -523,16 +680,24 @@ fn test_eval_array_literal() {
 let a = 4;
 [1, a, 1 + 1, 2 * 3]
 ";
-    let expected = Rc::new(Object::Array(vec![
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
         Rc::new(Object::Integer(1)),
         Rc::new(Object::Integer(4)),
         Rc::new(Object::Integer(2)),
         Rc::new(Object::Integer(6)),
-    ]));
+    ])));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_eval_array_literal_display() {
+    let input = "[1, 2 * 2, fn(x) { x }, [true, \"a\"], []]";
+    let expected = "[1, 4, fn(...), [true, a], []]";
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
 #[test]
 fn test_eval_indexing_into_array_one() {
     let input = "[1, 2][0]";
@@ -583,12 +748,51 @@ fn test_eval_index_out_of_bounds_one() {
 
 #[test]
 fn test_eval_index_out_of_bounds_two() {
-    let input = "[1, 2, 3][-1]";
+    let input = "[1, 2, 3][-4]";
     let expected_error = EvalError::IndexOutOfBounds;
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
 
+#[test]
+fn test_eval_negative_index() {
+    let tests = [
+        ("[1, 2, 3][-1]", "3"),
+        ("[1, 2, 3][-3]", "1"),
+        (
+            "let a = [1, 2, 3]; a[-1] = 30; a[-2] += 18; a",
+            "[1, 20, 30]",
+        ),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_negative_index_out_of_bounds() {
+    let tests = [
+        "[][0]",
+        "[][-1]",
+        "[1, 2][-9223372036854775807 - 1]",
+        "let a = []; a[-1] = 0;",
+    ];
+    for input in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::IndexOutOfBounds, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_index_error_if_not_indexable() {
+    let input = "5[0]";
+    let expected_error = EvalError::IncompatibleTypes;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
 #[test]
 fn test_eval_builtin_len_for_array() {
     let input = "len([1, 2, 3])";
@@ -648,10 +852,10 @@ fn test_eval_builtin_last_if_not_array() {
 #[test]
 fn test_eval_builtin_rest() {
     let input = "rest([1, 2, 3])";
-    let expected = Rc::new(Object::Array(vec![
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
         Rc::new(Object::Integer(2)),
         Rc::new(Object::Integer(3)),
-    ]));
+    ])));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
@@ -675,12 +879,12 @@ fn test_eval_builtin_rest_if_not_array() {
 #[test]
 fn test_eval_builtin_push() {
     let input = "push([1, 2, 3], 4)";
-    let expected = Rc::new(Object::Array(vec![
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
         Rc::new(Object::Integer(1)),
         Rc::new(Object::Integer(2)),
         Rc::new(Object::Integer(3)),
         Rc::new(Object::Integer(4)),
-    ]));
+    ])));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
@@ -688,7 +892,9 @@ fn test_eval_builtin_push() {
 #[test]
 fn test_eval_builtin_push_on_empty() {
     let input = "push([], 1)";
-    let expected = Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))]));
+    let expected = Rc::new(Object::Array(RefCell::new(vec![Rc::new(Object::Integer(
+        1,
+    ))])));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
@@ -710,175 +916,2182 @@ fn test_eval_builtin_push_error_if_wrong_number_of_args() {
 }
 
 #[test]
-fn test_eval_hash_literal() {
-    let input = "
-let two = \"two\";
-{
-    \"one\": 10 - 9,
-    two: 1 + 1,
-    \"three\": 3,
-    2 + 2: 4,
-    true: 5,
-    false: 6
-}";
-    let expected = Rc::new(Object::Hash(HashMap::from([
-        (
-            Hashable::String(String::from("one")),
-            Rc::new(Object::Integer(1)),
-        ),
-        (
-            Hashable::String(String::from("two")),
-            Rc::new(Object::Integer(2)),
-        ),
-        (
-            Hashable::String(String::from("three")),
-            Rc::new(Object::Integer(3)),
-        ),
-        (Hashable::Integer(4), Rc::new(Object::Integer(4))),
-        (Hashable::Boolean(true), Rc::new(Object::Integer(5))),
-        (Hashable::Boolean(false), Rc::new(Object::Integer(6))),
-    ])));
-    let result = parse_and_eval(input).ok().unwrap();
-    assert_eq!(result, expected);
+fn test_eval_builtin_print() {
+    let input = "print(\"a\"); print(\"b\")";
+    let output = capture_output(|| {
+        parse_and_eval(input).unwrap();
+    });
+    assert_eq!(output, "ab");
 }
 
 #[test]
-fn test_eval_hash_index_expression_one() {
-    let input = "{\"one\": 1}[\"one\"]";
-    let expected = Rc::new(Object::Integer(1));
+fn test_eval_builtin_println() {
+    let input = "println(\"a\"); println(\"b\")";
+    let output = capture_output(|| {
+        parse_and_eval(input).unwrap();
+    });
+    assert_eq!(output, "a\nb\n");
+}
+
+#[test]
+fn test_eval_builtin_puts() {
+    let input = "puts(1, [2], \"three\")";
+    let output = capture_output(|| {
+        parse_and_eval(input).unwrap();
+    });
+    assert_eq!(output, "1\n[2]\nthree\n");
+}
+
+#[test]
+fn test_eval_builtin_print_error_if_wrong_number_of_args() {
+    let input = "print(\"a\", \"b\")";
+    let expected_error = EvalError::IncorrectNumberOfArgs;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_inspect_integer() {
+    let input = "inspect(42)";
+    let expected = Rc::new(Object::String(String::from("42")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_hash_index_expression_two() {
-    let input = "{1 + 1: 1}[2]";
-    let expected = Rc::new(Object::Integer(1));
+fn test_eval_builtin_inspect_array() {
+    let input = "inspect([1, \"two\"])";
+    let expected = Rc::new(Object::String(String::from("[1, \"two\"]")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_hash_index_expression_three() {
-    let input = "{true: 1}[true]";
-    let expected = Rc::new(Object::Integer(1));
+fn test_eval_builtin_inspect_function() {
+    let input = "inspect(fn(x) { x })";
+    let expected = Rc::new(Object::String(String::from("<function(1 param)>")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_hash_index_expression_four() {
-    let input = "let map = {\"one\": 1}; map[\"one\"]";
-    let expected = Rc::new(Object::Integer(1));
+fn test_eval_builtin_inspect_null() {
+    let input = "inspect(if (false) { 1 })";
+    let expected = Rc::new(Object::String(String::from("null")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_hash_index_expression_five() {
-    let input = "{0: 1}[3]";
-    let expected = Rc::new(Object::Null);
+fn test_eval_builtin_error_message() {
+    let input = "error_message(error(\"something went wrong\"))";
+    let expected = Rc::new(Object::String(String::from("something went wrong")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_or_operator() {
-    let input = "false || true";
-    let expected = Rc::new(Object::Boolean(true));
+fn test_eval_builtin_error_message_error_if_not_error() {
+    let input = "error_message(\"something went wrong\")";
+    let expected_error = EvalError::IncompatibleTypes;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_is_error() {
+    let input = "
+let result = error(\"bad\");
+if (is_error(result)) { \"Error: \" + error_message(result) } else { result }
+";
+    let expected = Rc::new(Object::String(String::from("Error: bad")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_and_operator() {
-    let input = "false && true";
-    let expected = Rc::new(Object::Boolean(false));
+fn test_eval_builtin_is_error_on_non_error() {
+    let input = "[is_error(1), is_error(\"error\")]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(false)),
+    ])));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_or_operator_with_truthy_and_falsy_values() {
-    let input = "1 || 0";
-    let expected = Rc::new(Object::Boolean(true));
+fn test_eval_builtin_recover_calls_handler_on_error() {
+    let input =
+        "recover(fn() { error(\"oops\") }, fn(err) { \"recovered: \" + error_message(err) })";
+    let expected = Rc::new(Object::String(String::from("recovered: oops")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_and_operator_with_truthy_and_falsy_values() {
-    let input = "1 && 0";
-    let expected = Rc::new(Object::Boolean(false));
+fn test_eval_builtin_recover_returns_value_on_success() {
+    let input = "recover(fn() { 1 + 1 }, fn(err) { 0 })";
+    let expected = Rc::new(Object::Integer(2));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_while_loop() {
+fn test_eval_builtin_recover_nested() {
     let input = "
-let i = 0;
-while (i < 3) {
-    let i = i + 1;
-}
-return i;
+let inner = fn() { recover(fn() { error(\"inner\") }, fn(err) { err }) };
+recover(inner, fn(err) { \"outer caught \" + error_message(err) })
 ";
-    let expected = Rc::new(Object::Integer(3));
+    let expected = Rc::new(Object::String(String::from("outer caught inner")));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_while_loop_early_return() {
-    let input = "
-let i = 0;
-while (true) {
-    let i = i + 1;
-    if (i == 3) {
-        return i;
+fn test_eval_builtin_try_parse_int() {
+    let input = "[try_parse_int(\"42\"), try_parse_int(\" -7 \"), try_parse_int(\"\"), try_parse_int(\"abc\"), try_parse_int(\"1.5\")]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(-7)),
+        Rc::new(Object::Null),
+        Rc::new(Object::Null),
+        Rc::new(Object::Null),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_try_parse_int_error_if_not_string() {
+    let input = "try_parse_int(42)";
+    let expected_error = EvalError::IncompatibleTypes;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_try_parse_float() {
+    let tests = [
+        (r#"try_parse_float("2.5")"#, Object::Float(2.5)),
+        (r#"try_parse_float(" -7 ")"#, Object::Float(-7.0)),
+        (r#"try_parse_float("1e3")"#, Object::Float(1000.0)),
+        (r#"try_parse_float("")"#, Object::Null),
+        (r#"try_parse_float("abc")"#, Object::Null),
+        (r#"try_parse_float("inf")"#, Object::Null),
+        (r#"try_parse_float("NaN")"#, Object::Null),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, Rc::new(expected), "{input}");
     }
+
+    let error = parse_and_eval("try_parse_float(2.5)").err().unwrap();
+    assert_eq!(error, EvalError::IncompatibleTypes);
 }
-";
+
+#[test]
+fn test_eval_builtin_apply() {
+    let input = "apply(fn(a, b) { a + b }, [3, 4])";
+    let expected = Rc::new(Object::Integer(7));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_apply_with_builtin() {
+    let input = "apply(len, [[1, 2, 3]])";
     let expected = Rc::new(Object::Integer(3));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_assignment_expression() {
-    let input = "
-let i = 0;
-i = i + 1;
-i
-";
-    let expected = Rc::new(Object::Integer(1));
+fn test_eval_builtin_apply_error_if_wrong_number_of_args() {
+    let input = "apply(fn(a, b) { a + b }, [3])";
+    let expected_error = EvalError::IncorrectNumberOfArgs;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_apply_error_if_args_not_array() {
+    let input = "apply(fn(a) { a }, 3)";
+    let expected_error = EvalError::IncompatibleTypes;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_partial() {
+    let input = "let add_five = partial(fn(a, b) { a + b }, 5); add_five(3)";
+    let expected = Rc::new(Object::Integer(8));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_assignment_expression_closure() {
+fn test_eval_builtin_partial_with_builtin_and_nested() {
     let input = "
-let count = 0;
-let counter = fn() { count = count + 1; count };
-counter();
-counter();
+let add_three = fn(a, b, c) { a + b + c };
+[partial(push, [1])(2), partial(partial(add_three, 1), 2)(3)]
 ";
-    let expected = Rc::new(Object::Integer(2));
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+        ]))),
+        Rc::new(Object::Integer(6)),
+    ])));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_assignment_expression_closure_and_hof() {
+fn test_eval_builtin_partial_without_args_returns_function() {
+    let input = "partial(fn(a) { a * 2 })";
+    let result = parse_and_eval(input).ok().unwrap();
+    assert!(matches!(&*result, Object::Function(_)));
+}
+
+#[test]
+fn test_eval_builtin_partial_error_if_too_many_args() {
+    let input = "partial(fn(a, b) { a + b }, 5)(3, 4)";
+    let expected_error = EvalError::IncorrectNumberOfArgs;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_partial_error_if_not_function() {
+    let input = "partial(5, 1)";
+    let expected_error = EvalError::IncompatibleTypes;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_once_runs_function_once() {
     let input = "
-let makeCounter = fn() { let count = 0; fn() { count = count + 1; count } };
-let counter = makeCounter();
-counter();
-counter();
-counter();
+let init = once(fn() { puts(\"initializing\"); 42 });
+[init(), init(), init()]
 ";
-    let expected = Rc::new(Object::Integer(3));
-    let result = parse_and_eval(input).ok().unwrap();
-    assert_eq!(result, expected);
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(42)),
+    ])));
+    let output = capture_output(|| {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    });
+    assert_eq!(output, "initializing\n");
+}
+
+#[test]
+fn test_eval_builtin_once_with_argument_caches_first_result() {
+    let input = "let double = once(fn(x) { x * 2 }); [double(1), double(5)]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(2)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_once_recursive_call_returns_null() {
+    let input = "let f = once(fn() { [1, f()] }); [f(), f()]";
+    let inner = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Null),
+    ])));
+    let expected = Rc::new(Object::Array(RefCell::new(vec![Rc::clone(&inner), inner])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_tap_returns_value_and_discards_result() {
+    let input = "tap(42, fn(x) { puts(x); x + 1 }) == 42";
+    let expected = Rc::new(Object::Boolean(true));
+    let output = capture_output(|| {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    });
+    assert_eq!(output, "42\n");
+}
+
+#[test]
+fn test_eval_builtin_tap_propagates_errors() {
+    let input = "tap(42, fn(x) { len(x) })";
+    let expected_error = EvalError::IncompatibleTypes;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_builtin_swap() {
+    let input = "let arr = [1, 2, 3, 4]; [swap(arr, 0, 3), swap(arr, 1, 1), swap(arr, -1, 0), arr]";
+    let to_array = |ints: [i64; 4]| {
+        Rc::new(Object::Array(RefCell::new(
+            ints.iter().map(|i| Rc::new(Object::Integer(*i))).collect(),
+        )))
+    };
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        to_array([4, 2, 3, 1]),
+        to_array([1, 2, 3, 4]),
+        to_array([4, 2, 3, 1]),
+        to_array([1, 2, 3, 4]),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_swap_error_if_out_of_bounds() {
+    for input in [
+        "swap([1, 2], 0, 2)",
+        "swap([1, 2], -3, 0)",
+        "swap([], 0, 0)",
+    ] {
+        let expected_error = EvalError::IndexOutOfBounds;
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error);
+    }
+}
+
+#[test]
+fn test_eval_builtin_insert() {
+    let tests = vec![
+        ("insert([1, 2, 4, 5], 2, 3)", vec![1, 2, 3, 4, 5]),
+        ("insert([2, 3], 0, 1)", vec![1, 2, 3]),
+        ("insert([1, 2], 2, 3)", vec![1, 2, 3]),
+        ("insert([1, 3], -1, 2)", vec![1, 2, 3]),
+        ("insert([1], 0, 0)", vec![0, 1]),
+        ("insert([1], 1, 2)", vec![1, 2]),
+        ("insert([], 0, 1)", vec![1]),
+        ("let arr = [1]; insert(arr, 0, 0); arr", vec![1]),
+    ];
+
+    for (input, ints) in tests {
+        let expected = Rc::new(Object::Array(RefCell::new(
+            ints.iter().map(|i| Rc::new(Object::Integer(*i))).collect(),
+        )));
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_eval_builtin_insert_errors() {
+    let tests = vec![
+        ("insert([1, 2], 3, 0)", EvalError::IndexOutOfBounds),
+        ("insert([1, 2], -3, 0)", EvalError::IndexOutOfBounds),
+        ("insert([], 1, 0)", EvalError::IndexOutOfBounds),
+        ("insert([], -1, 0)", EvalError::IndexOutOfBounds),
+        ("insert(\"ab\", 0, 1)", EvalError::IncompatibleTypes),
+        ("insert([1], 0)", EvalError::IncorrectNumberOfArgs),
+    ];
+
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error);
+    }
+}
+
+#[test]
+fn test_eval_builtin_remove() {
+    let tests = vec![
+        ("remove([1, 2, 3, 4], 1)", vec![1, 3, 4]),
+        ("remove([1, 2, 3], 0)", vec![2, 3]),
+        ("remove([1, 2, 3], 2)", vec![1, 2]),
+        ("remove([1, 2, 3], -1)", vec![1, 2]),
+        ("remove([1], 0)", vec![]),
+        ("let arr = [1]; remove(arr, 0); arr", vec![1]),
+    ];
+
+    for (input, ints) in tests {
+        let expected = Rc::new(Object::Array(RefCell::new(
+            ints.iter().map(|i| Rc::new(Object::Integer(*i))).collect(),
+        )));
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_eval_builtin_remove_errors() {
+    let tests = vec![
+        ("remove([], 0)", EvalError::IndexOutOfBounds),
+        ("remove([1, 2], 2)", EvalError::IndexOutOfBounds),
+        ("remove([1, 2], -3)", EvalError::IndexOutOfBounds),
+        ("remove(\"ab\", 0)", EvalError::IncompatibleTypes),
+        ("remove([1], 0, 0)", EvalError::IncorrectNumberOfArgs),
+    ];
+
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error);
+    }
+}
+
+#[test]
+fn test_eval_builtin_deep_clone() {
+    let tests = vec![
+        "[1, [2, 3]]",
+        "{\"a\": [1], \"b\": {\"c\": 2}}",
+        "\"monkey\"",
+        "5",
+        "true",
+    ];
+
+    for value in tests {
+        let expected = parse_and_eval(value).ok().unwrap();
+        let result = parse_and_eval(&format!("deep_clone({value})"))
+            .ok()
+            .unwrap();
+        assert_eq!(result, expected);
+    }
+
+    let input = "let f = fn(x) { x * 2 }; deep_clone(f)(4)";
+    let expected = Rc::new(Object::Integer(8));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_hash_update() {
+    let tests = vec![
+        (
+            "let h = {\"count\": 0}; [hash_update(h, \"count\", fn(v) { v + 1 }), h]",
+            "[{\"count\": 1}, {\"count\": 0}]",
+        ),
+        (
+            "hash_update({1: [2]}, 1, fn(v) { push(v, 3) })",
+            "{1: [2, 3]}",
+        ),
+        (
+            "hash_update({}, true, fn(v) { if (v) { v } else { \"missing\" } })",
+            "{true: \"missing\"}",
+        ),
+    ];
+
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_eval_builtin_hash_update_errors() {
+    let tests = vec![
+        (
+            "hash_update([1], 0, fn(v) { v })",
+            EvalError::IncompatibleTypes,
+        ),
+        (
+            "hash_update({}, [1], fn(v) { v })",
+            EvalError::UnhashableKey(String::from("array")),
+        ),
+        ("hash_update({}, 1, 5)", EvalError::NotAFunction),
+        ("hash_update({}, 1)", EvalError::IncorrectNumberOfArgs),
+    ];
+
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error);
+    }
+}
+
+#[test]
+fn test_eval_builtin_bench() {
+    // only the timed calls read the clock, once before and once after
+    let input = "let calls = 0; let stats = bench(fn() { calls = calls + 1; }, 3); [stats, calls]";
+    let expected = parse_and_eval(
+        "[{\"iters\": 3, \"total_ms\": 12.0, \"mean_ms\": 4.0, \"min_ms\": 2.0, \"max_ms\": 6.0}, 6]",
+    )
+    .ok()
+    .unwrap();
+    let result = with_clock(&[0, 2, 2, 6, 6, 12], || parse_and_eval(input))
+        .ok()
+        .unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_bench_passes_setup_result() {
+    let input = "
+let total = 0;
+let setups = 0;
+let setup = fn() { setups = setups + 1; 10 };
+bench(fn(x) { total = total + x; }, 2, setup)[\"max_ms\"];
+[total, setups]
+";
+    let expected = parse_and_eval("[50, 5]").ok().unwrap();
+    let result = with_clock(&[0, 1, 5, 8], || parse_and_eval(input))
+        .ok()
+        .unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_bench_errors() {
+    let tests = vec![
+        ("bench(5, 1)", EvalError::NotAFunction),
+        ("bench(fn() { 1 }, 0)", EvalError::IncompatibleTypes),
+        ("bench(fn() { 1 }, \"1\")", EvalError::IncompatibleTypes),
+        ("bench(fn() { 1 })", EvalError::IncorrectNumberOfArgs),
+        ("bench(fn() { 1 + true }, 1)", EvalError::IncompatibleTypes),
+    ];
+
+    for (input, expected_error) in tests {
+        let error = with_clock(&[0, 1], || parse_and_eval(input)).err().unwrap();
+        assert_eq!(error, expected_error);
+    }
+}
+
+#[test]
+fn test_eval_hash_literal() {
+    let input = "
+let two = \"two\";
+{
+    \"one\": 10 - 9,
+    two: 1 + 1,
+    \"three\": 3,
+    2 + 2: 4,
+    true: 5,
+    false: 6
+}";
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::from([
+        (
+            Hashable::String(String::from("one")),
+            Rc::new(Object::Integer(1)),
+        ),
+        (
+            Hashable::String(String::from("two")),
+            Rc::new(Object::Integer(2)),
+        ),
+        (
+            Hashable::String(String::from("three")),
+            Rc::new(Object::Integer(3)),
+        ),
+        (Hashable::Integer(4), Rc::new(Object::Integer(4))),
+        (Hashable::Boolean(true), Rc::new(Object::Integer(5))),
+        (Hashable::Boolean(false), Rc::new(Object::Integer(6))),
+    ]))));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_hash_index_expression_one() {
+    let input = "{\"one\": 1}[\"one\"]";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_hash_index_expression_two() {
+    let input = "{1 + 1: 1}[2]";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_hash_index_expression_three() {
+    let input = "{true: 1}[true]";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_hash_index_expression_four() {
+    let input = "let map = {\"one\": 1}; map[\"one\"]";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_hash_index_expression_five() {
+    let input = "{0: 1}[3]";
+    let expected = Rc::new(Object::Null);
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_hash_literal_error_if_key_unhashable() {
+    let input = "{fn(x) { x }: 1}";
+    let expected_error = EvalError::UnhashableKey(String::from("function"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_hash_index_expression_error_if_key_unhashable() {
+    let input = "{1: 2}[[1]]";
+    let expected_error = EvalError::UnhashableKey(String::from("array"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_hash_literal_display() {
+    let input = "{\"name\": \"monkey\"}";
+    let expected = "{\"name\": monkey}";
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[test]
+fn test_eval_or_operator() {
+    let input = "false || true";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_and_operator() {
+    let input = "false && true";
+    let expected = Rc::new(Object::Boolean(false));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_or_operator_with_truthy_and_falsy_values() {
+    let input = "1 || 0";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_and_operator_with_truthy_and_falsy_values() {
+    let input = "1 && 0";
+    let expected = Rc::new(Object::Boolean(false));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_and_operator_short_circuits() {
+    // `len(1)` would be an error if it were evaluated
+    let input = "false && len(1)";
+    let expected = Rc::new(Object::Boolean(false));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_or_operator_short_circuits() {
+    let input = "true || len(1)";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_logical_operator_evaluates_right_side_when_needed() {
+    let input = "false || len(1)";
+    let result = parse_and_eval(input);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_eval_while_loop() {
+    let input = "
+let i = 0;
+while (i < 3) {
+    let i = i + 1;
+}
+return i;
+";
+    let expected = Rc::new(Object::Integer(3));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_while_loop_early_return() {
+    let input = "
+let i = 0;
+while (true) {
+    let i = i + 1;
+    if (i == 3) {
+        return i;
+    }
+}
+";
+    let expected = Rc::new(Object::Integer(3));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop() {
+    let input = "
+let sum = 0;
+for (x in [1, 2, 3]) {
+    let double = x * 2;
+    sum = sum + double;
+}
+sum
+";
+    let expected = Rc::new(Object::Integer(12));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop_evaluates_to_null() {
+    let input = "let f = fn() { for (x in [1, 2]) { x } }; f()";
+    let expected = Rc::new(Object::Null);
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop_early_return() {
+    let input = "
+let find = fn(xs) {
+    for (x in xs) {
+        if (x > 2) {
+            return x;
+        }
+    }
+    return -1;
+};
+[find([1, 5, 3]), find([])]
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(5)),
+        Rc::new(Object::Integer(-1)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop_variable_does_not_leak() {
+    let input = "let x = 0; for (x in [1, 2]) { let y = x; }; x";
+    let expected = Rc::new(Object::Integer(0));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+
+    let input = "for (x in [1, 2]) { let y = x; }; y";
+    let expected_error = EvalError::UndefinedVariable(String::from("y"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_for_loop_error_if_not_array() {
+    let input = "for (x in \"abc\") { x }";
+    let expected_error = EvalError::NotIterable(String::from("string"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_while_break_and_continue() {
+    let input = "
+let r = [];
+let i = 0;
+while (true) {
+    i = i + 1;
+    if (i == 2) { continue; }
+    if (i > 4) { break; }
+    r = push(r, i);
+};
+[r, i]
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(3)),
+            Rc::new(Object::Integer(4)),
+        ]))),
+        Rc::new(Object::Integer(5)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_break_and_continue() {
+    let input = "
+let r = [];
+for (x in [1, 2, 3, 4, 5]) {
+    if (x == 2) { continue; }
+    if (x == 4) { break }
+    r = push(r, x);
+}
+r
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(3)),
+    ])));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_break_exits_innermost_loop() {
+    let input = "
+let pairs = fn() {
+    let r = [];
+    for (x in [1, 2, 3]) {
+        let y = 0;
+        while (true) {
+            y = y + 1;
+            if (y > 2) { break; }
+            if (x == 2) { continue; }
+            r = push(r, x * 10 + y);
+        }
+        if (x == 3) { break; }
+        r = push(r, 0);
+    }
+    r
+};
+pairs()
+";
+    let expected = Rc::new(Object::Array(RefCell::new(
+        [11, 12, 0, 0, 31, 32]
+            .iter()
+            .map(|i| Rc::new(Object::Integer(*i)))
+            .collect(),
+    )));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_expression() {
+    let input = "
+let i = 0;
+i = i + 1;
+i
+";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_expression_closure() {
+    let input = "
+let count = 0;
+let counter = fn() { count = count + 1; count };
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(2));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_expression_closure_and_hof() {
+    let input = "
+let makeCounter = fn() { let count = 0; fn() { count = count + 1; count } };
+let counter = makeCounter();
+counter();
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(3));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_error_if_undefined() {
+    let input = "let f = fn() { x = 2; }; f()";
+    let expected_error = EvalError::AssignmentWithoutLet(String::from("x"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_compound_assignment() {
+    let input = "
+let x = 10;
+let f = fn() { x -= 4; };
+f();
+x += 1 + 2;
+x *= 2;
+x /= 3;
+x
+";
+    let expected = Rc::new(Object::Integer(6));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+
+    let input = "x += 1;";
+    let expected_error = EvalError::AssignmentWithoutLet(String::from("x"));
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_conditional() {
+    let tests = [
+        ("true ? 1 : 2", Object::Integer(1)),
+        ("1 > 2 ? 1 : 2", Object::Integer(2)),
+        (
+            "let n = 0; n < 0 ? \"neg\" : n == 0 ? \"zero\" : \"pos\"",
+            Object::String(String::from("zero")),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_and_eval(input).ok().unwrap(), Rc::new(expected));
+    }
+}
+
+#[test]
+fn test_eval_bitwise_operators() {
+    let tests = [
+        ("12 & 10", Object::Integer(8)),
+        ("12 | 10", Object::Integer(14)),
+        ("12 ^ 10", Object::Integer(6)),
+        ("1 << 2 + 3", Object::Integer(32)),
+        ("-16 >> 2", Object::Integer(-4)),
+        ("1 << 63 >> 63", Object::Integer(-1)),
+        ("5 & 3 == 1", Object::Boolean(true)),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(
+            parse_and_eval(input).ok().unwrap(),
+            Rc::new(expected),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_eval_bitwise_error_if_not_integers() {
+    let tests = [
+        ("1.0 & 1", EvalError::IncompatibleTypes),
+        ("1 | 2.0", EvalError::IncompatibleTypes),
+        (
+            "\"a\" << 1",
+            EvalError::UnsupportedOperands("<<", "string", "integer"),
+        ),
+        ("true ^ false", EvalError::UnknownOperator),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_and_eval(input).err().unwrap(), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_shift_error_if_amount_out_of_range() {
+    let tests = [("1 << 64", 64), ("1 >> -1", -1), ("1 << -100", -100)];
+    for (input, amount) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::InvalidShift(amount));
+    }
+}
+
+#[test]
+fn test_eval_let_in_if_block_visible_after_block() {
+    let tests = [
+        ("fn() { if (true) { let x = 1; }; x }()", Object::Integer(1)),
+        ("if (true) { let y = 2; }; y", Object::Integer(2)),
+        ("if (true) { let y = 2; }", Object::Null),
+        ("if (false) { 1 } else { }", Object::Null),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(
+            parse_and_eval(input).ok().unwrap(),
+            Rc::new(expected),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_eval_power() {
+    let tests = [
+        ("2 ** 3 ** 2", Object::Integer(512)),
+        ("(2 ** 3) ** 2", Object::Integer(64)),
+        ("0 ** 0", Object::Integer(1)),
+        ("-2 ** 3", Object::Integer(-8)),
+        ("2 ** 62", Object::Integer(1 << 62)),
+        ("2.0 ** -1", Object::Float(0.5)),
+        ("4 ** 0.5", Object::Float(2.0)),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(
+            parse_and_eval(input).ok().unwrap(),
+            Rc::new(expected),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_eval_power_errors() {
+    let tests = [
+        ("2 ** -1", EvalError::NegativeExponent(-1)),
+        ("2 ** 63", EvalError::IntegerOverflow),
+        ("10 ** 5000000000", EvalError::IntegerOverflow),
+        ("true ** 2", EvalError::IncompatibleTypes),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_and_eval(input).err().unwrap(), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_null_literal() {
+    let tests = [
+        ("null", Object::Null),
+        ("let x = null; x == null", Object::Boolean(true)),
+        ("let x = null; x != null", Object::Boolean(false)),
+        ("5 == null", Object::Boolean(false)),
+        ("null != \"a\"", Object::Boolean(true)),
+        ("if (false) { 1 } == null", Object::Boolean(true)),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(
+            parse_and_eval(input).ok().unwrap(),
+            Rc::new(expected),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_eval_null_error_if_used_in_arithmetic() {
+    let error = parse_and_eval("null + 1").err().unwrap();
+    assert_eq!(error, EvalError::IncompatibleTypes);
+}
+
+#[test]
+fn test_eval_builtin_puts_null() {
+    let output = capture_output(|| {
+        parse_and_eval("puts(null)").unwrap();
+    });
+    assert_eq!(output, "null\n");
+}
+
+#[test]
+fn test_eval_raw_string() {
+    let input = r#"len(r"C:\Users\foo") + len(r"\n")"#;
+    assert_eq!(
+        parse_and_eval(input).ok().unwrap(),
+        Rc::new(Object::Integer(14))
+    );
+}
+
+#[test]
+fn test_eval_triple_quoted_string() {
+    let input = "let s = \"\"\"one
+\"two\"\"\"\"; [len(s), s]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(9)),
+        Rc::new(Object::String(String::from("one\n\"two\""))),
+    ])));
+    assert_eq!(parse_and_eval(input).ok().unwrap(), expected);
+}
+
+#[test]
+fn test_eval_builtin_puts_escaped_string() {
+    let output = capture_output(|| {
+        parse_and_eval(r#"puts("a\tb\nc")"#).unwrap();
+    });
+    assert_eq!(output, "a\tb\nc\n");
+}
+
+#[test]
+fn test_inspect_output_parses_back_to_same_string() {
+    let input = r#"inspect("tab\t \"quoted\" back\\slash\nnew line")"#;
+    let inspected = parse_and_eval(input).ok().unwrap();
+    let Object::String(literal) = &*inspected else {
+        panic!("inspect returns a string");
+    };
+    assert_eq!(
+        parse_and_eval(literal).ok().unwrap(),
+        Rc::new(Object::String(String::from(
+            "tab\t \"quoted\" back\\slash\nnew line"
+        )))
+    );
+}
+
+#[test]
+fn test_eval_builtin_to_json() {
+    let tests = vec![
+        ("to_json(null)", "null"),
+        ("to_json(-5)", "-5"),
+        ("to_json(2.5)", "2.5"),
+        ("to_json(true)", "true"),
+        (r#"to_json("say \"hi\"\n")"#, r#""say \"hi\"\n""#),
+        (r#"to_json([1, "a", [false]])"#, r#"[1,"a",[false]]"#),
+        (
+            r#"to_json({"b": [], "a": {"c": null}})"#,
+            r#"{"a":{"c":null},"b":[]}"#,
+        ),
+    ];
+
+    for (input, output) in tests {
+        let expected = Rc::new(Object::String(String::from(output)));
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_eval_builtin_from_json() {
+    let tests = vec![
+        (r#"from_json(" null ")"#, "null"),
+        (r#"from_json("-12")"#, "-12"),
+        (r#"from_json("1.5e2")"#, "150.0"),
+        (
+            r#"from_json("99999999999999999999")"#,
+            "100000000000000000000.0",
+        ),
+        (r#"from_json("false")"#, "false"),
+        (
+            r#"from_json("\"a\\u00e9\\ud83d\\ude00\\n\"")"#,
+            "\"a\u{e9}\u{1f600}\\n\"",
+        ),
+        (
+            r#"from_json("""{"a": [1, {"b": true}], "c": "d"}""")"#,
+            r#"{"a": [1, {"b": true}], "c": "d"}"#,
+        ),
+    ];
+
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_eval_builtin_json_round_trip() {
+    let input = r#"let v = {"name": "monkey", "tags": ["a\tb", ""], "n": [0, -1.25, null]};
+        [from_json(to_json(v)), v]"#;
+    let Object::Array(pair) = &*parse_and_eval(input).ok().unwrap() else {
+        panic!("expected an array");
+    };
+    let pair = pair.borrow();
+    assert_eq!(pair[0], pair[1]);
+}
+
+#[test]
+fn test_eval_builtin_json_errors() {
+    let tests = vec![
+        (
+            "to_json(fn(x) { x })",
+            EvalError::NotSerializable(String::from("function")),
+        ),
+        (
+            "to_json([len])",
+            EvalError::NotSerializable(String::from("builtin")),
+        ),
+        (
+            "to_json({1: 2})",
+            EvalError::NotSerializable(String::from("integer key")),
+        ),
+        (
+            r#"from_json("[1, 2")"#,
+            EvalError::InvalidJson(String::from("unexpected end of input")),
+        ),
+        (
+            r#"from_json("[1,]")"#,
+            EvalError::InvalidJson(String::from("unexpected ']'")),
+        ),
+        (
+            r#"from_json("01")"#,
+            EvalError::InvalidJson(String::from("unexpected '1'")),
+        ),
+        (
+            r#"from_json("""{1: 2}""")"#,
+            EvalError::InvalidJson(String::from("unexpected '1'")),
+        ),
+        (
+            r#"from_json("\"\\x\"")"#,
+            EvalError::InvalidJson(String::from(r"unknown escape \x")),
+        ),
+        ("from_json(1)", EvalError::IncompatibleTypes),
+        ("to_json(1, 2)", EvalError::IncorrectNumberOfArgs),
+    ];
+
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error);
+    }
+}
+
+#[test]
+fn test_eval_builtin_from_json_error_if_nested_too_deeply() {
+    let input = format!("from_json(\"{}\")", "[".repeat(1000));
+    let error = parse_and_eval(&input).err().unwrap();
+    assert_eq!(error, EvalError::NestingTooDeep);
+}
+
+// a URL that nothing is listening on, so requests to it fail straight away
+fn closed_url() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}/", listener.local_addr().unwrap())
+}
+
+// serves a single request with `response`, returning the URL to request
+#[cfg(feature = "http")]
+fn serve_once(response: &'static str) -> String {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.read(&mut [0; 1024]);
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+    url
+}
+
+#[test]
+fn test_eval_builtin_http_get_error_if_unreachable() {
+    let input = format!("is_error(http_get(\"{}\"))", closed_url());
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(&input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_eval_builtin_http_get() {
+    let url = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\n[1, 2]\n");
+    let input = format!("from_json(http_get(\"{url}\"))");
+    let expected = parse_and_eval("[1, 2]").ok().unwrap();
+    let result = parse_and_eval(&input).ok().unwrap();
+    assert_eq!(result, expected);
+
+    let url = serve_once("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    let input = format!("http_get(\"{url}\")");
+    let expected = Rc::new(Object::Error(format!("{url}: status code 404")));
+    let result = parse_and_eval(&input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[cfg(not(feature = "http"))]
+#[test]
+fn test_eval_builtin_http_get_without_feature() {
+    let expected = Rc::new(Object::Error(String::from(
+        "http_get needs rust-monkey to be built with `--features http`",
+    )));
+    let result = parse_and_eval("http_get(\"http://example.com\")")
+        .ok()
+        .unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_builtin_http_timeout() {
+    let result = parse_and_eval("http_timeout(0.5)").ok().unwrap();
+    assert_eq!(result, Rc::new(Object::Null));
+    // the timeout is per thread, so this only restores this test's one
+    let previous = http::set_timeout(Duration::from_secs(5));
+    assert_eq!(previous, Duration::from_millis(500));
+
+    let tests = vec![
+        ("http_timeout(0)", EvalError::IncompatibleTypes),
+        ("http_timeout(-1.5)", EvalError::IncompatibleTypes),
+        ("http_timeout(\"5\")", EvalError::IncompatibleTypes),
+        ("http_timeout()", EvalError::IncorrectNumberOfArgs),
+        ("http_get(1)", EvalError::IncompatibleTypes),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_string_comparison() {
+    let tests = [
+        ("\"abc\" < \"abd\"", true),
+        ("\"abd\" < \"abc\"", false),
+        ("\"ab\" < \"abc\"", true),
+        ("\"abc\" > \"ab\"", true),
+        ("\"\" < \"a\"", true),
+        ("\"\" <= \"\"", true),
+        ("\"b\" >= \"abc\"", true),
+        ("\"B\" < \"a\"", true),
+        ("\"abc\" == \"abc\"", true),
+        ("\"abc\" == \"ab\"", false),
+        ("\"\" == \"\"", true),
+        ("\"ab\" != \"abc\"", true),
+        ("let a = \"mon\"; a + \"key\" == \"monkey\"", true),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, Rc::new(Object::Boolean(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_eval_string_comparison_error_if_mixed_types() {
+    let tests = [
+        ("\"1\" < 2", "<", "string", "integer"),
+        ("1 >= \"2\"", ">=", "integer", "string"),
+        ("\"a\" > true", ">", "string", "boolean"),
+    ];
+    for (input, operator, left, right) in tests {
+        let expected_error = EvalError::UnsupportedOperands(operator, left, right);
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_string_repetition() {
+    let tests = [
+        ("\"-\" * 5", "-----"),
+        ("3 * \"ab\"", "ababab"),
+        ("\"abc\" * 0", ""),
+        ("\"\" * 1000", ""),
+        ("let n = 2; \"=\" * (n + 1)", "==="),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(
+            result,
+            Rc::new(Object::String(String::from(expected))),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_eval_string_repetition_errors() {
+    let tests = [
+        ("\"a\" * -1", EvalError::InvalidRepeatCount(-1)),
+        (
+            "\"a\" * 10_000_000_000",
+            EvalError::InvalidRepeatCount(10_000_000_000),
+        ),
+        (
+            "\"ab\" * 9_223_372_036_854_775_807",
+            EvalError::InvalidRepeatCount(i64::MAX),
+        ),
+        (
+            "\"a\" * 2.0",
+            EvalError::UnsupportedOperands("*", "string", "float"),
+        ),
+        (
+            "\"a\" * \"b\"",
+            EvalError::UnsupportedOperands("*", "string", "string"),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_array_concatenation() {
+    let tests = [
+        ("[1, 2] + [3]", "[1, 2, 3]"),
+        ("[] + []", "[]"),
+        ("[[1]] + [\"a\", [2]]", "[[1], \"a\", [2]]"),
+        ("let a = [1]; let b = a + a; [a, b]", "[[1], [1, 1]]"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_array_concatenation_error_if_not_array() {
+    let tests = [
+        ("[1] + 2", EvalError::UsePushToAppend("integer")),
+        ("[1] + \"a\"", EvalError::UsePushToAppend("string")),
+        ("[1] + {}", EvalError::UsePushToAppend("hash")),
+        ("2 + [1]", EvalError::IncompatibleTypes),
+        ("[1] - [1]", EvalError::IncompatibleTypes),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_deep_equality() {
+    let tests = [
+        ("[1, 2] == [1, 2]", true),
+        ("[1, 2] == [2, 1]", false),
+        ("[1, 2] == [1, 2, 3]", false),
+        ("[1, 2, 3] != [1, 2]", true),
+        ("[] == []", true),
+        ("[[1, [2]], \"a\"] == [[1, [2]], \"a\"]", true),
+        ("[[1, [2]]] == [[1, [3]]]", false),
+        ("[1] == [1.0]", true),
+        ("[1] == [\"1\"]", false),
+        ("[null] == [null]", true),
+        ("{\"a\": [1]} == {\"a\": [1]}", true),
+        ("{\"a\": 1} == {\"b\": 1}", false),
+        ("{\"a\": 1} == {\"a\": 1, \"b\": 2}", false),
+        ("{} == []", false),
+        ("let f = fn() { 1 }; [f] == [f]", true),
+        ("[fn() { 1 }] == [fn() { 1 }]", false),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, Rc::new(Object::Boolean(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_eval_index_assignment() {
+    let tests = [
+        ("let a = [1, 2]; a[1] = 5; a", "[1, 5]"),
+        ("let a = [1, 2]; a[0] *= 10; a", "[10, 2]"),
+        (
+            "let h = {}; h[1] = \"one\"; h[true] = 2; h",
+            "{1: \"one\", true: 2}",
+        ),
+        ("let a = [1]; a[0] = 2;", "null"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_index_assignment_errors() {
+    let tests = [
+        ("let a = [1, 2]; a[2] = 0;", EvalError::IndexOutOfBounds),
+        ("let a = [1, 2]; a[-3] = 0;", EvalError::IndexOutOfBounds),
+        (
+            "let a = [1, 2]; a[\"0\"] = 0;",
+            EvalError::IncompatibleTypes,
+        ),
+        (
+            "let s = \"ab\"; s[0] = \"c\";",
+            EvalError::IncompatibleTypes,
+        ),
+        (
+            "let h = {}; h[[1]] = 0;",
+            EvalError::UnhashableKey(String::from("array")),
+        ),
+        ("b[0] = 1;", EvalError::UndefinedVariable(String::from("b"))),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_import() {
+    let path = write_module(
+        "import_bindings.mk",
+        "export let answer = 42; export let double = fn(x) { x * 2 };",
+    );
+    let input = format!("import {path:?}; double(answer)");
+    let result = parse_and_eval(&input).ok().unwrap();
+    assert_eq!(result, Rc::new(Object::Integer(84)));
+}
+
+#[test]
+fn test_eval_import_runs_module_once() {
+    let path = write_module(
+        "import_once.mk",
+        r#"puts("loading"); let count = [0]; export let bump = fn() { count[0] += 1; count[0] };"#,
+    );
+    let input = format!("import {path:?}; bump(); import {path:?}; bump()");
+    let mut result = None;
+    let output = capture_output(|| result = parse_and_eval(&input).ok());
+    assert_eq!(output, "loading\n");
+    // the second import shares the state of the first rather than resetting it
+    assert_eq!(result, Some(Rc::new(Object::Integer(2))));
+}
+
+#[test]
+fn test_eval_import_relative_to_importer() {
+    write_module("relative/lib/inner.mk", "export let inner = 2;");
+    write_module(
+        "relative/lib/outer.mk",
+        r#"import "inner.mk"; export let outer = inner * 10;"#,
+    );
+    let path = write_module(
+        "relative/main.mk",
+        r#"import "lib/outer.mk"; export let total = outer + 1;"#,
+    );
+    let input = format!("import {path:?}; total");
+    let result = parse_and_eval(&input).ok().unwrap();
+    assert_eq!(result, Rc::new(Object::Integer(21)));
+}
+
+#[test]
+fn test_eval_circular_import() {
+    let canonical = |path: &str| {
+        std::fs::canonicalize(path)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let itself = write_module("circular_itself.mk", r#"import "circular_itself.mk";"#);
+    let first = write_module("circular_first.mk", r#"import "circular_second.mk";"#);
+    let second = write_module(
+        "circular_second.mk",
+        r#"export let x = 1; import "circular_first.mk";"#,
+    );
+    let (itself, first, second) = (canonical(&itself), canonical(&first), canonical(&second));
+    let tests = [
+        (&itself, format!("{itself} -> {itself}")),
+        (&first, format!("{first} -> {second} -> {first}")),
+        // nothing in the cycle was cached, so it is reported again
+        (&second, format!("{second} -> {first} -> {second}")),
+    ];
+    for (path, chain) in tests {
+        let error = parse_and_eval(&format!("import {path:?};")).err().unwrap();
+        assert_eq!(error, EvalError::CircularImport(chain), "{path}");
+    }
+}
+
+#[test]
+fn test_eval_import_errors() {
+    let unparsable = write_module("import_unparsable.mk", "let = 1;");
+    let failing = write_module("import_failing.mk", "let x = 1 + true;");
+    let importer = write_module("import_missing.mk", r#"import "missing.mk";"#);
+    let tests = [
+        (
+            String::from("/nonexistent/lib.mk"),
+            EvalError::ImportFailed(String::from("/nonexistent/lib.mk: NotFound")),
+        ),
+        (
+            unparsable.clone(),
+            EvalError::ImportFailed(format!("{unparsable}: UnexpectedToken(Assign)")),
+        ),
+        // the file that failed to import it is named too
+        (
+            importer.clone(),
+            EvalError::ImportFailed(format!("missing.mk (imported from {importer}): NotFound")),
+        ),
+        (failing.clone(), EvalError::IncompatibleTypes),
+        // a module that failed isn't cached, so it fails again
+        (failing, EvalError::IncompatibleTypes),
+    ];
+    for (path, expected_error) in tests {
+        let error = parse_and_eval(&format!("import {path:?};")).err().unwrap();
+        assert_eq!(error, expected_error, "{path}");
+    }
+}
+
+#[test]
+fn test_eval_import_only_sees_exports() {
+    let path = write_module(
+        "import_exports.mk",
+        "
+export let area = fn(r) { pi * square(r) };
+let pi = 3;
+let square = fn(x) { x * x };
+export square;
+",
+    );
+    let tests = [
+        (format!("import {path:?}; area(2)"), Ok(12)),
+        (format!("import {path:?}; square(5)"), Ok(25)),
+        (
+            format!("import {path:?}; pi"),
+            Err(EvalError::UndefinedVariable(String::from("pi"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(&input);
+        assert_eq!(result, expected.map(|int| Rc::new(Object::Integer(int))));
+    }
+}
+
+#[test]
+fn test_eval_export_of_missing_binding() {
+    let error = parse_and_eval("export missing;").err().unwrap();
+    assert_eq!(error, EvalError::UndefinedVariable(String::from("missing")));
+}
+
+#[test]
+fn test_eval_slice() {
+    let tests = [
+        ("[1, 2, 3, 4][1:3]", "[2, 3]"),
+        ("[1, 2, 3, 4][:2]", "[1, 2]"),
+        ("[1, 2, 3, 4][2:]", "[3, 4]"),
+        ("[1, 2, 3, 4][-2:]", "[3, 4]"),
+        ("[1, 2, 3, 4][:-1]", "[1, 2, 3]"),
+        ("[1, 2, 3, 4][-100:100]", "[1, 2, 3, 4]"),
+        ("[1, 2, 3, 4][3:1]", "[]"),
+        ("[][-1:1]", "[]"),
+        ("\"hello\"[1:3]", "\"el\""),
+        ("\"hello\"[-3:]", "\"llo\""),
+        ("\"hello\"[4:2]", "\"\""),
+        // a slice is a new array, so changing it leaves the original alone
+        ("let a = [1, 2]; let b = a[:]; b[0] = 5; a", "[1, 2]"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_slice_errors() {
+    let tests = ["5[1:2]", "{1: 2}[1:2]", "[1, 2][\"a\":]", "[1, 2][:1.5]"];
+    for input in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::IncompatibleTypes, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_range() {
+    let tests = [
+        ("1..5", "[1, 2, 3, 4]"),
+        ("-2..1", "[-2, -1, 0]"),
+        ("len(1..1) == 0", "true"),
+        ("5..1", "[]"),
+        ("let n = 3; 0..n + 1", "[0, 1, 2, 3]"),
+        ("let sum = 0; for (i in 1..11) { sum += i; } sum", "55"),
+        (
+            "9223372036854775806..9223372036854775807",
+            "[9223372036854775806]",
+        ),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_range_errors() {
+    let tests = [
+        (
+            "1..2.5",
+            EvalError::UnsupportedOperands("..", "integer", "float"),
+        ),
+        (
+            "\"a\"..\"c\"",
+            EvalError::UnsupportedOperands("..", "string", "string"),
+        ),
+        ("0..4294967296", EvalError::RangeTooLong(0, 4294967296)),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_match() {
+    let tests = [
+        (r#"match (2) { 1 => "one", 2 => "two", _ => "many" }"#, r#""two""#),
+        (r#"match (7) { 1 => "one", 2 => "two", _ => "many" }"#, r#""many""#),
+        ("match (7) { 1 => 10 }", "null"),
+        ("match (1) { 1 => {} }", "null"),
+        ("match (1) { 1 => { let y = 2; y * 3 } }", "6"),
+        // compared like `==`, but values of different types don't match
+        ("match (1.0) { 1 => true }", "true"),
+        (r#"match ("1") { 1 => true, _ => false }"#, "false"),
+        ("match ([1, [2]]) { [1, [2]] => true }", "true"),
+        ("let x = 3; match (x + 1) { x => 1, x + 1 => 2 }", "2"),
+        ("let x = match (1) { 1 => 5 } * 2; x", "10"),
+        ("let f = fn(n) { match (n) { 0 => { return -1; } } n }; [f(0), f(4)]", "[-1, 4]"),
+        ("let s = 0; for (i in 0..5) { match (i) { 1 => { continue; }, 3 => { break; } } s += i; } s", "2"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_match_stops_at_first_matching_arm() {
+    let input = r#"match (1) { 1 => puts("a"), 1 => puts("b"), puts("c") => 0 }"#;
+    let output = capture_output(|| {
+        parse_and_eval(input).ok().unwrap();
+    });
+    assert_eq!(output, "a\n");
+}
+
+#[test]
+fn test_eval_function_statement() {
+    let tests = [
+        (
+            "fn fact(n) { if (n < 2) { 1 } else { n * fact(n - 1) } } fact(5)",
+            "120",
+        ),
+        (
+            "fn outer() { fn inner(n) { if (n == 0) { 0 } else { 1 + inner(n - 1) } } inner(3) } outer()",
+            "3",
+        ),
+        ("fn(x) { x * 3 }(2)", "6"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_default_parameters() {
+    let tests = [
+        (
+            r#"let greet = fn(name, greeting = "hello") { greeting + " " + name }; [greet("sam"), greet("sam", "hi")]"#,
+            r#"["hello sam", "hi sam"]"#,
+        ),
+        (
+            "let f = fn(a = 1, b = 2) { [a, b] }; [f(), f(5), f(5, 6)]",
+            "[[1, 2], [5, 2], [5, 6]]",
+        ),
+        // a default can use the parameters before it and the bindings where
+        // the function was defined
+        ("let x = 10; let f = fn(a, b = a + x) { b }; f(1)", "11"),
+        // and is evaluated again on every call
+        (
+            "let f = fn(a = []) { push(a, 1) }; [f(), f()]",
+            "[[1], [1]]",
+        ),
+        ("let f = fn(a = null) { a }; f(null)", "null"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_default_parameters_arity_errors() {
+    for input in [
+        "let f = fn(a, b = 2) { a }; f()",
+        "let f = fn(a, b = 2) { a }; f(1, 2, 3)",
+    ] {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::IncorrectNumberOfArgs, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_spread_arguments() {
+    let tests = [
+        (
+            "let add = fn(a, b, c) { a + b + c }; add(...[1, 2, 3])",
+            "6",
+        ),
+        (
+            "let add = fn(a, b, c) { a + b + c }; add(1, ...[2], 3)",
+            "6",
+        ),
+        (
+            "let add = fn(a, b, c) { a + b + c }; add(...[1], ...[], ...[2, 3])",
+            "6",
+        ),
+        (
+            "let f = fn(a, b = 10) { a + b }; [f(...[1]), f(...[1, 2])]",
+            "[11, 3]",
+        ),
+        ("let xs = [1, 2]; len(...[xs])", "2"),
+        ("let f = fn() { 0 }; f(...[])", "0"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_spread_argument_errors() {
+    let tests = [
+        (
+            "let f = fn(a) { a }; f(...1)",
+            EvalError::CannotSpread("integer"),
+        ),
+        (
+            r#"let f = fn(a) { a }; f(..."ab")"#,
+            EvalError::CannotSpread("string"),
+        ),
+        // arity is checked once the arguments have been spread
+        (
+            "let f = fn(a) { a }; f(...[1, 2])",
+            EvalError::IncorrectNumberOfArgs,
+        ),
+        (
+            "let f = fn(a, b) { a }; f(...[1])",
+            EvalError::IncorrectNumberOfArgs,
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_named_arguments() {
+    let tests = [
+        ("fn(a, b) { a - b }(b: 3, a: 10)", "7"),
+        ("fn(a, b, c) { [a, b, c] }(1, c: 3, b: 2)", "[1, 2, 3]"),
+        ("fn(a, b) { [a, b] }(...[1], b: 2)", "[1, 2]"),
+        ("fn(a, b = 2, c = 3) { [a, b, c] }(1, b: 4)", "[1, 4, 3]"),
+        ("fn(a, b = a * 2) { [a, b] }(a: 5)", "[5, 10]"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+
+    let tests = [
+        (
+            "fn(a) { a }(b: 1)",
+            EvalError::UnknownArgument(String::from("b")),
+        ),
+        (
+            "fn(a, b) { a }(1, a: 2)",
+            EvalError::DuplicateArgument(String::from("a")),
+        ),
+        (
+            "fn(a, b) { a }(a: 1, a: 2)",
+            EvalError::DuplicateArgument(String::from("a")),
+        ),
+        (
+            "fn(a, b) { a }(b: 1)",
+            EvalError::MissingArgument(String::from("a")),
+        ),
+        // only the parameters at the end can be left out
+        (
+            "fn(a, b = 2, c = 3) { a }(1, c: 4)",
+            EvalError::MissingArgument(String::from("b")),
+        ),
+        (
+            "len(array: [])",
+            EvalError::UnknownArgument(String::from("array")),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_recursive_local_function() {
+    let tests = [
+        (
+            "let outer = fn() { let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5) }; outer()",
+            "120",
+        ),
+        (
+            "let f = fn(n) { if (n == 0) { \"outer\" } else { f(n - 1) } };
+             let g = fn() { let f = fn(n) { if (n == 0) { \"inner\" } else { f(n - 1) } }; f(2) };
+             [g(), f(2)]",
+            "[\"inner\", \"outer\"]",
+        ),
+        // a function keeps calling itself after its name is rebound
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; let g = f; let f = fn(n) { 999 }; g(3)",
+            "0",
+        ),
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { let f = fn(m) { 100 + m }; f(n) } }; f(1)",
+            "101",
+        ),
+        ("let f = fn(f) { f }; f(5)", "5"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_quote() {
+    let tests = [
+        ("quote(5)", "QUOTE(5)"),
+        ("quote(5 + 8)", "QUOTE(5 + 8)"),
+        ("quote(foobar + barfoo)", "QUOTE(foobar + barfoo)"),
+        ("quote(unquote(4 + 4))", "QUOTE(8)"),
+        ("quote(8 + unquote(4 + 4))", "QUOTE(8 + 8)"),
+        ("let foobar = 8; quote(foobar)", "QUOTE(foobar)"),
+        ("let foobar = 8; quote(unquote(foobar))", "QUOTE(8)"),
+        ("quote(unquote(true == false))", "QUOTE(false)"),
+        ("quote(unquote(quote(4 + 4)))", "QUOTE(4 + 4)"),
+        (
+            "let q = quote(4 + 4); quote(unquote(4 + 4) + unquote(q))",
+            "QUOTE(8 + (4 + 4))",
+        ),
+        (
+            r#"quote(unquote([1.5, "a", null]))"#,
+            r#"QUOTE([1.5, "a", null])"#,
+        ),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result.to_string(), expected, "{input}");
+    }
+
+    let tests = [
+        ("quote()", EvalError::IncorrectNumberOfArgs),
+        ("quote(...[1])", EvalError::MacroSpread),
+        ("quote(unquote(1, 2))", EvalError::IncorrectNumberOfArgs),
+        ("quote(unquote(len))", EvalError::CannotUnquote("builtin")),
+        (
+            "quote(unquote(x))",
+            EvalError::UndefinedVariable(String::from("x")),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+// defines and expands the macros in `input`, returning what is left of it
+fn expand(input: &str) -> Result<Program, EvalError> {
+    let program = Parser::parse_program(input).unwrap();
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let program = define_macros(program, &env);
+    expand_macros(program, &env)
+}
+
+#[test]
+fn test_define_macros() {
+    let input =
+        "let number = 1; let function = fn(x, y) { x + y }; let mymacro = macro(x, y) { x + y; };";
+    let program = Parser::parse_program(input).unwrap();
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let Program(statements) = define_macros(program, &env);
+
+    assert_eq!(statements.len(), 2);
+    assert_eq!(env.borrow().get("number"), None);
+    assert_eq!(env.borrow().get("function"), None);
+    let mymacro = env.borrow().get("mymacro").unwrap();
+    let Object::Macro(mymacro) = &*mymacro else {
+        panic!("expected a macro, got {mymacro:?}");
+    };
+    assert_eq!(
+        mymacro.parameters,
+        [(String::from("x"), None), (String::from("y"), None)]
+    );
+    assert_eq!(mymacro.body.to_string(), "{\n    x + y;\n}");
+}
+
+#[test]
+fn test_expand_macros() {
+    let tests = [
+        ("let infix = macro() { quote(1 + 2); }; infix();", "1 + 2;"),
+        (
+            "let reverse = macro(a, b) { quote(unquote(b) - unquote(a)); }; reverse(2 + 2, 10 - 5);",
+            "(10 - 5) - (2 + 2);",
+        ),
+        (
+            "let unless = macro(condition, consequence, alternative) {
+                quote(if (!(unquote(condition))) {
+                    unquote(consequence);
+                } else {
+                    unquote(alternative);
+                });
+            };
+            unless(10 > 5, puts(\"not greater\"), puts(\"greater\"));",
+            "if (!(10 > 5)) { puts(\"not greater\") } else { puts(\"greater\") };",
+        ),
+        // arguments are expanded first
+        (
+            "let twice = macro(x) { quote(unquote(x) * 2) }; twice(twice(1));",
+            "(1 * 2) * 2;",
+        ),
+        (
+            "let inc = macro(x, by = quote(1)) { quote(unquote(x) + unquote(by)) }; [inc(a), inc(a, 5)];",
+            "[a + 1, a + 5];",
+        ),
+        (
+            "let m = macro() { return quote(1); }; let f = fn() { m() };",
+            "let f = fn() { 1 };",
+        ),
+        // only calls by name are expanded
+        ("let m = macro() { quote(1) }; [m][0]();", "[m][0]();"),
+    ];
+    for (input, expected) in tests {
+        let program = expand(input).unwrap();
+        assert_eq!(program, Parser::parse_program(expected).unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn test_expand_macro_errors() {
+    let tests = [
+        (
+            "let m = macro(x) { x }; m(1, 2)",
+            EvalError::IncorrectNumberOfArgs,
+        ),
+        (
+            "let m = macro(x) { x }; m()",
+            EvalError::IncorrectNumberOfArgs,
+        ),
+        (
+            "let m = macro() { 1 }; m()",
+            EvalError::NotQuoted("integer"),
+        ),
+        ("let m = macro(x) { x }; m(...xs)", EvalError::MacroSpread),
+        (
+            "let m = macro() { quote(unquote(fn() { 1 })) }; m()",
+            EvalError::CannotUnquote("function"),
+        ),
+        (
+            "let m = macro() { quote(unquote(y)) }; m()",
+            EvalError::UndefinedVariable(String::from("y")),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let error = expand(input).err().unwrap();
+        assert_eq!(error, expected_error, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_unexpanded_macro() {
+    let tests = [
+        "macro(x) { x }",
+        "let f = fn() { let m = macro() { quote(1) }; 1 }; f()",
+    ];
+    for input in tests {
+        let error = expand(input)
+            .and_then(|program| eval(program, Rc::new(RefCell::new(Environment::new()))));
+        assert_eq!(error, Err(EvalError::UnexpandedMacro), "{input}");
+    }
+}
+
+#[test]
+fn test_eval_const() {
+    let tests = [
+        ("const PI = 3; PI * 2", "6"),
+        (
+            "const f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(3)",
+            "0",
+        ),
+        // shadowing in an inner scope is allowed
+        (
+            "const PI = 3; let f = fn() { let PI = 4; PI = 5; PI }; [f(), PI]",
+            "[5, 3]",
+        ),
+        (
+            "const n = 1; let f = fn(n) { n = 2; n }; [f(0), n]",
+            "[2, 1]",
+        ),
+        // as is binding the name again
+        ("const x = 1; let x = 2; x = 3; x", "3"),
+        // only the binding is constant, not the value
+        ("const xs = [1]; xs[0] = 2; xs", "[2]"),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+
+    let tests = [
+        "const PI = 3; PI = 4;",
+        "const PI = 3; PI += 1;",
+        "let x = 1; const PI = 3; let x = 2; PI = x;",
+        "const PI = 3; let f = fn() { PI = 4; }; f()",
+        "const PI = 3; let f = fn() { fn() { PI = 4; } }; f()()",
+        "let f = fn() { const PI = 3; fn() { PI = 4; } }; f()()",
+    ];
+    for input in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(
+            error,
+            EvalError::ReassignConstant(String::from("PI")),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_eval_let_destructure() {
+    let tests = [
+        ("let [a, b, c] = [1, 2, 3]; a + b + c", "6"),
+        ("let [a, b] = [1, 2, 3]; [a, b]", "[1, 2]"),
+        ("let [a, ...rest] = [1, 2, 3]; [a, rest]", "[1, [2, 3]]"),
+        ("let [a, b, ...rest] = [1, 2]; rest", "[]"),
+        ("let [] = [1]; 1", "1"),
+        (
+            "let a = 1; let b = 2; let [a, b] = [b, a]; [a, b]",
+            "[2, 1]",
+        ),
+        (
+            "let f = fn(xs) { let [x, ...rest] = xs; [x, len(rest)] }; f([1, 2, 3])",
+            "[1, 2]",
+        ),
+    ];
+    for (input, output) in tests {
+        let expected = parse_and_eval(output).ok().unwrap();
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, expected, "{input}");
+    }
+
+    let tests = [
+        ("let [a, b] = [1];", EvalError::TooFewElements(2, 1)),
+        ("let [a, ...rest] = [];", EvalError::TooFewElements(1, 0)),
+        ("let [a] = 1;", EvalError::CannotDestructure("integer")),
+        (r#"let [a] = "a";"#, EvalError::CannotDestructure("string")),
+    ];
+    for (input, expected) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_string_template() {
+    let tests = [
+        (
+            r#"let name = "sam"; let age = 41; "hello {name}, you are {age + 1}""#,
+            "hello sam, you are 42",
+        ),
+        (r#""{[1, 2]} {null} {1.5} {true}""#, "[1, 2] null 1.5 true"),
+        (r#"let name = "sam"; "a {"b {name}"} c""#, "a b sam c"),
+        (r#""{len("}")} {{x}}""#, "1 {x}"),
+        (
+            r#"let f = fn(n) { "n is {if (n > 0) { "positive" } else { "not" }}" }; f(1)"#,
+            "n is positive",
+        ),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(
+            result,
+            Rc::new(Object::String(String::from(expected))),
+            "{input}"
+        );
+    }
+
+    let error = parse_and_eval(r#""{missing}""#).err().unwrap();
+    assert_eq!(error, EvalError::UndefinedVariable(String::from("missing")));
 }
@@ -1,6 +1,6 @@
-use std::{iter::Peekable, str::Chars};
+use std::{iter::Peekable, mem, str::Chars};
 
-use token::Token;
+use token::{TemplateToken, Token};
 
 mod tests;
 pub mod token;
@@ -21,6 +21,35 @@ impl<'a> LexerIter<'a> {
         }
     }
 
+    // a line ends at `\n`, `\r\n` or a lone `\r`, any `\n` left being
+    // skipped as whitespace
+    fn skip_line(&mut self) {
+        for c in self.iter.by_ref() {
+            if c == '\n' || c == '\r' {
+                break;
+            }
+        }
+    }
+
+    // block comments don't nest, so the first `*/` ends the comment; returns
+    // false if the input ends before the comment does
+    fn skip_block_comment(&mut self) -> bool {
+        while let Some(c) = self.iter.next() {
+            if c == '*' && self.iter.next_if_eq(&'/').is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    // an operator followed directly by '=' is its compound assignment
+    fn with_assign(&mut self, operator: Token, compound: Token) -> Token {
+        match self.iter.next_if_eq(&'=') {
+            Some(_) => compound,
+            None => operator,
+        }
+    }
+
     fn get_rest_of_word(&mut self, ch: char) -> String {
         let mut word = String::from(ch);
         while let Some(c) = self.iter.peek() {
@@ -37,7 +66,8 @@ impl<'a> LexerIter<'a> {
     fn get_rest_of_number(&mut self, ch: char) -> String {
         let mut num = String::from(ch);
         while let Some(c) = self.iter.peek() {
-            if c.is_ascii_digit() {
+            // underscores separate digits; the parser checks where they are
+            if c.is_ascii_digit() || *c == '_' {
                 // unwrap safe here since already peeked
                 num.push(self.iter.next().unwrap());
             } else {
@@ -47,18 +77,183 @@ impl<'a> LexerIter<'a> {
         num
     }
 
-    fn get_string(&mut self) -> String {
-        let mut string = String::new();
-        while let Some(c) = self.iter.peek() {
-            if *c == '"' {
+    // the digits of a `0x`, `0o` or `0b` literal; anything alphanumeric is
+    // taken, so that the parser can reject digits that don't fit the base
+    fn get_radix_number(&mut self, prefix: char) -> String {
+        let mut num = format!("0{prefix}");
+        while let Some(c) = self
+            .iter
+            .next_if(|c| c.is_ascii_alphanumeric() || *c == '_')
+        {
+            num.push(c);
+        }
+        num
+    }
+
+    // `1.` and `.5` are lexed as floats so that the parser can reject them with
+    // a specific error rather than an unexpected token
+    fn get_number(&mut self, ch: char) -> Token {
+        if ch == '0' {
+            if let Some(prefix) = self.iter.next_if(|c| matches!(c, 'x' | 'o' | 'b')) {
+                return Token::Int(self.get_radix_number(prefix));
+            }
+        }
+        let num = self.get_rest_of_number(ch);
+        if ch == '.' {
+            return Token::Float(num);
+        }
+        // `1..` is the start of a range rather than a float
+        if self.next_is("..") {
+            return Token::Int(num);
+        }
+        match self.iter.peek() {
+            Some('.') => {
                 self.iter.next();
-                break;
+                let fraction = self.get_rest_of_number('.');
+                Token::Float(num + &fraction)
             }
-            string.push(*c);
+            _ => Token::Int(num),
+        }
+    }
+
+    // whether the input continues with `text`, without consuming it
+    fn next_is(&self, text: &str) -> bool {
+        let mut ahead = self.iter.clone();
+        text.chars().all(|c| ahead.next() == Some(c))
+    }
+
+    // the opening `"""` has been consumed up to its first quote; the string
+    // runs to the next `"""`, so it can contain newlines and quotes, and
+    // ends with any quotes that come before the last three of a run
+    fn get_triple_quoted_string(&mut self) -> Token {
+        self.iter.nth(1);
+        let mut string = String::new();
+        while !self.next_is("\"\"\"") {
+            match self.iter.next() {
+                Some('\\') => self.push_escape(&mut string),
+                Some(c) => string.push(c),
+                None => return Self::unescape(&string),
+            }
+        }
+        while self.next_is("\"\"\"\"") {
+            string.push('"');
             self.iter.next();
         }
+        self.iter.nth(2);
+        Self::unescape(&string)
+    }
+
+    // `{{` and `}}` stand for `{` and `}`, and any other `{` starts an
+    // expression that runs to its matching `}`, making the string a template
+    fn get_string(&mut self) -> Token {
+        let mut parts = vec![];
+        let mut string = String::new();
+        while let Some(c) = self.iter.next() {
+            match c {
+                '"' => break,
+                '\\' => self.push_escape(&mut string),
+                '{' if self.iter.next_if_eq(&'{').is_some() => string.push('{'),
+                '}' if self.iter.next_if_eq(&'}').is_some() => string.push('}'),
+                '{' => {
+                    let Some(code) = self.get_interpolation() else {
+                        return Token::UnclosedInterpolation;
+                    };
+                    parts.push(TemplateToken::Text(mem::take(&mut string)));
+                    parts.push(TemplateToken::Code(code));
+                }
+                _ => string.push(c),
+            }
+        }
+        if parts.is_empty() {
+            return Self::unescape(&string);
+        }
+        parts.push(TemplateToken::Text(string));
+
+        let mut template = vec![];
+        for part in parts {
+            match part {
+                TemplateToken::Text(text) => match Self::unescape(&text) {
+                    Token::String(text) if text.is_empty() => {}
+                    Token::String(text) => template.push(TemplateToken::Text(text)),
+                    invalid => return invalid,
+                },
+                code => template.push(code),
+            }
+        }
+        Token::Template(template)
+    }
+
+    // the source up to the `}` that closes the `{` just consumed, skipping
+    // over any braces in nested blocks, hashes and strings; `None` if the
+    // input ends first
+    fn get_interpolation(&mut self) -> Option<String> {
+        let mut code = String::new();
+        let mut depth = 0;
+        loop {
+            let c = self.iter.next()?;
+            match c {
+                '}' if depth == 0 => return Some(code),
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' => {
+                    code.push(c);
+                    loop {
+                        match self.iter.next()? {
+                            '"' => break,
+                            '\\' => self.push_escape(&mut code),
+                            c => code.push(c),
+                        }
+                    }
+                }
+                _ => {}
+            }
+            code.push(c);
+        }
+    }
+
+    fn get_raw_string(&mut self) -> String {
+        let mut string = String::new();
+        for c in self.iter.by_ref() {
+            if c == '"' {
+                break;
+            }
+            string.push(c);
+        }
         string
     }
+
+    // keeps a backslash together with the character after it, so that `\"`
+    // doesn't end a string; `unescape` then works out what it stands for
+    fn push_escape(&mut self, string: &mut String) {
+        string.push('\\');
+        if let Some(c) = self.iter.next() {
+            string.push(c);
+        }
+    }
+
+    // the string with every escape sequence replaced by the character it
+    // stands for, or the first sequence that isn't known
+    fn unescape(string: &str) -> Token {
+        let mut unescaped = String::new();
+        let mut chars = string.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            unescaped.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some(other) => return Token::InvalidEscape(format!("\\{other}")),
+                None => return Token::InvalidEscape(String::from("\\")),
+            });
+        }
+        Token::String(unescaped)
+    }
 }
 
 impl<'a> Iterator for LexerIter<'a> {
@@ -68,14 +263,48 @@ impl<'a> Iterator for LexerIter<'a> {
         self.skip_whitespace();
         let ch = self.iter.next()?;
         match ch {
-            '+' => Some(Token::Plus),
-            '-' => Some(Token::Minus),
-            '*' => Some(Token::Asterisk),
-            '/' => Some(Token::Slash),
-            '<' => Some(Token::Lt),
-            '>' => Some(Token::Gt),
+            '+' => Some(self.with_assign(Token::Plus, Token::PlusAssign)),
+            '-' => Some(self.with_assign(Token::Minus, Token::MinusAssign)),
+            '*' => match self.iter.next_if_eq(&'*') {
+                Some(_) => Some(Token::Power),
+                None => Some(self.with_assign(Token::Asterisk, Token::AsteriskAssign)),
+            },
+            '/' => {
+                match self.iter.peek() {
+                    Some('/') => {
+                        // comments run to the end of the line
+                        self.skip_line();
+                        self.next()
+                    }
+                    Some('*') => {
+                        self.iter.next();
+                        if self.skip_block_comment() {
+                            self.next()
+                        } else {
+                            Some(Token::UnterminatedComment)
+                        }
+                    }
+                    Some('=') => {
+                        self.iter.next();
+                        Some(Token::SlashAssign)
+                    }
+                    _ => Some(Token::Slash),
+                }
+            }
+            '<' => match self.iter.next_if(|c| *c == '=' || *c == '<') {
+                Some('=') => Some(Token::LtEq),
+                Some(_) => Some(Token::ShiftLeft),
+                None => Some(Token::Lt),
+            },
+            '>' => match self.iter.next_if(|c| *c == '=' || *c == '>') {
+                Some('=') => Some(Token::GtEq),
+                Some(_) => Some(Token::ShiftRight),
+                None => Some(Token::Gt),
+            },
+            '^' => Some(Token::Caret),
             ';' => Some(Token::Semicolon),
             ':' => Some(Token::Colon),
+            '?' => Some(Token::Question),
             '(' => Some(Token::Lparen),
             ')' => Some(Token::Rparen),
             ',' => Some(Token::Comma),
@@ -90,7 +319,7 @@ impl<'a> Iterator for LexerIter<'a> {
                         return Some(Token::And);
                     }
                 }
-                Some(Token::Illegal)
+                Some(Token::Ampersand)
             }
             '|' => {
                 if let Some(c) = self.iter.peek() {
@@ -99,13 +328,16 @@ impl<'a> Iterator for LexerIter<'a> {
                         return Some(Token::Or);
                     }
                 }
-                Some(Token::Illegal)
+                Some(Token::Pipe)
             }
             '=' => {
                 if let Some(c) = self.iter.peek() {
                     if *c == '=' {
                         self.iter.next();
                         return Some(Token::Eq);
+                    } else if *c == '>' {
+                        self.iter.next();
+                        return Some(Token::FatArrow);
                     }
                 }
                 Some(Token::Assign)
@@ -119,23 +351,48 @@ impl<'a> Iterator for LexerIter<'a> {
                 }
                 Some(Token::Bang)
             }
-            '"' => Some(Token::String(self.get_string())),
+            '.' if self.next_is("..") => {
+                self.iter.nth(1);
+                Some(Token::Ellipsis)
+            }
+            '.' if self.iter.next_if_eq(&'.').is_some() => Some(Token::DotDot),
+            '"' if self.next_is("\"\"") => Some(self.get_triple_quoted_string()),
+            '"' => Some(self.get_string()),
+            // a raw string runs to the next '"', so it can't contain one
+            'r' if self.iter.next_if_eq(&'"').is_some() => {
+                Some(Token::String(self.get_raw_string()))
+            }
             _ => {
-                if ch.is_ascii_alphabetic() || ch == '_' {
+                // `_1` can't be an identifier since those have no digits, so it
+                // is lexed as a number for the parser to reject
+                if ch.is_ascii_digit()
+                    || (matches!(ch, '.' | '_')
+                        && self.iter.peek().is_some_and(char::is_ascii_digit))
+                {
+                    Some(self.get_number(ch))
+                } else if ch.is_ascii_alphabetic() || ch == '_' {
                     let word = self.get_rest_of_word(ch);
                     match word.as_str() {
                         "let" => Some(Token::Let),
+                        "const" => Some(Token::Const),
                         "fn" => Some(Token::Function),
                         "true" => Some(Token::True),
                         "false" => Some(Token::False),
+                        "null" => Some(Token::Null),
                         "if" => Some(Token::If),
                         "else" => Some(Token::Else),
                         "return" => Some(Token::Return),
                         "while" => Some(Token::While),
+                        "for" => Some(Token::For),
+                        "in" => Some(Token::In),
+                        "break" => Some(Token::Break),
+                        "continue" => Some(Token::Continue),
+                        "import" => Some(Token::Import),
+                        "export" => Some(Token::Export),
+                        "match" => Some(Token::Match),
+                        "macro" => Some(Token::Macro),
                         _ => Some(Token::Identifier(word)),
                     }
-                } else if ch.is_ascii_digit() {
-                    Some(Token::Int(self.get_rest_of_number(ch)))
                 } else {
                     Some(Token::Illegal)
                 }
@@ -145,13 +402,19 @@ impl<'a> Iterator for LexerIter<'a> {
 }
 
 pub trait Lexer {
-    fn tokens(&self) -> LexerIter;
+    fn tokens(&self) -> LexerIter<'_>;
 }
 
 impl Lexer for str {
-    fn tokens(&self) -> LexerIter {
-        LexerIter {
+    fn tokens(&self) -> LexerIter<'_> {
+        let mut lexer = LexerIter {
             iter: self.chars().peekable(),
+        };
+        // a `#!` line is only skipped at the very start, so that a script can
+        // be run directly on Unix
+        if self.starts_with("#!") {
+            lexer.skip_line();
         }
+        lexer
     }
 }
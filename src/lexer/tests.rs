@@ -1,6 +1,7 @@
 #![cfg(test)]
 
-use crate::lexer::{token::Token, Lexer};
+use crate::lexer::token::{TemplateToken, Token};
+use crate::lexer::Lexer;
 
 #[test]
 fn test_lexer_one() {
@@ -74,7 +75,7 @@ let result = add(five, ten);
 
 #[test]
 fn test_lexer_three() {
-    let input = "!-/*5;
+    let input = "!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -172,3 +173,538 @@ while (x < 0) { x + x; }
     let tokens: Vec<_> = input.tokens().collect();
     assert_eq!(tests, tokens);
 }
+
+#[test]
+fn test_lexer_comparison_operators() {
+    let input = "a <= b >= c < d > e";
+    let tests = vec![
+        Token::Identifier(String::from("a")),
+        Token::LtEq,
+        Token::Identifier(String::from("b")),
+        Token::GtEq,
+        Token::Identifier(String::from("c")),
+        Token::Lt,
+        Token::Identifier(String::from("d")),
+        Token::Gt,
+        Token::Identifier(String::from("e")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_not_equal_and_bang() {
+    let a = || Token::Identifier(String::from("a"));
+    let b = || Token::Identifier(String::from("b"));
+    let tests = [
+        ("a != b", vec![a(), Token::Noteq, b()]),
+        ("a!=b", vec![a(), Token::Noteq, b()]),
+        (
+            "!(a == b)",
+            vec![
+                Token::Bang,
+                Token::Lparen,
+                a(),
+                Token::Eq,
+                b(),
+                Token::Rparen,
+            ],
+        ),
+        ("!!a", vec![Token::Bang, Token::Bang, a()]),
+        // only adjacent characters make one token
+        ("a ! = b", vec![a(), Token::Bang, Token::Assign, b()]),
+        ("a !== b", vec![a(), Token::Noteq, Token::Assign, b()]),
+    ];
+    for (input, expected) in tests {
+        let tokens: Vec<_> = input.tokens().collect();
+        assert_eq!(tokens, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_lexer_float_literals() {
+    let input = "3.14 10 0.5 1. .5";
+    let tests = vec![
+        Token::Float(String::from("3.14")),
+        Token::Int(String::from("10")),
+        Token::Float(String::from("0.5")),
+        Token::Float(String::from("1.")),
+        Token::Float(String::from(".5")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_ranges() {
+    let input = "1..10 a..b 1.5..2 ..5";
+    let tests = vec![
+        Token::Int(String::from("1")),
+        Token::DotDot,
+        Token::Int(String::from("10")),
+        Token::Identifier(String::from("a")),
+        Token::DotDot,
+        Token::Identifier(String::from("b")),
+        Token::Float(String::from("1.5")),
+        Token::DotDot,
+        Token::Int(String::from("2")),
+        Token::DotDot,
+        Token::Int(String::from("5")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_single_line_comments() {
+    let input = "// a comment
+let x = 5; // hi
+x / 2; //";
+    let tests = vec![
+        Token::Let,
+        Token::Identifier(String::from("x")),
+        Token::Assign,
+        Token::Int(String::from("5")),
+        Token::Semicolon,
+        Token::Identifier(String::from("x")),
+        Token::Slash,
+        Token::Int(String::from("2")),
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_block_comments() {
+    let input = "let /* a
+multi-line ** comment */ x = 5 /**/ / 2;";
+    let tests = vec![
+        Token::Let,
+        Token::Identifier(String::from("x")),
+        Token::Assign,
+        Token::Int(String::from("5")),
+        Token::Slash,
+        Token::Int(String::from("2")),
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_block_comments_do_not_nest() {
+    let input = "/* a /* b */ c */";
+    let tests = vec![
+        Token::Identifier(String::from("c")),
+        Token::Asterisk,
+        Token::Slash,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_unterminated_block_comment() {
+    let input = "x; /* never closed *";
+    let tests = vec![
+        Token::Identifier(String::from("x")),
+        Token::Semicolon,
+        Token::UnterminatedComment,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_comment_markers_inside_strings() {
+    let input = r#""/* not a comment */" "// nor this""#;
+    let tests = vec![
+        Token::String(String::from("/* not a comment */")),
+        Token::String(String::from("// nor this")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_for_loop() {
+    let input = "for (x in xs) { x }";
+    let tests = vec![
+        Token::For,
+        Token::Lparen,
+        Token::Identifier(String::from("x")),
+        Token::In,
+        Token::Identifier(String::from("xs")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Identifier(String::from("x")),
+        Token::Rbrace,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_break_and_continue() {
+    let input = "break; continue;";
+    let tests = vec![
+        Token::Break,
+        Token::Semicolon,
+        Token::Continue,
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_import_and_export() {
+    let input = r#"import "lib.mk"; important; export x;"#;
+    let tests = vec![
+        Token::Import,
+        Token::String(String::from("lib.mk")),
+        Token::Semicolon,
+        Token::Identifier(String::from("important")),
+        Token::Semicolon,
+        Token::Export,
+        Token::Identifier(String::from("x")),
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_compound_assignment() {
+    let input = "x += 1; x -= 2; x *= 3; x /= 4; + = -";
+    let mut tests = vec![];
+    for (operator, value) in [
+        (Token::PlusAssign, "1"),
+        (Token::MinusAssign, "2"),
+        (Token::AsteriskAssign, "3"),
+        (Token::SlashAssign, "4"),
+    ] {
+        tests.extend([
+            Token::Identifier(String::from("x")),
+            operator,
+            Token::Int(String::from(value)),
+            Token::Semicolon,
+        ]);
+    }
+    tests.extend([Token::Plus, Token::Assign, Token::Minus]);
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_conditional() {
+    let input = "a ? b : c";
+    let expected = vec![
+        Token::Identifier(String::from("a")),
+        Token::Question,
+        Token::Identifier(String::from("b")),
+        Token::Colon,
+        Token::Identifier(String::from("c")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_bitwise_operators() {
+    let input = "a & b && c | d || e ^ f << 1 >> 2 <= 3 >= 4";
+    let expected = vec![
+        Token::Identifier(String::from("a")),
+        Token::Ampersand,
+        Token::Identifier(String::from("b")),
+        Token::And,
+        Token::Identifier(String::from("c")),
+        Token::Pipe,
+        Token::Identifier(String::from("d")),
+        Token::Or,
+        Token::Identifier(String::from("e")),
+        Token::Caret,
+        Token::Identifier(String::from("f")),
+        Token::ShiftLeft,
+        Token::Int(String::from("1")),
+        Token::ShiftRight,
+        Token::Int(String::from("2")),
+        Token::LtEq,
+        Token::Int(String::from("3")),
+        Token::GtEq,
+        Token::Int(String::from("4")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_power() {
+    let input = "2 ** 3 * 4 *= 5";
+    let expected = vec![
+        Token::Int(String::from("2")),
+        Token::Power,
+        Token::Int(String::from("3")),
+        Token::Asterisk,
+        Token::Int(String::from("4")),
+        Token::AsteriskAssign,
+        Token::Int(String::from("5")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_null() {
+    let input = "let x = null; nullable";
+    let expected = vec![
+        Token::Let,
+        Token::Identifier(String::from("x")),
+        Token::Assign,
+        Token::Null,
+        Token::Semicolon,
+        Token::Identifier(String::from("nullable")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_radix_integers() {
+    let input = "0xFF 0o755 0b1010 0b102 0x 0 0.5";
+    let expected = vec![
+        Token::Int(String::from("0xFF")),
+        Token::Int(String::from("0o755")),
+        Token::Int(String::from("0b1010")),
+        Token::Int(String::from("0b102")),
+        Token::Int(String::from("0x")),
+        Token::Int(String::from("0")),
+        Token::Float(String::from("0.5")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_raw_strings() {
+    let input = r#"r"C:\Users\foo" r"a\nb" r"" let r = r; r "x""#;
+    let expected = vec![
+        Token::String(String::from(r"C:\Users\foo")),
+        Token::String(String::from(r"a\nb")),
+        Token::String(String::new()),
+        Token::Let,
+        Token::Identifier(String::from("r")),
+        Token::Assign,
+        Token::Identifier(String::from("r")),
+        Token::Semicolon,
+        Token::Identifier(String::from("r")),
+        Token::String(String::from("x")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_digit_separators() {
+    let input = "1_000_000 100_ _100 _foo 0xFF_FF 1__0";
+    let expected = vec![
+        Token::Int(String::from("1_000_000")),
+        Token::Int(String::from("100_")),
+        Token::Int(String::from("_100")),
+        Token::Identifier(String::from("_foo")),
+        Token::Int(String::from("0xFF_FF")),
+        Token::Int(String::from("1__0")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_triple_quoted_strings() {
+    let input = "\"\"\"line one
+line two\"\"\" \"\"\"say \"hi\" or \"\"\"\" \"\" \"a\"";
+    let expected = vec![
+        Token::String(String::from("line one\nline two")),
+        Token::String(String::from("say \"hi\" or \"")),
+        Token::String(String::new()),
+        Token::String(String::from("a")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_unterminated_triple_quoted_string() {
+    let input = "\"\"\"abc\n\"\"";
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, vec![Token::String(String::from("abc\n\"\""))]);
+}
+
+#[test]
+fn test_lexer_escape_sequences() {
+    let input =
+        r#""line1\nline2" "\t\r\0" "say \"hi\"" "back\\slash" r"\n" """a\n"b\"""" "\q" "ok""#;
+    let expected = vec![
+        Token::String(String::from("line1\nline2")),
+        Token::String(String::from("\t\r\0")),
+        Token::String(String::from("say \"hi\"")),
+        Token::String(String::from("back\\slash")),
+        Token::String(String::from("\\n")),
+        Token::String(String::from("a\n\"b\"")),
+        Token::InvalidEscape(String::from("\\q")),
+        Token::String(String::from("ok")),
+    ];
+    let actual: Vec<Token> = input.tokens().collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_lexer_skips_shebang_line() {
+    let input = "#!/usr/bin/env rust-monkey\nlet x";
+    let tests = vec![Token::Let, Token::Identifier(String::from("x"))];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+
+    let tokens: Vec<_> = "#!/bin/sh".tokens().collect();
+    assert_eq!(tokens, vec![]);
+}
+
+#[test]
+fn test_lexer_shebang_only_at_start() {
+    for input in [" #!/bin/sh", "x\n#!/bin/sh"] {
+        let tokens: Vec<_> = input.tokens().collect();
+        assert!(tokens.contains(&Token::Illegal), "{input}");
+    }
+}
+
+#[test]
+fn test_lexer_match() {
+    let input = "match (x) { 1 => a, _ => b } = >";
+    let tests = vec![
+        Token::Match,
+        Token::Lparen,
+        Token::Identifier(String::from("x")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Int(String::from("1")),
+        Token::FatArrow,
+        Token::Identifier(String::from("a")),
+        Token::Comma,
+        Token::Identifier(String::from("_")),
+        Token::FatArrow,
+        Token::Identifier(String::from("b")),
+        Token::Rbrace,
+        Token::Assign,
+        Token::Gt,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_ellipsis() {
+    let input = "f(...xs) 1..2 ....";
+    let tests = vec![
+        Token::Identifier(String::from("f")),
+        Token::Lparen,
+        Token::Ellipsis,
+        Token::Identifier(String::from("xs")),
+        Token::Rparen,
+        Token::Int(String::from("1")),
+        Token::DotDot,
+        Token::Int(String::from("2")),
+        Token::Ellipsis,
+        Token::Illegal,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_macro() {
+    let input = "let m = macro(x) { x }; macros";
+    let tests = vec![
+        Token::Let,
+        Token::Identifier(String::from("m")),
+        Token::Assign,
+        Token::Macro,
+        Token::Lparen,
+        Token::Identifier(String::from("x")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Identifier(String::from("x")),
+        Token::Rbrace,
+        Token::Semicolon,
+        Token::Identifier(String::from("macros")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_const() {
+    let input = "const PI = 3; constant";
+    let tests = vec![
+        Token::Const,
+        Token::Identifier(String::from("PI")),
+        Token::Assign,
+        Token::Int(String::from("3")),
+        Token::Semicolon,
+        Token::Identifier(String::from("constant")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_string_template() {
+    let text = |text: &str| TemplateToken::Text(String::from(text));
+    let code = |code: &str| TemplateToken::Code(String::from(code));
+    let tests = [
+        (
+            r#""hello {name}, you are {age + 1}""#,
+            Token::Template(vec![
+                text("hello "),
+                code("name"),
+                text(", you are "),
+                code("age + 1"),
+            ]),
+        ),
+        (
+            r#""{a}{b}\n""#,
+            Token::Template(vec![code("a"), code("b"), text("\n")]),
+        ),
+        // braces and strings inside the expression don't end it, though a
+        // hash needs a space after the `{` that starts the expression
+        (
+            r#""x: { {"k": "}"}["k"] }""#,
+            Token::Template(vec![text("x: "), code(r#" {"k": "}"}["k"] "#)]),
+        ),
+        (
+            r#""{"inner {name} \"q\""}""#,
+            Token::Template(vec![code(r#""inner {name} \"q\"""#)]),
+        ),
+        (r#""{{a}} {{ }""#, Token::String(String::from("{a} { }"))),
+        (r#""a {b""#, Token::UnclosedInterpolation),
+        (r#""\q{a}""#, Token::InvalidEscape(String::from(r"\q"))),
+        // only ordinary strings are templates
+        (r#"r"{a}""#, Token::String(String::from("{a}"))),
+        (r#""""{a}""""#, Token::String(String::from("{a}"))),
+    ];
+    for (input, expected) in tests {
+        let tokens: Vec<_> = input.tokens().collect();
+        assert_eq!(tokens, [expected], "{input}");
+    }
+}
+
+#[test]
+fn test_lexer_line_endings() {
+    let source = "let x = 1; // one\nlet y = \"a\";\n// two\nx + y\n";
+    let expected: Vec<_> = source.tokens().collect();
+    for ending in ["\r\n", "\r"] {
+        let input = source.replace('\n', ending);
+        let tokens: Vec<_> = input.tokens().collect();
+        assert_eq!(tokens, expected, "{input:?}");
+    }
+}
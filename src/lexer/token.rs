@@ -5,7 +5,10 @@ pub enum Token {
     // identifiers and literals
     Identifier(String),
     Int(String),
+    Float(String),
     String(String),
+    // a string with `{...}` in it, in the pieces it alternates between
+    Template(Vec<TemplateToken>),
 
     // operators
     Assign,
@@ -13,18 +16,34 @@ pub enum Token {
     Minus,
     Bang,
     Asterisk,
+    Power,
     Slash,
     Lt,
     Gt,
+    LtEq,
+    GtEq,
     Eq,
     Noteq,
     Or,
     And,
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    DotDot,
+    FatArrow,
+    Ellipsis,
 
     // delimiters
     Comma,
     Semicolon,
     Colon,
+    Question,
     Lparen,
     Rparen,
     Lbrace,
@@ -35,18 +54,41 @@ pub enum Token {
     // keywords
     Function,
     Let,
+    Const,
     True,
     False,
+    Null,
     If,
     Else,
     Return,
     While,
+    For,
+    In,
+    Break,
+    Continue,
+    Import,
+    Export,
+    Match,
+    Macro,
 
     // misc
     Illegal,
+    // a string with an unknown escape sequence, holding that sequence
+    InvalidEscape(String),
+    // a string with a `{` that has no `}` to close it
+    UnclosedInterpolation,
+    // a `/*` with no `*/` to close it, which swallows the rest of the input
+    UnterminatedComment,
     Eof,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplateToken {
+    Text(String),
+    // the source of the expression between `{` and `}`
+    Code(String),
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -55,22 +97,46 @@ impl Display for Token {
             match self {
                 Token::Identifier(id) => id.to_string(),
                 Token::Int(val) => val.to_string(),
+                Token::Float(val) => val.to_string(),
                 Token::String(string) => string.to_string(),
+                Token::Template(parts) => parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplateToken::Text(text) => text.replace('{', "{{").replace('}', "}}"),
+                        TemplateToken::Code(code) => format!("{{{code}}}"),
+                    })
+                    .collect(),
                 Token::Assign => String::from("="),
                 Token::Plus => String::from("+"),
                 Token::Minus => String::from("-"),
                 Token::Bang => String::from("!"),
                 Token::Asterisk => String::from("*"),
+                Token::Power => String::from("**"),
                 Token::Slash => String::from("/"),
                 Token::Lt => String::from("<"),
                 Token::Gt => String::from(">"),
+                Token::LtEq => String::from("<="),
+                Token::GtEq => String::from(">="),
                 Token::Eq => String::from("=="),
                 Token::Noteq => String::from("!="),
                 Token::Or => String::from("||"),
                 Token::And => String::from("&&"),
+                Token::Ampersand => String::from("&"),
+                Token::Pipe => String::from("|"),
+                Token::Caret => String::from("^"),
+                Token::ShiftLeft => String::from("<<"),
+                Token::ShiftRight => String::from(">>"),
+                Token::PlusAssign => String::from("+="),
+                Token::MinusAssign => String::from("-="),
+                Token::AsteriskAssign => String::from("*="),
+                Token::SlashAssign => String::from("/="),
+                Token::DotDot => String::from(".."),
+                Token::FatArrow => String::from("=>"),
+                Token::Ellipsis => String::from("..."),
                 Token::Comma => String::from(","),
                 Token::Semicolon => String::from(";"),
                 Token::Colon => String::from(":"),
+                Token::Question => String::from("?"),
                 Token::Lparen => String::from("("),
                 Token::Rparen => String::from(")"),
                 Token::Lbrace => String::from("{"),
@@ -79,13 +145,26 @@ impl Display for Token {
                 Token::Rbracket => String::from("]"),
                 Token::Function => String::from("fn"),
                 Token::Let => String::from("let"),
+                Token::Const => String::from("const"),
                 Token::True => String::from("true"),
+                Token::Null => String::from("null"),
                 Token::False => String::from("false"),
                 Token::If => String::from("if"),
                 Token::Else => String::from("else"),
                 Token::Return => String::from("return"),
                 Token::While => String::from("while"),
+                Token::For => String::from("for"),
+                Token::In => String::from("in"),
+                Token::Break => String::from("break"),
+                Token::Continue => String::from("continue"),
+                Token::Import => String::from("import"),
+                Token::Export => String::from("export"),
+                Token::Match => String::from("match"),
+                Token::Macro => String::from("macro"),
                 Token::Illegal => String::from("illegal token"),
+                Token::InvalidEscape(sequence) => sequence.to_string(),
+                Token::UnclosedInterpolation => String::from("unclosed '{' in string"),
+                Token::UnterminatedComment => String::from("unterminated comment"),
                 Token::Eof => String::from("EOF"),
             }
         )
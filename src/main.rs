@@ -1,5 +1,7 @@
-use std::io;
+use std::io::{self, Write};
+use std::{env, process};
 
+use crate::parser::precedence::Precedence;
 use crate::repl::Repl;
 
 mod code;
@@ -9,9 +11,48 @@ pub mod lexer;
 mod object;
 mod parser;
 pub mod repl;
+mod script;
 mod symtab;
+mod tests;
 mod vm;
 
-fn main() -> io::Result<()> {
-    Repl::start()
+// EX_USAGE from sysexits.h
+const USAGE_EXIT_CODE: i32 = 64;
+
+fn main() {
+    let repl = Repl::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let code = match args[..] {
+        ["explain-precedence"] => {
+            let result = io::stdout().write_all(Precedence::describe().as_bytes());
+            repl.exit_code(result, &mut io::stderr())
+        }
+        ["--profile", path] => {
+            let result = script::run_file(path, true, &mut io::stderr());
+            script_exit_code(&repl, result)
+        }
+        [path] => {
+            let result = script::run_file(path, false, &mut io::stderr());
+            script_exit_code(&repl, result)
+        }
+        [] => repl.start(),
+        _ => {
+            let _ = writeln!(
+                io::stderr(),
+                "usage: rust-monkey [explain-precedence | [--profile] FILE]"
+            );
+            USAGE_EXIT_CODE
+        }
+    };
+    process::exit(code);
+}
+
+// a script whose output can't be written ends the way a REPL session would
+fn script_exit_code(repl: &Repl, result: io::Result<i32>) -> i32 {
+    match result {
+        Ok(code) => code,
+        Err(e) => repl.exit_code(Err(e), &mut io::stderr()),
+    }
 }
@@ -1,16 +1,147 @@
-use crate::object::Object;
+use crate::object::json::{self, JsonError};
+use crate::object::{http, Hashable, Object};
+use std::cell::RefCell;
+use std::io::{self, ErrorKind, Write};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-pub const NUM_BUILTINS: usize = 6;
+pub const NUM_BUILTINS: usize = 29;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+// the name of each builtin, at the index the compiler refers to it by
+pub const BUILTIN_NAMES: [&str; NUM_BUILTINS] = [
+    "len",
+    "first",
+    "last",
+    "rest",
+    "push",
+    "puts",
+    "print",
+    "println",
+    "inspect",
+    "error",
+    "is_error",
+    "error_message",
+    "recover",
+    "try_parse_int",
+    "apply",
+    "partial",
+    "once",
+    "tap",
+    "swap",
+    "insert",
+    "remove",
+    "deep_clone",
+    "hash_update",
+    "bench",
+    "to_json",
+    "from_json",
+    "http_get",
+    "http_timeout",
+    "try_parse_float",
+];
+
+thread_local! {
+    // all output from builtins goes through this writer so that it can be
+    // redirected (e.g. captured in tests) instead of always hitting stdout
+    static WRITER: RefCell<Box<dyn Write>> = RefCell::new(Box::new(io::stdout()));
+
+    // timing goes through this clock so that it can be replaced (e.g. by a
+    // fake one in tests) instead of always reading the system clock
+    static CLOCK: RefCell<Clock> = RefCell::new(system_clock());
+}
+
+// the time since some fixed point, which only has to be consistent between
+// readings
+pub type Clock = Box<dyn FnMut() -> Duration>;
+
+// calls made before the timed ones, so that they don't pay for any first-run costs
+const BENCH_WARMUP: i64 = 3;
+
+// lets a builtin call a function object using whichever backend is running it
+pub type CallFn<'a, E> = dyn FnMut(&Rc<Object>, &[Rc<Object>]) -> Result<Rc<Object>, E> + 'a;
+
+/// Replaces the writer used by the output builtins, returning the previous one.
+pub fn set_writer(writer: Box<dyn Write>) -> Box<dyn Write> {
+    WRITER.with(|w| w.replace(writer))
+}
+
+/// Replaces the clock used by `bench`, returning the previous one.
+#[allow(unused)]
+pub fn set_clock(clock: Clock) -> Clock {
+    CLOCK.with(|c| c.replace(clock))
+}
+
+fn system_clock() -> Clock {
+    let start = Instant::now();
+    Box::new(move || start.elapsed())
+}
+
+pub fn now() -> Duration {
+    CLOCK.with(|c| (c.borrow_mut())())
+}
+
+// a failed write stops the program, so that e.g. a loop printing into a closed
+// pipe doesn't carry on forever
+fn write_output(output: &str) -> Result<(), BuiltinError> {
+    WRITER
+        .with(|w| {
+            let mut writer = w.borrow_mut();
+            writer.write_all(output.as_bytes())?;
+            writer.flush()
+        })
+        .map_err(|e| BuiltinError::Io(e.kind()))
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Builtin {
     Len,
     First,
     Last,
     Rest,
     Push,
+    // `puts` takes any number of arguments and writes each on its own line,
+    // `print` writes its single argument with no trailing newline, and
+    // `println` writes its single argument followed by a newline
     Puts,
+    Print,
+    Println,
+    Inspect,
+    Error,
+    IsError,
+    ErrorMessage,
+    Recover,
+    TryParseInt,
+    Apply,
+    Partial,
+    // the callable returned by `partial`: a function and its leading arguments
+    PartialApplication(Rc<Object>, Vec<Rc<Object>>),
+    Once,
+    // the callable returned by `once`
+    OnceFunction(Rc<RefCell<OnceState>>),
+    Tap,
+    Swap,
+    Insert,
+    Remove,
+    DeepClone,
+    HashUpdate,
+    Bench,
+    ToJson,
+    FromJson,
+    // always defined, but `http_get` only fetches anything when built with
+    // the `http` feature
+    HttpGet,
+    HttpTimeout,
+    TryParseFloat,
+}
+
+// `called` is set before the wrapped function runs, so a call made while it is
+// still running (i.e. a recursive one) gets null rather than running it again;
+// nothing here is thread safe, as objects are never shared between threads
+#[derive(Debug, PartialEq)]
+pub struct OnceState {
+    func: Rc<Object>,
+    called: bool,
+    result: Rc<Object>,
 }
 
 impl Builtin {
@@ -22,23 +153,196 @@ impl Builtin {
             "rest" => Rc::new(Object::Builtin(Builtin::Rest)),
             "push" => Rc::new(Object::Builtin(Builtin::Push)),
             "puts" => Rc::new(Object::Builtin(Builtin::Puts)),
+            "print" => Rc::new(Object::Builtin(Builtin::Print)),
+            "println" => Rc::new(Object::Builtin(Builtin::Println)),
+            "inspect" => Rc::new(Object::Builtin(Builtin::Inspect)),
+            "error" => Rc::new(Object::Builtin(Builtin::Error)),
+            "is_error" => Rc::new(Object::Builtin(Builtin::IsError)),
+            "error_message" => Rc::new(Object::Builtin(Builtin::ErrorMessage)),
+            "recover" => Rc::new(Object::Builtin(Builtin::Recover)),
+            "try_parse_int" => Rc::new(Object::Builtin(Builtin::TryParseInt)),
+            "apply" => Rc::new(Object::Builtin(Builtin::Apply)),
+            "partial" => Rc::new(Object::Builtin(Builtin::Partial)),
+            "once" => Rc::new(Object::Builtin(Builtin::Once)),
+            "tap" => Rc::new(Object::Builtin(Builtin::Tap)),
+            "swap" => Rc::new(Object::Builtin(Builtin::Swap)),
+            "insert" => Rc::new(Object::Builtin(Builtin::Insert)),
+            "remove" => Rc::new(Object::Builtin(Builtin::Remove)),
+            "deep_clone" => Rc::new(Object::Builtin(Builtin::DeepClone)),
+            "hash_update" => Rc::new(Object::Builtin(Builtin::HashUpdate)),
+            "bench" => Rc::new(Object::Builtin(Builtin::Bench)),
+            "to_json" => Rc::new(Object::Builtin(Builtin::ToJson)),
+            "from_json" => Rc::new(Object::Builtin(Builtin::FromJson)),
+            "http_get" => Rc::new(Object::Builtin(Builtin::HttpGet)),
+            "http_timeout" => Rc::new(Object::Builtin(Builtin::HttpTimeout)),
+            "try_parse_float" => Rc::new(Object::Builtin(Builtin::TryParseFloat)),
             _ => return None,
         })
     }
 
     pub fn get_by_idx(id: usize) -> Option<Rc<Object>> {
-        Some(match id {
-            0 => Rc::new(Object::Builtin(Builtin::Len)),
-            1 => Rc::new(Object::Builtin(Builtin::First)),
-            2 => Rc::new(Object::Builtin(Builtin::Last)),
-            3 => Rc::new(Object::Builtin(Builtin::Rest)),
-            4 => Rc::new(Object::Builtin(Builtin::Push)),
-            5 => Rc::new(Object::Builtin(Builtin::Puts)),
-            _ => return None,
-        })
+        Builtin::get(BUILTIN_NAMES.get(id)?)
+    }
+
+    pub fn apply<E: From<BuiltinError>>(
+        &self,
+        args: &[Rc<Object>],
+        call: &mut CallFn<E>,
+    ) -> Result<Rc<Object>, E> {
+        match self {
+            Builtin::Recover => {
+                if args.len() != 2 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                }
+
+                let result = call(&args[0], &[])?;
+                if let Object::Error(_) = &*result {
+                    call(&args[1], &[result])
+                } else {
+                    Ok(result)
+                }
+            }
+            Builtin::Apply => {
+                if args.len() != 2 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                }
+
+                match &*args[1] {
+                    Object::Array(elements) => {
+                        let elements = elements.borrow().clone();
+                        call(&args[0], &elements)
+                    }
+                    _ => Err(BuiltinError::IncompatibleTypes.into()),
+                }
+            }
+            Builtin::Partial => {
+                let Some((func, prefilled)) = args.split_first() else {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                };
+
+                match &**func {
+                    Object::Function(_) | Object::Closure(_) | Object::Builtin(_) => {}
+                    _ => return Err(BuiltinError::IncompatibleTypes.into()),
+                }
+
+                if prefilled.is_empty() {
+                    return Ok(Rc::clone(func));
+                }
+
+                Ok(Rc::new(Object::Builtin(Builtin::PartialApplication(
+                    Rc::clone(func),
+                    prefilled.to_vec(),
+                ))))
+            }
+            Builtin::PartialApplication(func, prefilled) => {
+                // any arity mismatch is reported by the underlying function
+                let all_args = [prefilled.as_slice(), args].concat();
+                call(func, &all_args)
+            }
+            Builtin::Once => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                }
+
+                match &*args[0] {
+                    Object::Function(_) | Object::Closure(_) | Object::Builtin(_) => {}
+                    _ => return Err(BuiltinError::IncompatibleTypes.into()),
+                }
+
+                Ok(Rc::new(Object::Builtin(Builtin::OnceFunction(Rc::new(
+                    RefCell::new(OnceState {
+                        func: Rc::clone(&args[0]),
+                        called: false,
+                        result: Rc::new(Object::Null),
+                    }),
+                )))))
+            }
+            Builtin::OnceFunction(state) => {
+                if state.borrow().called {
+                    return Ok(Rc::clone(&state.borrow().result));
+                }
+
+                let func = {
+                    let mut state = state.borrow_mut();
+                    state.called = true;
+                    Rc::clone(&state.func)
+                };
+
+                match call(&func, args) {
+                    Ok(result) => {
+                        state.borrow_mut().result = Rc::clone(&result);
+                        Ok(result)
+                    }
+                    Err(error) => {
+                        // nothing was cached, so let the next call try again
+                        state.borrow_mut().called = false;
+                        Err(error)
+                    }
+                }
+            }
+            Builtin::Tap => {
+                if args.len() != 2 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                }
+
+                // only called for its side effects, so the result is dropped
+                call(&args[1], &args[..1])?;
+                Ok(Rc::clone(&args[0]))
+            }
+            Builtin::HashUpdate => {
+                if args.len() != 3 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                }
+
+                let Object::Hash(pairs) = &*args[0] else {
+                    return Err(BuiltinError::IncompatibleTypes.into());
+                };
+                let key = Hashable::try_from(&*args[1])
+                    .map_err(|type_name| BuiltinError::UnhashableKey(type_name.to_string()))?;
+
+                // a missing key is updated from null
+                let current = match pairs.borrow().get(&key) {
+                    Some(value) => Rc::clone(value),
+                    None => Rc::new(Object::Null),
+                };
+                let value = call(&args[2], &[current])?;
+                let mut updated = pairs.borrow().clone();
+                updated.insert(key, value);
+                Ok(Rc::new(Object::Hash(RefCell::new(updated))))
+            }
+            Builtin::Bench => {
+                if args.len() != 2 && args.len() != 3 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs.into());
+                }
+
+                let iterations = match &*args[1] {
+                    Object::Integer(n) if *n > 0 => *n,
+                    _ => return Err(BuiltinError::IncompatibleTypes.into()),
+                };
+
+                let mut times = vec![];
+                for i in 0..BENCH_WARMUP + iterations {
+                    // the setup's result is passed on, but making it isn't timed
+                    let bench_args = match args.get(2) {
+                        Some(setup) => vec![call(setup, &[])?],
+                        None => vec![],
+                    };
+                    if i < BENCH_WARMUP {
+                        call(&args[0], &bench_args)?;
+                    } else {
+                        let start = now();
+                        call(&args[0], &bench_args)?;
+                        times.push(now().saturating_sub(start));
+                    }
+                }
+
+                Ok(Rc::new(bench_statistics(&times)))
+            }
+            _ => self.apply_first_order(args).map_err(E::from),
+        }
     }
 
-    pub fn apply(&self, args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    fn apply_first_order(&self, args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
         Ok(match self {
             Builtin::Len => {
                 if args.len() != 1 {
@@ -48,7 +352,7 @@ impl Builtin {
                 // safe to unwrap as the length of args is 1
                 let result = match &**args.first().unwrap() {
                     Object::String(string) => string.len() as i64,
-                    Object::Array(array) => array.len() as i64,
+                    Object::Array(array) => array.borrow().len() as i64,
                     _ => return Err(BuiltinError::IncompatibleTypes),
                 };
 
@@ -60,7 +364,7 @@ impl Builtin {
                 }
 
                 if let Object::Array(array) = &**args.first().unwrap() {
-                    match array.first() {
+                    match array.borrow().first() {
                         Some(element) => Rc::clone(element),
                         None => Rc::new(Object::Null),
                     }
@@ -74,7 +378,7 @@ impl Builtin {
                 }
 
                 if let Object::Array(array) = &**args.first().unwrap() {
-                    match array.last() {
+                    match array.borrow().last() {
                         Some(object) => Rc::clone(object),
                         None => Rc::new(Object::Null),
                     }
@@ -88,10 +392,11 @@ impl Builtin {
                 }
 
                 if let Object::Array(array) = &**args.first().unwrap() {
+                    let array = array.borrow();
                     if array.is_empty() {
                         Rc::new(Object::Null)
                     } else {
-                        Rc::new(Object::Array(array[1..].to_vec()))
+                        Rc::new(Object::Array(RefCell::new(array[1..].to_vec())))
                     }
                 } else {
                     return Err(BuiltinError::IncompatibleTypes);
@@ -105,26 +410,305 @@ impl Builtin {
                 if let Object::Array(array) = &**args.first().unwrap() {
                     let mut new_array = vec![];
                     array
+                        .borrow()
                         .iter()
                         .for_each(|element| new_array.push(element.clone()));
                     let element = Rc::clone(&args[1].clone());
                     new_array.push(element);
-                    Rc::new(Object::Array(new_array))
+                    Rc::new(Object::Array(RefCell::new(new_array)))
                 } else {
                     return Err(BuiltinError::IncompatibleTypes);
                 }
             }
             Builtin::Puts => {
                 for arg in args {
-                    println!("{arg}");
+                    write_output(&format!("{arg}\n"))?;
+                }
+                Rc::new(Object::Null)
+            }
+            Builtin::Print => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                write_output(&args[0].to_string())?;
+                Rc::new(Object::Null)
+            }
+            Builtin::Println => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                write_output(&format!("{}\n", args[0]))?;
+                Rc::new(Object::Null)
+            }
+            Builtin::Inspect => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                Rc::new(Object::String(args[0].inspect()))
+            }
+            Builtin::Error => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                if let Object::String(message) = &*args[0] {
+                    Rc::new(Object::Error(message.clone()))
+                } else {
+                    return Err(BuiltinError::IncompatibleTypes);
+                }
+            }
+            Builtin::IsError => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                Rc::new(Object::Boolean(matches!(&*args[0], Object::Error(_))))
+            }
+            Builtin::ErrorMessage => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                if let Object::Error(message) = &*args[0] {
+                    Rc::new(Object::String(message.clone()))
+                } else {
+                    return Err(BuiltinError::IncompatibleTypes);
+                }
+            }
+            Builtin::TryParseInt => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                if let Object::String(string) = &*args[0] {
+                    Rc::new(Object::from(string.trim().parse::<i64>().ok()))
+                } else {
+                    return Err(BuiltinError::IncompatibleTypes);
+                }
+            }
+            Builtin::TryParseFloat => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                // "inf" and "nan" parse, but can't be written as literals, so
+                // they are rejected like any other non-number
+                if let Object::String(string) = &*args[0] {
+                    let float = string.trim().parse::<f64>().ok();
+                    Rc::new(Object::from(float.filter(|float| float.is_finite())))
+                } else {
+                    return Err(BuiltinError::IncompatibleTypes);
+                }
+            }
+            Builtin::Swap => {
+                if args.len() != 3 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                match (&*args[0], &*args[1], &*args[2]) {
+                    (Object::Array(array), Object::Integer(i), Object::Integer(j)) => {
+                        let array = array.borrow();
+                        let i = resolve_index(*i, array.len())?;
+                        let j = resolve_index(*j, array.len())?;
+                        let mut swapped = array.clone();
+                        swapped.swap(i, j);
+                        Rc::new(Object::Array(RefCell::new(swapped)))
+                    }
+                    _ => return Err(BuiltinError::IncompatibleTypes),
+                }
+            }
+            Builtin::Insert => {
+                if args.len() != 3 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                match (&*args[0], &*args[1]) {
+                    (Object::Array(array), Object::Integer(index)) => {
+                        let array = array.borrow();
+                        // one past the end is allowed, and appends
+                        let index = if *index == array.len() as i64 {
+                            array.len()
+                        } else {
+                            resolve_index(*index, array.len())?
+                        };
+                        let mut inserted = array.clone();
+                        inserted.insert(index, Rc::clone(&args[2]));
+                        Rc::new(Object::Array(RefCell::new(inserted)))
+                    }
+                    _ => return Err(BuiltinError::IncompatibleTypes),
+                }
+            }
+            Builtin::Remove => {
+                if args.len() != 2 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                match (&*args[0], &*args[1]) {
+                    (Object::Array(array), Object::Integer(index)) => {
+                        let array = array.borrow();
+                        let index = resolve_index(*index, array.len())?;
+                        let mut removed = array.clone();
+                        removed.remove(index);
+                        Rc::new(Object::Array(RefCell::new(removed)))
+                    }
+                    _ => return Err(BuiltinError::IncompatibleTypes),
+                }
+            }
+            Builtin::DeepClone => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                deep_clone(&args[0], 0)?
+            }
+            Builtin::ToJson => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                Rc::new(Object::String(json::to_json(&args[0])?))
+            }
+            Builtin::FromJson => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                if let Object::String(string) = &*args[0] {
+                    Rc::new(json::from_json(string)?)
+                } else {
+                    return Err(BuiltinError::IncompatibleTypes);
                 }
+            }
+            Builtin::HttpGet => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                // network failures are values, so scripts can check for them
+                // with `is_error` rather than being aborted
+                if let Object::String(url) = &*args[0] {
+                    Rc::new(match http::get(url) {
+                        Ok(body) => Object::String(body),
+                        Err(message) => Object::Error(message),
+                    })
+                } else {
+                    return Err(BuiltinError::IncompatibleTypes);
+                }
+            }
+            Builtin::HttpTimeout => {
+                if args.len() != 1 {
+                    return Err(BuiltinError::IncorrectNumberOfArgs);
+                }
+
+                let seconds = match &*args[0] {
+                    Object::Integer(seconds) if *seconds > 0 => *seconds as f64,
+                    Object::Float(seconds) if *seconds > 0.0 => *seconds,
+                    _ => return Err(BuiltinError::IncompatibleTypes),
+                };
+                let timeout = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| BuiltinError::IncompatibleTypes)?;
+                http::set_timeout(timeout);
                 Rc::new(Object::Null)
             }
+            Builtin::Recover
+            | Builtin::Apply
+            | Builtin::Partial
+            | Builtin::PartialApplication(..)
+            | Builtin::Once
+            | Builtin::OnceFunction(_)
+            | Builtin::Tap
+            | Builtin::HashUpdate
+            | Builtin::Bench => {
+                unreachable!("higher-order builtins are handled in `apply`")
+            }
         })
     }
 }
 
+// PRE: `times` is not empty
+fn bench_statistics(times: &[Duration]) -> Object {
+    let ms = |time: Duration| time.as_nanos() as f64 / 1_000_000.0;
+    let total: Duration = times.iter().sum();
+
+    let statistics = [
+        ("iters", Object::Integer(times.len() as i64)),
+        ("total_ms", Object::Float(ms(total))),
+        ("mean_ms", Object::Float(ms(total) / times.len() as f64)),
+        ("min_ms", Object::Float(ms(*times.iter().min().unwrap()))),
+        ("max_ms", Object::Float(ms(*times.iter().max().unwrap()))),
+    ];
+    Object::Hash(RefCell::new(
+        statistics
+            .into_iter()
+            .map(|(key, value)| (Hashable::String(key.to_string()), Rc::new(value)))
+            .collect(),
+    ))
+}
+
+// deeper than this is treated as a cycle, which index assignment can create
+const MAX_CLONE_DEPTH: usize = 200;
+
+// copies arrays and hashes all the way down; everything else is immutable, or
+// (like functions) not clonable, so it is shared with the original
+fn deep_clone(object: &Rc<Object>, depth: usize) -> Result<Rc<Object>, BuiltinError> {
+    if depth > MAX_CLONE_DEPTH {
+        return Err(BuiltinError::NestingTooDeep);
+    }
+
+    Ok(match &**object {
+        Object::Array(elements) => Rc::new(Object::Array(RefCell::new(
+            elements
+                .borrow()
+                .iter()
+                .map(|element| deep_clone(element, depth + 1))
+                .collect::<Result<_, _>>()?,
+        ))),
+        Object::Hash(pairs) => Rc::new(Object::Hash(RefCell::new(
+            pairs
+                .borrow()
+                .iter()
+                .map(|(key, value)| {
+                    Ok::<_, BuiltinError>((key.clone(), deep_clone(value, depth + 1)?))
+                })
+                .collect::<Result<_, _>>()?,
+        ))),
+        _ => Rc::clone(object),
+    })
+}
+
+// negative indices count back from the end of the array, so -1 is the last
+// element
+pub fn resolve_index(index: i64, len: usize) -> Result<usize, BuiltinError> {
+    let resolved = if index < 0 { len as i64 + index } else { index };
+
+    if (0..len as i64).contains(&resolved) {
+        Ok(resolved as usize)
+    } else {
+        Err(BuiltinError::IndexOutOfBounds)
+    }
+}
+
 pub enum BuiltinError {
     IncompatibleTypes,
     IncorrectNumberOfArgs,
+    IndexOutOfBounds,
+    NestingTooDeep,
+    UnhashableKey(String),
+    NotSerializable(String),
+    InvalidJson(String),
+    // writing output failed
+    Io(ErrorKind),
+}
+
+impl From<JsonError> for BuiltinError {
+    fn from(error: JsonError) -> Self {
+        match error {
+            JsonError::NotSerializable(type_name) => BuiltinError::NotSerializable(type_name),
+            JsonError::InvalidJson(message) => BuiltinError::InvalidJson(message),
+            JsonError::NestingTooDeep => BuiltinError::NestingTooDeep,
+        }
+    }
 }
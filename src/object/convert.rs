@@ -0,0 +1,114 @@
+use crate::object::{Hashable, Object};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+impl From<i64> for Object {
+    fn from(value: i64) -> Self {
+        Object::Integer(value)
+    }
+}
+
+impl From<f64> for Object {
+    fn from(value: f64) -> Self {
+        Object::Float(value)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        Object::Boolean(value)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(value: &str) -> Self {
+        Object::String(value.to_string())
+    }
+}
+
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(value)
+    }
+}
+
+impl<T: Into<Object>> From<Option<T>> for Object {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => value.into(),
+            None => Object::Null,
+        }
+    }
+}
+
+impl<T: Into<Object>> From<Vec<T>> for Object {
+    fn from(value: Vec<T>) -> Self {
+        Object::Array(RefCell::new(
+            value
+                .into_iter()
+                .map(|element| Rc::new(element.into()))
+                .collect(),
+        ))
+    }
+}
+
+// hashes are unordered, so the iteration order of the source map is not kept
+impl<T: Into<Object>> From<HashMap<String, T>> for Object {
+    fn from(value: HashMap<String, T>) -> Self {
+        Object::Hash(RefCell::new(
+            value
+                .into_iter()
+                .map(|(k, v)| (Hashable::String(k), Rc::new(v.into())))
+                .collect(),
+        ))
+    }
+}
+
+impl<T: Into<Object>> From<BTreeMap<String, T>> for Object {
+    fn from(value: BTreeMap<String, T>) -> Self {
+        Object::Hash(RefCell::new(
+            value
+                .into_iter()
+                .map(|(k, v)| (Hashable::String(k), Rc::new(v.into())))
+                .collect(),
+        ))
+    }
+}
+
+/// Builds an `Object` from a JSON-like literal, e.g. for returning nested
+/// structures from builtins:
+///
+/// ```ignore
+/// Builtin::Profile => Rc::new(value!({
+///     "name": "ada",
+///     "scores": [1, 2, 3]
+/// })),
+/// ```
+///
+/// Keys and values must be single token trees, so anything more complex than
+/// a literal or variable (including negative numbers) needs parentheses, e.g.
+/// `value!([(-1), (a + b)])`. Keys must be hashable or this will panic.
+#[macro_export]
+macro_rules! value {
+    (null) => {
+        $crate::object::Object::Null
+    };
+    ([ $($element:tt),* $(,)? ]) => {
+        $crate::object::Object::Array(::std::cell::RefCell::new(vec![
+            $( ::std::rc::Rc::new($crate::value!($element)) ),*
+        ]))
+    };
+    ({ $($key:tt : $value:tt),* $(,)? }) => {
+        $crate::object::Object::Hash(::std::cell::RefCell::new(::std::collections::HashMap::from([
+            $((
+                $crate::object::Hashable::try_from(&$crate::value!($key))
+                    .expect("unhashable key in value!"),
+                ::std::rc::Rc::new($crate::value!($value)),
+            )),*
+        ])))
+    };
+    ($other:expr) => {
+        $crate::object::Object::from($other)
+    };
+}
@@ -0,0 +1,33 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+thread_local! {
+    // applies to every request made after it is set, by `http_timeout`
+    static TIMEOUT: Cell<Duration> = const { Cell::new(DEFAULT_TIMEOUT) };
+}
+
+/// Replaces the timeout used by `http_get`, returning the previous one.
+pub fn set_timeout(timeout: Duration) -> Duration {
+    TIMEOUT.with(|t| t.replace(timeout))
+}
+
+/// Fetches `url` and returns the body of the response. Failing to connect, a
+/// timeout and an error status are all reported as a message.
+#[cfg(feature = "http")]
+pub fn get(url: &str) -> Result<String, String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT.with(Cell::get))
+        .build();
+    match agent.get(url).call() {
+        Ok(response) => response.into_string().map_err(|e| e.to_string()),
+        Err(ureq::Error::Status(code, _)) => Err(format!("{url}: status code {code}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(not(feature = "http"))]
+pub fn get(_url: &str) -> Result<String, String> {
+    Err("http_get needs rust-monkey to be built with `--features http`".to_string())
+}
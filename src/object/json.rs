@@ -0,0 +1,339 @@
+use crate::object::{Hashable, Object};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+// deeper than this is rejected rather than risking overflowing the stack
+const MAX_DEPTH: usize = 200;
+
+#[derive(Debug, PartialEq)]
+pub enum JsonError {
+    // the name of a type (or hash key type) that has no JSON equivalent
+    NotSerializable(String),
+    // a description of what was wrong with the input
+    InvalidJson(String),
+    NestingTooDeep,
+}
+
+/// Converts an object to JSON. Hash keys must be strings, and are written in
+/// sorted order so that the output doesn't depend on how the hash iterates.
+pub fn to_json(object: &Object) -> Result<String, JsonError> {
+    let mut json = String::new();
+    write_value(object, &mut json, 0)?;
+    Ok(json)
+}
+
+/// Parses JSON into an object. Numbers without a fraction or exponent become
+/// integers if they fit in one, and floats otherwise.
+pub fn from_json(json: &str) -> Result<Object, JsonError> {
+    let mut parser = JsonParser {
+        chars: json.chars().peekable(),
+    };
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(unexpected(c)),
+    }
+}
+
+fn write_value(object: &Object, json: &mut String, depth: usize) -> Result<(), JsonError> {
+    if depth > MAX_DEPTH {
+        return Err(JsonError::NestingTooDeep);
+    }
+
+    match object {
+        Object::Null => json.push_str("null"),
+        Object::Integer(int) => json.push_str(&int.to_string()),
+        // JSON has no way of writing NaN or infinity
+        Object::Float(float) if float.is_finite() => json.push_str(&format!("{float:?}")),
+        Object::Boolean(bool) => json.push_str(&bool.to_string()),
+        Object::String(string) => write_string(string, json),
+        Object::Array(elements) => {
+            json.push('[');
+            for (i, element) in elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_value(element, json, depth + 1)?;
+            }
+            json.push(']');
+        }
+        Object::Hash(pairs) => {
+            let pairs = pairs.borrow();
+            let mut sorted = pairs
+                .iter()
+                .map(|(key, value)| match key {
+                    Hashable::String(key) => Ok((key, value)),
+                    Hashable::Integer(_) => Err(JsonError::NotSerializable("integer key".into())),
+                    Hashable::Boolean(_) => Err(JsonError::NotSerializable("boolean key".into())),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            sorted.sort_by_key(|(key, _)| *key);
+
+            json.push('{');
+            for (i, (key, value)) in sorted.into_iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_string(key, json);
+                json.push(':');
+                write_value(value, json, depth + 1)?;
+            }
+            json.push('}');
+        }
+        Object::Cell(object) => write_value(&object.borrow(), json, depth)?,
+        other => return Err(JsonError::NotSerializable(other.type_name().to_string())),
+    }
+    Ok(())
+}
+
+fn write_string(string: &str, json: &mut String) {
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            '\u{08}' => json.push_str("\\b"),
+            '\u{0c}' => json.push_str("\\f"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn unexpected(c: char) -> JsonError {
+    JsonError::InvalidJson(format!("unexpected {c:?}"))
+}
+
+fn unexpected_end() -> JsonError {
+    JsonError::InvalidJson("unexpected end of input".to_string())
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(unexpected(c)),
+            None => Err(unexpected_end()),
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Object, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(JsonError::NestingTooDeep);
+        }
+
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('n') => self.parse_keyword("null", Object::Null),
+            Some('t') => self.parse_keyword("true", Object::Boolean(true)),
+            Some('f') => self.parse_keyword("false", Object::Boolean(false)),
+            Some('"') => Ok(Object::String(self.parse_string()?)),
+            Some('[') => self.parse_array(depth),
+            Some('{') => self.parse_object(depth),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(&c) => Err(unexpected(c)),
+            None => Err(unexpected_end()),
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Object) -> Result<Object, JsonError> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Object, JsonError> {
+        self.expect('[')?;
+        let mut elements = vec![];
+
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Object::Array(RefCell::new(elements)));
+        }
+        loop {
+            elements.push(Rc::new(self.parse_value(depth + 1)?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Object::Array(RefCell::new(elements))),
+                Some(c) => return Err(unexpected(c)),
+                None => return Err(unexpected_end()),
+            }
+        }
+    }
+
+    // a repeated key keeps its last value
+    fn parse_object(&mut self, depth: usize) -> Result<Object, JsonError> {
+        self.expect('{')?;
+        let mut pairs = HashMap::new();
+
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Object::Hash(RefCell::new(pairs)));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value(depth + 1)?;
+            pairs.insert(Hashable::String(key), Rc::new(value));
+
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Object::Hash(RefCell::new(pairs))),
+                Some(c) => return Err(unexpected(c)),
+                None => return Err(unexpected_end()),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.parse_escape()?),
+                Some(c) if c < ' ' => return Err(unexpected(c)),
+                Some(c) => string.push(c),
+                None => return Err(unexpected_end()),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, JsonError> {
+        Ok(match self.chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{08}',
+            Some('f') => '\u{0c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = self.parse_hex4()?;
+                // characters outside the basic plane are written as a
+                // surrogate pair of escapes
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    self.expect('\\')?;
+                    self.expect('u')?;
+                    let low = self.parse_hex4()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(JsonError::InvalidJson(format!(
+                            "invalid surrogate pair \\u{high:04x}\\u{low:04x}"
+                        )));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| {
+                    JsonError::InvalidJson(format!("invalid character \\u{code:04x}"))
+                })?
+            }
+            Some(c) => return Err(JsonError::InvalidJson(format!("unknown escape \\{c}"))),
+            None => return Err(unexpected_end()),
+        })
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let c = self.chars.next().ok_or_else(unexpected_end)?;
+            let digit = c.to_digit(16).ok_or_else(|| unexpected(c))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn parse_number(&mut self) -> Result<Object, JsonError> {
+        let mut number = String::new();
+        if let Some(minus) = self.chars.next_if_eq(&'-') {
+            number.push(minus);
+        }
+
+        // no leading zeros, so a zero must be the whole integer part
+        match self.chars.next() {
+            Some('0') => number.push('0'),
+            Some(c @ '1'..='9') => {
+                number.push(c);
+                self.push_digits(&mut number);
+            }
+            Some(c) => return Err(unexpected(c)),
+            None => return Err(unexpected_end()),
+        }
+
+        let mut is_float = false;
+        if let Some(point) = self.chars.next_if_eq(&'.') {
+            is_float = true;
+            number.push(point);
+            self.push_at_least_one_digit(&mut number)?;
+        }
+        if let Some(e) = self.chars.next_if(|c| matches!(c, 'e' | 'E')) {
+            is_float = true;
+            number.push(e);
+            if let Some(sign) = self.chars.next_if(|c| matches!(c, '+' | '-')) {
+                number.push(sign);
+            }
+            self.push_at_least_one_digit(&mut number)?;
+        }
+
+        if !is_float {
+            if let Ok(int) = number.parse::<i64>() {
+                return Ok(Object::Integer(int));
+            }
+        }
+        // only fails for numbers too large to be a finite float
+        match number.parse::<f64>() {
+            Ok(float) if float.is_finite() => Ok(Object::Float(float)),
+            _ => Err(JsonError::InvalidJson(format!(
+                "number {number} out of range"
+            ))),
+        }
+    }
+
+    fn push_digits(&mut self, number: &mut String) {
+        while let Some(digit) = self.chars.next_if(char::is_ascii_digit) {
+            number.push(digit);
+        }
+    }
+
+    fn push_at_least_one_digit(&mut self, number: &mut String) -> Result<(), JsonError> {
+        match self.chars.next_if(char::is_ascii_digit) {
+            Some(digit) => number.push(digit),
+            None => {
+                return Err(self
+                    .chars
+                    .peek()
+                    .map_or_else(unexpected_end, |&c| unexpected(c)))
+            }
+        }
+        self.push_digits(number);
+        Ok(())
+    }
+}
@@ -1,28 +1,49 @@
-use crate::code::Instructions;
+use crate::code::{Instructions, WORD_SIZE};
 use crate::evaluator::environment::Environment;
-use crate::parser::ast::Statement;
+use crate::parser::ast::{Expression, Statement};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 use std::rc::Rc;
 
 use self::builtins::Builtin;
 
 pub mod builtins;
+mod convert;
+pub mod http;
+pub mod json;
+mod tests;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Object {
     Null,
     Integer(i64),
+    Float(f64),
     Boolean(bool),
     String(String),
     Return(Rc<Object>),
+    // like `Return`, these unwind blocks in the evaluator until they reach
+    // the nearest enclosing loop, which consumes them
+    Break,
+    Continue,
     Function(Function),
     Builtin(Builtin),
-    Array(Vec<Rc<Object>>),
-    Hash(HashMap<Hashable, Rc<Object>>),
+    // arrays and hashes can be changed in place by index assignment, which
+    // every value sharing them sees; nothing may hold a borrow of one while
+    // evaluating code, as that code could assign to it
+    Array(RefCell<Vec<Rc<Object>>>),
+    Hash(RefCell<HashMap<Hashable, Rc<Object>>>),
     CompiledFunc(Rc<CompiledFunction>),
     Closure(Rc<Closure>),
+    Error(String),
+    // a local variable captured by a closure in the VM; the enclosing frame and
+    // every closure capturing it share the same cell, so they all see updates
+    Cell(RefCell<Rc<Object>>),
+    // code passed to `quote`, and to macros as their arguments
+    Quote(Expression),
+    // only defined while macros are expanded, never when a program runs
+    Macro(Function),
 }
 
 impl Display for Object {
@@ -31,39 +52,267 @@ impl Display for Object {
             f,
             "{}",
             match self {
-                Object::Null => "Null".to_string(),
+                Object::Null => "null".to_string(),
                 Object::Integer(int) => int.to_string(),
+                // always shows a decimal point so floats can't be mistaken for integers
+                Object::Float(float) => format!("{float:?}"),
                 Object::Boolean(bool) => bool.to_string(),
                 Object::String(string) => string.to_string(),
                 Object::Return(object) => object.to_string(),
-                Object::Function(_) => "".to_string(),
+                Object::Break => "break".to_string(),
+                Object::Continue => "continue".to_string(),
+                Object::Function(_) => "fn(...)".to_string(),
                 Object::Builtin(_) => "".to_string(),
-                Object::Array(elements) => format!(
+                // a collection is borrowed mutably while it is being shown, so
+                // finding it borrowed means it contains itself
+                Object::Array(elements) => match elements.try_borrow_mut() {
+                    Ok(elements) => format!(
+                        "[{}]",
+                        elements
+                            .iter()
+                            .map(|element| element.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Err(_) => "[...]".to_string(),
+                },
+                Object::Hash(pairs) => match pairs.try_borrow_mut() {
+                    Ok(pairs) => format!(
+                        "{{{}}}",
+                        pairs
+                            .iter()
+                            .map(|(k, v)| format!("{}: {}", k, v))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Err(_) => "{...}".to_string(),
+                },
+                Object::CompiledFunc(_) => "fn(...)".to_string(),
+                Object::Closure(_) => "fn(...)".to_string(),
+                Object::Error(message) => format!("error: {message}"),
+                Object::Cell(object) => object.borrow().to_string(),
+                Object::Quote(expression) => format!("QUOTE({expression})"),
+                Object::Macro(_) => "macro(...)".to_string(),
+            }
+        )
+    }
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Null => "null",
+            Object::Integer(_) => "integer",
+            Object::Float(_) => "float",
+            Object::Boolean(_) => "boolean",
+            Object::String(_) => "string",
+            Object::Return(object) => object.type_name(),
+            Object::Break => "break",
+            Object::Continue => "continue",
+            Object::Function(_) | Object::CompiledFunc(_) | Object::Closure(_) => "function",
+            Object::Builtin(_) => "builtin",
+            Object::Array(_) => "array",
+            Object::Hash(_) => "hash",
+            Object::Error(_) => "error",
+            Object::Cell(object) => object.borrow().type_name(),
+            Object::Quote(_) => "quote",
+            Object::Macro(_) => "macro",
+        }
+    }
+
+    // like `Display` but unambiguous: strings are quoted, null is spelled out
+    // and functions show how many parameters they take
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::Null => "null".to_string(),
+            Object::String(string) => format!("{string:?}"),
+            Object::Return(object) => object.inspect(),
+            Object::Function(function) => Self::inspect_function(function.parameters.len()),
+            Object::CompiledFunc(function) => Self::inspect_function(function.num_params as usize),
+            Object::Closure(closure) => {
+                Self::inspect_function(closure.function.num_params as usize)
+            }
+            Object::Builtin(_) => "<builtin>".to_string(),
+            // borrowed mutably for the same reason as in `Display`
+            Object::Array(elements) => match elements.try_borrow_mut() {
+                Ok(elements) => format!(
                     "[{}]",
                     elements
                         .iter()
-                        .map(|element| element.to_string())
+                        .map(|element| element.inspect())
                         .collect::<Vec<_>>()
                         .join(", ")
                 ),
-                Object::Hash(pairs) => format!(
+                Err(_) => "[...]".to_string(),
+            },
+            Object::Hash(pairs) => match pairs.try_borrow_mut() {
+                Ok(pairs) => format!(
                     "{{{}}}",
                     pairs
                         .iter()
-                        .map(|(k, v)| format!("{}: {}", k, v))
+                        .map(|(k, v)| format!("{}: {}", k, v.inspect()))
                         .collect::<Vec<_>>()
                         .join(", ")
                 ),
-                Object::CompiledFunc(_) => "".to_string(),
-                Object::Closure(_) => "".to_string(),
-            }
-        )
+                Err(_) => "{...}".to_string(),
+            },
+            Object::Error(message) => format!("error({message:?})"),
+            Object::Cell(object) => object.borrow().inspect(),
+            Object::Integer(_)
+            | Object::Float(_)
+            | Object::Boolean(_)
+            | Object::Break
+            | Object::Continue
+            | Object::Quote(_)
+            | Object::Macro(_) => self.to_string(),
+        }
+    }
+
+    fn inspect_function(num_params: usize) -> String {
+        match num_params {
+            1 => "<function(1 param)>".to_string(),
+            n => format!("<function({n} params)>"),
+        }
+    }
+}
+
+// the longest string that `*` will build, so a mistyped count fails rather
+// than trying to allocate all of memory
+const MAX_REPEAT_LEN: usize = 1 << 28;
+
+/// Repeats `string` `count` times, or returns `None` if `count` is negative
+/// or the result would be unreasonably long.
+pub fn repeat_string(string: &str, count: i64) -> Option<String> {
+    let count = usize::try_from(count).ok()?;
+    match string.len().checked_mul(count) {
+        Some(len) if len <= MAX_REPEAT_LEN => Some(string.repeat(count)),
+        _ => None,
+    }
+}
+
+// the longest range that `..` will build, as each element takes up a few
+// hundred bytes
+const MAX_RANGE_LEN: i128 = 1 << 20;
+
+/// The integers from `start` up to but not including `end`, as an array; it is
+/// empty unless `start` comes before `end`. Returns `None` if the array would
+/// be unreasonably long.
+pub fn range(start: i64, end: i64) -> Option<Object> {
+    if i128::from(end) - i128::from(start) > MAX_RANGE_LEN {
+        return None;
+    }
+    let elements = (start..end).map(|int| Rc::new(Object::Integer(int)));
+    Some(Object::Array(RefCell::new(elements.collect())))
+}
+
+/// Copies the part of an array, or of a string (counting in characters), from
+/// `start` up to but not including `end`. Either bound can be null, to slice
+/// from the beginning or to the end. Negative bounds count back from the end,
+/// and bounds past either end are clamped to it, so a slice is never out of
+/// bounds. Returns None for anything else.
+pub fn slice(object: &Object, start: &Object, end: &Object) -> Option<Object> {
+    let bound = |bound: &Object| match bound {
+        Object::Null => Some(None),
+        Object::Integer(int) => Some(Some(*int)),
+        _ => None,
+    };
+    let (start, end) = (bound(start)?, bound(end)?);
+
+    match object {
+        Object::Array(elements) => {
+            let elements = elements.borrow();
+            let range = slice_range(start, end, elements.len());
+            Some(Object::Array(RefCell::new(elements[range].to_vec())))
+        }
+        Object::String(string) => {
+            let range = slice_range(start, end, string.chars().count());
+            let sliced = string.chars().skip(range.start).take(range.len());
+            Some(Object::String(sliced.collect()))
+        }
+        _ => None,
+    }
+}
+
+// a start after the end gives an empty range
+fn slice_range(start: Option<i64>, end: Option<i64>, len: usize) -> Range<usize> {
+    let clamp = |bound: i64| match usize::try_from(bound.unsigned_abs()) {
+        Ok(distance) if bound < 0 => len.saturating_sub(distance),
+        Ok(distance) => distance.min(len),
+        Err(_) if bound < 0 => 0,
+        Err(_) => len,
+    };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp).max(start);
+    start..end
+}
+
+// deeper than this is treated as a cycle, which would otherwise never finish
+// comparing
+const MAX_EQUAL_DEPTH: usize = 200;
+
+/// Compares two values like `==` does, looking inside arrays and hashes.
+/// Integers equal floats with the same value, and functions are only equal to
+/// themselves. Returns `None` if the values are nested too deeply to compare.
+pub fn deep_equal(left: &Object, right: &Object) -> Option<bool> {
+    equal_at_depth(left, right, 0)
+}
+
+fn equal_at_depth(left: &Object, right: &Object, depth: usize) -> Option<bool> {
+    if depth > MAX_EQUAL_DEPTH {
+        return None;
     }
+
+    Some(match (left, right) {
+        (Object::Cell(left), _) => equal_at_depth(&left.borrow(), right, depth)?,
+        (_, Object::Cell(right)) => equal_at_depth(left, &right.borrow(), depth)?,
+        (Object::Integer(left), Object::Float(right)) => *left as f64 == *right,
+        (Object::Float(left), Object::Integer(right)) => *left == *right as f64,
+        (Object::Array(left), Object::Array(right)) => {
+            let (left, right) = (left.borrow(), right.borrow());
+            if left.len() != right.len() {
+                return Some(false);
+            }
+            for (left, right) in left.iter().zip(right.iter()) {
+                if !equal_at_depth(left, right, depth + 1)? {
+                    return Some(false);
+                }
+            }
+            true
+        }
+        (Object::Hash(left), Object::Hash(right)) => {
+            let (left, right) = (left.borrow(), right.borrow());
+            if left.len() != right.len() {
+                return Some(false);
+            }
+            for (key, left) in left.iter() {
+                let Some(right) = right.get(key) else {
+                    return Some(false);
+                };
+                if !equal_at_depth(left, right, depth + 1)? {
+                    return Some(false);
+                }
+            }
+            true
+        }
+        (
+            Object::Null
+            | Object::Integer(_)
+            | Object::Float(_)
+            | Object::Boolean(_)
+            | Object::String(_)
+            | Object::Error(_),
+            _,
+        ) => left == right,
+        _ => std::ptr::eq(left, right),
+    })
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Function {
-    pub parameters: Vec<String>,
+    // empty for a function that was never bound with `let`
+    pub name: String,
+    // each with its default value if it has one
+    pub parameters: Vec<(String, Option<Expression>)>,
     pub body: Statement,
     pub env: Rc<RefCell<Environment>>,
 }
@@ -75,6 +324,21 @@ pub enum Hashable {
     Boolean(bool),
 }
 
+impl TryFrom<&Object> for Hashable {
+    // only strings, integers and booleans can be used as hash keys; for
+    // anything else the error is the name of the offending type
+    type Error = &'static str;
+
+    fn try_from(object: &Object) -> Result<Self, Self::Error> {
+        match object {
+            Object::String(key) => Ok(Hashable::String(key.clone())),
+            Object::Integer(key) => Ok(Hashable::Integer(*key)),
+            Object::Boolean(key) => Ok(Hashable::Boolean(*key)),
+            other => Err(other.type_name()),
+        }
+    }
+}
+
 impl Display for Hashable {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -94,6 +358,14 @@ pub struct CompiledFunction {
     pub instructions: Rc<Instructions>,
     pub num_locals: u32,
     pub num_params: u32,
+    // how many of the last parameters have defaults; the instructions then
+    // start with a jump for each number of arguments the function accepts
+    pub num_defaults: u32,
+    // the parameters' names, for calls with named arguments
+    pub parameters: Vec<String>,
+    // what the profiler records calls of it as: the name it is bound to with
+    // `let`, or where it was defined
+    pub name: String,
 }
 
 impl CompiledFunction {
@@ -102,8 +374,67 @@ impl CompiledFunction {
             instructions: Rc::new(instructions),
             num_locals,
             num_params,
+            num_defaults: 0,
+            parameters: vec![],
+            name: String::new(),
         }
     }
+
+    /// Returns the address to start running from when called with
+    /// `num_args` arguments, or `None` if it can't be called with that many.
+    pub fn entry_point(&self, num_args: usize) -> Option<usize> {
+        let num_required = (self.num_params - self.num_defaults) as usize;
+        if num_args < num_required || num_args > self.num_params as usize {
+            return None;
+        }
+        Some((num_args - num_required) * WORD_SIZE)
+    }
+}
+
+pub enum ArgumentError {
+    // a named argument that isn't one of the parameters
+    Unknown(String),
+    // a parameter given both by position and by name, or by name twice
+    Duplicate(String),
+    // a parameter left out before one that was given by name
+    Missing(String),
+}
+
+/// The arguments to pass, in order, to a function with `parameters` for a
+/// call with `positional` arguments followed by `named` ones. Only the
+/// parameters at the end can be left out, to be filled in by their defaults.
+pub fn order_arguments<'a>(
+    parameters: impl Iterator<Item = &'a str>,
+    positional: Vec<Rc<Object>>,
+    named: Vec<(String, Rc<Object>)>,
+) -> Result<Vec<Rc<Object>>, ArgumentError> {
+    let parameters: Vec<_> = parameters.collect();
+    let mut slots: Vec<_> = positional.into_iter().map(Some).collect();
+    if slots.len() < parameters.len() {
+        slots.resize(parameters.len(), None);
+    }
+    for (name, value) in named {
+        let Some(index) = parameters.iter().position(|parameter| *parameter == name) else {
+            return Err(ArgumentError::Unknown(name));
+        };
+        if slots[index].is_some() {
+            return Err(ArgumentError::Duplicate(name));
+        }
+        slots[index] = Some(value);
+    }
+
+    let num_given = slots
+        .iter()
+        .rposition(Option::is_some)
+        .map_or(0, |last| last + 1);
+    slots
+        .into_iter()
+        .take(num_given)
+        .enumerate()
+        .map(|(index, slot)| {
+            slot.ok_or_else(|| ArgumentError::Missing(parameters[index].to_string()))
+        })
+        .collect()
 }
 
 #[derive(Clone, Debug, PartialEq)]
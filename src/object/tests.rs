@@ -0,0 +1,185 @@
+#![cfg(test)]
+
+use crate::evaluator::environment::Environment;
+use crate::evaluator::eval;
+use crate::object::builtins::{Builtin, BuiltinError, BUILTIN_NAMES, NUM_BUILTINS};
+use crate::object::{deep_equal, slice, Hashable, Object};
+use crate::parser::Parser;
+use crate::value;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+fn parse_and_eval(input: &str) -> Rc<Object> {
+    // PRE: `input` is a well-formed program that evaluates without error
+    let program = Parser::parse_program(input).unwrap();
+    let env = Rc::new(RefCell::new(Environment::new()));
+    eval(program, env).unwrap()
+}
+
+#[test]
+fn test_from_scalars() {
+    assert_eq!(Object::from(5), Object::Integer(5));
+    assert_eq!(Object::from(0.5), Object::Float(0.5));
+    assert_eq!(Object::from(true), Object::Boolean(true));
+    assert_eq!(
+        Object::from("monkey"),
+        Object::String(String::from("monkey"))
+    );
+    assert_eq!(Object::from(None::<i64>), Object::Null);
+    assert_eq!(Object::from(Some(1)), Object::Integer(1));
+}
+
+#[test]
+fn test_from_vec_matches_evaluated_array() {
+    let expected = parse_and_eval("[[1, 2], [3]]");
+    let result = Object::from(vec![vec![1, 2], vec![3]]);
+    assert_eq!(result, *expected);
+}
+
+#[test]
+fn test_from_hash_map_matches_evaluated_hash() {
+    let expected = parse_and_eval("{\"one\": 1, \"two\": 2}");
+    let map = HashMap::from([(String::from("one"), 1), (String::from("two"), 2)]);
+    assert_eq!(Object::from(map), *expected);
+}
+
+#[test]
+fn test_from_btree_map_matches_evaluated_hash() {
+    let expected = parse_and_eval("{\"a\": true}");
+    let map = BTreeMap::from([(String::from("a"), true)]);
+    assert_eq!(Object::from(map), *expected);
+}
+
+#[test]
+fn test_value_macro_matches_evaluated_literal() {
+    let expected = parse_and_eval("{\"name\": \"ada\", \"scores\": [1, 2, 3], 4: [true, -1]}");
+    let result = value!({"name": "ada", "scores": [1, 2, 3], 4: [true, (-1)]});
+    assert_eq!(result, *expected);
+}
+
+#[test]
+fn test_value_macro_null_and_empty() {
+    assert_eq!(value!(null), Object::Null);
+    assert_eq!(value!([]), Object::Array(RefCell::new(vec![])));
+    assert_eq!(value!({}), Object::Hash(RefCell::new(HashMap::new())));
+}
+
+#[test]
+fn test_value_macro_with_variables() {
+    let name = "ada";
+    let expected = Object::Hash(RefCell::new(HashMap::from([(
+        Hashable::String(String::from("name")),
+        Rc::new(Object::String(String::from("ada"))),
+    )])));
+    assert_eq!(value!({"name": name}), expected);
+}
+
+#[test]
+fn test_deep_clone_copies_nested_collections() {
+    let original = parse_and_eval("[1, [2, 3], {\"a\": [4]}, fn(x) { x }]");
+    let clone = Builtin::DeepClone
+        .apply::<BuiltinError>(&[Rc::clone(&original)], &mut |_, _| unreachable!())
+        .ok()
+        .unwrap();
+    assert_eq!(clone, original);
+    assert!(!Rc::ptr_eq(&clone, &original));
+
+    let (Object::Array(original), Object::Array(clone)) = (&*original, &*clone) else {
+        panic!("expected arrays");
+    };
+    let (original, clone) = (original.borrow(), clone.borrow());
+    // integers and functions are shared, arrays and hashes are copied
+    assert!(Rc::ptr_eq(&clone[0], &original[0]));
+    assert!(!Rc::ptr_eq(&clone[1], &original[1]));
+    assert!(!Rc::ptr_eq(&clone[2], &original[2]));
+    assert!(Rc::ptr_eq(&clone[3], &original[3]));
+
+    let (Object::Hash(original), Object::Hash(clone)) = (&*original[2], &*clone[2]) else {
+        panic!("expected hashes");
+    };
+    let (original, clone) = (original.borrow(), clone.borrow());
+    let key = Hashable::String(String::from("a"));
+    assert!(!Rc::ptr_eq(&clone[&key], &original[&key]));
+}
+
+#[test]
+fn test_deep_clone_error_if_nested_too_deeply() {
+    let mut nested = Rc::new(Object::Array(RefCell::new(vec![])));
+    for _ in 0..2000 {
+        nested = Rc::new(Object::Array(RefCell::new(vec![nested])));
+    }
+    let result = Builtin::DeepClone.apply::<BuiltinError>(&[nested], &mut |_, _| unreachable!());
+    assert!(matches!(result, Err(BuiltinError::NestingTooDeep)));
+}
+
+#[test]
+fn test_deep_equal_none_if_nested_too_deeply() {
+    let nested = |depth| {
+        let mut nested = Rc::new(Object::Array(RefCell::new(vec![])));
+        for _ in 0..depth {
+            nested = Rc::new(Object::Array(RefCell::new(vec![nested])));
+        }
+        nested
+    };
+    assert_eq!(deep_equal(&nested(100), &nested(100)), Some(true));
+    assert_eq!(deep_equal(&nested(100), &nested(99)), Some(false));
+    assert_eq!(deep_equal(&nested(2000), &nested(2000)), None);
+}
+
+#[test]
+fn test_slice_clamps_bounds() {
+    let array = value!([0, 1, 2, 3]);
+    let tests = [
+        (Object::Integer(1), Object::Integer(3), value!([1, 2])),
+        (Object::Null, Object::Integer(2), value!([0, 1])),
+        (Object::Integer(2), Object::Null, value!([2, 3])),
+        (Object::Integer(-3), Object::Integer(-1), value!([1, 2])),
+        (
+            Object::Integer(-10),
+            Object::Integer(10),
+            value!([0, 1, 2, 3]),
+        ),
+        (Object::Integer(3), Object::Integer(1), value!([])),
+        (
+            Object::Integer(i64::MIN),
+            Object::Integer(i64::MAX),
+            value!([0, 1, 2, 3]),
+        ),
+        (Object::Integer(i64::MAX), Object::Null, value!([])),
+    ];
+    for (start, end, expected) in tests {
+        assert_eq!(slice(&array, &start, &end), Some(expected), "{start}:{end}");
+    }
+}
+
+#[test]
+fn test_slice_string_by_character() {
+    let string = Object::String(String::from("héllo"));
+    let sliced = slice(&string, &Object::Integer(1), &Object::Integer(-2));
+    assert_eq!(sliced, Some(Object::String(String::from("él"))));
+
+    let empty = Object::String(String::new());
+    let sliced = slice(&empty, &Object::Integer(-1), &Object::Null);
+    assert_eq!(sliced, Some(Object::String(String::new())));
+}
+
+#[test]
+fn test_slice_none_for_other_types() {
+    let array = value!([1]);
+    assert_eq!(
+        slice(&Object::Integer(1), &Object::Null, &Object::Null),
+        None
+    );
+    assert_eq!(slice(&array, &Object::Boolean(true), &Object::Null), None);
+    assert_eq!(slice(&array, &Object::Null, &Object::Float(1.0)), None);
+}
+
+#[test]
+fn test_every_builtin_name_and_index_resolves() {
+    for (idx, name) in BUILTIN_NAMES.iter().enumerate() {
+        assert!(Builtin::get(name).is_some(), "{name}");
+        assert_eq!(Builtin::get_by_idx(idx), Builtin::get(name), "{name}");
+    }
+    assert_eq!(Builtin::get_by_idx(NUM_BUILTINS), None);
+}
@@ -1,30 +1,115 @@
+use std::fmt::{self, Display, Formatter};
+
 #[derive(Debug, PartialEq)]
 pub struct Program(pub Vec<Statement>);
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Statement {
     Let(Expression, Expression),
+    // a `let` whose name can't be assigned to afterwards
+    Const(Expression, Expression),
+    // `let [a, b, ...rest] = array;`, which binds each name to the element
+    // at its position and the rest, if named, to an array of those left over
+    LetDestructure(Vec<String>, Option<String>, Expression),
     Return(Expression),
     Expression(Expression),
-    BlockStatement(Vec<Statement>),
+    Block(Vec<Statement>),
     Assignment(Expression, Expression),
+    // collection, index, and the value assigned to that element
+    IndexAssignment(Expression, Expression, Expression),
+    // loop variable, array to iterate over, and loop body
+    For(Expression, Expression, Box<Statement>),
+    Break,
+    Continue,
+    // the path of a file whose exported bindings are brought into scope
+    Use(String),
+    // a `let`, or an expression naming an existing binding, that modules
+    // importing this one can see
+    Export(Box<Statement>),
+}
+
+impl Statement {
+    // the statement that an `export` applies to, or this one if it isn't
+    // exported
+    pub fn unexported(&self) -> &Statement {
+        match self {
+            Statement::Export(statement) => statement,
+            statement => statement,
+        }
+    }
+
+    // the name bound by a `let` or `const` statement
+    pub fn let_name(&self) -> Option<&str> {
+        match self.unexported() {
+            Statement::Let(Expression::Identifier(name), _)
+            | Statement::Const(Expression::Identifier(name), _) => Some(name),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
     Identifier(String),
     Integer(i64),
+    Float(f64),
     Prefix(Prefix, Box<Expression>),
     Infix(Box<Expression>, Infix, Box<Expression>),
     Boolean(bool),
+    Null,
     If(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
-    Function(Vec<Expression>, Box<Statement>, String),
-    Call(Box<Expression>, Vec<Expression>),
+    // the parameters, each with its default value if it has one, the body,
+    // and the name the function was bound to with `let`
+    Function(
+        Vec<(Expression, Option<Expression>)>,
+        Box<Statement>,
+        String,
+    ),
+    Call(Box<Expression>, Vec<CallArg>),
     String(String),
+    // a string with expressions in it, e.g. `"hello {name}"`
+    StringTemplate(Vec<TemplatePart>),
     Array(Vec<Expression>),
     Index(Box<Expression>, Box<Expression>),
+    // collection, and the start and end of the slice, either of which may be
+    // left out
+    Slice(
+        Box<Expression>,
+        Option<Box<Expression>>,
+        Option<Box<Expression>>,
+    ),
     Hash(Vec<(Expression, Expression)>),
+    // the start and end of a range of integers, which includes the start but
+    // not the end
+    Range(Box<Expression>, Box<Expression>),
     While(Box<Expression>, Box<Statement>),
+    // the value being matched, each arm's pattern and block, and the block
+    // of the `_` arm if there is one
+    Match(
+        Box<Expression>,
+        Vec<(Expression, Statement)>,
+        Option<Box<Statement>>,
+    ),
+    // the parameters and body of a macro, which is only expanded when bound
+    // with a top-level `let`
+    Macro(Vec<(Expression, Option<Expression>)>, Box<Statement>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallArg {
+    Expression(Expression),
+    // `...array`, whose elements are passed as separate arguments
+    Spread(Expression),
+    // `name: value`, passed as the parameter with that name; these come
+    // after any other arguments
+    Named(String, Expression),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplatePart {
+    Literal(String),
+    // an expression whose value is written into the string
+    Expression(Expression),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -41,8 +126,296 @@ pub enum Infix {
     Divide,
     GreaterThan,
     LessThan,
+    GreaterEqual,
+    LessEqual,
     Equal,
     NotEqual,
     And,
     Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Power,
+}
+
+impl Infix {
+    // how the operator is written in source code
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Infix::Plus => "+",
+            Infix::Minus => "-",
+            Infix::Multiply => "*",
+            Infix::Divide => "/",
+            Infix::GreaterThan => ">",
+            Infix::LessThan => "<",
+            Infix::GreaterEqual => ">=",
+            Infix::LessEqual => "<=",
+            Infix::Equal => "==",
+            Infix::NotEqual => "!=",
+            Infix::And => "&&",
+            Infix::Or => "||",
+            Infix::BitAnd => "&",
+            Infix::BitOr => "|",
+            Infix::BitXor => "^",
+            Infix::ShiftLeft => "<<",
+            Infix::ShiftRight => ">>",
+            Infix::Power => "**",
+        }
+    }
+}
+
+// Displaying the AST gives Monkey source that parses back to the same AST.
+// Operands that are themselves operators, or that end in a block, are put in
+// parentheses, so the output never depends on precedence.
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for statement in self.0.iter() {
+            writeln!(f, "{}", Terminated(statement))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Let(name, value) => write!(f, "let {name} = {value};"),
+            Statement::Const(name, value) => write!(f, "const {name} = {value};"),
+            Statement::LetDestructure(names, rest, value) => {
+                let rest = rest.iter().map(|rest| format!("...{rest}"));
+                let names: Vec<_> = names.iter().cloned().chain(rest).collect();
+                write!(f, "let [{}] = {value};", names.join(", "))
+            }
+            Statement::Return(Expression::Null) => write!(f, "return;"),
+            Statement::Return(value) => write!(f, "return {value};"),
+            Statement::Expression(expression) => write!(f, "{expression}"),
+            Statement::Block(statements) => {
+                if statements.is_empty() {
+                    return write!(f, "{{}}");
+                }
+                writeln!(f, "{{")?;
+                for statement in statements.iter() {
+                    writeln!(f, "{}", indent(&Terminated(statement).to_string()))?;
+                }
+                write!(f, "}}")
+            }
+            Statement::Assignment(name, value) => write!(f, "{name} = {value};"),
+            Statement::IndexAssignment(collection, index, value) => {
+                write!(f, "{}[{index}] = {value};", Operand(collection))
+            }
+            Statement::For(variable, iterable, body) => {
+                write!(f, "for ({variable} in {iterable}) {body}")
+            }
+            Statement::Break => write!(f, "break;"),
+            Statement::Continue => write!(f, "continue;"),
+            Statement::Use(path) => write!(f, "import {};", Quoted(path)),
+            Statement::Export(statement) => write!(f, "export {}", Terminated(statement)),
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Identifier(name) => write!(f, "{name}"),
+            Expression::Integer(int) => write!(f, "{int}"),
+            // always with a '.', so it is read back as a float
+            Expression::Float(float) if float.fract() == 0.0 => write!(f, "{float}.0"),
+            Expression::Float(float) => write!(f, "{float}"),
+            Expression::Prefix(Prefix::Minus, operand) => write!(f, "-{}", Operand(operand)),
+            Expression::Prefix(Prefix::Bang, operand) => write!(f, "!{}", Operand(operand)),
+            Expression::Infix(left, infix, right) => {
+                write!(f, "{} {} {}", Operand(left), infix.symbol(), Operand(right))
+            }
+            Expression::Boolean(bool) => write!(f, "{bool}"),
+            Expression::Null => write!(f, "null"),
+            Expression::If(condition, consequence, alternative) => {
+                write!(f, "if ({condition}) {consequence}")?;
+                match alternative {
+                    Some(alternative) => write!(f, " else {alternative}"),
+                    None => Ok(()),
+                }
+            }
+            Expression::Function(parameters, body, _) => {
+                write!(f, "fn({}) {body}", Parameters(parameters))
+            }
+            Expression::Macro(parameters, body) => {
+                write!(f, "macro({}) {body}", Parameters(parameters))
+            }
+            Expression::Call(function, args) => {
+                write!(f, "{}(", Operand(function))?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match arg {
+                        CallArg::Expression(arg) => write!(f, "{arg}")?,
+                        CallArg::Spread(arg) => write!(f, "...{arg}")?,
+                        CallArg::Named(name, arg) => write!(f, "{name}: {arg}")?,
+                    }
+                }
+                write!(f, ")")
+            }
+            Expression::String(string) => write!(f, "{}", Quoted(string)),
+            Expression::StringTemplate(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(text) => write!(f, "{}", Escaped(text))?,
+                        TemplatePart::Expression(expression) => {
+                            // so that a hash at the start isn't read as `{{`
+                            let code = expression.to_string();
+                            let space = if code.starts_with('{') { " " } else { "" };
+                            write!(f, "{{{space}{code}}}")?
+                        }
+                    }
+                }
+                write!(f, "\"")
+            }
+            Expression::Array(elements) => write!(f, "[{}]", List(elements)),
+            Expression::Index(collection, index) => {
+                write!(f, "{}[{index}]", Operand(collection))
+            }
+            Expression::Slice(collection, start, end) => {
+                write!(f, "{}[", Operand(collection))?;
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                write!(f, "]")
+            }
+            Expression::Hash(pairs) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+            Expression::Range(start, end) => {
+                write!(f, "{}..{}", Operand(start), Operand(end))
+            }
+            Expression::While(condition, body) => write!(f, "while ({condition}) {body}"),
+            Expression::Match(value, arms, default) => {
+                writeln!(f, "match ({value}) {{")?;
+                for (pattern, block) in arms.iter() {
+                    writeln!(f, "{},", indent(&format!("{pattern} => {block}")))?;
+                }
+                if let Some(block) = default {
+                    writeln!(f, "{}", indent(&format!("_ => {block}")))?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// a statement followed by ';' if it needs one to be told apart from the next
+struct Terminated<'a>(&'a Statement);
+
+impl Display for Terminated<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Statement::Expression(expression) => write!(f, "{expression};"),
+            statement => write!(f, "{statement}"),
+        }
+    }
+}
+
+// an expression used as the operand of an operator, a call or an index
+struct Operand<'a>(&'a Expression);
+
+impl Display for Operand<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expression::Prefix(..)
+            | Expression::Infix(..)
+            | Expression::If(..)
+            | Expression::Function(..)
+            | Expression::Macro(..)
+            | Expression::Range(..)
+            | Expression::While(..)
+            | Expression::Match(..) => write!(f, "({})", self.0),
+            expression => write!(f, "{expression}"),
+        }
+    }
+}
+
+struct List<'a>(&'a [Expression]);
+
+impl Display for List<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, expression) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{expression}")?;
+        }
+        Ok(())
+    }
+}
+
+// a function's or macro's parameters, each with its default if it has one
+struct Parameters<'a>(&'a [(Expression, Option<Expression>)]);
+
+impl Display for Parameters<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, (parameter, default)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{parameter}")?;
+            if let Some(default) = default {
+                write!(f, " = {default}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// a string literal, escaped so that the lexer reads back the same string
+struct Quoted<'a>(&'a str);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", Escaped(self.0))
+    }
+}
+
+// the contents of a string literal, which are read back as `text`
+struct Escaped<'a>(&'a str);
+
+impl Display for Escaped<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\n' => write!(f, "\\n")?,
+                '\t' => write!(f, "\\t")?,
+                '\r' => write!(f, "\\r")?,
+                '\0' => write!(f, "\\0")?,
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                '{' => write!(f, "{{{{")?,
+                '}' => write!(f, "}}}}")?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn indent(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| format!("    {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
@@ -1,23 +1,28 @@
 use std::fmt::Formatter;
 use std::iter::Peekable;
 
-use self::ast::{Expression, Infix, Prefix, Statement};
-use crate::lexer::{token::Token, Lexer, LexerIter};
+use self::ast::{CallArg, Expression, Infix, Prefix, Statement, TemplatePart};
+use crate::lexer::token::{TemplateToken, Token};
+use crate::lexer::{Lexer, LexerIter};
 use crate::parser::ast::Program;
 use crate::parser::precedence::Precedence;
 
 pub mod ast;
-mod precedence;
+mod modify;
+pub mod precedence;
 mod tests;
 
 pub struct Parser<'a> {
     iter: Peekable<LexerIter<'a>>,
+    // how many loops enclose the current statement within the current function
+    loop_depth: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn parse_program(program: &str) -> Result<Program, Vec<ParsingError>> {
         let mut parser = Parser {
             iter: program.tokens().peekable(),
+            loop_depth: 0,
         };
 
         let mut program = vec![];
@@ -35,7 +40,7 @@ impl<'a> Parser<'a> {
 
             match parser.parse_statement(&token) {
                 Ok(statement) => program.push(statement),
-                Err(error) => errors.push(error),
+                Err(error) => errors.push(unterminated_comment(error)),
             }
         }
 
@@ -46,32 +51,78 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a single expression, which may be followed by semicolons but
+    /// nothing else; e.g. for evaluating an expression typed in by a user.
+    #[allow(unused)]
+    pub fn parse_expression_from_str(src: &str) -> Result<Expression, Vec<ParsingError>> {
+        let mut parser = Parser {
+            iter: src.tokens().peekable(),
+            loop_depth: 0,
+        };
+
+        let token = parser.next_token_or_end().map_err(|e| vec![e])?;
+        let expression = parser
+            .parse_expression(&token, Precedence::Lowest)
+            .map_err(|e| vec![unterminated_comment(e)])?;
+
+        while parser.iter.next_if_eq(&Token::Semicolon).is_some() {}
+        match parser.iter.next() {
+            None => Ok(expression),
+            Some(token) => Err(vec![unterminated_comment(ParsingError::UnexpectedToken(
+                token,
+            ))]),
+        }
+    }
+
     fn parse_statement(&mut self, token: &Token) -> Result<Statement, ParsingError> {
         self.iter.next();
         match token {
+            Token::Let if matches!(self.iter.peek(), Some(Token::Lbracket)) => {
+                let r = self.parse_let_destructure();
+                self.recover(r)
+            }
             Token::Let => {
                 let r = self.parse_let();
-                self.skip_to_semicolon();
-                r
+                self.recover(r)
+            }
+            Token::Const => {
+                let r = self.parse_const();
+                self.recover(r)
             }
             Token::Return => {
                 let r = self.parse_return();
-                self.skip_to_semicolon();
-                r
+                self.recover(r)
+            }
+            Token::For => self.parse_for(),
+            Token::Function if matches!(self.iter.peek(), Some(Token::Identifier(_))) => {
+                self.parse_function_statement()
+            }
+            Token::Import => {
+                let r = self.parse_import();
+                self.recover(r)
+            }
+            Token::Export => {
+                let r = self.parse_export();
+                self.recover(r)
+            }
+            Token::Break | Token::Continue => {
+                let r = self.parse_loop_control(token);
+                self.recover(r)
             }
             t => {
-                if let Some(Token::Assign) = self.iter.peek() {
+                if let Some(
+                    Token::Assign
+                    | Token::PlusAssign
+                    | Token::MinusAssign
+                    | Token::AsteriskAssign
+                    | Token::SlashAssign,
+                ) = self.iter.peek()
+                {
                     let r = self.parse_assignment(t);
-                    self.skip_to_semicolon();
-                    r
+                    self.recover(r)
                 } else {
-                    match self.parse_expression_statement(t) {
-                        Ok(s) => Ok(s),
-                        Err(e) => {
-                            self.skip_to_semicolon();
-                            Err(e)
-                        }
-                    }
+                    let r = self.parse_expression_statement(t);
+                    self.recover(r)
                 }
             }
         }
@@ -84,9 +135,21 @@ impl<'a> Parser<'a> {
             token => return Err(ParsingError::UnexpectedToken(token.clone())),
         });
 
-        // after identifier next token should be '='
-        match self.next_token_or_end()? {
-            Token::Assign => {}
+        let expression = self.parse_assigned_value(&identifier)?;
+        Ok(Statement::Assignment(identifier, expression))
+    }
+
+    // parses the rest of an assignment to `target`, whose operator is the
+    // next token, returning the value to assign
+    fn parse_assigned_value(&mut self, target: &Expression) -> Result<Expression, ParsingError> {
+        // after the target next token should be '=', or a compound assignment
+        // like '+=' which applies its operator to the current value
+        let operator = match self.next_token_or_end()? {
+            Token::Assign => None,
+            Token::PlusAssign => Some(Infix::Plus),
+            Token::MinusAssign => Some(Infix::Minus),
+            Token::AsteriskAssign => Some(Infix::Multiply),
+            Token::SlashAssign => Some(Infix::Divide),
             token => return Err(ParsingError::UnexpectedToken(token)),
         };
 
@@ -94,19 +157,17 @@ impl<'a> Parser<'a> {
         // means it should not be ';' or EOF
         let token = self.next_token_or_end()?;
 
-        let expression = match self.parse_expression(&token, Precedence::Lowest) {
-            Ok(exp) => exp,
-            Err(e) => return Err(e),
-        };
+        let mut expression = self.parse_expression(&token, Precedence::Lowest)?;
 
-        // after expression next token should be ';'
-        match self.iter.peek() {
-            Some(Token::Semicolon) => {}
-            Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
-            None => return Err(ParsingError::UnexpectedEof),
+        self.expect_end_of_statement()?;
+
+        // `x += 1 + 2` is `x = x + (1 + 2)`
+        if let Some(operator) = operator {
+            expression =
+                Expression::Infix(Box::new(target.clone()), operator, Box::new(expression));
         }
 
-        Ok(Statement::Assignment(identifier, expression))
+        Ok(expression)
     }
 
     fn parse_let(&mut self) -> Result<Statement, ParsingError> {
@@ -141,42 +202,205 @@ impl<'a> Parser<'a> {
             Err(e) => return Err(e),
         };
 
-        // after expression next token should be ';'
-        match self.iter.peek() {
-            Some(Token::Semicolon) => {}
-            Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
-            None => return Err(ParsingError::UnexpectedEof),
-        }
+        self.expect_end_of_statement()?;
 
         Ok(Statement::Let(identifier, expression))
     }
 
-    fn parse_return(&mut self) -> Result<Statement, ParsingError> {
-        // after 'let' next token should be beginning of expression, which
-        // means it should not be ';' or EOF
+    // `const` is written as `let` is
+    fn parse_const(&mut self) -> Result<Statement, ParsingError> {
+        let Statement::Let(identifier, expression) = self.parse_let()? else {
+            unreachable!("parse_let always returns a let statement")
+        };
+        Ok(Statement::Const(identifier, expression))
+    }
+
+    // `let [a, b, ...rest] = value;`, where the `...rest` is optional
+    fn parse_let_destructure(&mut self) -> Result<Statement, ParsingError> {
+        // after 'let' next token is '['
+        self.next_token_or_end()?;
+
+        let mut names = vec![];
+        let mut rest = None;
+        if self.iter.next_if_eq(&Token::Rbracket).is_none() {
+            loop {
+                match self.next_token_or_end()? {
+                    Token::Identifier(name) => names.push(name),
+                    Token::Ellipsis => {
+                        match self.next_token_or_end()? {
+                            Token::Identifier(name) => rest = Some(name),
+                            token => return Err(ParsingError::UnexpectedToken(token)),
+                        }
+                        // the rest has to come last
+                        match self.next_token_or_end()? {
+                            Token::Rbracket => break,
+                            token => return Err(ParsingError::UnexpectedToken(token)),
+                        }
+                    }
+                    token => return Err(ParsingError::UnexpectedToken(token)),
+                }
+                match self.next_token_or_end()? {
+                    Token::Comma if self.iter.next_if_eq(&Token::Rbracket).is_some() => break,
+                    Token::Comma => {}
+                    Token::Rbracket => break,
+                    token => return Err(ParsingError::UnexpectedToken(token)),
+                }
+            }
+        }
+
+        // after the names next token should be '='
+        match self.next_token_or_end()? {
+            Token::Assign => {}
+            token => return Err(ParsingError::UnexpectedToken(token)),
+        };
+
         let token = self.next_token_or_end()?;
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
 
-        let expression = match self.parse_expression(&token, Precedence::Lowest) {
-            Ok(exp) => exp,
-            Err(e) => return Err(e),
+        self.expect_end_of_statement()?;
+
+        Ok(Statement::LetDestructure(names, rest, expression))
+    }
+
+    // `fn name(...) { ... }` is short for `let name = fn(...) { ... };`, and
+    // like a block it needs no ';' after it
+    fn parse_function_statement(&mut self) -> Result<Statement, ParsingError> {
+        let name = match self.next_token_or_end()? {
+            Token::Identifier(id) => id,
+            token => return Err(ParsingError::UnexpectedToken(token)),
         };
 
-        // after expression next token should be ';'
-        match self.iter.peek() {
-            Some(Token::Semicolon) => {}
-            Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
-            None => return Err(ParsingError::UnexpectedEof),
+        let Expression::Function(parameters, body, _) = self.parse_function_literal()? else {
+            unreachable!("parse_function_literal always returns a function")
         };
 
+        Ok(Statement::Let(
+            Expression::Identifier(name.clone()),
+            Expression::Function(parameters, body, name),
+        ))
+    }
+
+    fn parse_return(&mut self) -> Result<Statement, ParsingError> {
+        // a bare 'return' returns null
+        if matches!(
+            self.iter.peek(),
+            Some(Token::Semicolon | Token::Rbrace) | None
+        ) {
+            return Ok(Statement::Return(Expression::Null));
+        }
+        let token = self.next_token_or_end()?;
+
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
+
+        self.expect_end_of_statement()?;
+
         Ok(Statement::Return(expression))
     }
 
-    fn parse_expression_statement(&mut self, token: &Token) -> Result<Statement, ParsingError> {
-        let expression = match self.parse_expression(token, Precedence::Lowest) {
-            Ok(s) => s,
-            Err(e) => return Err(e),
+    fn parse_import(&mut self) -> Result<Statement, ParsingError> {
+        // after 'import' next token should be the path, as a string
+        let path = match self.next_token_or_end()? {
+            Token::String(path) => path,
+            token => return Err(ParsingError::UnexpectedToken(token)),
+        };
+
+        self.expect_end_of_statement()?;
+
+        Ok(Statement::Use(path))
+    }
+
+    fn parse_export(&mut self) -> Result<Statement, ParsingError> {
+        // after 'export' next token should be 'let' or 'const', or the name of
+        // a binding made earlier on
+        let statement = match self.next_token_or_end()? {
+            Token::Let => self.parse_let()?,
+            Token::Const => self.parse_const()?,
+            Token::Function => self.parse_function_statement()?,
+            Token::Identifier(name) => {
+                self.expect_end_of_statement()?;
+                Statement::Expression(Expression::Identifier(name))
+            }
+            token => return Err(ParsingError::UnexpectedToken(token)),
+        };
+
+        Ok(Statement::Export(Box::new(statement)))
+    }
+
+    fn parse_loop_control(&mut self, token: &Token) -> Result<Statement, ParsingError> {
+        if self.loop_depth == 0 {
+            return Err(ParsingError::OutsideLoop(token.clone()));
+        }
+
+        // 'break' and 'continue' end the statement
+        self.expect_end_of_statement()?;
+
+        Ok(match token {
+            Token::Break => Statement::Break,
+            _ => Statement::Continue,
+        })
+    }
+
+    fn parse_loop_body(&mut self) -> Result<Statement, ParsingError> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn parse_for(&mut self) -> Result<Statement, ParsingError> {
+        // after 'for' expect '(', then the loop variable and 'in'
+        match self.next_token_or_end()? {
+            Token::Lparen => {}
+            token => return Err(ParsingError::UnexpectedToken(token)),
         };
 
+        let variable = Expression::Identifier(match self.next_token_or_end()? {
+            Token::Identifier(id) => id,
+            token => return Err(ParsingError::UnexpectedToken(token)),
+        });
+
+        match self.next_token_or_end()? {
+            Token::In => {}
+            token => return Err(ParsingError::UnexpectedToken(token)),
+        };
+
+        let token = self.next_token_or_end()?;
+        let iterable = self.parse_expression(&token, Precedence::Lowest)?;
+
+        match self.next_token_or_end()? {
+            Token::Rparen => {}
+            token => return Err(ParsingError::UnexpectedToken(token)),
+        };
+
+        let body = Box::new(self.parse_loop_body()?);
+
+        Ok(Statement::For(variable, iterable, body))
+    }
+
+    fn parse_expression_statement(&mut self, token: &Token) -> Result<Statement, ParsingError> {
+        let expression = self.parse_expression(token, Precedence::Lowest)?;
+
+        // `a[i] = x` can only be told apart from an index expression once the
+        // index has been parsed
+        if let (
+            Expression::Index(collection, index),
+            Some(
+                Token::Assign
+                | Token::PlusAssign
+                | Token::MinusAssign
+                | Token::AsteriskAssign
+                | Token::SlashAssign,
+            ),
+        ) = (&expression, self.iter.peek())
+        {
+            let value = self.parse_assigned_value(&expression)?;
+            return Ok(Statement::IndexAssignment(
+                (**collection).clone(),
+                (**index).clone(),
+                value,
+            ));
+        }
+
         Ok(Statement::Expression(expression))
     }
 
@@ -213,7 +437,7 @@ impl<'a> Parser<'a> {
             token => return Err(ParsingError::UnexpectedToken(token)),
         }
 
-        Ok(Statement::BlockStatement(block))
+        Ok(Statement::Block(block))
     }
 
     fn next_token_or_end(&mut self) -> Result<Token, ParsingError> {
@@ -224,6 +448,27 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // a statement ends at ';', or without one at the end of its block or of
+    // the input; the ';' or '}' is left for the caller
+    fn expect_end_of_statement(&mut self) -> Result<(), ParsingError> {
+        match self.iter.peek() {
+            Some(Token::Semicolon | Token::Rbrace) | None => Ok(()),
+            Some(token) => Err(ParsingError::UnexpectedToken(token.clone())),
+        }
+    }
+
+    // after a statement that failed to parse, skips to the next ';' so that
+    // the statements after it can still be parsed
+    fn recover(
+        &mut self,
+        result: Result<Statement, ParsingError>,
+    ) -> Result<Statement, ParsingError> {
+        if result.is_err() {
+            self.skip_to_semicolon();
+        }
+        result
+    }
+
     fn skip_to_semicolon(&mut self) {
         while let Some(token) = self.iter.peek() {
             if *token != Token::Semicolon {
@@ -243,16 +488,23 @@ impl<'a> Parser<'a> {
         let mut left_expression = match token {
             Token::Identifier(id) => Self::parse_identifier(id),
             Token::Int(int) => Self::parse_integer(int),
+            Token::Float(float) => Self::parse_float(float),
             Token::Bang | Token::Minus => self.parse_prefix_expression(token),
             Token::True => Parser::parse_boolean(true),
             Token::False => Parser::parse_boolean(false),
+            Token::Null => Ok(Expression::Null),
             Token::Lparen => self.parse_grouped_expression(),
             Token::If => self.parse_if_expression(),
             Token::Function => self.parse_function_literal(),
+            Token::Macro => self.parse_macro_literal(),
             Token::String(string) => Self::parse_string(string),
+            Token::Template(parts) => self.parse_string_template(parts),
+            Token::InvalidEscape(sequence) => Err(ParsingError::InvalidEscape(sequence.clone())),
+            Token::UnclosedInterpolation => Err(ParsingError::UnclosedInterpolation),
             Token::Lbracket => self.parse_array_literal(),
             Token::Lbrace => self.parse_hash_literal(),
             Token::While => self.parse_while_expression(),
+            Token::Match => self.parse_match_expression(),
             _ => return Err(ParsingError::InvalidPrefixOperator(token.clone())),
         }?;
 
@@ -272,12 +524,22 @@ impl<'a> Parser<'a> {
                     | Token::Slash
                     | Token::Lt
                     | Token::Gt
+                    | Token::LtEq
+                    | Token::GtEq
                     | Token::Eq
                     | Token::Noteq
                     | Token::And
-                    | Token::Or => self.parse_infix_expression(left_expression, &operator)?,
+                    | Token::Or
+                    | Token::Ampersand
+                    | Token::Pipe
+                    | Token::Caret
+                    | Token::ShiftLeft
+                    | Token::ShiftRight
+                    | Token::Power => self.parse_infix_expression(left_expression, &operator)?,
                     Token::Lparen => self.parse_call_expression(left_expression)?,
                     Token::Lbracket => self.parse_index_expression(left_expression)?,
+                    Token::Question => self.parse_conditional_expression(left_expression)?,
+                    Token::DotDot => self.parse_range_expression(left_expression)?,
                     _ => break,
                 }
             } else {
@@ -298,11 +560,75 @@ impl<'a> Parser<'a> {
         // expect grouped expression after 'if' token
         let condition = self.parse_expression(&token, Precedence::Lowest)?;
 
-        let loop_block = Box::new(self.parse_block_statement()?);
+        let loop_block = Box::new(self.parse_loop_body()?);
 
         Ok(Expression::While(Box::new(condition), loop_block))
     }
 
+    fn parse_match_expression(&mut self) -> Result<Expression, ParsingError> {
+        let token = match self.next_token_or_end()? {
+            Token::Lparen => Token::Lparen,
+            t => return Err(ParsingError::UnexpectedToken(t)),
+        };
+        let value = self.parse_expression(&token, Precedence::Lowest)?;
+
+        match self.next_token_or_end()? {
+            Token::Lbrace => {}
+            t => return Err(ParsingError::UnexpectedToken(t)),
+        }
+
+        let mut arms = vec![];
+        let mut default = None;
+        loop {
+            let token = self.next_token_or_end()?;
+            if token == Token::Rbrace {
+                break;
+            }
+            // the `_` arm matches anything, so no arm may follow it
+            if default.is_some() {
+                return Err(ParsingError::UnexpectedToken(token));
+            }
+
+            let pattern = match token {
+                Token::Identifier(ref id) if id == "_" => None,
+                token => Some(self.parse_expression(&token, Precedence::Lowest)?),
+            };
+            match self.next_token_or_end()? {
+                Token::FatArrow => {}
+                t => return Err(ParsingError::UnexpectedToken(t)),
+            }
+            let block = self.parse_match_arm()?;
+
+            match pattern {
+                Some(pattern) => arms.push((pattern, block)),
+                None => default = Some(Box::new(block)),
+            }
+
+            match self.iter.peek() {
+                Some(Token::Comma) => {
+                    self.next_token_or_end()?;
+                }
+                Some(Token::Rbrace) => continue,
+                Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
+                None => return Err(ParsingError::UnexpectedEof),
+            }
+        }
+
+        Ok(Expression::Match(Box::new(value), arms, default))
+    }
+
+    // an arm's result is either a block or a single expression, which is
+    // treated as a block holding just that expression; `{` always starts a
+    // block, so a hash literal result has to be put in parentheses
+    fn parse_match_arm(&mut self) -> Result<Statement, ParsingError> {
+        if let Some(Token::Lbrace) = self.iter.peek() {
+            return self.parse_block_statement();
+        }
+        let token = self.next_token_or_end()?;
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
+        Ok(Statement::Block(vec![Statement::Expression(expression)]))
+    }
+
     fn parse_hash_literal(&mut self) -> Result<Expression, ParsingError> {
         let mut hash = vec![];
 
@@ -342,15 +668,49 @@ impl<'a> Parser<'a> {
             return Err(ParsingError::UnexpectedToken(Token::Rbracket));
         }
 
-        let next_token = self.next_token_or_end()?;
-        let right = self.parse_expression(&next_token, Precedence::Lowest)?;
+        // the start of a slice can be left out, as in `arr[:2]`
+        let right = match self.iter.peek() {
+            Some(Token::Colon) => None,
+            _ => {
+                let next_token = self.next_token_or_end()?;
+                Some(self.parse_expression(&next_token, Precedence::Lowest)?)
+            }
+        };
+
+        match (self.next_token_or_end()?, right) {
+            (Token::Rbracket, Some(right)) => {
+                Ok(Expression::Index(Box::new(left), Box::new(right)))
+            }
+            (Token::Colon, start) => self.parse_slice(left, start),
+            (token, _) => Err(ParsingError::UnexpectedToken(token)),
+        }
+    }
+
+    // parses the rest of `left[start:end]` after the ':'; the end can be left
+    // out too, as in `arr[2:]`
+    fn parse_slice(
+        &mut self,
+        left: Expression,
+        start: Option<Expression>,
+    ) -> Result<Expression, ParsingError> {
+        let end = match self.iter.peek() {
+            Some(Token::Rbracket) => None,
+            _ => {
+                let next_token = self.next_token_or_end()?;
+                Some(self.parse_expression(&next_token, Precedence::Lowest)?)
+            }
+        };
 
         match self.next_token_or_end()? {
             Token::Rbracket => {}
             token => return Err(ParsingError::UnexpectedToken(token)),
         }
 
-        Ok(Expression::Index(Box::new(left), Box::new(right)))
+        Ok(Expression::Slice(
+            Box::new(left),
+            start.map(Box::new),
+            end.map(Box::new),
+        ))
     }
 
     fn parse_array_literal(&mut self) -> Result<Expression, ParsingError> {
@@ -359,7 +719,7 @@ impl<'a> Parser<'a> {
             return Ok(Expression::Array(vec![]));
         }
 
-        let array = self.parse_expression_list()?;
+        let array = self.parse_expression_list(Some(&Token::Rbracket))?;
 
         match self.next_token_or_end()? {
             Token::Rbracket => {}
@@ -369,7 +729,11 @@ impl<'a> Parser<'a> {
         Ok(Expression::Array(array))
     }
 
-    fn parse_expression_list(&mut self) -> Result<Vec<Expression>, ParsingError> {
+    // if `end` is given, a trailing comma directly before that closing token is allowed
+    fn parse_expression_list(
+        &mut self,
+        end: Option<&Token>,
+    ) -> Result<Vec<Expression>, ParsingError> {
         let mut array = vec![];
 
         let next_token = self.next_token_or_end()?;
@@ -377,6 +741,9 @@ impl<'a> Parser<'a> {
 
         while let Some(Token::Comma) = self.iter.peek() {
             self.next_token_or_end()?;
+            if end.is_some() && self.iter.peek() == end {
+                break;
+            }
             let next_token = self.next_token_or_end()?;
             array.push(self.parse_expression(&next_token, Precedence::Lowest)?);
         }
@@ -389,15 +756,64 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_integer(int: &str) -> Result<Expression, ParsingError> {
-        int.parse::<i64>()
+        let (digits, radix) = match int.get(..2) {
+            Some("0x") => (&int[2..], 16),
+            Some("0o") => (&int[2..], 8),
+            Some("0b") => (&int[2..], 2),
+            _ => (int, 10),
+        };
+        // underscores may only separate digits, one at a time
+        if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+            return Err(ParsingError::InvalidInteger(int.to_string()));
+        }
+        i64::from_str_radix(&digits.replace('_', ""), radix)
             .map(Expression::Integer)
             .map_err(|_| ParsingError::InvalidInteger(int.to_string()))
     }
 
+    fn parse_float(float: &str) -> Result<Expression, ParsingError> {
+        // digits are required on both sides of the decimal point
+        if float.starts_with('.') || float.ends_with('.') {
+            return Err(ParsingError::InvalidFloat(float.to_string()));
+        }
+        float
+            .parse::<f64>()
+            .map(Expression::Float)
+            .map_err(|_| ParsingError::InvalidFloat(float.to_string()))
+    }
+
     fn parse_string(string: &str) -> Result<Expression, ParsingError> {
         Ok(Expression::String(string.to_string()))
     }
 
+    fn parse_string_template(&self, parts: &[TemplateToken]) -> Result<Expression, ParsingError> {
+        let parts = parts
+            .iter()
+            .map(|part| match part {
+                TemplateToken::Text(text) => Ok(TemplatePart::Literal(text.clone())),
+                TemplateToken::Code(code) => {
+                    Ok(TemplatePart::Expression(self.parse_embedded(code)?))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Expression::StringTemplate(parts))
+    }
+
+    // the single expression between a template's braces, parsed as though it
+    // were written where the template is
+    fn parse_embedded(&self, code: &str) -> Result<Expression, ParsingError> {
+        let mut parser = Parser {
+            iter: code.tokens().peekable(),
+            loop_depth: self.loop_depth,
+        };
+        let token = parser.next_token_or_end()?;
+        let expression = parser.parse_expression(&token, Precedence::Lowest)?;
+        match parser.iter.next() {
+            Some(token) => Err(ParsingError::UnexpectedToken(token)),
+            None => Ok(expression),
+        }
+    }
+
     fn parse_boolean(val: bool) -> Result<Expression, ParsingError> {
         Ok(Expression::Boolean(val))
     }
@@ -443,17 +859,58 @@ impl<'a> Parser<'a> {
         ))
     }
 
+    // `cond ? a : b` is sugar for `if (cond) { a } else { b }`; both branches
+    // are parsed from the lowest precedence, which makes `?` right-associative
+    fn parse_conditional_expression(
+        &mut self,
+        condition: Expression,
+    ) -> Result<Expression, ParsingError> {
+        let token = self.next_token_or_end()?;
+        let consequence = self.parse_expression(&token, Precedence::Lowest)?;
+
+        match self.iter.peek() {
+            Some(Token::Colon) => self.iter.next(),
+            _ => return Err(ParsingError::MissingColon),
+        };
+
+        let token = self.next_token_or_end()?;
+        let alternative = self.parse_expression(&token, Precedence::Lowest)?;
+
+        Ok(Expression::If(
+            Box::new(condition),
+            Box::new(Statement::Block(vec![Statement::Expression(consequence)])),
+            Some(Box::new(Statement::Block(vec![Statement::Expression(
+                alternative,
+            )]))),
+        ))
+    }
+
     fn parse_function_literal(&mut self) -> Result<Expression, ParsingError> {
         // expect parameter list after 'fn' keyword
         let parameters = self.parse_function_parameters()?;
 
-        // expect block statement after parameter list
-        let body = Box::new(self.parse_block_statement()?);
+        // expect block statement after parameter list; loops around the
+        // function can't be exited from inside it
+        let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.parse_block_statement();
+        self.loop_depth = outer_loop_depth;
+        let body = Box::new(body?);
 
         Ok(Expression::Function(parameters, body, String::new()))
     }
 
-    fn parse_function_parameters(&mut self) -> Result<Vec<Expression>, ParsingError> {
+    // a macro's parameters and body are written as a function's are
+    fn parse_macro_literal(&mut self) -> Result<Expression, ParsingError> {
+        let Expression::Function(parameters, body, _) = self.parse_function_literal()? else {
+            unreachable!("parse_function_literal always returns a function")
+        };
+        Ok(Expression::Macro(parameters, body))
+    }
+
+    // once one parameter has a default, every parameter after it needs one
+    fn parse_function_parameters(
+        &mut self,
+    ) -> Result<Vec<(Expression, Option<Expression>)>, ParsingError> {
         // expect first token of parameter list to be '('
         match self.next_token_or_end()? {
             Token::Lparen => {}
@@ -469,14 +926,30 @@ impl<'a> Parser<'a> {
         }
 
         loop {
-            match self.next_token_or_end()? {
-                Token::Identifier(id) => parameters.push(Expression::Identifier(id)),
+            let name = match self.next_token_or_end()? {
+                Token::Identifier(id) => id,
                 t => return Err(ParsingError::UnexpectedToken(t)),
-            }
+            };
+
+            let default = match self.iter.next_if_eq(&Token::Assign) {
+                Some(_) => {
+                    let token = self.next_token_or_end()?;
+                    Some(self.parse_expression(&token, Precedence::Lowest)?)
+                }
+                None if matches!(parameters.last(), Some((_, Some(_)))) => {
+                    return Err(ParsingError::MissingDefault(name))
+                }
+                None => None,
+            };
+            parameters.push((Expression::Identifier(name), default));
 
             match self.iter.peek() {
+                // a trailing comma is allowed before the ')'
                 Some(Token::Comma) => {
                     self.next_token_or_end()?;
+                    if self.iter.next_if_eq(&Token::Rparen).is_some() {
+                        break;
+                    }
                 }
                 Some(Token::Rparen) => {
                     self.next_token_or_end()?;
@@ -520,10 +993,18 @@ impl<'a> Parser<'a> {
             Token::Slash => Infix::Divide,
             Token::Lt => Infix::LessThan,
             Token::Gt => Infix::GreaterThan,
+            Token::LtEq => Infix::LessEqual,
+            Token::GtEq => Infix::GreaterEqual,
             Token::Eq => Infix::Equal,
             Token::Noteq => Infix::NotEqual,
             Token::And => Infix::And,
             Token::Or => Infix::Or,
+            Token::Ampersand => Infix::BitAnd,
+            Token::Pipe => Infix::BitOr,
+            Token::Caret => Infix::BitXor,
+            Token::ShiftLeft => Infix::ShiftLeft,
+            Token::ShiftRight => Infix::ShiftRight,
+            Token::Power => Infix::Power,
             _ => {
                 return Err(ParsingError::Generic(String::from(
                     "should never get here... fix types",
@@ -531,7 +1012,7 @@ impl<'a> Parser<'a> {
             }
         };
 
-        let precedence = Precedence::get_precedence(operator);
+        let precedence = Precedence::get_right_precedence(operator);
 
         let next_token = self.next_token_or_end()?;
 
@@ -544,20 +1025,52 @@ impl<'a> Parser<'a> {
         ))
     }
 
+    fn parse_range_expression(&mut self, start: Expression) -> Result<Expression, ParsingError> {
+        let precedence = Precedence::get_right_precedence(&Token::DotDot);
+        let next_token = self.next_token_or_end()?;
+        let end = self.parse_expression(&next_token, precedence)?;
+        Ok(Expression::Range(Box::new(start), Box::new(end)))
+    }
+
     fn parse_call_expression(
         &mut self,
         left_expression: Expression,
     ) -> Result<Expression, ParsingError> {
+        let mut arguments = vec![];
         if let Some(Token::Rparen) = self.iter.peek() {
             self.next_token_or_end()?;
-            return Ok(Expression::Call(Box::new(left_expression), vec![]));
+            return Ok(Expression::Call(Box::new(left_expression), arguments));
         }
 
-        let arguments = self.parse_expression_list()?;
+        loop {
+            let spread = self.iter.next_if_eq(&Token::Ellipsis).is_some();
+            let next_token = self.next_token_or_end()?;
+            let argument = self.parse_expression(&next_token, Precedence::Lowest)?;
+            let argument = match (spread, argument) {
+                (true, argument) => CallArg::Spread(argument),
+                (false, Expression::Identifier(name))
+                    if self.iter.next_if_eq(&Token::Colon).is_some() =>
+                {
+                    let next_token = self.next_token_or_end()?;
+                    let value = self.parse_expression(&next_token, Precedence::Lowest)?;
+                    CallArg::Named(name, value)
+                }
+                (false, argument) => CallArg::Expression(argument),
+            };
+            if matches!(arguments.last(), Some(CallArg::Named(..)))
+                && !matches!(argument, CallArg::Named(..))
+            {
+                return Err(ParsingError::PositionalAfterNamed);
+            }
+            arguments.push(argument);
 
-        match self.next_token_or_end()? {
-            Token::Rparen => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            match self.next_token_or_end()? {
+                // a trailing comma is allowed before the ')'
+                Token::Comma if self.iter.next_if_eq(&Token::Rparen).is_some() => break,
+                Token::Comma => {}
+                Token::Rparen => break,
+                token => return Err(ParsingError::UnexpectedToken(token)),
+            }
         }
 
         Ok(Expression::Call(Box::new(left_expression), arguments))
@@ -571,9 +1084,36 @@ pub enum ParsingError {
     UnexpectedSemicolon,
     InvalidPrefixOperator(Token),
     InvalidInteger(String),
+    InvalidFloat(String),
+    OutsideLoop(Token),
+    // an escape sequence in a string that doesn't stand for any character
+    InvalidEscape(String),
+    // a `{` in a string with no `}` to close it; `{{` stands for a `{`
+    UnclosedInterpolation,
+    // a `/*` with no `*/` to close it
+    UnterminatedComment,
+    // a `?` whose second branch is missing
+    MissingColon,
+    // an argument that isn't named after one that is
+    PositionalAfterNamed,
+    // a parameter without a default after one with a default
+    MissingDefault(String),
     Generic(String),
 }
 
+// an unterminated comment runs to the end of the input, so it can be the last
+// token wherever that is; it is reported as itself rather than as whatever the
+// parser expected to find there
+fn unterminated_comment(error: ParsingError) -> ParsingError {
+    match error {
+        ParsingError::UnexpectedToken(Token::UnterminatedComment)
+        | ParsingError::InvalidPrefixOperator(Token::UnterminatedComment) => {
+            ParsingError::UnterminatedComment
+        }
+        error => error,
+    }
+}
+
 impl std::fmt::Display for ParsingError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -587,6 +1127,21 @@ impl std::fmt::Display for ParsingError {
                     format!("'{token}' is not a valid prefix operator"),
                 ParsingError::InvalidInteger(string) =>
                     format!("Cannot parse '{}' as a valid integer", *string),
+                ParsingError::InvalidFloat(string) =>
+                    format!("Cannot parse '{}' as a valid float", *string),
+                ParsingError::OutsideLoop(token) => format!("'{token}' used outside of a loop"),
+                ParsingError::InvalidEscape(sequence) =>
+                    format!("Unknown escape sequence '{sequence}' in string"),
+                ParsingError::UnclosedInterpolation =>
+                    "Missing '}' after '{' in string; write '{{' for a '{'".to_string(),
+                ParsingError::UnterminatedComment =>
+                    "Missing '*/' to end the comment started by '/*'".to_string(),
+                ParsingError::MissingColon =>
+                    "Expected ':' after the first branch of '?'".to_string(),
+                ParsingError::PositionalAfterNamed =>
+                    "Named arguments must come after all other arguments".to_string(),
+                ParsingError::MissingDefault(name) =>
+                    format!("Parameter '{name}' needs a default, as it follows one with a default"),
                 ParsingError::Generic(string) => string.to_string(),
             }
         )
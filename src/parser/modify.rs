@@ -0,0 +1,230 @@
+use super::ast::{CallArg, Expression, Program, Statement, TemplatePart};
+use std::mem;
+
+// Rewriting the AST bottom-up: every expression has its children rewritten
+// first, and is then passed to the modifier, which returns the expression
+// to put in its place. The names bound by `let`, assignments, `for` loops
+// and parameters are left alone.
+
+impl Program {
+    pub fn modify<E, F>(self, modifier: &mut F) -> Result<Program, E>
+    where
+        F: FnMut(Expression) -> Result<Expression, E>,
+    {
+        let Program(statements) = self;
+        Ok(Program(modify_statements(statements, modifier)?))
+    }
+}
+
+impl Statement {
+    pub fn modify<E, F>(self, modifier: &mut F) -> Result<Statement, E>
+    where
+        F: FnMut(Expression) -> Result<Expression, E>,
+    {
+        Ok(match self {
+            Statement::Let(name, value) => Statement::Let(name, value.modify(modifier)?),
+            Statement::Const(name, value) => Statement::Const(name, value.modify(modifier)?),
+            Statement::LetDestructure(names, rest, value) => {
+                Statement::LetDestructure(names, rest, value.modify(modifier)?)
+            }
+            Statement::Return(value) => Statement::Return(value.modify(modifier)?),
+            Statement::Expression(expression) => {
+                Statement::Expression(expression.modify(modifier)?)
+            }
+            Statement::Block(statements) => {
+                Statement::Block(modify_statements(statements, modifier)?)
+            }
+            Statement::Assignment(name, value) => {
+                Statement::Assignment(name, value.modify(modifier)?)
+            }
+            Statement::IndexAssignment(collection, index, value) => Statement::IndexAssignment(
+                collection.modify(modifier)?,
+                index.modify(modifier)?,
+                value.modify(modifier)?,
+            ),
+            Statement::For(variable, iterable, body) => Statement::For(
+                variable,
+                iterable.modify(modifier)?,
+                Box::new(body.modify(modifier)?),
+            ),
+            Statement::Export(statement) => {
+                Statement::Export(Box::new(statement.modify(modifier)?))
+            }
+            statement @ (Statement::Break | Statement::Continue | Statement::Use(_)) => statement,
+        })
+    }
+}
+
+impl Expression {
+    pub fn modify<E, F>(self, modifier: &mut F) -> Result<Expression, E>
+    where
+        F: FnMut(Expression) -> Result<Expression, E>,
+    {
+        let expression = match self {
+            Expression::Prefix(prefix, operand) => {
+                Expression::Prefix(prefix, modify_boxed(operand, modifier)?)
+            }
+            Expression::Infix(left, infix, right) => Expression::Infix(
+                modify_boxed(left, modifier)?,
+                infix,
+                modify_boxed(right, modifier)?,
+            ),
+            Expression::If(condition, consequence, alternative) => Expression::If(
+                modify_boxed(condition, modifier)?,
+                Box::new(consequence.modify(modifier)?),
+                match alternative {
+                    Some(alternative) => Some(Box::new(alternative.modify(modifier)?)),
+                    None => None,
+                },
+            ),
+            Expression::Function(parameters, body, name) => Expression::Function(
+                modify_parameters(parameters, modifier)?,
+                Box::new(body.modify(modifier)?),
+                name,
+            ),
+            Expression::Macro(parameters, body) => Expression::Macro(
+                modify_parameters(parameters, modifier)?,
+                Box::new(body.modify(modifier)?),
+            ),
+            Expression::Call(function, args) => {
+                let function = modify_boxed(function, modifier)?;
+                let args = args
+                    .into_iter()
+                    .map(|arg| {
+                        Ok(match arg {
+                            CallArg::Expression(arg) => CallArg::Expression(arg.modify(modifier)?),
+                            CallArg::Spread(arg) => CallArg::Spread(arg.modify(modifier)?),
+                            CallArg::Named(name, arg) => {
+                                CallArg::Named(name, arg.modify(modifier)?)
+                            }
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Expression::Call(function, args)
+            }
+            Expression::Array(elements) => {
+                Expression::Array(modify_expressions(elements, modifier)?)
+            }
+            Expression::StringTemplate(parts) => Expression::StringTemplate(
+                parts
+                    .into_iter()
+                    .map(|part| {
+                        Ok(match part {
+                            TemplatePart::Expression(expression) => {
+                                TemplatePart::Expression(expression.modify(modifier)?)
+                            }
+                            literal => literal,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Index(collection, index) => Expression::Index(
+                modify_boxed(collection, modifier)?,
+                modify_boxed(index, modifier)?,
+            ),
+            Expression::Slice(collection, start, end) => Expression::Slice(
+                modify_boxed(collection, modifier)?,
+                match start {
+                    Some(start) => Some(modify_boxed(start, modifier)?),
+                    None => None,
+                },
+                match end {
+                    Some(end) => Some(modify_boxed(end, modifier)?),
+                    None => None,
+                },
+            ),
+            Expression::Hash(pairs) => Expression::Hash(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| Ok((key.modify(modifier)?, value.modify(modifier)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Range(start, end) => {
+                Expression::Range(modify_boxed(start, modifier)?, modify_boxed(end, modifier)?)
+            }
+            Expression::While(condition, body) => Expression::While(
+                modify_boxed(condition, modifier)?,
+                Box::new(body.modify(modifier)?),
+            ),
+            Expression::Match(value, arms, default) => Expression::Match(
+                modify_boxed(value, modifier)?,
+                arms.into_iter()
+                    .map(|(pattern, block)| {
+                        Ok((pattern.modify(modifier)?, block.modify(modifier)?))
+                    })
+                    .collect::<Result<_, _>>()?,
+                match default {
+                    Some(block) => Some(Box::new(block.modify(modifier)?)),
+                    None => None,
+                },
+            ),
+            expression @ (Expression::Identifier(_)
+            | Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::Null
+            | Expression::String(_)) => expression,
+        };
+        modifier(expression)
+    }
+}
+
+// rewrites the expression in place, keeping its box
+fn modify_boxed<E, F>(
+    mut expression: Box<Expression>,
+    modifier: &mut F,
+) -> Result<Box<Expression>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    *expression = mem::replace(&mut *expression, Expression::Null).modify(modifier)?;
+    Ok(expression)
+}
+
+fn modify_expressions<E, F>(
+    expressions: Vec<Expression>,
+    modifier: &mut F,
+) -> Result<Vec<Expression>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    expressions
+        .into_iter()
+        .map(|expression| expression.modify(modifier))
+        .collect()
+}
+
+fn modify_statements<E, F>(
+    statements: Vec<Statement>,
+    modifier: &mut F,
+) -> Result<Vec<Statement>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    statements
+        .into_iter()
+        .map(|statement| statement.modify(modifier))
+        .collect()
+}
+
+// only the defaults are rewritten, not the parameters' names
+fn modify_parameters<E, F>(
+    parameters: Vec<(Expression, Option<Expression>)>,
+    modifier: &mut F,
+) -> Result<Vec<(Expression, Option<Expression>)>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    parameters
+        .into_iter()
+        .map(|(parameter, default)| {
+            Ok((
+                parameter,
+                match default {
+                    Some(default) => Some(default.modify(modifier)?),
+                    None => None,
+                },
+            ))
+        })
+        .collect()
+}
@@ -1,29 +1,183 @@
 use crate::lexer::token::Token;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum Precedence {
     Lowest = 0,
-    Logical,
+    Conditional,
+    Or,
+    And,
+    Range,
     Equals,
     LessGreater,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Sum,
     Product,
+    Power,
     Prefix,
     Call,
     Index,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    // an infix operator grouping to the right, so `a op b op c` is `a op (b op c)`
+    InfixRight,
+    // written after the expression it applies to, i.e. calls and indexing
+    Postfix,
+    // `a ? b : c`, the only operator taking three operands
+    Ternary,
+}
+
+#[derive(Debug)]
+pub struct Level {
+    pub precedence: Precedence,
+    pub fixity: Fixity,
+    pub tokens: &'static [Token],
+}
+
+// every operator by how tightly it binds, from loosest to tightest; both
+// `get_precedence` and the table from `describe` are built from this
+pub const LEVELS: [Level; 16] = [
+    Level {
+        precedence: Precedence::Conditional,
+        fixity: Fixity::Ternary,
+        tokens: &[Token::Question],
+    },
+    Level {
+        precedence: Precedence::Or,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Or],
+    },
+    Level {
+        precedence: Precedence::And,
+        fixity: Fixity::Infix,
+        tokens: &[Token::And],
+    },
+    Level {
+        precedence: Precedence::Range,
+        fixity: Fixity::Infix,
+        tokens: &[Token::DotDot],
+    },
+    Level {
+        precedence: Precedence::Equals,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Eq, Token::Noteq],
+    },
+    Level {
+        precedence: Precedence::LessGreater,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Lt, Token::Gt, Token::LtEq, Token::GtEq],
+    },
+    Level {
+        precedence: Precedence::BitOr,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Pipe],
+    },
+    Level {
+        precedence: Precedence::BitXor,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Caret],
+    },
+    Level {
+        precedence: Precedence::BitAnd,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Ampersand],
+    },
+    Level {
+        precedence: Precedence::Shift,
+        fixity: Fixity::Infix,
+        tokens: &[Token::ShiftLeft, Token::ShiftRight],
+    },
+    Level {
+        precedence: Precedence::Sum,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Plus, Token::Minus],
+    },
+    Level {
+        precedence: Precedence::Product,
+        fixity: Fixity::Infix,
+        tokens: &[Token::Asterisk, Token::Slash],
+    },
+    Level {
+        precedence: Precedence::Power,
+        fixity: Fixity::InfixRight,
+        tokens: &[Token::Power],
+    },
+    Level {
+        precedence: Precedence::Prefix,
+        fixity: Fixity::Prefix,
+        tokens: &[Token::Bang, Token::Minus],
+    },
+    Level {
+        precedence: Precedence::Call,
+        fixity: Fixity::Postfix,
+        tokens: &[Token::Lparen],
+    },
+    Level {
+        precedence: Precedence::Index,
+        fixity: Fixity::Postfix,
+        tokens: &[Token::Lbracket],
+    },
+];
+
 impl Precedence {
+    // the precedence of a token following an expression, so prefix operators
+    // are not considered
     pub fn get_precedence(token: &Token) -> Precedence {
-        match token {
-            Token::And | Token::Or => Precedence::Logical,
-            Token::Eq | Token::Noteq => Precedence::Equals,
-            Token::Lt | Token::Gt => Precedence::LessGreater,
-            Token::Plus | Token::Minus => Precedence::Sum,
-            Token::Asterisk | Token::Slash => Precedence::Product,
-            Token::Lparen => Precedence::Call,
-            Token::Lbracket => Precedence::Index,
-            _ => Precedence::Lowest,
+        LEVELS
+            .iter()
+            .find(|level| level.fixity != Fixity::Prefix && level.tokens.contains(token))
+            .map_or(Precedence::Lowest, |level| level.precedence)
+    }
+
+    // the precedence the right operand of an infix operator is parsed with;
+    // one level lower for right-associative operators, so that the operand
+    // takes in further operators of their own level
+    pub fn get_right_precedence(token: &Token) -> Precedence {
+        let position = LEVELS
+            .iter()
+            .position(|level| level.fixity != Fixity::Prefix && level.tokens.contains(token));
+        match position {
+            Some(0) | None => Precedence::Lowest,
+            Some(i) if LEVELS[i].fixity == Fixity::InfixRight => LEVELS[i - 1].precedence,
+            Some(i) => LEVELS[i].precedence,
         }
     }
+
+    /// A Markdown table of every operator, from loosest to tightest binding.
+    pub fn describe() -> String {
+        let mut table = String::from(
+            "| Level | Name | Operators | Position | Associativity |\n\
+             | ----- | ---- | --------- | -------- | ------------- |\n",
+        );
+
+        for level in LEVELS.iter() {
+            let operators = level
+                .tokens
+                .iter()
+                .map(|token| format!("`{}`", token.to_string().replace('|', "\\|")))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let (position, associativity) = match level.fixity {
+                Fixity::Prefix => ("prefix", "right"),
+                Fixity::Infix => ("infix", "left"),
+                Fixity::InfixRight => ("infix", "right"),
+                Fixity::Postfix => ("postfix", "left"),
+                Fixity::Ternary => ("ternary", "right"),
+            };
+
+            table.push_str(&format!(
+                "| {} | {:?} | {operators} | {position} | {associativity} |\n",
+                level.precedence as u8, level.precedence,
+            ));
+        }
+
+        table
+    }
 }
@@ -6,7 +6,8 @@ use crate::{
     parser::ast::{Expression, Statement},
 };
 
-use super::ast::{Infix, Prefix, Program};
+use super::ast::{CallArg, Infix, Prefix, Program, TemplatePart};
+use super::precedence::{Fixity, Level, Precedence, LEVELS};
 
 #[test]
 fn test_let_statement() {
@@ -19,6 +20,48 @@ fn test_let_statement() {
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_let_statement_followed_by_comment() {
+    let input = "let x = 5; // hi";
+    let expected = Program(vec![Statement::Let(
+        Expression::Identifier(String::from("x")),
+        Expression::Integer(5),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_program_of_only_comments() {
+    let input = "// one
+// two, with punctuation!
+//";
+    let expected = Program(vec![]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_unterminated_block_comment_parse_error() {
+    let tests = [
+        "let x = 5; /* never closed",
+        "let x = 5 /* never closed",
+        "f(1, /* never closed",
+        "/* never closed *",
+    ];
+    for input in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors, [ParsingError::UnterminatedComment], "{input}");
+    }
+
+    let errors = Parser::parse_expression_from_str("1 + 2 /* never closed").unwrap_err();
+    assert_eq!(errors, [ParsingError::UnterminatedComment]);
+    assert_eq!(
+        ParsingError::UnterminatedComment.to_string(),
+        "Missing '*/' to end the comment started by '/*'"
+    );
+}
+
 #[test]
 fn test_let_parse_error_if_no_identifier() {
     let input = "let = 5;";
@@ -36,11 +79,29 @@ fn test_let_parse_error_if_no_assign() {
 }
 
 #[test]
-fn test_let_statement_parse_error_if_no_semicolon() {
+fn test_let_statement_without_semicolon_at_end_of_input() {
     let input = "let x = 5";
-    let expected_errors = vec![ParsingError::UnexpectedEof];
-    let errors = Parser::parse_program(input).err().unwrap();
-    assert_eq!(errors, expected_errors);
+    let expected = Program(vec![Statement::Let(
+        Expression::Identifier(String::from("x")),
+        Expression::Integer(5),
+    )]);
+    assert_eq!(Parser::parse_program(input).unwrap(), expected);
+}
+
+#[test]
+fn test_let_statement_parse_error_if_no_semicolon_between_statements() {
+    let tests = [
+        ("let x = 5 let y = 6", Token::Let),
+        ("let x = 5 y", Token::Identifier(String::from("y"))),
+        ("return 5 6", Token::Int(String::from("6"))),
+        ("const x = 5 x", Token::Identifier(String::from("x"))),
+        ("let [x] = [5] x", Token::Identifier(String::from("x"))),
+        ("x = 5 x", Token::Identifier(String::from("x"))),
+    ];
+    for (input, token) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors, [ParsingError::UnexpectedToken(token)], "{input}");
+    }
 }
 
 #[test]
@@ -78,11 +139,20 @@ fn test_return_statement() {
 }
 
 #[test]
-fn test_return_statement_parse_error_if_no_expression() {
-    let input = "return ;";
-    let expected_errors = vec![ParsingError::UnexpectedSemicolon];
-    let errors = Parser::parse_program(input).err().unwrap();
-    assert_eq!(errors, expected_errors);
+fn test_bare_return_statement() {
+    let tests = ["return;", "return ;", "return", "fn() { return }"];
+    for input in tests {
+        let program = Parser::parse_program(input).ok().unwrap();
+        let statement = match &program.0[0] {
+            Statement::Expression(Expression::Function(_, body, _)) => match &**body {
+                Statement::Block(statements) => &statements[0],
+                _ => unreachable!(),
+            },
+            statement => statement,
+        };
+        assert_eq!(*statement, Statement::Return(Expression::Null), "{input}");
+        assert_eq!(statement.to_string(), "return;", "{input}");
+    }
 }
 
 #[test]
@@ -145,6 +215,30 @@ fn test_integer_expression_statement() {
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_float_expression_statement() {
+    let input = "2.5;";
+    let expected = Program(vec![Statement::Expression(Expression::Float(2.5))]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_float_parse_error_if_no_digits_after_point() {
+    let input = "1.;";
+    let expected_errors = vec![ParsingError::InvalidFloat(String::from("1."))];
+    let errors = Parser::parse_program(input).err().unwrap();
+    assert_eq!(errors, expected_errors);
+}
+
+#[test]
+fn test_float_parse_error_if_no_digits_before_point() {
+    let input = ".5;";
+    let expected_errors = vec![ParsingError::InvalidFloat(String::from(".5"))];
+    let errors = Parser::parse_program(input).err().unwrap();
+    assert_eq!(errors, expected_errors);
+}
+
 #[test]
 fn test_parsing_bang_prefix_expressions() {
     let input = "let x = !5;";
@@ -552,6 +646,30 @@ fn test_operator_precedence_thirteen() {
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_operator_precedence_less_equal_and_greater_equal() {
+    let input = "1 + 2 <= 3 == 4 >= 5"; // (((1 + 2) <= 3) == (4 >= 5))
+    let expected = Program(vec![Statement::Expression(Expression::Infix(
+        Box::new(Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(1)),
+                Infix::Plus,
+                Box::new(Expression::Integer(2)),
+            )),
+            Infix::LessEqual,
+            Box::new(Expression::Integer(3)),
+        )),
+        Infix::Equal,
+        Box::new(Expression::Infix(
+            Box::new(Expression::Integer(4)),
+            Infix::GreaterEqual,
+            Box::new(Expression::Integer(5)),
+        )),
+    ))]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
 #[test]
 fn test_precedence_promotion_with_parentheses_one() {
     let input = "(5 + 5) * 2;";
@@ -607,7 +725,7 @@ fn test_if_expression() {
             Infix::LessThan,
             Box::new(Expression::Identifier(String::from("y"))),
         )),
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
+        Box::new(Statement::Block(vec![Statement::Expression(
             Expression::Identifier(String::from("x")),
         )])),
         None,
@@ -625,12 +743,12 @@ fn test_if_expression_with_else() {
             Infix::LessThan,
             Box::new(Expression::Identifier(String::from("y"))),
         )),
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
+        Box::new(Statement::Block(vec![Statement::Expression(
             Expression::Identifier(String::from("x")),
         )])),
-        Some(Box::new(Statement::BlockStatement(vec![
-            Statement::Expression(Expression::Identifier(String::from("y"))),
-        ]))),
+        Some(Box::new(Statement::Block(vec![Statement::Expression(
+            Expression::Identifier(String::from("y")),
+        )]))),
     ))]);
     let program = Parser::parse_program(input).ok().unwrap();
     assert_eq!(program, expected);
@@ -649,10 +767,10 @@ fn test_function_literal() {
     let input = "fn(x, y) { x + y; };";
     let expected = Program(vec![Statement::Expression(Expression::Function(
         vec![
-            Expression::Identifier(String::from("x")),
-            Expression::Identifier(String::from("y")),
+            (Expression::Identifier(String::from("x")), None),
+            (Expression::Identifier(String::from("y")), None),
         ],
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
+        Box::new(Statement::Block(vec![Statement::Expression(
             Expression::Infix(
                 Box::new(Expression::Identifier(String::from("x"))),
                 Infix::Plus,
@@ -670,7 +788,7 @@ fn test_function_literal_no_parameters() {
     let input = "fn() { 1 };";
     let expected = Program(vec![Statement::Expression(Expression::Function(
         vec![],
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
+        Box::new(Statement::Block(vec![Statement::Expression(
             Expression::Integer(1),
         )])),
         String::new(),
@@ -689,8 +807,8 @@ fn test_function_literal_error_if_missing_brace() {
 
 #[test]
 fn test_function_literal_error_if_misplaced_comma() {
-    let input = "fn(x, y,) { x + y }";
-    let expected_errors = vec![ParsingError::UnexpectedToken(Token::Rparen)];
+    let input = "fn(x, y,,) { x + y }";
+    let expected_errors = vec![ParsingError::UnexpectedToken(Token::Comma)];
     let errors = Parser::parse_program(input).err().unwrap();
     assert_eq!(errors, expected_errors);
 }
@@ -704,7 +822,7 @@ fn test_block_statement_with_multiple_statements() {
             Infix::LessThan,
             Box::new(Expression::Identifier(String::from("y"))),
         )),
-        Box::new(Statement::BlockStatement(vec![
+        Box::new(Statement::Block(vec![
             Statement::Expression(Expression::Identifier(String::from("x"))),
             Statement::Expression(Expression::Infix(
                 Box::new(Expression::Identifier(String::from("x"))),
@@ -724,7 +842,10 @@ fn test_call_expression_basic() {
     let input = "add(2, 3);";
     let expected = Program(vec![Statement::Expression(Expression::Call(
         Box::new(Expression::Identifier(String::from("add"))),
-        vec![Expression::Integer(2), Expression::Integer(3)],
+        vec![
+            CallArg::Expression(Expression::Integer(2)),
+            CallArg::Expression(Expression::Integer(3)),
+        ],
     ))]);
     let program = Parser::parse_program(input).ok().unwrap();
     assert_eq!(program, expected);
@@ -746,7 +867,7 @@ fn test_call_expression_one_arguments() {
     let input = "add(1);";
     let expected = Program(vec![Statement::Expression(Expression::Call(
         Box::new(Expression::Identifier(String::from("add"))),
-        vec![Expression::Integer(1)],
+        vec![CallArg::Expression(Expression::Integer(1))],
     ))]);
     let program = Parser::parse_program(input).ok().unwrap();
     assert_eq!(program, expected);
@@ -758,10 +879,10 @@ fn test_call_expression_inlined_function() {
     let expected = Program(vec![Statement::Expression(Expression::Call(
         Box::new(Expression::Function(
             vec![
-                Expression::Identifier(String::from("x")),
-                Expression::Identifier(String::from("y")),
+                (Expression::Identifier(String::from("x")), None),
+                (Expression::Identifier(String::from("y")), None),
             ],
-            Box::new(Statement::BlockStatement(vec![Statement::Expression(
+            Box::new(Statement::Block(vec![Statement::Expression(
                 Expression::Infix(
                     Box::new(Expression::Identifier(String::from("x"))),
                     Infix::Plus,
@@ -770,7 +891,10 @@ fn test_call_expression_inlined_function() {
             )])),
             String::new(),
         )),
-        vec![Expression::Integer(2), Expression::Integer(3)],
+        vec![
+            CallArg::Expression(Expression::Integer(2)),
+            CallArg::Expression(Expression::Integer(3)),
+        ],
     ))]);
     let program = Parser::parse_program(input).ok().unwrap();
     assert_eq!(program, expected);
@@ -786,8 +910,8 @@ fn test_call_expression_error_if_missing_paren() {
 
 #[test]
 fn test_call_expression_error_if_extra_comma() {
-    let input = "add(2, 3,)";
-    let expected_errors = vec![ParsingError::InvalidPrefixOperator(Token::Rparen)];
+    let input = "add(2, 3,,)";
+    let expected_errors = vec![ParsingError::InvalidPrefixOperator(Token::Comma)];
     let errors = Parser::parse_program(input).err().unwrap();
     assert_eq!(errors, expected_errors);
 }
@@ -802,6 +926,20 @@ fn test_string_expression() {
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_string_literal_is_not_an_identifier() {
+    let input = r#"let x = "x"; x"#;
+    let expected = Program(vec![
+        Statement::Let(
+            Expression::Identifier(String::from("x")),
+            Expression::String(String::from("x")),
+        ),
+        Statement::Expression(Expression::Identifier(String::from("x"))),
+    ]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
 #[test]
 fn test_array_expression() {
     let input = "[1, 2, 3 * 4, 1 + 1]";
@@ -831,11 +969,22 @@ fn test_array_expression_empty() {
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_array_expression_trailing_comma() {
+    let input = "[1, 2,]";
+    let expected = Program(vec![Statement::Expression(Expression::Array(vec![
+        Expression::Integer(1),
+        Expression::Integer(2),
+    ]))]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
 #[test]
 fn test_array_expression_errors() {
-    let input = "[1, 2, 3,]; [1, 2, 3";
+    let input = "[1, 2, 3,,]; [1, 2, 3";
     let expected_errors = vec![
-        ParsingError::InvalidPrefixOperator(Token::Rbracket),
+        ParsingError::InvalidPrefixOperator(Token::Comma),
         ParsingError::UnexpectedEof,
     ];
     let errors = Parser::parse_program(input).err().unwrap();
@@ -889,6 +1038,40 @@ fn test_array_index_operator_precedence() {
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_array_literal_index_operator_precedence() {
+    let input = "a * [1, 2][0]"; // (a * ([1, 2][0]))
+    let expected = Program(vec![Statement::Expression(Expression::Infix(
+        Box::new(Expression::Identifier(String::from("a"))),
+        Infix::Multiply,
+        Box::new(Expression::Index(
+            Box::new(Expression::Array(vec![
+                Expression::Integer(1),
+                Expression::Integer(2),
+            ])),
+            Box::new(Expression::Integer(0)),
+        )),
+    ))]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_index_binds_tighter_than_call() {
+    let input = "f(x)[0]"; // ((f(x))[0])
+    let expected = Program(vec![Statement::Expression(Expression::Index(
+        Box::new(Expression::Call(
+            Box::new(Expression::Identifier(String::from("f"))),
+            vec![CallArg::Expression(Expression::Identifier(String::from(
+                "x",
+            )))],
+        )),
+        Box::new(Expression::Integer(0)),
+    ))]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
 #[test]
 fn test_index_expression_errors() {
     let input = "myArray[1; myArray[1, 2]";
@@ -900,6 +1083,53 @@ fn test_index_expression_errors() {
     assert_eq!(errors, expected_errors);
 }
 
+#[test]
+fn test_slice_expression() {
+    let arr = || Box::new(Expression::Identifier(String::from("arr")));
+    let int = |int| Some(Box::new(Expression::Integer(int)));
+    let tests = [
+        ("arr[1:3]", Expression::Slice(arr(), int(1), int(3))),
+        ("arr[:2]", Expression::Slice(arr(), None, int(2))),
+        ("arr[2:]", Expression::Slice(arr(), int(2), None)),
+        ("arr[:]", Expression::Slice(arr(), None, None)),
+        (
+            "arr[-1:][0]",
+            Expression::Index(
+                Box::new(Expression::Slice(
+                    arr(),
+                    Some(Box::new(Expression::Prefix(
+                        Prefix::Minus,
+                        Box::new(Expression::Integer(1)),
+                    ))),
+                    None,
+                )),
+                Box::new(Expression::Integer(0)),
+            ),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(
+            program,
+            Program(vec![Statement::Expression(expected)]),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_slice_expression_errors() {
+    let tests = [
+        ("arr[1:2:3]", ParsingError::UnexpectedToken(Token::Colon)),
+        ("arr[1:2", ParsingError::UnexpectedEof),
+        ("arr[:", ParsingError::UnexpectedEof),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
 #[test]
 fn test_hash_literal() {
     let input = "{\"one\": 1, \"two\": 2, \"three\": 3}";
@@ -1064,7 +1294,7 @@ fn test_while_expression_parses() {
             Infix::LessThan,
             Box::new(Expression::Integer(10)),
         )),
-        Box::new(Statement::BlockStatement(vec![Statement::Let(
+        Box::new(Statement::Block(vec![Statement::Let(
             Expression::Identifier(String::from("i")),
             Expression::Infix(
                 Box::new(Expression::Identifier(String::from("i"))),
@@ -1077,6 +1307,92 @@ fn test_while_expression_parses() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_for_statement_parses() {
+    let input = "for (x in [1, 2]) { puts(x); }";
+    let expected = Program(vec![Statement::For(
+        Expression::Identifier(String::from("x")),
+        Expression::Array(vec![Expression::Integer(1), Expression::Integer(2)]),
+        Box::new(Statement::Block(vec![Statement::Expression(
+            Expression::Call(
+                Box::new(Expression::Identifier(String::from("puts"))),
+                vec![CallArg::Expression(Expression::Identifier(String::from(
+                    "x",
+                )))],
+            ),
+        )])),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_for_statement_parse_error_if_no_in() {
+    let input = "for (x xs) { x }";
+    let expected_errors = vec![
+        ParsingError::UnexpectedToken(Token::Identifier(String::from("xs"))),
+        ParsingError::InvalidPrefixOperator(Token::Rparen),
+    ];
+    let errors = Parser::parse_program(input).err().unwrap();
+    assert_eq!(errors, expected_errors);
+}
+
+#[test]
+fn test_for_statement_parse_error_if_variable_not_identifier() {
+    let input = "for (1 in xs) { 1 }";
+    let expected_errors = vec![
+        ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ParsingError::InvalidPrefixOperator(Token::In),
+    ];
+    let errors = Parser::parse_program(input).err().unwrap();
+    assert_eq!(errors, expected_errors);
+}
+
+#[test]
+fn test_break_and_continue_parse() {
+    let input = "while (true) { continue; break }";
+    let expected = Program(vec![Statement::Expression(Expression::While(
+        Box::new(Expression::Boolean(true)),
+        Box::new(Statement::Block(vec![
+            Statement::Continue,
+            Statement::Break,
+        ])),
+    ))]);
+    let result = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_break_and_continue_parse_error_if_outside_loop() {
+    let tests = vec![
+        ("break;", ParsingError::OutsideLoop(Token::Break)),
+        (
+            "if (true) { continue; }",
+            ParsingError::OutsideLoop(Token::Continue),
+        ),
+        (
+            "for (x in xs) { let f = fn() { break; }; }",
+            ParsingError::OutsideLoop(Token::Break),
+        ),
+    ];
+
+    for (input, expected_error) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected_error);
+    }
+}
+
+#[test]
+fn test_break_parse_error_if_followed_by_expression() {
+    let input = "while (true) { break 1; }";
+    let expected_errors = vec![
+        ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ParsingError::InvalidPrefixOperator(Token::Rbrace),
+    ];
+    let errors = Parser::parse_program(input).err().unwrap();
+    assert_eq!(errors, expected_errors);
+}
+
 #[test]
 fn test_assignment_expression_parses() {
     let input = "i = i + 1;";
@@ -1091,3 +1407,1071 @@ fn test_assignment_expression_parses() {
     let result = Parser::parse_program(input).ok().unwrap();
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_compound_assignment_desugars() {
+    let tests = vec![
+        ("x += 1 + 2;", Infix::Plus),
+        ("x -= 1 + 2;", Infix::Minus),
+        ("x *= 1 + 2;", Infix::Multiply),
+        ("x /= 1 + 2;", Infix::Divide),
+    ];
+
+    for (input, operator) in tests {
+        let expected = Program(vec![Statement::Assignment(
+            Expression::Identifier(String::from("x")),
+            Expression::Infix(
+                Box::new(Expression::Identifier(String::from("x"))),
+                operator,
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(1)),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(2)),
+                )),
+            ),
+        )]);
+        let result = Parser::parse_program(input).ok().unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_compound_assignment_parse_error_if_not_identifier() {
+    let input = "1 += 2;";
+    let expected_errors = vec![ParsingError::UnexpectedToken(Token::Int(String::from("1")))];
+    let errors = Parser::parse_program(input).err().unwrap();
+    assert_eq!(errors, expected_errors);
+}
+
+// writes an expression using the first operator of `level`; `left` is ignored
+// for prefix operators
+fn build_expression(level: &Level, left: &str, right: &str) -> String {
+    let operator = &level.tokens[0];
+    match (level.fixity, operator) {
+        (Fixity::Prefix, _) => format!("{operator}{right}"),
+        (Fixity::Infix | Fixity::InfixRight, _) => format!("{left} {operator} {right}"),
+        (Fixity::Postfix, Token::Lparen) => format!("{left}({right})"),
+        (Fixity::Postfix, _) => format!("{left}[{right}]"),
+        (Fixity::Ternary, _) => format!("{left} ? {right} : {right}"),
+    }
+}
+
+fn assert_same_parse(input: &str, parenthesised: &str) {
+    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Parser::parse_program(parenthesised).ok().unwrap();
+    assert_eq!(
+        program, expected,
+        "`{input}` should parse as `{parenthesised}`"
+    );
+}
+
+#[test]
+fn test_precedence_of_adjacent_levels() {
+    for pair in LEVELS.windows(2) {
+        let (lower, higher) = (&pair[0], &pair[1]);
+
+        let inner = build_expression(higher, "b", "c");
+        assert_same_parse(
+            &build_expression(lower, "a", &inner),
+            &build_expression(lower, "a", &format!("({inner})")),
+        );
+
+        if lower.fixity != Fixity::Prefix {
+            let inner = build_expression(higher, "a", "b");
+            assert_same_parse(
+                &build_expression(lower, &inner, "c"),
+                &build_expression(lower, &format!("({inner})"), "c"),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_infix_operators_are_left_associative() {
+    for level in LEVELS.iter().filter(|level| level.fixity == Fixity::Infix) {
+        for operator in level.tokens {
+            assert_same_parse(
+                &format!("a {operator} b {operator} c"),
+                &format!("(a {operator} b) {operator} c"),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_right_associative_infix_operators() {
+    for level in LEVELS
+        .iter()
+        .filter(|level| level.fixity == Fixity::InfixRight)
+    {
+        for operator in level.tokens {
+            assert_same_parse(
+                &format!("a {operator} b {operator} c"),
+                &format!("a {operator} (b {operator} c)"),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_power_precedence() {
+    assert_same_parse("2 * 3 ** 2", "2 * (3 ** 2)");
+    assert_same_parse("2 ** 3 * 2", "(2 ** 3) * 2");
+    assert_same_parse("-2 ** 2", "(-2) ** 2");
+    assert_same_parse("2 ** -1", "2 ** (-1)");
+    assert_same_parse("a[0] ** f(1)", "(a[0]) ** (f(1))");
+}
+
+#[test]
+fn test_and_binds_tighter_than_or() {
+    assert_same_parse("a || b && c", "a || (b && c)");
+    assert_same_parse("a && b || c", "(a && b) || c");
+    assert_same_parse("a || b || c", "(a || b) || c");
+}
+
+#[test]
+fn test_precedence_table_matches_docs() {
+    // regenerate with `cargo run -- explain-precedence > docs/precedence.md`
+    let docs = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/docs/precedence.md"));
+    assert_eq!(Precedence::describe(), docs);
+}
+
+#[test]
+fn test_conditional_is_sugar_for_if_else() {
+    assert_same_parse(
+        "let x = y > 1 ? y * 2 : [y][0];",
+        "let x = if (y > 1) { y * 2 } else { [y][0] };",
+    );
+}
+
+#[test]
+fn test_conditional_is_right_associative() {
+    assert_same_parse("a ? b : c ? d : e", "a ? b : (c ? d : e)");
+    assert_same_parse("a ? b ? c : d : e", "a ? (b ? c : d) : e");
+}
+
+#[test]
+fn test_conditional_in_hash_and_call() {
+    assert_same_parse(
+        "f(a ? 1 : 2, {a ? \"x\" : \"y\": b})",
+        "f(if (a) { 1 } else { 2 }, {if (a) { \"x\" } else { \"y\" }: b})",
+    );
+}
+
+#[test]
+fn test_conditional_parse_error_if_colon_missing() {
+    for input in ["a ? b;", "a ? b", "a ? b c"] {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], ParsingError::MissingColon, "for `{input}`");
+    }
+}
+
+#[test]
+fn test_bitwise_operator_precedence() {
+    assert_same_parse("1 << 2 + 3", "1 << (2 + 3)");
+    assert_same_parse("a | b ^ c & d << e", "a | (b ^ (c & (d << e)))");
+    assert_same_parse("x & 1 == 1", "(x & 1) == 1");
+    assert_same_parse("a < b | c", "a < (b | c)");
+}
+
+#[test]
+fn test_null_literal() {
+    let input = "let x = null; x == null";
+    let expected = Program(vec![
+        Statement::Let(Expression::Identifier(String::from("x")), Expression::Null),
+        Statement::Expression(Expression::Infix(
+            Box::new(Expression::Identifier(String::from("x"))),
+            Infix::Equal,
+            Box::new(Expression::Null),
+        )),
+    ]);
+    let program = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_radix_integer_literals() {
+    let tests = [
+        ("0xFF", 255),
+        ("0xff", 255),
+        ("0o755", 493),
+        ("0b1010", 10),
+        ("0x7FFFFFFFFFFFFFFF", i64::MAX),
+        ("0o777777777777777777777", i64::MAX),
+        ("9223372036854775807", i64::MAX),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).ok().unwrap();
+        assert_eq!(
+            program,
+            Program(vec![Statement::Expression(Expression::Integer(expected))]),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_radix_integer_parse_error_if_invalid() {
+    for input in [
+        "0b102",
+        "0o8",
+        "0xFG",
+        "0x",
+        "0x8000000000000000",
+        "9223372036854775808",
+    ] {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(
+            errors,
+            vec![ParsingError::InvalidInteger(input.to_string())]
+        );
+    }
+    assert_eq!(
+        ParsingError::InvalidInteger(String::from("0b102")).to_string(),
+        "Cannot parse '0b102' as a valid integer"
+    );
+}
+
+#[test]
+fn test_integer_digit_separators() {
+    let tests = [
+        ("1_000_000_000", 1_000_000_000),
+        ("1_2_3", 123),
+        ("0xFF_FF", 0xFFFF),
+        ("0b1010_1010", 0b1010_1010),
+        ("0xFF_00", 65280),
+        ("0b1111_0000", 240),
+        ("0o7_7", 0o77),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).ok().unwrap();
+        assert_eq!(
+            program,
+            Program(vec![Statement::Expression(Expression::Integer(expected))]),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_integer_digit_separators_parse_error_if_misplaced() {
+    for input in ["_100", "100_", "1__000", "0x_FF", "0b1_"] {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(
+            errors,
+            vec![ParsingError::InvalidInteger(input.to_string())]
+        );
+    }
+}
+
+#[test]
+fn test_string_parse_error_if_unknown_escape() {
+    let errors = Parser::parse_program(r#"let s = "a\qb";"#).err().unwrap();
+    assert_eq!(
+        errors,
+        vec![ParsingError::InvalidEscape(String::from(r"\q"))]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        r"Unknown escape sequence '\q' in string"
+    );
+}
+
+#[test]
+fn test_parse_expression_from_str() {
+    let tests = [
+        ("1 + 2 * 3", "(1 + (2 * 3))"),
+        ("add(x, [1, 2][0]);", "add(x, [1, 2][0])"),
+        ("fn(a) { a }(1);;", "fn(a) { a }(1)"),
+        ("cond ? a : b", "if (cond) { a } else { b }"),
+    ];
+    for (input, parenthesised) in tests {
+        let expected = match Parser::parse_program(parenthesised).unwrap().0.remove(0) {
+            Statement::Expression(expression) => expression,
+            statement => panic!("expected an expression statement, got {statement:?}"),
+        };
+        let expression = Parser::parse_expression_from_str(input).unwrap();
+        assert_eq!(expression, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_parse_expression_from_str_errors() {
+    let tests = [
+        ("", ParsingError::UnexpectedEof),
+        (";", ParsingError::UnexpectedSemicolon),
+        (
+            "1 2",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+        (
+            "1; 2",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+        ("let x = 1", ParsingError::InvalidPrefixOperator(Token::Let)),
+        ("1 +", ParsingError::UnexpectedEof),
+    ];
+    for (input, expected_error) in tests {
+        let errors = Parser::parse_expression_from_str(input).err().unwrap();
+        assert_eq!(errors, vec![expected_error], "{input}");
+    }
+}
+
+#[test]
+fn test_index_assignment() {
+    let index = |collection: Expression, i: i64| {
+        Expression::Index(Box::new(collection), Box::new(Expression::Integer(i)))
+    };
+    let a = || Expression::Identifier(String::from("a"));
+    let tests = [
+        (
+            "a[0] = 1;",
+            Statement::IndexAssignment(a(), Expression::Integer(0), Expression::Integer(1)),
+        ),
+        (
+            "a[0][1] = 2;",
+            Statement::IndexAssignment(
+                index(a(), 0),
+                Expression::Integer(1),
+                Expression::Integer(2),
+            ),
+        ),
+        (
+            "a[0] += 3;",
+            Statement::IndexAssignment(
+                a(),
+                Expression::Integer(0),
+                Expression::Infix(
+                    Box::new(index(a(), 0)),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(3)),
+                ),
+            ),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(program, Program(vec![expected]), "{input}");
+    }
+}
+
+#[test]
+fn test_index_assignment_parse_error_if_no_semicolon() {
+    let errors = Parser::parse_program("a[0] = 1 2").err().unwrap();
+    assert_eq!(
+        errors[0],
+        ParsingError::UnexpectedToken(Token::Int(String::from("2")))
+    );
+}
+
+#[test]
+fn test_import() {
+    let program = Parser::parse_program(r#"import "lib/math.mk"; 1;"#).unwrap();
+    let expected = Program(vec![
+        Statement::Use(String::from("lib/math.mk")),
+        Statement::Expression(Expression::Integer(1)),
+    ]);
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_import_without_semicolon() {
+    let use_statement = || Statement::Use(String::from("a.mk"));
+    let tests = [
+        (r#"import "a.mk""#, vec![use_statement()]),
+        (
+            r#"if (true) { import "a.mk" }"#,
+            vec![Statement::Expression(Expression::If(
+                Box::new(Expression::Boolean(true)),
+                Box::new(Statement::Block(vec![use_statement()])),
+                None,
+            ))],
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(program, Program(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_import_errors() {
+    let tests = [
+        (
+            "import math;",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("math"))),
+        ),
+        (
+            r#"import "a.mk" "b.mk";"#,
+            ParsingError::UnexpectedToken(Token::String(String::from("b.mk"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_export() {
+    let input = "export let x = 1; export x;";
+    let expected = Program(vec![
+        Statement::Export(Box::new(Statement::Let(
+            Expression::Identifier(String::from("x")),
+            Expression::Integer(1),
+        ))),
+        Statement::Export(Box::new(Statement::Expression(Expression::Identifier(
+            String::from("x"),
+        )))),
+    ]);
+    assert_eq!(Parser::parse_program(input).unwrap(), expected);
+}
+
+#[test]
+fn test_export_without_semicolon() {
+    let program = Parser::parse_program("let x = 1; export x").unwrap();
+    assert_eq!(
+        program.0[1],
+        Statement::Export(Box::new(Statement::Expression(Expression::Identifier(
+            String::from("x"),
+        ))))
+    );
+}
+
+#[test]
+fn test_export_errors() {
+    let tests = [
+        (
+            "export 1;",
+            ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ),
+        ("export x + 1;", ParsingError::UnexpectedToken(Token::Plus)),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_program_starting_with_shebang() {
+    let program = Parser::parse_program("#!/bin/sh\n5;").unwrap();
+    assert_eq!(
+        program,
+        Program(vec![Statement::Expression(Expression::Integer(5))])
+    );
+}
+
+#[test]
+fn test_range_expression() {
+    let int = |int| Box::new(Expression::Integer(int));
+    let n = || Box::new(Expression::Identifier(String::from("n")));
+    let tests = [
+        ("1..10", Expression::Range(int(1), int(10))),
+        (
+            "1..n + 1",
+            Expression::Range(
+                int(1),
+                Box::new(Expression::Infix(n(), Infix::Plus, int(1))),
+            ),
+        ),
+        // below comparison, so the comparison is the end of the range
+        (
+            "1..n == 2",
+            Expression::Range(
+                int(1),
+                Box::new(Expression::Infix(n(), Infix::Equal, int(2))),
+            ),
+        ),
+        (
+            "n && 1..2",
+            Expression::Infix(n(), Infix::And, Box::new(Expression::Range(int(1), int(2)))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(
+            program,
+            Program(vec![Statement::Expression(expected)]),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_match_expression() {
+    let input = r#"match (x) { 1 => "one", 2 => { let y = 2; y }, _ => "many" }"#;
+    let block = |statements| Statement::Block(statements);
+    let expected = Expression::Match(
+        Box::new(Expression::Identifier(String::from("x"))),
+        vec![
+            (
+                Expression::Integer(1),
+                block(vec![Statement::Expression(Expression::String(
+                    String::from("one"),
+                ))]),
+            ),
+            (
+                Expression::Integer(2),
+                block(vec![
+                    Statement::Let(
+                        Expression::Identifier(String::from("y")),
+                        Expression::Integer(2),
+                    ),
+                    Statement::Expression(Expression::Identifier(String::from("y"))),
+                ]),
+            ),
+        ],
+        Some(Box::new(block(vec![Statement::Expression(
+            Expression::String(String::from("many")),
+        )]))),
+    );
+    let program = Parser::parse_program(input).unwrap();
+    assert_eq!(program, Program(vec![Statement::Expression(expected)]));
+}
+
+#[test]
+fn test_match_without_default_or_arms() {
+    let tests = [
+        ("match (x) { 1 => 2, }", 1),
+        ("match (x) { 1 => { 2 }, 3 => 4 }", 2),
+        ("match (x) {}", 0),
+    ];
+    for (input, num_arms) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        match &program.0[..] {
+            [Statement::Expression(Expression::Match(_, arms, None))] => {
+                assert_eq!(arms.len(), num_arms, "{input}")
+            }
+            other => panic!("{input}: expected a match without a default, got {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_match_precedence() {
+    assert_same_parse(
+        "let x = match (y) { 1 => 2, _ => 3 };",
+        "let x = (match (y) { 1 => 2, _ => 3 });",
+    );
+    assert_same_parse(
+        "let x = match (y) { 1 => 2 } + 1;",
+        "let x = (match (y) { 1 => 2 }) + 1;",
+    );
+    assert_same_parse(
+        "let x = match (y + 1) { a || b => c ? d : e };",
+        "let x = match ((y + 1)) { (a || b) => (c ? d : e) };",
+    );
+    assert_same_parse(
+        "let x = 2 * match (y) { 1 => 2 }[0];",
+        "let x = 2 * ((match (y) { 1 => 2 })[0]);",
+    );
+}
+
+#[test]
+fn test_match_errors() {
+    let tests = [
+        (
+            "match x { 1 => 2 }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("x"))),
+        ),
+        (
+            "match (x) { 1 2 }",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+        (
+            "match (x) { 1 => 2 3 => 4 }",
+            ParsingError::UnexpectedToken(Token::Int(String::from("3"))),
+        ),
+        (
+            "match (x) { _ => 1, 2 => 3 }",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+        ("match (x) { 1 => 2", ParsingError::UnexpectedEof),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_function_statement_desugars_to_let() {
+    assert_same_parse(
+        "fn add(a, b) { a + b } add(1, 2);",
+        "let add = fn(a, b) { a + b }; add(1, 2);",
+    );
+    assert_same_parse("export fn f() { 1 }", "export let f = fn() { 1 };");
+
+    let program = Parser::parse_program("fn f() { f() }").unwrap();
+    match &program.0[..] {
+        [Statement::Let(_, Expression::Function(_, _, name))] => assert_eq!(name, "f"),
+        other => panic!("expected a let of a named function, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_anonymous_function_in_statement_position() {
+    let program = Parser::parse_program("fn(x) { x }(1);").unwrap();
+    assert!(matches!(
+        &program.0[..],
+        [Statement::Expression(Expression::Call(..))]
+    ));
+}
+
+#[test]
+fn test_function_statement_errors() {
+    let tests = [
+        ("fn f { 1 }", ParsingError::UnexpectedToken(Token::Lbrace)),
+        (
+            "fn f(1) { 1 }",
+            ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ),
+        (
+            "export fn () { 1 }",
+            ParsingError::UnexpectedToken(Token::Lparen),
+        ),
+        ("fn f()", ParsingError::UnexpectedEof),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_function_literal_with_defaults() {
+    let input = r#"fn(name, greeting = "hello", n = 1 + 2) { name }"#;
+    let program = Parser::parse_program(input).unwrap();
+    let [Statement::Expression(Expression::Function(parameters, _, _))] = &program.0[..] else {
+        panic!("expected a function, got {program:?}");
+    };
+    let id = |name: &str| Expression::Identifier(String::from(name));
+    assert_eq!(
+        parameters,
+        &vec![
+            (id("name"), None),
+            (
+                id("greeting"),
+                Some(Expression::String(String::from("hello")))
+            ),
+            (
+                id("n"),
+                Some(Expression::Infix(
+                    Box::new(Expression::Integer(1)),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(2)),
+                )),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_function_literal_default_errors() {
+    let tests = [
+        (
+            "fn(a = 1, b) { a }",
+            ParsingError::MissingDefault(String::from("b")),
+        ),
+        (
+            "fn(a = ) { a }",
+            ParsingError::InvalidPrefixOperator(Token::Rparen),
+        ),
+        (
+            "fn(a = 1 b) { a }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("b"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_display_parses_back_to_same_program() {
+    let inputs = [
+        "let x = 5; return x; x;",
+        "1 + 2 * 3 - -4 / (5 - 6); !true == false; 2 ** 3 ** 2; a && b || c & d;",
+        "if (a < b) { 1 } else { let c = 2; c }; if (x) { } else { };",
+        "let add = fn(a, b = 1, c = [2]) { a + b }; fn fact(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }",
+        "fn(x) { x }(1); add(1, f(2), [3, 4][0]); (a + b)[c]; a[1:]; a[:-1]; a[1:2]; a[:];",
+        r#"{"a": 1, true: [null], 2: {}}; "tab\t quote\" slash\\ newline\n nul\0 cr\r";"#,
+        "1.5; 2.0; 0.1; 1..n + 1; (1..3)[0]; -(1..3);",
+        "while (i < 10) { i += 1; if (i == 5) { break; } continue; }",
+        "for (x in [1, 2]) { puts(x); }",
+        "x = 1; a[0] = 2; a[0][1] += 3; f()[0] = 4;",
+        r#"let x = match (y) { 1 => "one", 2 => { let z = 2; z }, _ => "many" }; match (y) { }"#,
+        r#"import "lib/a \"b\".mk"; export let x = 1; export y; export fn f() { 1 }"#,
+        "a ? b : c ? d : e;",
+        "",
+    ];
+    for input in inputs {
+        let program = Parser::parse_program(input).unwrap();
+        let displayed = program.to_string();
+        let reparsed = Parser::parse_program(&displayed)
+            .unwrap_or_else(|errors| panic!("{input}\ndisplayed as\n{displayed}\n{errors:?}"));
+        assert_eq!(reparsed, program, "{input}\ndisplayed as\n{displayed}");
+    }
+}
+
+#[test]
+fn test_display_statement() {
+    let tests = [
+        ("let x = 1 + 2 * 3;", "let x = 1 + (2 * 3);"),
+        ("return -x;", "return -x;"),
+        ("f(a)[0]", "f(a)[0]"),
+        (
+            "let f = fn(a, b = 2) { let c = a; if (c) { c } };",
+            "let f = fn(a, b = 2) {\n    let c = a;\n    if (c) {\n        c;\n    };\n};",
+        ),
+        ("while (x) {}", "while (x) {}"),
+        (
+            "match (x) { 1 => a, _ => b }",
+            "match (x) {\n    1 => {\n        a;\n    },\n    _ => {\n        b;\n    }\n}",
+        ),
+        ("for (x in xs) { break; }", "for (x in xs) {\n    break;\n}"),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(program.0[0].to_string(), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_call_expression_with_spread() {
+    let input = "f(1, ...xs, ...[2] + ys)";
+    let id = |name: &str| Expression::Identifier(String::from(name));
+    let expected = Program(vec![Statement::Expression(Expression::Call(
+        Box::new(id("f")),
+        vec![
+            CallArg::Expression(Expression::Integer(1)),
+            CallArg::Spread(id("xs")),
+            CallArg::Spread(Expression::Infix(
+                Box::new(Expression::Array(vec![Expression::Integer(2)])),
+                Infix::Plus,
+                Box::new(id("ys")),
+            )),
+        ],
+    ))]);
+    let program = Parser::parse_program(input).unwrap();
+    assert_eq!(program, expected);
+
+    let tests = [
+        ("f(...)", ParsingError::InvalidPrefixOperator(Token::Rparen)),
+        (
+            "[...xs]",
+            ParsingError::InvalidPrefixOperator(Token::Ellipsis),
+        ),
+        ("f(x...)", ParsingError::UnexpectedToken(Token::Ellipsis)),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_call_expression_with_named_arguments() {
+    let input = "f(1, ...xs, b: 2, c: x + 1)";
+    let id = |name: &str| Expression::Identifier(String::from(name));
+    let expected = Program(vec![Statement::Expression(Expression::Call(
+        Box::new(id("f")),
+        vec![
+            CallArg::Expression(Expression::Integer(1)),
+            CallArg::Spread(id("xs")),
+            CallArg::Named(String::from("b"), Expression::Integer(2)),
+            CallArg::Named(
+                String::from("c"),
+                Expression::Infix(
+                    Box::new(id("x")),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(1)),
+                ),
+            ),
+        ],
+    ))]);
+    let program = Parser::parse_program(input).unwrap();
+    assert_eq!(program, expected);
+    assert_eq!(program.0[0].to_string(), "f(1, ...xs, b: 2, c: x + 1)");
+
+    let tests = [
+        ("f(a: 1, 2)", ParsingError::PositionalAfterNamed),
+        ("f(a: 1, ...xs)", ParsingError::PositionalAfterNamed),
+        ("f(a:)", ParsingError::InvalidPrefixOperator(Token::Rparen)),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
+
+#[test]
+fn test_macro_literal() {
+    let program = Parser::parse_program("macro(x, y = 1) { x + y; }").unwrap();
+    let id = |name: &str| Expression::Identifier(String::from(name));
+    let expected = Program(vec![Statement::Expression(Expression::Macro(
+        vec![(id("x"), None), (id("y"), Some(Expression::Integer(1)))],
+        Box::new(Statement::Block(vec![Statement::Expression(
+            Expression::Infix(Box::new(id("x")), Infix::Plus, Box::new(id("y"))),
+        )])),
+    ))]);
+    assert_eq!(program, expected);
+    assert_eq!(program.0[0].to_string(), "macro(x, y = 1) {\n    x + y;\n}");
+    assert_same_parse("macro(x) { x }(1)", "(macro(x) { x })(1)");
+}
+
+#[test]
+fn test_modify() {
+    let one_to_two = &mut |expression| -> Result<Expression, ()> {
+        match expression {
+            Expression::Integer(1) => Ok(Expression::Integer(2)),
+            expression => Ok(expression),
+        }
+    };
+    let tests = [
+        ("1", "2"),
+        ("1 + 2", "2 + 2"),
+        ("-1", "-2"),
+        ("[1, 1][1:1]", "[2, 2][2:2]"),
+        ("{1: 1}", "{2: 2}"),
+        ("if (1) { 1 } else { 1 }", "if (2) { 2 } else { 2 }"),
+        ("while (1) { 1 }", "while (2) { 2 }"),
+        (
+            "match (1) { 1 => 1, _ => 1 }",
+            "match (2) { 2 => 2, _ => 2 }",
+        ),
+        ("fn(a = 1) { return 1; }", "fn(a = 2) { return 2; }"),
+        ("f(1, ...1)", "f(2, ...2)"),
+        ("let x = 1; x = 1; x[1] = 1;", "let x = 2; x = 2; x[2] = 2;"),
+        ("for (x in 1..1) { 1 }", "for (x in 2..2) { 2 }"),
+        ("export let x = 1;", "export let x = 2;"),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        let modified = program.modify(one_to_two).unwrap();
+        assert_eq!(
+            modified,
+            Parser::parse_program(expected).unwrap(),
+            "{input}"
+        );
+    }
+
+    // children are rewritten before their parent, and the first error stops it
+    let mut seen = vec![];
+    let program = Parser::parse_program("f(1 + x, y)").unwrap();
+    let result = program.modify(&mut |expression| {
+        seen.push(expression.to_string());
+        match expression {
+            Expression::Identifier(name) if name == "y" => Err(name),
+            expression => Ok(expression),
+        }
+    });
+    assert_eq!(result, Err(String::from("y")));
+    assert_eq!(seen, ["f", "1", "x", "1 + x", "y"]);
+}
+
+#[test]
+fn test_const_statement() {
+    let id = |name: &str| Expression::Identifier(String::from(name));
+    let program = Parser::parse_program("const PI = 3; export const E = 2;").unwrap();
+    let expected = Program(vec![
+        Statement::Const(id("PI"), Expression::Integer(3)),
+        Statement::Export(Box::new(Statement::Const(id("E"), Expression::Integer(2)))),
+    ]);
+    assert_eq!(program, expected);
+    assert_eq!(program.to_string(), "const PI = 3;\nexport const E = 2;\n");
+
+    // a function bound with `const` is named like one bound with `let`
+    let program = Parser::parse_program("const f = fn() { f() };").unwrap();
+    assert!(matches!(
+        &program.0[0],
+        Statement::Const(_, Expression::Function(_, _, name)) if name == "f"
+    ));
+
+    let errors = Parser::parse_program("const = 1;").err().unwrap();
+    assert_eq!(errors, [ParsingError::UnexpectedToken(Token::Assign)]);
+}
+
+#[test]
+fn test_let_destructure_statement() {
+    let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+    let tests = [
+        (
+            "let [a, b] = xs;",
+            Statement::LetDestructure(
+                names(&["a", "b"]),
+                None,
+                Expression::Identifier(String::from("xs")),
+            ),
+        ),
+        (
+            "let [first, ...rest] = [1, 2, 3];",
+            Statement::LetDestructure(
+                names(&["first"]),
+                Some(String::from("rest")),
+                Expression::Array(vec![
+                    Expression::Integer(1),
+                    Expression::Integer(2),
+                    Expression::Integer(3),
+                ]),
+            ),
+        ),
+        (
+            "let [...all] = xs;",
+            Statement::LetDestructure(
+                vec![],
+                Some(String::from("all")),
+                Expression::Identifier(String::from("xs")),
+            ),
+        ),
+        (
+            "let [] = xs;",
+            Statement::LetDestructure(vec![], None, Expression::Identifier(String::from("xs"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(program, Program(vec![expected]), "{input}");
+        assert_eq!(program.to_string(), format!("{input}\n"));
+    }
+
+    let tests = [
+        ("let [a, ...rest, b] = xs;", Token::Comma),
+        ("let [a, 1] = xs;", Token::Int(String::from("1"))),
+        ("let [a b] = xs;", Token::Identifier(String::from("b"))),
+        ("let [a] xs;", Token::Identifier(String::from("xs"))),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(
+            errors[0],
+            ParsingError::UnexpectedToken(expected),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_string_template() {
+    let input = r#""hello {name}, you are {age + 1}""#;
+    let expression = Parser::parse_expression_from_str(input).unwrap();
+    let expected = Expression::StringTemplate(vec![
+        TemplatePart::Literal(String::from("hello ")),
+        TemplatePart::Expression(Expression::Identifier(String::from("name"))),
+        TemplatePart::Literal(String::from(", you are ")),
+        TemplatePart::Expression(Expression::Infix(
+            Box::new(Expression::Identifier(String::from("age"))),
+            Infix::Plus,
+            Box::new(Expression::Integer(1)),
+        )),
+    ]);
+    assert_eq!(expression, expected);
+
+    let tests = [
+        (input, input),
+        (r#""a {"b {c + "d"}"}\n""#, r#""a {"b {c + "d"}"}\n""#),
+        (r#""{{{x}}}""#, r#""{{{x}}}""#),
+        (r#""{ {"k": 1}["k"] }""#, r#""{ {"k": 1}["k"]}""#),
+        (r#""{{}}""#, r#""{{}}""#),
+    ];
+    for (input, printed) in tests {
+        let expression = Parser::parse_expression_from_str(input).unwrap();
+        assert_eq!(expression.to_string(), printed, "{input}");
+        let reparsed = Parser::parse_expression_from_str(printed).unwrap();
+        assert_eq!(reparsed, expression, "{input}");
+    }
+
+    let tests = [
+        (r#"let s = "a {b";"#, ParsingError::UnclosedInterpolation),
+        (r#"let s = "{}";"#, ParsingError::UnexpectedEof),
+        (
+            r#"let s = "{a b}";"#,
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("b"))),
+        ),
+        (
+            r#"let s = "{let a = 1}";"#,
+            ParsingError::InvalidPrefixOperator(Token::Let),
+        ),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+    assert_eq!(
+        ParsingError::UnclosedInterpolation.to_string(),
+        "Missing '}' after '{' in string; write '{{' for a '{'"
+    );
+}
+
+#[test]
+fn test_parse_windows_line_endings() {
+    let source = "#!/usr/bin/env monkey\nlet add = fn(a, b) {\n    a + b // sum\n};\n/* a\nblock */\nadd(1, 2)\n";
+    let expected = Parser::parse_program(source).unwrap();
+    assert_eq!(expected.0.len(), 2);
+    for ending in ["\r\n", "\r"] {
+        let input = source.replace('\n', ending);
+        let program = Parser::parse_program(&input).unwrap();
+        assert_eq!(program, expected, "{input:?}");
+    }
+}
+
+#[test]
+fn test_statements_without_semicolon_before_rbrace() {
+    let input = "
+let f = fn(a) {
+    let b = a + 1;
+    let [c] = [b];
+    return c
+};
+if (true) { b = 1 } else { let [d] = [2] }
+let g = fn() {
+    const x = 1
+}";
+    let expected = "
+let f = fn(a) {
+    let b = a + 1;
+    let [c] = [b];
+    return c;
+};
+if (true) { b = 1; } else { let [d] = [2]; };
+let g = fn() {
+    const x = 1;
+};";
+    let program = Parser::parse_program(input).unwrap();
+    assert_eq!(program, Parser::parse_program(expected).unwrap());
+}
+
+#[test]
+fn test_trailing_commas() {
+    let tests = [
+        ("fn(a, b,) { a }", "fn(a, b) { a }"),
+        ("fn(a, b = 1,) { a }", "fn(a, b = 1) { a }"),
+        ("add(1, 2,)", "add(1, 2)"),
+        ("add(1, ...xs,)", "add(1, ...xs)"),
+        ("[1, 2,]", "[1, 2]"),
+        (r#"{"a": 1,}"#, r#"{"a": 1}"#),
+        ("let [a, b,] = xs;", "let [a, b] = xs;"),
+        (
+            "let add = fn(\n    a,\n    b,\n) {\n    a + b\n};\nadd(\n    1,\n    2,\n)",
+            "let add = fn(a, b) { a + b }; add(1, 2)",
+        ),
+    ];
+    for (input, without) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        assert_eq!(program, Parser::parse_program(without).unwrap(), "{input}");
+    }
+
+    let tests = [
+        ("fn(,) { 1 }", ParsingError::UnexpectedToken(Token::Comma)),
+        ("f(,)", ParsingError::InvalidPrefixOperator(Token::Comma)),
+        ("[,]", ParsingError::InvalidPrefixOperator(Token::Comma)),
+        ("{,}", ParsingError::InvalidPrefixOperator(Token::Comma)),
+        ("let [,] = xs;", ParsingError::UnexpectedToken(Token::Comma)),
+    ];
+    for (input, expected) in tests {
+        let errors = Parser::parse_program(input).err().unwrap();
+        assert_eq!(errors[0], expected, "{input}");
+    }
+}
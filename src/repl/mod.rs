@@ -0,0 +1,186 @@
+use crate::compiler::{optimize_instructions, ByteCode, Compiler};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::macro_expansion::{define_macros, expand_macros};
+use crate::evaluator::EvalError;
+use crate::object::builtins::set_writer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::symtab::SymbolTable;
+use crate::vm::{VirtualMachine, VmError, GLOBAL_SIZE};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::rc::Rc;
+
+mod tests;
+
+const PROMPT: &str = ">> ";
+
+// EX_IOERR from sysexits.h
+pub const IO_ERROR_EXIT_CODE: i32 = 74;
+
+pub struct Repl {
+    broken_pipe_exit_code: i32,
+    hoist_functions: bool,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Repl {
+            broken_pipe_exit_code: 0,
+            hoist_functions: false,
+        }
+    }
+
+    /// Sets the exit code used when the output is closed by the reader, e.g.
+    /// when piping into `head`. Defaults to 0, since nothing went wrong.
+    pub fn with_broken_pipe_exit_code(mut self, code: i32) -> Self {
+        self.broken_pipe_exit_code = code;
+        self
+    }
+
+    /// Lets the functions in each input call functions it defines further
+    /// down, as scripts can. Off by default, so every name must be bound
+    /// before the code using it.
+    #[allow(unused)]
+    pub fn with_function_hoisting(mut self) -> Self {
+        self.hoist_functions = true;
+        self
+    }
+
+    /// Runs a session on stdin and stdout, returning the process exit code.
+    pub fn start(&self) -> i32 {
+        let result = self.run(io::stdin().lock(), io::stdout().lock());
+        self.exit_code(result, &mut io::stderr())
+    }
+
+    /// Runs a session until the reader is exhausted, writing every prompt and
+    /// result to `writer`, as well as the output of builtins such as `puts`.
+    /// Stops at the first IO error.
+    pub fn run(&self, reader: impl BufRead, writer: impl Write + 'static) -> io::Result<()> {
+        let writer = Rc::new(RefCell::new(writer));
+        let previous = set_writer(Box::new(SharedWriter(Rc::clone(&writer))));
+        let result = self.session(reader, SharedWriter(writer));
+        set_writer(previous);
+        result
+    }
+
+    fn session(&self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        let mut symtab = SymbolTable::new();
+        symtab.define_all_builtins();
+        let mut constants = vec![];
+        let mut function_cache = HashMap::new();
+        let mut modules = HashMap::new();
+        let null = Rc::new(Object::Null);
+        let mut globals = vec![null; GLOBAL_SIZE];
+        let macros = Rc::new(RefCell::new(Environment::new()));
+
+        loop {
+            writer.write_all(PROMPT.as_bytes())?;
+            writer.flush()?;
+
+            let mut buffer = String::new();
+            let bytes_read = reader.read_line(&mut buffer)?;
+
+            if bytes_read == 0 {
+                writeln!(writer)?;
+                break;
+            }
+
+            let parsing_result = Parser::parse_program(buffer.as_str());
+            let program = match parsing_result {
+                Ok(program) => program,
+                Err(errors) => {
+                    for e in errors.iter() {
+                        writeln!(writer, "{e:?}")?;
+                    }
+                    continue;
+                }
+            };
+
+            let program = define_macros(program, &macros);
+            let program = match expand_macros(program, &macros) {
+                Ok(program) => program,
+                // the writer has failed, so there's no point carrying on
+                Err(EvalError::Io(kind)) => return Err(kind.into()),
+                Err(e) => {
+                    writeln!(writer, "{e:?}")?;
+                    continue;
+                }
+            };
+
+            let mut compiler = Compiler::new_with_state(symtab, constants);
+            compiler.function_cache = function_cache;
+            compiler.modules = modules;
+
+            let compiled = if self.hoist_functions {
+                compiler.compile_hoisted(program)
+            } else {
+                compiler.compile(program)
+            };
+            let byte_code = match compiled {
+                Ok(ByteCode(instructions, constants)) => {
+                    ByteCode(optimize_instructions(instructions), constants)
+                }
+                Err(e) => {
+                    symtab = compiler.symbol_table;
+                    constants = compiler.constants;
+                    function_cache = compiler.function_cache;
+                    modules = compiler.modules;
+                    writeln!(writer, "{e:?}")?;
+                    continue;
+                }
+            };
+
+            let mut vm = VirtualMachine::new_with_global_state(byte_code, globals);
+            let result = vm.run();
+
+            symtab = compiler.symbol_table;
+            constants = compiler.constants;
+            function_cache = compiler.function_cache;
+            modules = compiler.modules;
+            globals = vm.globals;
+
+            match result {
+                Ok(obj) => writeln!(writer, "{obj}")?,
+                Err(VmError::Io(kind)) => return Err(kind.into()),
+                Err(e) => writeln!(writer, "{e:?}")?,
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Maps the outcome of a session to an exit code. A closed pipe ends the
+    /// session quietly; any other IO error is reported once to `errors`.
+    pub fn exit_code(&self, result: io::Result<()>, errors: &mut impl Write) -> i32 {
+        match result {
+            Ok(()) => 0,
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => self.broken_pipe_exit_code,
+            Err(e) => {
+                // nothing more can be done if stderr is gone as well
+                let _ = writeln!(errors, "rust-monkey: {e}");
+                IO_ERROR_EXIT_CODE
+            }
+        }
+    }
+}
+
+// lets the REPL and the builtins it runs write to the same writer
+struct SharedWriter<W>(Rc<RefCell<W>>);
+
+impl<W: Write> Write for SharedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
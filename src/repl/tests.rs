@@ -0,0 +1,311 @@
+#![cfg(test)]
+
+use std::fs;
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+
+use super::{Repl, IO_ERROR_EXIT_CODE};
+use crate::tests::{write_module, FailingWriter, SharedBuffer};
+
+const SESSION: &str = "1 + 2\n3 * 4\n[5, 6]\n";
+
+#[test]
+fn test_repl_session_output() {
+    let output = SharedBuffer::default();
+    let result = Repl::new().run(SESSION.as_bytes(), output.clone());
+    assert!(result.is_ok());
+    assert_eq!(output.contents(), ">> 3\n>> 12\n>> [5, 6]\n>> \n");
+}
+
+#[test]
+fn test_repl_reports_errors_to_writer() {
+    let output = SharedBuffer::default();
+    let result = Repl::new().run("let = 1;\ny\n1 + true\n".as_bytes(), output.clone());
+    assert!(result.is_ok());
+    assert_eq!(
+        output.contents(),
+        ">> UnexpectedToken(Assign)\n>> UndefinedVariable\n>> IncompatibleTypes\n>> \n"
+    );
+}
+
+#[test]
+fn test_repl_bench_transcript() {
+    let input = "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)
+let stats = bench(fn() { fib(15) }, 10); stats[\"iters\"]
+stats[\"min_ms\"] <= stats[\"mean_ms\"] && stats[\"mean_ms\"] <= stats[\"max_ms\"]
+bench(fn() { fib(15) }, 10)
+";
+    let output = SharedBuffer::default();
+    Repl::new().run(input.as_bytes(), output.clone()).unwrap();
+    let output = output.contents();
+    let lines: Vec<_> = output.lines().collect();
+
+    assert_eq!(lines[..3], [">> 610", ">> 10", ">> true"]);
+    // the order of a hash's keys is not fixed
+    let stats = lines[3]
+        .strip_prefix(">> {")
+        .unwrap()
+        .strip_suffix('}')
+        .unwrap();
+    let mut keys: Vec<_> = stats
+        .split(", ")
+        .map(|pair| pair.split(": ").next().unwrap())
+        .collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        [
+            "\"iters\"",
+            "\"max_ms\"",
+            "\"mean_ms\"",
+            "\"min_ms\"",
+            "\"total_ms\""
+        ]
+    );
+}
+
+#[test]
+fn test_repl_broken_pipe_ends_session_cleanly() {
+    let repl = Repl::new();
+    let writer = FailingWriter::new(8, ErrorKind::BrokenPipe);
+    let result = repl.run(SESSION.as_bytes(), writer.clone());
+    assert_eq!(writer.written.contents(), ">> 3\n>> ");
+
+    let mut errors = vec![];
+    assert_eq!(repl.exit_code(result, &mut errors), 0);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_repl_broken_pipe_exit_code_is_configurable() {
+    let repl = Repl::new().with_broken_pipe_exit_code(141);
+    let writer = FailingWriter::new(0, ErrorKind::BrokenPipe);
+    let result = repl.run(SESSION.as_bytes(), writer.clone());
+
+    let mut errors = vec![];
+    assert_eq!(repl.exit_code(result, &mut errors), 141);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_repl_io_error_is_reported_once() {
+    let repl = Repl::new();
+    let writer = FailingWriter::new(10, ErrorKind::Other);
+    let result = repl.run(SESSION.as_bytes(), writer.clone());
+    assert_eq!(writer.written.contents(), ">> 3\n>> 12");
+
+    let mut errors = vec![];
+    assert_eq!(repl.exit_code(result, &mut errors), IO_ERROR_EXIT_CODE);
+    assert_eq!(
+        String::from_utf8(errors).unwrap(),
+        "rust-monkey: writer failed\n"
+    );
+}
+
+#[test]
+fn test_repl_puts_writes_to_session_writer() {
+    assert_eq!(session("puts(1, \"a\")\n"), ">> 1\na\nnull\n>> \n");
+}
+
+#[test]
+fn test_repl_puts_into_broken_pipe_ends_session() {
+    let repl = Repl::new();
+    let writer = FailingWriter::new(20, ErrorKind::BrokenPipe);
+    let input = "let i = 0; while (true) { puts(i); i += 1; }\n1 + 1\n";
+    let result = repl.run(input.as_bytes(), writer.clone());
+    assert_eq!(writer.written.contents(), ">> 0\n1\n2\n3\n4\n5\n6\n7\n8");
+
+    let mut errors = vec![];
+    assert_eq!(repl.exit_code(result, &mut errors), 0);
+    assert!(errors.is_empty());
+}
+
+fn find_print_macros(dir: &Path, found: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            // integration-style tests live in src/tests
+            if path.file_name().unwrap() != "tests" {
+                find_print_macros(&path, found);
+            }
+        } else if path.file_name().unwrap() != "tests.rs" {
+            let source = fs::read_to_string(&path).unwrap();
+            for (i, line) in source.lines().enumerate() {
+                if line.contains("print!(") || line.contains("println!(") {
+                    found.push(format!("{}:{}", path.display(), i + 1));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_no_print_macros_outside_tests() {
+    // all output must go through a writer so IO errors can be handled
+    let mut found = vec![];
+    find_print_macros(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+        &mut found,
+    );
+    assert_eq!(found, Vec::<String>::new());
+}
+
+#[test]
+fn test_repl_redefined_function() {
+    let input = "let f = fn(x) { x + 1 }; f(1)\nlet f = fn(x) { x + 1 }; f(2)\nlet f = fn(x) { x + 2 }; f(2)\n";
+    let output = SharedBuffer::default();
+    Repl::new().run(input.as_bytes(), output.clone()).unwrap();
+    assert_eq!(output.contents(), ">> 2\n>> 3\n>> 4\n>> \n");
+}
+
+#[test]
+fn test_repl_shows_value_of_last_constant_statement() {
+    let output = SharedBuffer::default();
+    Repl::new()
+        .run("1; 2\n3; let x = 4;\nx; 5\n".as_bytes(), output.clone())
+        .unwrap();
+    assert_eq!(output.contents(), ">> 2\n>> 3\n>> 5\n>> \n");
+}
+
+#[test]
+fn test_repl_shows_escaped_string() {
+    let output = SharedBuffer::default();
+    Repl::new()
+        .run(r#""a\tb\nc""#.as_bytes(), output.clone())
+        .unwrap();
+    assert_eq!(output.contents(), ">> a\tb\nc\n>> \n");
+}
+
+// runs `input` as one session and returns everything written to the output
+fn session(input: &str) -> String {
+    let output = SharedBuffer::default();
+    Repl::new().run(Cursor::new(input), output.clone()).unwrap();
+    output.contents()
+}
+
+#[test]
+fn test_repl_empty_session() {
+    assert_eq!(session(""), ">> \n");
+}
+
+#[test]
+fn test_repl_globals_persist_between_inputs() {
+    let input = "let a = 1; a\nlet b = a + 1; b\nlet a = a * 10; a\n[a, b]\n";
+    assert_eq!(session(input), ">> 1\n>> 2\n>> 10\n>> [10, 2]\n>> \n");
+}
+
+#[test]
+fn test_repl_import() {
+    let path = write_module(
+        "repl_import.mk",
+        r#"puts("loading"); let count = [0]; export let bump = fn() { count[0] += 1; count[0] };"#,
+    );
+    // the module keeps its state between inputs, and only runs once
+    let input = format!("import {path:?}; bump()\nbump()\nimport {path:?}; bump()\ncount\n");
+    assert_eq!(
+        session(&input),
+        ">> loading\n1\n>> 2\n>> 3\n>> UndefinedVariable\n>> \n"
+    );
+}
+
+#[test]
+fn test_repl_functions_persist_between_inputs() {
+    let input = "let add = fn(a, b) { a + b }; add(1, 2)
+fn twice(f, x) { f(f(x, x), x) }; twice
+twice(add, 3)
+";
+    assert_eq!(session(input), ">> 3\n>> fn(...)\n>> 9\n>> \n");
+}
+
+#[test]
+fn test_repl_closures_keep_their_state() {
+    let input = "let counter = fn() { let n = [0]; fn() { n[0] = n[0] + 1; n[0] } }(); 0
+counter()
+counter()
+counter()
+";
+    assert_eq!(session(input), ">> 0\n>> 1\n>> 2\n>> 3\n>> \n");
+}
+
+#[test]
+fn test_repl_builtin_calls() {
+    let input = "let xs = push([1, 2], 3); xs
+len(xs)
+[first(xs), last(xs)]
+rest(xs)
+len(\"monkey\")
+apply(fn(a, b) { a - b }, [5, 3])
+";
+    assert_eq!(
+        session(input),
+        ">> [1, 2, 3]\n>> 3\n>> [1, 3]\n>> [2, 3]\n>> 6\n>> 2\n>> \n"
+    );
+}
+
+#[test]
+fn test_repl_builtin_errors_do_not_end_session() {
+    let input = "len(1)\nlen(\"ab\", \"c\")\nlen([1])\n";
+    let output = session(input);
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[..2].iter().all(|line| line.starts_with(">> ")));
+    assert_eq!(lines[2..], [">> 1", ">> "]);
+}
+
+#[test]
+fn test_repl_recovers_after_parse_error() {
+    let input = "let x = 5; x\nlet = 6;\nx\n";
+    assert_eq!(
+        session(input),
+        ">> 5\n>> UnexpectedToken(Assign)\n>> 5\n>> \n"
+    );
+}
+
+#[test]
+fn test_repl_recovers_after_compile_error() {
+    let input = "let x = 1; x\nlet y = z;\nlet z = 2; z\n[x, z]\n";
+    assert_eq!(
+        session(input),
+        ">> 1\n>> UndefinedVariable\n>> 2\n>> [1, 2]\n>> \n"
+    );
+}
+
+#[test]
+fn test_repl_recovers_after_runtime_error() {
+    let input = "let x = 1; x\nx + true\nlet x = x + 1; x\n";
+    assert_eq!(session(input), ">> 1\n>> IncompatibleTypes\n>> 2\n>> \n");
+}
+
+#[test]
+fn test_repl_multiple_statements_per_line() {
+    let input = "let a = 2; let b = 3; a * b\nlet c = [a, b]; c[1]; c[0]\n";
+    assert_eq!(session(input), ">> 6\n>> 2\n>> \n");
+}
+
+#[test]
+fn test_repl_last_line_without_newline() {
+    assert_eq!(session("let x = 7; x\nx * 6"), ">> 7\n>> 42\n>> \n");
+}
+
+#[test]
+fn test_repl_control_flow() {
+    let input = "let n = 0; let total = 0; n
+while (n < 5) { let n = n + 1; let total = total + n; }; total
+if (total > 10) { \"big\" } else { \"small\" }
+";
+    assert_eq!(session(input), ">> 0\n>> 15\n>> big\n>> \n");
+}
+
+#[test]
+fn test_repl_macros_persist_between_inputs() {
+    let input = "let double = macro(x) { quote(unquote(x) * 2) }; 0
+double(21)
+double(1, 2)
+let m = macro() { 1 }; m()
+double(double(1 + 1))
+";
+    assert_eq!(
+        session(input),
+        ">> 0\n>> 42\n>> IncorrectNumberOfArgs\n>> NotQuoted(\"integer\")\n>> 8\n>> \n"
+    );
+}
@@ -0,0 +1,95 @@
+use crate::compiler::{optimize_instructions, ByteCode, Compiler};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::macro_expansion::{define_macros, expand_macros};
+use crate::evaluator::{module, EvalError};
+use crate::parser::ast::Program;
+use crate::parser::Parser;
+use crate::repl::IO_ERROR_EXIT_CODE;
+use crate::vm::{profile, VirtualMachine, VmError};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+mod tests;
+
+// a script that fails to parse, compile or run
+pub const SCRIPT_ERROR_EXIT_CODE: i32 = 1;
+
+/// Runs the script at `path`, returning the process exit code, or the error
+/// that stopped the script writing its output. When profiling, a report of the
+/// calls made is written to `errors` as well.
+pub fn run_file(path: &str, profiling: bool, errors: &mut impl Write) -> io::Result<i32> {
+    match fs::read_to_string(path) {
+        Ok(source) if profiling => module::run_script(path, || run_profiled(&source, errors)),
+        Ok(source) => module::run_script(path, || run(&source, errors)),
+        Err(e) => {
+            let _ = writeln!(errors, "rust-monkey: {path}: {e}");
+            Ok(IO_ERROR_EXIT_CODE)
+        }
+    }
+}
+
+// parses a script and expands its macros, writing any errors to `errors`
+fn parse(source: &str, errors: &mut impl Write) -> io::Result<Option<Program>> {
+    let program = match Parser::parse_program(source) {
+        Ok(program) => program,
+        Err(parsing_errors) => {
+            for e in parsing_errors.iter() {
+                let _ = writeln!(errors, "{e:?}");
+            }
+            return Ok(None);
+        }
+    };
+
+    let macros = Rc::new(RefCell::new(Environment::new()));
+    let program = define_macros(program, &macros);
+    match expand_macros(program, &macros) {
+        Ok(program) => Ok(Some(program)),
+        Err(EvalError::Io(kind)) => Err(kind.into()),
+        Err(e) => {
+            let _ = writeln!(errors, "{e:?}");
+            Ok(None)
+        }
+    }
+}
+
+/// Runs a whole script with the VM, writing any error to `errors`. Unlike in
+/// the REPL, every top-level name is declared up front, so a script's
+/// functions can call functions defined further down.
+pub fn run(source: &str, errors: &mut impl Write) -> io::Result<i32> {
+    let Some(program) = parse(source, errors)? else {
+        return Ok(SCRIPT_ERROR_EXIT_CODE);
+    };
+
+    let byte_code = match Compiler::new().compile_hoisted(program) {
+        Ok(ByteCode(instructions, constants)) => {
+            ByteCode(optimize_instructions(instructions), constants)
+        }
+        Err(e) => {
+            let _ = writeln!(errors, "{e:?}");
+            return Ok(SCRIPT_ERROR_EXIT_CODE);
+        }
+    };
+
+    match VirtualMachine::new(byte_code).run() {
+        Ok(_) => Ok(0),
+        Err(VmError::Io(kind)) => Err(kind.into()),
+        Err(e) => {
+            let _ = writeln!(errors, "{e:?}");
+            Ok(SCRIPT_ERROR_EXIT_CODE)
+        }
+    }
+}
+
+/// Runs a whole script like `run`, recording every call of a user defined
+/// function, then writes the profile to `errors`.
+pub fn run_profiled(source: &str, errors: &mut impl Write) -> io::Result<i32> {
+    profile::start();
+    let code = run(source, errors);
+    // always some, as it was started above
+    let report = profile::finish().map(|p| p.report()).unwrap_or_default();
+    let code = code?;
+    let _ = write!(errors, "{report}");
+    Ok(code)
+}
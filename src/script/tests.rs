@@ -0,0 +1,316 @@
+#![cfg(test)]
+
+use super::{run, run_file, run_profiled, SCRIPT_ERROR_EXIT_CODE};
+use crate::compiler::{Compiler, CompilerError};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::{eval, eval_hoisted, EvalError};
+use crate::object::builtins::set_writer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::repl::{Repl, IO_ERROR_EXIT_CODE};
+use crate::tests::{capture_output, FailingWriter, SharedBuffer};
+use crate::vm::VirtualMachine;
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::rc::Rc;
+
+fn eval_and_run_hoisted(
+    input: &str,
+) -> (Result<Rc<Object>, EvalError>, Result<Rc<Object>, String>) {
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let evaluated = eval_hoisted(Parser::parse_program(input).unwrap(), env);
+
+    let program = Parser::parse_program(input).unwrap();
+    let ran = match Compiler::new().compile_hoisted(program) {
+        Ok(byte_code) => {
+            let mut vm = VirtualMachine::new(byte_code);
+            vm.run().map_err(|e| format!("{e:?}"))
+        }
+        Err(e) => Err(format!("{e:?}")),
+    };
+
+    (evaluated, ran)
+}
+
+#[test]
+fn test_main_at_top_runs() {
+    let input = "
+let main = fn() { double(add(1, 2)) };
+let add = fn(a, b) { a + b };
+let double = fn(x) { x * 2 };
+main();
+";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(evaluated, Ok(Rc::new(Object::Integer(6))));
+    assert_eq!(ran, Ok(Rc::new(Object::Integer(6))));
+}
+
+#[test]
+fn test_mutual_recursion_at_top_level() {
+    let input = "
+let is_even = fn(n) { if (n == 0) { true } else { is_odd(n - 1) } };
+let is_odd = fn(n) { if (n == 0) { false } else { is_even(n - 1) } };
+[is_even(10), is_odd(7), is_even(3)];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+    ])));
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(evaluated, Ok(Rc::clone(&expected)));
+    assert_eq!(ran, Ok(expected));
+}
+
+#[test]
+fn test_function_statements_are_hoisted() {
+    let input = "
+let quadruple = fn(n) { double(double(n)) };
+fn double(n) { n * 2 }
+quadruple(2)
+";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(evaluated, Ok(Rc::new(Object::Integer(8))));
+    assert_eq!(ran, Ok(Rc::new(Object::Integer(8))));
+}
+
+#[test]
+fn test_redefined_function_runs_in_source_order() {
+    let input = "let f = fn() { 1 }; puts(f()); let f = fn() { 2 }; puts(f());";
+    let mut results = None;
+    let output = capture_output(|| results = Some(eval_and_run_hoisted(input)));
+    // the evaluator's output, then the VM's
+    assert_eq!(output, "1\n2\n1\n2\n");
+    let (evaluated, ran) = results.unwrap();
+    assert_eq!(evaluated, Ok(Rc::new(Object::Null)));
+    assert_eq!(ran, Ok(Rc::new(Object::Null)));
+}
+
+#[test]
+fn test_call_before_function_is_defined_is_an_error() {
+    let input = "helper(); let helper = fn() { 1 };";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(
+        evaluated,
+        Err(EvalError::UsedBeforeDefinition(String::from("helper")))
+    );
+    assert_eq!(ran, Err(String::from("UsedBeforeDefinition(\"helper\")")));
+}
+
+#[test]
+fn test_forward_reference_to_value_is_an_error() {
+    let input = "let y = x + 1; let x = 5;";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(
+        evaluated,
+        Err(EvalError::UsedBeforeDefinition(String::from("x")))
+    );
+    assert_eq!(ran, Err(String::from("UsedBeforeDefinition(\"x\")")));
+
+    // a function can read the value, but only once the value's `let` has run
+    let input = "let f = fn() { x }; f(); let x = 5;";
+    let (evaluated, _) = eval_and_run_hoisted(input);
+    assert_eq!(
+        evaluated,
+        Err(EvalError::UsedBeforeDefinition(String::from("x")))
+    );
+
+    let input = "y + 1";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(
+        evaluated,
+        Err(EvalError::UndefinedVariable(String::from("y")))
+    );
+    assert_eq!(ran, Err(String::from("UndefinedVariable")));
+}
+
+#[test]
+fn test_hoisted_function_reads_value_defined_later() {
+    let input = "
+let count = [0];
+let bump = fn() { count[0] += 1; count[0] };
+bump();
+bump()
+";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(evaluated, Ok(Rc::new(Object::Integer(2))));
+    assert_eq!(ran, Ok(Rc::new(Object::Integer(2))));
+}
+
+#[test]
+fn test_without_hoisting_forward_reference_fails() {
+    let input = "helper(); let helper = fn() { 1 };";
+
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let error = eval(Parser::parse_program(input).unwrap(), env).err();
+    assert_eq!(
+        error,
+        Some(EvalError::UndefinedVariable(String::from("helper")))
+    );
+
+    let program = Parser::parse_program(input).unwrap();
+    let error = Compiler::new().compile(program).err();
+    assert_eq!(error, Some(CompilerError::UndefinedVariable));
+}
+
+#[test]
+fn test_repl_hoisting_is_opt_in() {
+    let input = "let main = fn() { helper() }; let helper = fn() { 1 }; main()\n".as_bytes();
+
+    let output = SharedBuffer::default();
+    Repl::new().run(input, output.clone()).unwrap();
+    assert_eq!(output.contents(), ">> UndefinedVariable\n>> \n");
+
+    let output = SharedBuffer::default();
+    Repl::new()
+        .with_function_hoisting()
+        .run(input, output.clone())
+        .unwrap();
+    assert_eq!(output.contents(), ">> 1\n>> \n");
+}
+
+#[test]
+fn test_run_script_puts_into_failing_writer() {
+    let writer = FailingWriter::new(6, ErrorKind::BrokenPipe);
+    let previous = set_writer(Box::new(writer.clone()));
+    let mut errors = vec![];
+    let result = run("let i = 0; while (true) { puts(i); i += 1; }", &mut errors);
+    set_writer(previous);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::BrokenPipe);
+    assert_eq!(writer.written.contents(), "0\n1\n2\n");
+    assert!(errors.is_empty());
+
+    // the evaluator stops the same way when profiling
+    let writer = FailingWriter::new(6, ErrorKind::BrokenPipe);
+    let previous = set_writer(Box::new(writer.clone()));
+    let result = run_profiled("let i = 0; while (true) { puts(i); i += 1; }", &mut errors);
+    set_writer(previous);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::BrokenPipe);
+    assert_eq!(writer.written.contents(), "0\n1\n2\n");
+}
+
+#[test]
+fn test_run_script_exit_codes() {
+    let mut errors = vec![];
+    assert_eq!(
+        run(
+            "let main = fn() { helper() }; let helper = fn() { 1 }; main();",
+            &mut errors
+        )
+        .unwrap(),
+        0
+    );
+    assert!(errors.is_empty());
+
+    let mut errors = vec![];
+    assert_eq!(
+        run("let x = ;", &mut errors).unwrap(),
+        SCRIPT_ERROR_EXIT_CODE
+    );
+    assert_eq!(String::from_utf8(errors).unwrap(), "UnexpectedSemicolon\n");
+
+    let mut errors = vec![];
+    assert_eq!(
+        run("1 + true;", &mut errors).unwrap(),
+        SCRIPT_ERROR_EXIT_CODE
+    );
+    assert_eq!(String::from_utf8(errors).unwrap(), "IncompatibleTypes\n");
+
+    // macros are expanded, and fail, before anything runs
+    let mut errors = vec![];
+    let input = "let m = macro(x) { quote(unquote(x) + 1) }; m(1); m();";
+    assert_eq!(run(input, &mut errors).unwrap(), SCRIPT_ERROR_EXIT_CODE);
+    assert_eq!(
+        String::from_utf8(errors).unwrap(),
+        "IncorrectNumberOfArgs\n"
+    );
+
+    let mut errors = vec![];
+    let input = "let m = macro(x) { quote(unquote(x) + 1) }; if (m(1) != 2) { 1 + true; }";
+    assert_eq!(run(input, &mut errors).unwrap(), 0);
+    assert!(errors.is_empty());
+
+    let mut errors = vec![];
+    let code = run_file("/nonexistent/script.monkey", false, &mut errors).unwrap();
+    assert_eq!(code, IO_ERROR_EXIT_CODE);
+    assert!(String::from_utf8(errors)
+        .unwrap()
+        .starts_with("rust-monkey: /nonexistent/script.monkey: "));
+}
+
+#[test]
+fn test_run_profiled_reports_calls() {
+    let input = "let main = fn() { helper(1) + helper(2) }; let helper = fn(x) { x }; main();";
+    let mut errors = vec![];
+    assert_eq!(run_profiled(input, &mut errors).unwrap(), 0);
+
+    let report = String::from_utf8(errors).unwrap();
+    let mut lines: Vec<_> = report
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let columns: Vec<_> = line.split_whitespace().collect();
+            (columns[0], columns[2])
+        })
+        .collect();
+    lines.sort();
+    assert_eq!(lines, [("1", "main"), ("2", "helper")]);
+
+    let mut errors = vec![];
+    assert_eq!(
+        run_profiled("1 + true", &mut errors).unwrap(),
+        SCRIPT_ERROR_EXIT_CODE
+    );
+    assert!(String::from_utf8(errors)
+        .unwrap()
+        .starts_with("IncompatibleTypes\n"));
+
+    // profiling doesn't change what the script does
+    let mut errors = vec![];
+    let mut code = None;
+    let output =
+        capture_output(|| code = run_profiled("let a = [1]; puts(a[5]);", &mut errors).ok());
+    assert_eq!((code, output.as_str()), (Some(0), "null\n"));
+}
+
+#[test]
+fn test_run_file_imports_relative_to_script() {
+    let dir = std::env::temp_dir().join(format!("rust-monkey-script-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("main.mk");
+    let script_path = script.to_str().unwrap();
+    std::fs::write(dir.join("lib.mk"), "export let answer = 42;").unwrap();
+    std::fs::write(dir.join("cycle.mk"), r#"import "main.mk";"#).unwrap();
+
+    for profiling in [false, true] {
+        std::fs::write(
+            &script,
+            r#"import "lib.mk"; if (answer != 42) { 1 + true; }"#,
+        )
+        .unwrap();
+        let mut errors = vec![];
+        assert_eq!(run_file(script_path, profiling, &mut errors).unwrap(), 0);
+
+        // a module importing the script is a cycle, as the script is still running
+        std::fs::write(&script, r#"import "cycle.mk";"#).unwrap();
+        let mut errors = vec![];
+        assert_eq!(
+            run_file(script_path, profiling, &mut errors).unwrap(),
+            SCRIPT_ERROR_EXIT_CODE
+        );
+        assert!(
+            String::from_utf8(errors)
+                .unwrap()
+                .starts_with("CircularImport("),
+            "{profiling}"
+        );
+    }
+}
+
+#[test]
+fn test_script_with_shebang_line() {
+    let input = "#!/usr/bin/env rust-monkey\nlet x = 1;\nx + 1;";
+    let (evaluated, ran) = eval_and_run_hoisted(input);
+    assert_eq!(evaluated, Ok(Rc::new(Object::Integer(2))));
+    assert_eq!(ran, Ok(Rc::new(Object::Integer(2))));
+}
@@ -1,14 +1,18 @@
+use crate::object::builtins::BUILTIN_NAMES;
 use std::{collections::HashMap, rc::Rc};
 
 mod tests;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SymbolScope {
     Global,
     Local,
     Builtin,
     Free,
     Function,
+    // a variable of a block at the top level; it lives in a global slot but
+    // closures capture it like a local so each block run gets its own binding
+    BlockGlobal,
 }
 
 #[derive(Debug, PartialEq)]
@@ -16,6 +20,8 @@ pub struct Symbol {
     name: String,
     pub scope: SymbolScope,
     pub index: u32,
+    // bound with `const`, so it can't be assigned to
+    pub constant: bool,
 }
 
 impl Symbol {
@@ -24,6 +30,7 @@ impl Symbol {
             name: name.to_string(),
             scope,
             index,
+            constant: false,
         }
     }
 }
@@ -34,6 +41,7 @@ pub struct SymbolTable {
     store: HashMap<String, Rc<Symbol>>,
     pub num_definitions: u32,
     pub free_symbols: Vec<Rc<Symbol>>,
+    block: bool,
 }
 
 impl SymbolTable {
@@ -43,6 +51,7 @@ impl SymbolTable {
             store: HashMap::new(),
             num_definitions: 0,
             free_symbols: vec![],
+            block: false,
         }
     }
 
@@ -52,6 +61,29 @@ impl SymbolTable {
         new
     }
 
+    // a block has its own names but allocates their slots in the function (or
+    // the program) it is part of, as it runs in the same frame
+    pub fn new_block(table: SymbolTable) -> Self {
+        let mut new = Self::new_enclosed(table);
+        new.block = true;
+        new
+    }
+
+    // whether this is the table of the top level, or of a block in it
+    pub fn is_top_level(&self) -> bool {
+        match &self.outer {
+            Some(outer) => self.block && outer.is_top_level(),
+            None => true,
+        }
+    }
+
+    // the symbols stored directly in this table, in the order they were defined
+    pub fn definitions(&self) -> Vec<Rc<Symbol>> {
+        let mut definitions: Vec<_> = self.store.values().cloned().collect();
+        definitions.sort_by_key(|symbol| symbol.index);
+        definitions
+    }
+
     pub fn define_builtin(&mut self, idx: u32, name: String) -> Rc<Symbol> {
         let symbol = Rc::new(Symbol::new(name.as_str(), SymbolScope::Builtin, idx));
         self.store.insert(name, Rc::clone(&symbol));
@@ -59,12 +91,9 @@ impl SymbolTable {
     }
 
     pub fn define_all_builtins(&mut self) {
-        self.define_builtin(0, "len".to_string());
-        self.define_builtin(1, "first".to_string());
-        self.define_builtin(2, "last".to_string());
-        self.define_builtin(3, "rest".to_string());
-        self.define_builtin(4, "push".to_string());
-        self.define_builtin(5, "puts".to_string());
+        for (idx, name) in BUILTIN_NAMES.iter().enumerate() {
+            self.define_builtin(idx as u32, name.to_string());
+        }
     }
 
     pub fn define_function_name(&mut self, name: String) -> Rc<Symbol> {
@@ -74,16 +103,76 @@ impl SymbolTable {
     }
 
     pub fn define(&mut self, name: String) -> Rc<Symbol> {
+        self.define_binding(name, false)
+    }
+
+    pub fn define_constant(&mut self, name: String) -> Rc<Symbol> {
+        self.define_binding(name, true)
+    }
+
+    fn define_binding(&mut self, name: String, constant: bool) -> Rc<Symbol> {
+        if self.block {
+            return self.define_in_block(name, constant);
+        }
         let scope = match &self.outer {
             Some(_) => SymbolScope::Local,
             None => SymbolScope::Global,
         };
-        let symbol = Rc::new(Symbol::new(name.as_str(), scope, self.num_definitions));
+        // redefining a name in the same scope rebinds the existing slot
+        if let Some(existing) = self.store.get(&name) {
+            if existing.scope == scope {
+                return self.rebind(name, constant);
+            }
+        }
+        let mut symbol = Symbol::new(name.as_str(), scope, self.num_definitions);
+        symbol.constant = constant;
+        let symbol = Rc::new(symbol);
         self.store.insert(name, Rc::clone(&symbol));
         self.num_definitions += 1;
         symbol
     }
 
+    fn define_in_block(&mut self, name: String, constant: bool) -> Rc<Symbol> {
+        // redefining a name in the same block rebinds the existing slot
+        if self.store.contains_key(&name) {
+            return self.rebind(name, constant);
+        }
+        let mut frame = &mut *self;
+        while frame.block {
+            frame = frame
+                .outer
+                .as_deref_mut()
+                .expect("a block is always enclosed");
+        }
+        let scope = match &frame.outer {
+            Some(_) => SymbolScope::Local,
+            None => SymbolScope::BlockGlobal,
+        };
+        let mut symbol = Symbol::new(name.as_str(), scope, frame.num_definitions);
+        symbol.constant = constant;
+        let symbol = Rc::new(symbol);
+        frame.num_definitions += 1;
+        self.store.insert(name, Rc::clone(&symbol));
+        symbol
+    }
+
+    // the existing symbol for `name` in this table, bound with `let` or
+    // `const` as it now is
+    fn rebind(&mut self, name: String, constant: bool) -> Rc<Symbol> {
+        let existing = &self.store[&name];
+        if existing.constant == constant {
+            return Rc::clone(existing);
+        }
+        let symbol = Rc::new(Symbol {
+            name: name.clone(),
+            scope: existing.scope,
+            index: existing.index,
+            constant,
+        });
+        self.store.insert(name, Rc::clone(&symbol));
+        symbol
+    }
+
     pub fn resolve(&mut self, name: String) -> Option<Rc<Symbol>> {
         let symbol = self.store.get(&name).cloned();
         if let Some(sym) = symbol {
@@ -94,6 +183,10 @@ impl SymbolTable {
                     SymbolScope::Global | SymbolScope::Builtin => {
                         return Some(object);
                     }
+                    // a block shares its frame with the enclosing scope
+                    _ if self.block => {
+                        return Some(object);
+                    }
                     _ => {
                         return Some(self.define_free(object));
                     }
@@ -105,11 +198,13 @@ impl SymbolTable {
 
     fn define_free(&mut self, original: Rc<Symbol>) -> Rc<Symbol> {
         self.free_symbols.push(original.clone());
-        let sym = Rc::new(Symbol::new(
+        let mut sym = Symbol::new(
             &original.name,
             SymbolScope::Free,
             (self.free_symbols.len() - 1) as u32,
-        ));
+        );
+        sym.constant = original.constant;
+        let sym = Rc::new(sym);
         self.store.insert(original.name.clone(), Rc::clone(&sym));
         Rc::clone(&sym)
     }
@@ -30,6 +30,16 @@ fn test_define() {
     assert_eq!(f, Rc::new(Symbol::new("f", SymbolScope::Local, 1)));
 }
 
+#[test]
+fn test_redefine_reuses_index() {
+    let mut global = SymbolTable::new();
+    global.define("a".to_string());
+    let a = global.define("a".to_string());
+
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Global, 0)));
+    assert_eq!(global.num_definitions, 1);
+}
+
 #[test]
 fn test_resolve() {
     let mut global = SymbolTable::new();
@@ -227,3 +237,76 @@ fn test_shadowing_function_name() {
     let a = global.resolve("a".to_string()).unwrap();
     assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Global, 0)));
 }
+
+#[test]
+fn test_block_allocates_slots_in_enclosing_scope() {
+    let mut global = SymbolTable::new();
+    global.define("a".to_string());
+
+    let mut block = SymbolTable::new_block(global);
+    let b = block.define("b".to_string());
+    let a = block.define("a".to_string());
+    assert_eq!(b, Rc::new(Symbol::new("b", SymbolScope::BlockGlobal, 1)));
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::BlockGlobal, 2)));
+    assert_eq!(block.definitions(), vec![b, a]);
+
+    let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+    local.define("c".to_string());
+    let mut block = SymbolTable::new_block(local);
+    let d = block.define("d".to_string());
+    assert_eq!(d, Rc::new(Symbol::new("d", SymbolScope::Local, 1)));
+    assert_eq!(block.outer.unwrap().num_definitions, 2);
+}
+
+#[test]
+fn test_resolve_through_block() {
+    let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+    local.define("a".to_string());
+    let mut block = SymbolTable::new_block(local);
+    block.define("b".to_string());
+
+    // the block runs in the same frame, so the local is not free in it
+    let a = block.resolve("a".to_string()).unwrap();
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Local, 0)));
+
+    let mut inner = SymbolTable::new_enclosed(block);
+    let a = inner.resolve("a".to_string()).unwrap();
+    let b = inner.resolve("b".to_string()).unwrap();
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Free, 0)));
+    assert_eq!(b, Rc::new(Symbol::new("b", SymbolScope::Free, 1)));
+}
+
+#[test]
+fn test_define_constant() {
+    let mut global = SymbolTable::new();
+    let a = global.define_constant("a".to_string());
+    assert!(a.constant);
+    assert_eq!((a.scope, a.index), (SymbolScope::Global, 0));
+
+    // a closure sees that a local it captures is constant
+    let mut local = SymbolTable::new_enclosed(global.clone());
+    local.define_constant("b".to_string());
+    let mut inner = SymbolTable::new_enclosed(local);
+    let b = inner.resolve("b".to_string()).unwrap();
+    assert!(b.constant);
+    assert_eq!(b.scope, SymbolScope::Free);
+
+    // rebinding the name with `let` keeps its slot, but it is no longer
+    // constant
+    let a = global.define("a".to_string());
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Global, 0)));
+    assert_eq!(global.num_definitions, 1);
+}
+
+#[test]
+fn test_is_top_level() {
+    let global = SymbolTable::new();
+    assert!(global.is_top_level());
+    let block = SymbolTable::new_block(global.clone());
+    assert!(block.is_top_level());
+
+    let local = SymbolTable::new_enclosed(global);
+    assert!(!local.is_top_level());
+    let block = SymbolTable::new_block(local);
+    assert!(!block.is_top_level());
+}
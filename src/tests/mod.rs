@@ -0,0 +1,114 @@
+#![cfg(test)]
+
+use crate::compiler::Compiler;
+use crate::evaluator::environment::Environment;
+use crate::evaluator::eval;
+use crate::object::builtins::{set_clock, set_writer};
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::vm::VirtualMachine;
+use std::cell::RefCell;
+use std::io::{self, ErrorKind, Write};
+use std::rc::Rc;
+use std::time::Duration;
+
+mod semantics_closures;
+mod semantics_mutation;
+
+// a writer whose output can still be read once it has been handed over
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// runs `f`, returning everything builtins such as `puts` wrote meanwhile
+pub fn capture_output(f: impl FnOnce()) -> String {
+    let buffer = SharedBuffer::default();
+    let previous = set_writer(Box::new(buffer.clone()));
+    f();
+    set_writer(previous);
+    buffer.contents()
+}
+
+// runs `f` with a clock that returns each of `readings_ms` in turn
+pub fn with_clock<T>(readings_ms: &'static [u64], f: impl FnOnce() -> T) -> T {
+    let mut readings = readings_ms.iter().copied().map(Duration::from_millis);
+    let previous = set_clock(Box::new(move || readings.next().unwrap()));
+    let result = f();
+    let _ = set_clock(previous);
+    result
+}
+
+// accepts `remaining` bytes and then fails every write with `kind`
+#[derive(Clone)]
+pub struct FailingWriter {
+    remaining: usize,
+    kind: ErrorKind,
+    pub written: SharedBuffer,
+}
+
+impl FailingWriter {
+    pub fn new(remaining: usize, kind: ErrorKind) -> Self {
+        FailingWriter {
+            remaining,
+            kind,
+            written: SharedBuffer::default(),
+        }
+    }
+}
+
+impl Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Err(io::Error::new(self.kind, "writer failed"));
+        }
+        let n = buf.len().min(self.remaining);
+        self.remaining -= n;
+        self.written.write(&buf[..n])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// writes `source` to a file of its own, returning its path
+pub fn write_module(name: &str, source: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("rust-monkey-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, source).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+// runs `input` through both the tree-walking evaluator and the compiler + VM
+// and checks that they agree on the result
+fn assert_backends_agree(input: &str, expected: Object) {
+    let program = Parser::parse_program(input).expect("got a parsing error");
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let evaluated = eval(program, env).expect("got an evaluation error");
+    assert_eq!(*evaluated, expected, "evaluator disagrees on: {input}");
+
+    let program = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(program).expect("got a compiler error");
+    let mut vm = VirtualMachine::new(byte_code);
+    let executed = vm.run().expect("got a vm error");
+    assert_eq!(*executed, expected, "vm disagrees on: {input}");
+}
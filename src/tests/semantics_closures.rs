@@ -0,0 +1,215 @@
+// Closures capture variables by reference: a closure shares the binding with
+// the scope it was created in, so assignments made after the closure was
+// created are visible through it, and vice versa. The body of a `while` loop
+// does not get a fresh scope per iteration; calling a function per iteration
+// does, and so does the body of a `for` loop.
+
+use super::assert_backends_agree;
+use crate::object::Object;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn integers(values: &[i64]) -> Object {
+    Object::Array(RefCell::new(
+        values
+            .iter()
+            .map(|value| Rc::new(Object::Integer(*value)))
+            .collect(),
+    ))
+}
+
+#[test]
+fn test_closure_over_global_loop_variable_sees_final_value() {
+    let input = "
+let fs = [];
+let i = 0;
+while (i < 3) {
+    fs = push(fs, fn() { i });
+    i = i + 1;
+};
+[fs[0](), fs[1](), fs[2]()]
+";
+    assert_backends_agree(input, integers(&[3, 3, 3]));
+}
+
+#[test]
+fn test_closure_over_local_loop_variable_shares_one_binding() {
+    let input = "
+let make = fn() {
+    let fs = [];
+    let i = 0;
+    while (i < 3) {
+        let j = i;
+        fs = push(fs, fn() { j });
+        i = i + 1;
+    }
+    fs
+};
+let fs = make();
+[fs[0](), fs[1](), fs[2]()]
+";
+    assert_backends_agree(input, integers(&[2, 2, 2]));
+}
+
+#[test]
+fn test_closure_over_loop_variable_with_per_iteration_scope() {
+    let input = "
+let fs = [];
+let i = 0;
+while (i < 3) {
+    fs = push(fs, fn(j) { fn() { j } }(i));
+    i = i + 1;
+};
+[fs[0](), fs[1](), fs[2]()]
+";
+    assert_backends_agree(input, integers(&[0, 1, 2]));
+}
+
+#[test]
+fn test_mutation_inside_closure_is_visible_outside() {
+    let input = "
+let f = fn() {
+    let count = 0;
+    let inc = fn() { count = count + 1; };
+    inc();
+    inc();
+    count
+};
+f()
+";
+    assert_backends_agree(input, Object::Integer(2));
+}
+
+#[test]
+fn test_mutation_outside_closure_is_visible_inside() {
+    let input = "
+let f = fn() {
+    let x = 1;
+    let get = fn() { x };
+    x = 5;
+    get()
+};
+f()
+";
+    assert_backends_agree(input, Object::Integer(5));
+}
+
+#[test]
+fn test_two_closures_share_one_captured_binding() {
+    let input = "
+let make = fn() {
+    let n = 0;
+    [fn() { n = n + 1; n }, fn() { n }]
+};
+let pair = make();
+pair[0]();
+pair[0]();
+pair[1]()
+";
+    assert_backends_agree(input, Object::Integer(2));
+}
+
+#[test]
+fn test_captured_parameter_is_shared() {
+    let input = "
+let adder = fn(total) { fn(x) { total = total + x; total } };
+let acc = adder(10);
+acc(1);
+acc(2)
+";
+    assert_backends_agree(input, Object::Integer(13));
+}
+
+#[test]
+fn test_capture_through_nested_closures() {
+    let input = "
+let outer = fn() {
+    let v = 1;
+    let middle = fn() { fn() { v = v + 1; v } };
+    let inner = middle();
+    inner();
+    v
+};
+outer()
+";
+    assert_backends_agree(input, Object::Integer(2));
+}
+
+#[test]
+fn test_parameter_shadows_outer_binding() {
+    let input = "
+let x = 1;
+let f = fn(x) { x = x + 1; x };
+[f(10), x]
+";
+    assert_backends_agree(input, integers(&[11, 1]));
+}
+
+#[test]
+fn test_let_in_function_shadows_outer_binding() {
+    let input = "
+let x = 1;
+let f = fn() { let x = 5; x };
+[f(), x]
+";
+    assert_backends_agree(input, integers(&[5, 1]));
+}
+
+#[test]
+fn test_closures_over_global_for_loop_variable_see_own_iteration() {
+    let input = "
+let fs = [];
+for (x in [1, 2, 3]) {
+    let y = x * 10;
+    fs = push(fs, fn() { x + y });
+}
+[fs[0](), fs[1](), fs[2]()]
+";
+    assert_backends_agree(input, integers(&[11, 22, 33]));
+}
+
+#[test]
+fn test_closures_over_local_for_loop_variable_see_own_iteration() {
+    let input = "
+let make = fn() {
+    let fs = [];
+    for (x in [1, 2, 3]) {
+        fs = push(fs, fn() { x });
+    }
+    fs
+};
+let fs = make();
+[fs[0](), fs[1](), fs[2]()]
+";
+    assert_backends_agree(input, integers(&[1, 2, 3]));
+}
+
+#[test]
+fn test_for_loop_iteration_binding_shared_within_iteration() {
+    let input = "
+let fs = [];
+for (x in [1, 2]) {
+    let n = x;
+    let get = fn() { n };
+    n = n * 100;
+    fs = push(fs, get);
+}
+[fs[0](), fs[1]()]
+";
+    assert_backends_agree(input, integers(&[100, 200]));
+}
+
+#[test]
+fn test_closure_in_for_loop_sees_outer_variable_by_reference() {
+    let input = "
+let total = 0;
+let adders = [];
+for (x in [1, 2]) {
+    adders = push(adders, fn() { total = total + x; total });
+}
+adders[0]();
+adders[1]();
+[total, adders[0]()]
+";
+    assert_backends_agree(input, integers(&[3, 4]));
+}
@@ -0,0 +1,111 @@
+// Index assignment changes an array or hash in place rather than rebinding a
+// variable, so every variable, element or captured binding that refers to the
+// same collection sees the change. Builtins such as `push` still return a new
+// collection and leave their argument alone.
+
+use super::assert_backends_agree;
+use crate::object::Object;
+use crate::value;
+
+#[test]
+fn test_index_assignment_is_seen_through_every_alias() {
+    let input = "
+let a = [1, 2, 3];
+let b = a;
+b[0] = 99;
+a[2] = 7;
+[a, b]
+";
+    assert_backends_agree(input, value!([[99, 2, 7], [99, 2, 7]]));
+}
+
+#[test]
+fn test_hash_assignment_inserts_and_replaces_keys() {
+    let input = r#"
+let h = {"k": 1};
+let alias = h;
+h["k"] = "v";
+alias["new"] = true;
+h
+"#;
+    assert_backends_agree(input, value!({"k": "v", "new": true}));
+}
+
+#[test]
+fn test_index_assignment_into_nested_collection() {
+    let input = r#"
+let inner = [0, 0];
+let grid = [inner, {"x": inner}];
+grid[0][1] = 5;
+grid[1]["x"][0] += 2;
+inner
+"#;
+    assert_backends_agree(input, value!([2, 5]));
+}
+
+#[test]
+fn test_index_assignment_inside_function_changes_argument() {
+    let input = "
+let fill = fn(arr, x) {
+    let i = 0;
+    while (i < len(arr)) {
+        arr[i] = x;
+        i += 1;
+    }
+};
+let a = [1, 2, 3];
+fill(a, 0);
+a
+";
+    assert_backends_agree(input, value!([0, 0, 0]));
+}
+
+#[test]
+fn test_index_assignment_through_closure() {
+    let input = "
+let makeStack = fn() {
+    let items = [null, null];
+    let size = [0];
+    fn(x) { items[size[0]] = x; size[0] += 1; items }
+};
+let push = makeStack();
+push(1);
+push(2)
+";
+    assert_backends_agree(input, value!([1, 2]));
+}
+
+#[test]
+fn test_for_loop_sees_assignments_made_by_its_body() {
+    let input = "
+let a = [1, 2, 3];
+let seen = [];
+for (x in a) {
+    a[2] = 30;
+    seen = push(seen, x);
+}
+seen
+";
+    assert_backends_agree(input, value!([1, 2, 30]));
+}
+
+#[test]
+fn test_builtins_do_not_change_their_argument() {
+    let input = "
+let a = [1];
+let b = push(a, 2);
+b[0] = 5;
+[a, b]
+";
+    assert_backends_agree(input, value!([[1], [5, 2]]));
+}
+
+#[test]
+fn test_collection_containing_itself_is_shown_elided() {
+    let input = "
+let a = [1];
+a[0] = a;
+inspect(a)
+";
+    assert_backends_agree(input, Object::String(String::from("[[...]]")));
+}
@@ -1,4 +1,5 @@
 use std::rc::Rc;
+use std::time::Duration;
 
 use crate::{code::Instructions, object::Closure};
 
@@ -7,11 +8,22 @@ pub struct Frame {
     pub closure: Closure,
     pub ip: usize,
     pub bp: usize,
+    // when the call was made, if it is being profiled
+    pub entered: Option<Duration>,
+    // the stack's height as each loop running in this frame started,
+    // innermost last, for `break` and `continue` to unwind to
+    pub loops: Vec<usize>,
 }
 
 impl Frame {
     pub fn new(closure: Closure, bp: usize) -> Self {
-        Frame { closure, ip: 0, bp }
+        Frame {
+            closure,
+            ip: 0,
+            bp,
+            entered: None,
+            loops: vec![],
+        }
     }
 
     pub fn instructions(&self) -> &Rc<Instructions> {
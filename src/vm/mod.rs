@@ -1,13 +1,19 @@
 use self::frame::Frame;
 use crate::code::{read_u16, OpCode, WORD_SIZE};
 use crate::compiler::ByteCode;
-use crate::object::builtins::{Builtin, BuiltinError};
-use crate::object::{Closure, CompiledFunction, Hashable, Object};
+use crate::object::builtins::{resolve_index, Builtin, BuiltinError};
+use crate::object::{
+    deep_equal, order_arguments, range, repeat_string, slice, ArgumentError, Closure,
+    CompiledFunction, Hashable, Object,
+};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::ErrorKind;
 use std::ops::Deref;
 use std::rc::Rc;
 
 pub mod frame;
+pub mod profile;
 mod tests;
 
 const STACK_SIZE: usize = 2048; // 2KB
@@ -25,6 +31,8 @@ pub struct VirtualMachine {
     pub globals: Vec<Rc<Object>>,
     frames: Vec<Frame>,
     frames_idx: usize,
+    // every null the VM makes is this one, rather than a new allocation
+    null: Rc<Object>,
 }
 
 impl VirtualMachine {
@@ -33,12 +41,14 @@ impl VirtualMachine {
         let main_fn = CompiledFunction::new(instructions, 0, 0);
         let main_closure = Closure::new(main_fn, vec![]);
         let main_frame = Frame::new(main_closure, 0);
+        let null = Rc::new(NULL);
         VirtualMachine {
             constants,
             stack: Vec::with_capacity(STACK_SIZE),
-            globals: vec![Rc::new(Object::Null); GLOBAL_SIZE],
+            globals: vec![Rc::clone(&null); GLOBAL_SIZE],
             frames: vec![main_frame],
             frames_idx: 0,
+            null,
         }
     }
 
@@ -48,11 +58,50 @@ impl VirtualMachine {
         vm
     }
 
+    /// Binds global slot `index` to `value`, as if a `let` had run. Panics if
+    /// `index` is not below `GLOBAL_SIZE`.
+    #[allow(unused)]
+    pub fn set_global(&mut self, index: u32, value: Rc<Object>) {
+        self.globals[index as usize] = value;
+    }
+
+    /// The value in global slot `index`, which is null until it is bound.
+    #[allow(unused)]
+    pub fn get_global(&self, index: u32) -> Option<&Rc<Object>> {
+        self.globals.get(index as usize)
+    }
+
+    /// A copy of the stack, from the bottom up. Every expression statement
+    /// pops its value, so the stack is empty once a program has run.
+    #[cfg(any(test, debug_assertions))]
+    #[allow(unused)]
+    pub fn dump_stack(&self) -> Vec<Rc<Object>> {
+        self.stack.clone()
+    }
+
+    /// Every global slot, including those still unbound (null).
+    #[cfg(any(test, debug_assertions))]
+    #[allow(unused)]
+    pub fn dump_globals(&self) -> &[Rc<Object>] {
+        &self.globals
+    }
+
     pub fn run(&mut self) -> Result<Rc<Object>, VmError> {
+        match self.execute(0)? {
+            Some(obj) => Ok(obj),
+            None => Err(VmError::EmptyStack),
+        }
+    }
+
+    // executes instructions until the frame stack has been unwound back down
+    // to `depth` frames, returning the last object popped off the stack
+    fn execute(&mut self, depth: usize) -> Result<Option<Rc<Object>>, VmError> {
         let mut last_popped = None;
         let mut ip: usize;
 
-        while self.frames[self.frames_idx].ip < self.frames[self.frames_idx].instructions().len() {
+        while self.frames.len() > depth
+            && self.frames[self.frames_idx].ip < self.frames[self.frames_idx].instructions().len()
+        {
             ip = self.frames[self.frames_idx].ip;
 
             let word = &self.frames[self.frames_idx].instructions()[ip..ip + WORD_SIZE];
@@ -75,8 +124,13 @@ impl VirtualMachine {
                 | OpCode::Equal
                 | OpCode::NotEqual
                 | OpCode::GreaterThan
-                | OpCode::And
-                | OpCode::Or => {
+                | OpCode::GreaterEqual
+                | OpCode::BitAnd
+                | OpCode::BitOr
+                | OpCode::BitXor
+                | OpCode::ShiftLeft
+                | OpCode::ShiftRight
+                | OpCode::Power => {
                     self.execute_binary_expression(op)?;
                 }
                 OpCode::True => {
@@ -102,27 +156,84 @@ impl VirtualMachine {
                 OpCode::JumpNotTruthy => {
                     let pos = read_u16(&word[1..=2]) as usize;
                     let condition = self.pop()?;
-                    if !VirtualMachine::is_truthy(&*condition) {
+                    if !VirtualMachine::is_truthy(&condition) {
                         self.frames[self.frames_idx].ip = pos;
                         continue;
                     }
                 }
                 OpCode::Null => {
-                    self.push(&Rc::new(NULL))?;
+                    self.push_null()?;
                 }
                 OpCode::SetGlobal => {
                     let global_idx = read_u16(&word[1..=2]) as usize;
-                    self.globals[global_idx] = self.pop()?;
+                    let value = self.pop()?;
+                    if let Object::Cell(cell) = &*self.globals[global_idx] {
+                        *cell.borrow_mut() = value;
+                    } else {
+                        self.globals[global_idx] = value;
+                    }
                 }
                 OpCode::GetGlobal => {
                     let global_idx = read_u16(&word[1..=2]) as usize;
+                    let obj = VirtualMachine::unwrap_cell(&self.globals[global_idx]);
+                    self.push(&obj)?;
+                }
+                OpCode::DefineGlobal => {
+                    let global_idx = read_u16(&word[1..=2]) as usize;
+                    self.globals[global_idx] = self.pop()?;
+                }
+                OpCode::GetGlobalCell => {
+                    let global_idx = read_u16(&word[1..=2]) as usize;
+                    // only block scoped globals are captured, see `GetLocalCell`
+                    if !matches!(&*self.globals[global_idx], Object::Cell(_)) {
+                        let value = Rc::clone(&self.globals[global_idx]);
+                        self.globals[global_idx] = Rc::new(Object::Cell(RefCell::new(value)));
+                    }
                     self.push(&self.globals[global_idx].clone())?;
                 }
+                OpCode::IterNext => {
+                    let pos = read_u16(&word[1..=2]) as usize;
+                    let index = self.pop()?;
+                    let iterable = self.pop()?;
+                    let (Object::Array(elements), Object::Integer(i)) = (&*iterable, &*index)
+                    else {
+                        return Err(VmError::NotIterable(iterable.type_name().to_string()));
+                    };
+                    let next = elements.borrow().get(*i as usize).cloned();
+                    match next {
+                        Some(element) => {
+                            self.push(&iterable)?;
+                            self.push(&Rc::new(Object::Integer(i + 1)))?;
+                            self.push(&element)?;
+                        }
+                        None => {
+                            self.frames[self.frames_idx].ip = pos;
+                            continue;
+                        }
+                    }
+                }
+                OpCode::LoopEnter => {
+                    let height = self.stack.len();
+                    self.frames[self.frames_idx].loops.push(height);
+                }
+                OpCode::LoopUnwind => {
+                    let frame = &self.frames[self.frames_idx];
+                    let height = *frame.loops.last().ok_or(VmError::StackUnderflow)?;
+                    self.stack.truncate(height);
+                }
+                OpCode::LoopExit => {
+                    self.frames[self.frames_idx].loops.pop();
+                }
                 OpCode::Array => {
                     let array_len = read_u16(&word[1..=2]) as usize;
                     let array = self.build_array(array_len)?;
                     self.push(&array)?;
                 }
+                OpCode::Template => {
+                    let num_parts = read_u16(&word[1..=2]) as usize;
+                    let string = self.build_template(num_parts);
+                    self.push(&string)?;
+                }
                 OpCode::Hash => {
                     let hash_len = read_u16(&word[1..=2]) as usize;
                     let hash = self.build_hash(hash_len)?;
@@ -131,84 +242,141 @@ impl VirtualMachine {
                 OpCode::Index => {
                     self.execute_index_expression()?;
                 }
+                OpCode::SetIndex => {
+                    self.execute_index_assignment()?;
+                }
+                OpCode::Range => {
+                    self.execute_range_expression()?;
+                }
+                OpCode::Match => {
+                    // the value being matched stays on the stack for the
+                    // next arm
+                    let pattern = self.pop()?;
+                    let value = self.stack.last().ok_or(VmError::StackUnderflow)?;
+                    let equal = deep_equal(value, &pattern).ok_or(VmError::NestingTooDeep)?;
+                    self.push(&Rc::new(if equal { TRUE } else { FALSE }))?;
+                }
+                OpCode::Slice => {
+                    let end = self.pop()?;
+                    let start = self.pop()?;
+                    let store = self.pop()?;
+                    let sliced = slice(&store, &start, &end).ok_or(VmError::IncompatibleTypes)?;
+                    self.push(&Rc::new(sliced))?;
+                }
                 OpCode::Call => {
                     let num_args = word[1] as usize;
-                    match &*self.stack[self.stack.len() - 1 - num_args] {
-                        Object::Closure(closure) => {
-                            let num_locals = closure.function.num_locals;
-                            if closure.function.num_params != num_args as u32 {
-                                return Err(VmError::WrongArguments);
-                            }
-                            let frame =
-                                Frame::new(closure.deref().clone(), self.stack.len() - num_args);
-                            self.push_frame(frame)?;
-                            for _ in 0..(num_locals - (num_args as u32)) {
-                                self.push(&Rc::new(NULL))?;
-                            }
-                            continue; // don't want to increment ip
-                        }
-                        Object::Builtin(builtin) => {
-                            let args = &self.stack[self.stack.len() - num_args..];
-                            let result = builtin.apply(args).map_err(|e| match e {
-                                BuiltinError::IncompatibleTypes => VmError::IncompatibleTypes,
-                                BuiltinError::IncorrectNumberOfArgs => VmError::WrongArguments,
-                            })?;
-                            for _ in 0..num_args {
-                                self.pop()?;
-                            }
-                            self.push(&result)?;
-                        }
-                        _ => {
-                            return Err(VmError::CallingNonFunction);
-                        }
+                    if self.call(num_args)? {
+                        continue; // don't want to increment ip
+                    }
+                }
+                OpCode::CallNamed => {
+                    let names_idx = read_u16(&word[1..=2]) as usize;
+                    let num_parts = word[3] as usize;
+                    let num_args = self.name_arguments(names_idx, num_parts)?;
+                    if self.call(num_args)? {
+                        continue;
+                    }
+                }
+                OpCode::CallSpread => {
+                    let num_parts = word[1] as usize;
+                    let num_args = self.spread_arguments(num_parts)?;
+                    if self.call(num_args)? {
+                        continue;
                     }
                 }
                 OpCode::ReturnValue => {
                     let return_val = self.pop()?;
                     let frame = self.pop_frame()?;
+                    profile::exit(&frame.closure.function.name, frame.entered);
                     // pop local bindings off stack
                     while self.stack.len() >= frame.bp {
                         self.pop()?;
                     }
                     self.push(&return_val)?;
+                    if self.frames.len() <= depth {
+                        break;
+                    }
                 }
                 OpCode::Return => {
                     let frame = self.pop_frame()?;
+                    profile::exit(&frame.closure.function.name, frame.entered);
                     // pop local bindings off stack
                     while self.stack.len() >= frame.bp {
                         self.pop()?;
                     }
-                    self.push(&Rc::new(NULL))?;
+                    self.push_null()?;
+                    if self.frames.len() <= depth {
+                        break;
+                    }
                 }
                 OpCode::SetLocal => {
                     let local_idx = word[1] as usize;
                     self.frames[self.frames_idx].ip += WORD_SIZE;
-                    self.stack[self.frames[self.frames_idx].bp + local_idx] = self.pop()?;
+                    let slot = self.frames[self.frames_idx].bp + local_idx;
+                    let value = self.pop()?;
+                    if let Object::Cell(cell) = &*self.stack[slot] {
+                        *cell.borrow_mut() = value;
+                    } else {
+                        self.stack[slot] = value;
+                    }
                     continue;
                 }
+                OpCode::DefineLocal => {
+                    let local_idx = word[1] as usize;
+                    let slot = self.frames[self.frames_idx].bp + local_idx;
+                    self.stack[slot] = self.pop()?;
+                }
                 OpCode::GetLocal => {
                     let local_idx = word[1] as usize;
                     self.frames[self.frames_idx].ip += WORD_SIZE;
                     let obj = self.stack[self.frames[self.frames_idx].bp + local_idx].clone();
-                    self.push(&obj)?;
+                    self.push(&VirtualMachine::unwrap_cell(&obj))?;
                     continue;
                 }
+                OpCode::GetLocalCell => {
+                    let local_idx = word[1] as usize;
+                    let slot = self.frames[self.frames_idx].bp + local_idx;
+                    // the local is moved into a cell the first time it is captured
+                    if !matches!(&*self.stack[slot], Object::Cell(_)) {
+                        let value = Rc::clone(&self.stack[slot]);
+                        self.stack[slot] = Rc::new(Object::Cell(RefCell::new(value)));
+                    }
+                    self.push(&self.stack[slot].clone())?;
+                }
                 OpCode::GetBuiltin => {
                     let builtin_idx = word[1] as usize;
                     if let Some(builtin) = Builtin::get_by_idx(builtin_idx) {
                         self.push(&Rc::clone(&builtin))?;
                     }
                 }
+                OpCode::Destructure => {
+                    let num_names = read_u16(&word[1..=2]) as usize;
+                    let has_rest = word[3] != 0;
+                    self.destructure(num_names, has_rest)?;
+                }
                 OpCode::Closure => {
                     let const_idx = read_u16(&word[1..=2]) as usize;
                     let num_free = word[3] as usize;
                     self.push_closure(const_idx, num_free)?;
                 }
                 OpCode::GetFree => {
+                    let free_idx = word[1] as usize;
+                    let free = self.frames[self.frames_idx].closure.free[free_idx].clone();
+                    self.push(&VirtualMachine::unwrap_cell(&free))?;
+                }
+                OpCode::GetFreeCell => {
                     let free_idx = word[1] as usize;
                     let free = self.frames[self.frames_idx].closure.free[free_idx].clone();
                     self.push(&free)?;
                 }
+                OpCode::SetFree => {
+                    let free_idx = word[1] as usize;
+                    let value = self.pop()?;
+                    match &*self.frames[self.frames_idx].closure.free[free_idx] {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        _ => return Err(VmError::InvalidAssignment),
+                    }
+                }
                 OpCode::CurrentClosure => {
                     let current_closure = self.frames[self.frames_idx].closure.clone();
                     self.push(&Rc::new(Object::Closure(Rc::new(current_closure))))?;
@@ -218,10 +386,173 @@ impl VirtualMachine {
             self.frames[self.frames_idx].ip += WORD_SIZE;
         }
 
-        match last_popped {
-            Some(obj) => Ok(obj),
-            None => Err(VmError::EmptyStack),
+        Ok(last_popped)
+    }
+
+    /// Calls `func` with `args` and runs it to completion, returning its
+    /// result. Builtins use this to call back into Monkey code, and so can Rust
+    /// code embedding the VM, e.g. with a function a script bound to a global
+    /// once `run` has finished. If the call fails, the frames and the stack
+    /// are left as they were before it.
+    pub fn call_function(
+        &mut self,
+        func: &Rc<Object>,
+        args: &[Rc<Object>],
+    ) -> Result<Rc<Object>, VmError> {
+        match &**func {
+            Object::Closure(closure) => {
+                let Some(entry_point) = closure.function.entry_point(args.len()) else {
+                    return Err(VmError::WrongArguments);
+                };
+                let depth = self.frames.len();
+                let stack_len = self.stack.len();
+                let result = self.call_closure(func, closure, args, entry_point, depth);
+                if result.is_err() {
+                    for frame in self.frames.drain(depth..) {
+                        profile::exit(&frame.closure.function.name, frame.entered);
+                    }
+                    self.frames_idx = depth - 1;
+                    self.stack.truncate(stack_len);
+                }
+                result
+            }
+            Object::Builtin(builtin) => {
+                builtin.apply(args, &mut |func, args| self.call_function(func, args))
+            }
+            _ => Err(VmError::CallingNonFunction),
+        }
+    }
+
+    fn call_closure(
+        &mut self,
+        func: &Rc<Object>,
+        closure: &Closure,
+        args: &[Rc<Object>],
+        entry_point: usize,
+        depth: usize,
+    ) -> Result<Rc<Object>, VmError> {
+        self.push(func)?;
+        for arg in args {
+            self.push(arg)?;
+        }
+        let mut frame = Frame::new(closure.clone(), self.stack.len() - args.len());
+        frame.ip = entry_point;
+        frame.entered = profile::enter(&closure.function.name);
+        self.push_frame(frame)?;
+        for _ in 0..(closure.function.num_locals - (args.len() as u32)) {
+            self.push_null()?;
+        }
+        self.execute(depth)?;
+        self.pop()
+    }
+
+    // calls the function below the `num_args` arguments on top of the stack,
+    // returning whether it entered a new frame; otherwise the result has
+    // already replaced the function and its arguments
+    fn call(&mut self, num_args: usize) -> Result<bool, VmError> {
+        let callee = Rc::clone(&self.stack[self.stack.len() - 1 - num_args]);
+        match &*callee {
+            Object::Closure(closure) => {
+                let num_locals = closure.function.num_locals;
+                let Some(entry_point) = closure.function.entry_point(num_args) else {
+                    return Err(VmError::WrongArguments);
+                };
+                let mut frame = Frame::new(closure.deref().clone(), self.stack.len() - num_args);
+                frame.ip = entry_point;
+                frame.entered = profile::enter(&closure.function.name);
+                self.push_frame(frame)?;
+                for _ in 0..(num_locals - (num_args as u32)) {
+                    self.push_null()?;
+                }
+                Ok(true)
+            }
+            Object::Builtin(builtin) => {
+                let args = self.stack[self.stack.len() - num_args..].to_vec();
+                let result =
+                    builtin.apply(&args, &mut |func, args| self.call_function(func, args))?;
+                for _ in 0..=num_args {
+                    self.pop()?;
+                }
+                self.push(&result)?;
+                Ok(false)
+            }
+            _ => Err(VmError::CallingNonFunction),
+        }
+    }
+
+    // replaces the `num_parts` arrays on top of the stack with their elements,
+    // returning how many there are, see `Compiler::compile_spread_arguments`
+    fn spread_arguments(&mut self, num_parts: usize) -> Result<usize, VmError> {
+        let parts = self.stack.split_off(self.stack.len() - num_parts);
+        let mut num_args = 0;
+        for part in parts {
+            let Object::Array(elements) = &*part else {
+                return Err(VmError::CannotSpread(part.type_name()));
+            };
+            for element in elements.borrow().iter() {
+                self.push(element)?;
+            }
+            num_args += elements.borrow().len();
+        }
+        Ok(num_args)
+    }
+
+    // replaces the arguments on top of the stack, pushed as described in
+    // `Compiler::compile_named_arguments`, with all of them in order, returning
+    // how many there are
+    fn name_arguments(&mut self, names_idx: usize, num_parts: usize) -> Result<usize, VmError> {
+        let Object::Array(names) = &*self.constants[names_idx] else {
+            unreachable!("the names of named arguments are an array")
+        };
+        let named: Vec<_> = {
+            let values = self
+                .stack
+                .split_off(self.stack.len() - names.borrow().len());
+            let names = names.borrow();
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .zip(values)
+                .collect()
+        };
+        let num_positional = self.spread_arguments(num_parts)?;
+        let positional = self.stack.split_off(self.stack.len() - num_positional);
+
+        let callee = Rc::clone(&self.stack[self.stack.len() - 1]);
+        let args = match &*callee {
+            Object::Closure(closure) => {
+                let parameters = closure.function.parameters.iter().map(String::as_str);
+                order_arguments(parameters, positional, named)?
+            }
+            Object::Builtin(_) => return Err(VmError::UnknownArgument(named[0].0.clone())),
+            _ => return Err(VmError::CallingNonFunction),
+        };
+        for arg in &args {
+            self.push(arg)?;
+        }
+        Ok(args.len())
+    }
+
+    // replaces the array on top of the stack with its first `num_names`
+    // elements, the first on top, and below them (if `has_rest`) an array of
+    // the rest, see `Compiler::compile_let_destructure`
+    fn destructure(&mut self, num_names: usize, has_rest: bool) -> Result<(), VmError> {
+        let array = self.pop()?;
+        let Object::Array(elements) = &*array else {
+            return Err(VmError::CannotDestructure(array.type_name()));
+        };
+        let elements = elements.borrow();
+        if elements.len() < num_names {
+            return Err(VmError::TooFewElements(num_names, elements.len()));
         }
+        if has_rest {
+            let rest = elements[num_names..].to_vec();
+            self.push(&Rc::new(Object::Array(RefCell::new(rest))))?;
+        }
+        for element in elements[..num_names].iter().rev() {
+            self.push(element)?;
+        }
+        Ok(())
     }
 
     fn push_closure(&mut self, idx: usize, num_free: usize) -> Result<(), VmError> {
@@ -245,28 +576,69 @@ impl VirtualMachine {
     }
 
     fn build_array(&mut self, length: usize) -> Result<Rc<Object>, VmError> {
-        let mut elements = vec![Rc::new(Object::Null); length];
+        let mut elements = vec![Rc::clone(&self.null); length];
         for i in 1..=length {
             elements[length - i] = self.pop()?;
         }
-        Ok(Rc::new(Object::Array(elements)))
+        Ok(Rc::new(Object::Array(RefCell::new(elements))))
+    }
+
+    // the string made by writing out the `num_parts` values on top of the
+    // stack in order, as `puts` would
+    fn build_template(&mut self, num_parts: usize) -> Rc<Object> {
+        let parts = self.stack.split_off(self.stack.len() - num_parts);
+        let string = parts.iter().map(|part| part.to_string()).collect();
+        Rc::new(Object::String(string))
     }
 
     fn build_hash(&mut self, length: usize) -> Result<Rc<Object>, VmError> {
         let mut table = HashMap::new();
         for _ in (0..length).step_by(2) {
             let val = self.pop()?;
-            let key = match &*self.pop()? {
-                Object::Integer(i) => Hashable::Integer(*i),
-                Object::Boolean(b) => Hashable::Boolean(*b),
-                Object::String(s) => Hashable::String(s.clone()),
-                _ => {
-                    return Err(VmError::UnhashableKey);
-                }
-            };
+            let key = Hashable::try_from(&*self.pop()?)
+                .map_err(|type_name| VmError::UnhashableKey(type_name.to_string()))?;
             table.insert(key, val);
         }
-        Ok(Rc::new(Object::Hash(table)))
+        Ok(Rc::new(Object::Hash(RefCell::new(table))))
+    }
+
+    // changes the collection in place; an array can only be assigned within
+    // its bounds, but a hash gains any key that it didn't have
+    fn execute_index_assignment(&mut self) -> Result<(), VmError> {
+        let value = self.pop()?;
+        let index = self.pop()?;
+        let store = self.pop()?;
+
+        match (&*store, &*index) {
+            (Object::Array(array), Object::Integer(i)) => {
+                let mut array = array.borrow_mut();
+                let i = resolve_index(*i, array.len())?;
+                array[i] = value;
+            }
+            (Object::Hash(table), index) => {
+                let key = Hashable::try_from(index)
+                    .map_err(|type_name| VmError::UnhashableKey(type_name.to_string()))?;
+                table.borrow_mut().insert(key, value);
+            }
+            _ => return Err(VmError::IndexNotSupported),
+        }
+        Ok(())
+    }
+
+    fn execute_range_expression(&mut self) -> Result<(), VmError> {
+        let end = self.pop()?;
+        let start = self.pop()?;
+        match (&*start, &*end) {
+            (Object::Integer(start), Object::Integer(end)) => {
+                let range = range(*start, *end).ok_or(VmError::RangeTooLong(*start, *end))?;
+                self.push(&Rc::new(range))
+            }
+            (start, end) => Err(VmError::UnsupportedOperands(
+                "..",
+                start.type_name(),
+                end.type_name(),
+            )),
+        }
     }
 
     fn execute_index_expression(&mut self) -> Result<(), VmError> {
@@ -275,39 +647,39 @@ impl VirtualMachine {
 
         match (&*store, &*index) {
             (Object::Array(array), Object::Integer(i)) => {
-                if *i < 0 || *i as usize >= array.len() {
-                    self.push(&Rc::new(NULL))
-                } else {
-                    self.push(&Rc::new(array[*i as usize].deref().clone()))
+                // the element itself is pushed, not a copy, so that assigning
+                // into a nested collection changes the one that is shared
+                let element = {
+                    let array = array.borrow();
+                    resolve_index(*i, array.len())
+                        .ok()
+                        .map(|i| Rc::clone(&array[i]))
+                };
+                match element {
+                    Some(element) => self.push(&element),
+                    None => self.push_null(),
                 }
             }
             (Object::Hash(table), index) => {
-                let idx = match index {
-                    Object::Integer(i) => Hashable::Integer(*i),
-                    Object::Boolean(b) => Hashable::Boolean(*b),
-                    Object::String(s) => Hashable::String(s.clone()),
-                    _ => {
-                        return Err(VmError::UnhashableKey);
-                    }
-                };
+                let idx = Hashable::try_from(index)
+                    .map_err(|type_name| VmError::UnhashableKey(type_name.to_string()))?;
 
-                match table.get(&idx) {
-                    Some(val) => self.push(val),
-                    None => self.push(&Rc::new(NULL)),
+                let value = table.borrow().get(&idx).cloned();
+                match value {
+                    Some(val) => self.push(&val),
+                    None => self.push_null(),
                 }
             }
-            _ => {
-                return Err(VmError::IndexNotSupported);
-            }
+            _ => Err(VmError::IndexNotSupported),
         }
     }
 
     fn execute_minus_expression(&mut self) -> Result<(), VmError> {
         let right = self.pop()?;
-        if let Object::Integer(int) = &*right {
-            self.push(&Rc::new(Object::Integer(-int)))?;
-        } else {
-            return Err(VmError::IncompatibleTypes);
+        match &*right {
+            Object::Integer(int) => self.push(&Rc::new(Object::Integer(-int)))?,
+            Object::Float(float) => self.push(&Rc::new(Object::Float(-float)))?,
+            _ => return Err(VmError::IncompatibleTypes),
         }
         Ok(())
     }
@@ -338,6 +710,16 @@ impl VirtualMachine {
             (Object::Integer(left_val), _, Object::Integer(right_val)) => {
                 self.execute_integer_operation(*left_val, op, *right_val)?;
             }
+            // mixing integers and floats promotes the integer to a float
+            (Object::Integer(left_val), _, Object::Float(right_val)) => {
+                self.execute_float_operation(*left_val as f64, op, *right_val)?;
+            }
+            (Object::Float(left_val), _, Object::Integer(right_val)) => {
+                self.execute_float_operation(*left_val, op, *right_val as f64)?;
+            }
+            (Object::Float(left_val), _, Object::Float(right_val)) => {
+                self.execute_float_operation(*left_val, op, *right_val)?;
+            }
             (Object::Boolean(left_val), OpCode::Equal, Object::Boolean(right_val)) => {
                 let result = if left_val == right_val { TRUE } else { FALSE };
                 self.push(&Rc::new(result))?;
@@ -346,22 +728,62 @@ impl VirtualMachine {
                 let result = if left_val != right_val { TRUE } else { FALSE };
                 self.push(&Rc::new(result))?;
             }
-            (Object::Boolean(left_val), OpCode::GreaterThan, Object::Boolean(right_val)) => {
-                let result = if left_val > right_val { TRUE } else { FALSE };
+            (Object::Array(left_val), OpCode::Add, Object::Array(right_val)) => {
+                let elements = [left_val.borrow().as_slice(), &right_val.borrow()].concat();
+                let result = Object::Array(RefCell::new(elements));
                 self.push(&Rc::new(result))?;
             }
-            (Object::Boolean(left_val), OpCode::And, Object::Boolean(right_val)) => {
-                let result = if *left_val && *right_val { TRUE } else { FALSE };
-                self.push(&Rc::new(result))?;
+            (Object::Array(_), OpCode::Add, other) => {
+                return Err(VmError::UsePushToAppend(other.type_name()))
             }
-            (Object::Boolean(left_val), OpCode::Or, Object::Boolean(right_val)) => {
-                let result = if *left_val || *right_val { TRUE } else { FALSE };
+            (
+                Object::Array(_) | Object::Hash(_),
+                OpCode::Equal | OpCode::NotEqual,
+                Object::Array(_) | Object::Hash(_),
+            ) => {
+                let equal = deep_equal(&left, &right).ok_or(VmError::NestingTooDeep)?;
+                let result = Object::Boolean(equal == (op == OpCode::Equal));
                 self.push(&Rc::new(result))?;
             }
             (Object::String(left_val), OpCode::Add, Object::String(right_val)) => {
-                let result = Object::String(left_val.to_owned() + right_val);
+                let result = Object::String([left_val.as_str(), right_val].concat());
                 self.push(&Rc::new(result))?;
             }
+            (Object::String(string), OpCode::Multiply, Object::Integer(count))
+            | (Object::Integer(count), OpCode::Multiply, Object::String(string)) => {
+                let Some(repeated) = repeat_string(string, *count) else {
+                    return Err(VmError::InvalidRepeatCount(*count));
+                };
+                self.push(&Rc::new(Object::String(repeated)))?;
+            }
+            // compares contents byte by byte, so a prefix sorts first
+            (
+                Object::String(left_val),
+                OpCode::GreaterThan | OpCode::GreaterEqual | OpCode::Equal | OpCode::NotEqual,
+                Object::String(right_val),
+            ) => {
+                let result = match op {
+                    OpCode::GreaterThan => left_val > right_val,
+                    OpCode::GreaterEqual => left_val >= right_val,
+                    OpCode::Equal => left_val == right_val,
+                    _ => left_val != right_val,
+                };
+                self.push(&Rc::new(Object::Boolean(result)))?;
+            }
+            // any value can be compared with null, which only equals itself
+            (Object::Null, OpCode::Equal, _) | (_, OpCode::Equal, Object::Null) => {
+                self.push(&Rc::new(Object::Boolean(left == right)))?;
+            }
+            (Object::Null, OpCode::NotEqual, _) | (_, OpCode::NotEqual, Object::Null) => {
+                self.push(&Rc::new(Object::Boolean(left != right)))?;
+            }
+            (Object::String(_), _, _) | (_, _, Object::String(_)) => {
+                return Err(VmError::UnsupportedOperands(
+                    operator_symbol(op),
+                    left.type_name(),
+                    right.type_name(),
+                ))
+            }
             _ => return Err(VmError::IncompatibleTypes),
         }
         Ok(())
@@ -399,15 +821,71 @@ impl VirtualMachine {
                     FALSE
                 }
             }
+            OpCode::GreaterEqual => {
+                if left >= right {
+                    TRUE
+                } else {
+                    FALSE
+                }
+            }
+            OpCode::BitAnd => Object::Integer(left & right),
+            OpCode::BitOr => Object::Integer(left | right),
+            OpCode::BitXor => Object::Integer(left ^ right),
+            // shifting by 64 or more bits, or by a negative amount, is an error
+            OpCode::ShiftLeft => {
+                match u32::try_from(right).ok().and_then(|n| left.checked_shl(n)) {
+                    Some(shifted) => Object::Integer(shifted),
+                    None => return Err(VmError::InvalidShift(right)),
+                }
+            }
+            OpCode::ShiftRight => match u32::try_from(right).ok().and_then(|n| left.checked_shr(n))
+            {
+                Some(shifted) => Object::Integer(shifted),
+                None => return Err(VmError::InvalidShift(right)),
+            },
+            OpCode::Power if right < 0 => return Err(VmError::NegativeExponent(right)),
+            OpCode::Power => match u32::try_from(right).ok().and_then(|n| left.checked_pow(n)) {
+                Some(power) => Object::Integer(power),
+                None => return Err(VmError::IntegerOverflow),
+            },
             _ => return Err(VmError::IncompatibleTypes),
         };
         self.push(&Rc::new(result))
     }
 
+    fn execute_float_operation(
+        &mut self,
+        left: f64,
+        op_code: OpCode,
+        right: f64,
+    ) -> Result<(), VmError> {
+        let result = match op_code {
+            OpCode::Add => Object::Float(left + right),
+            OpCode::Subtract => Object::Float(left - right),
+            OpCode::Multiply => Object::Float(left * right),
+            OpCode::Divide => Object::Float(left / right),
+            OpCode::Power => Object::Float(left.powf(right)),
+            OpCode::Equal => Object::Boolean(left == right),
+            OpCode::NotEqual => Object::Boolean(left != right),
+            OpCode::GreaterThan => Object::Boolean(left > right),
+            OpCode::GreaterEqual => Object::Boolean(left >= right),
+            _ => return Err(VmError::IncompatibleTypes),
+        };
+        self.push(&Rc::new(result))
+    }
+
+    fn unwrap_cell(object: &Rc<Object>) -> Rc<Object> {
+        match &**object {
+            Object::Cell(cell) => Rc::clone(&cell.borrow()),
+            _ => Rc::clone(object),
+        }
+    }
+
     fn is_truthy(object: &Object) -> bool {
         match object {
             Object::Null => false,
             Object::Integer(val) => *val != 0,
+            Object::Float(val) => *val != 0.0,
             Object::Boolean(val) => *val,
             _ => true,
         }
@@ -421,6 +899,11 @@ impl VirtualMachine {
         Ok(())
     }
 
+    fn push_null(&mut self) -> Result<(), VmError> {
+        let null = Rc::clone(&self.null);
+        self.push(&null)
+    }
+
     fn pop(&mut self) -> Result<Rc<Object>, VmError> {
         match self.stack.pop() {
             Some(object) => Ok(Rc::clone(&object)),
@@ -448,6 +931,28 @@ impl VirtualMachine {
     }
 }
 
+// `<` and `<=` are compiled as `>` and `>=` with their operands swapped, so
+// errors from them name the flipped operator
+fn operator_symbol(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Add => "+",
+        OpCode::Subtract => "-",
+        OpCode::Multiply => "*",
+        OpCode::Divide => "/",
+        OpCode::Equal => "==",
+        OpCode::NotEqual => "!=",
+        OpCode::GreaterThan => ">",
+        OpCode::GreaterEqual => ">=",
+        OpCode::BitAnd => "&",
+        OpCode::BitOr => "|",
+        OpCode::BitXor => "^",
+        OpCode::ShiftLeft => "<<",
+        OpCode::ShiftRight => ">>",
+        OpCode::Power => "**",
+        _ => unreachable!("{op} is not a binary operator"),
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum VmError {
     UnknownOpCode,
@@ -455,10 +960,76 @@ pub enum VmError {
     StackUnderflow,
     EmptyStack,
     IncompatibleTypes,
-    UnhashableKey,
+    UnhashableKey(String),
     IndexNotSupported,
     FrameStackUnderflow,
     FrameStackOverflow,
     CallingNonFunction,
     WrongArguments,
+    InvalidAssignment,
+    IndexOutOfBounds,
+    NotIterable(String),
+    NestingTooDeep,
+    // shifting by a negative amount or by more than 63 bits
+    InvalidShift(i64),
+    // raising an integer to a negative power, whose result is not an integer
+    NegativeExponent(i64),
+    IntegerOverflow,
+    // adding something other than an array to an array, which is done with
+    // `push`; holds the type of the value being added
+    UsePushToAppend(&'static str),
+    // repeating a string a negative number of times, or so many times that
+    // the result would be unreasonably long
+    InvalidRepeatCount(i64),
+    // an operator that the operand types don't support, e.g. `"a" - "b"`;
+    // holds the operator and the types of its left and right operands
+    UnsupportedOperands(&'static str, &'static str, &'static str),
+    // a range, given by its start and end, with too many integers in it to
+    // build an array of
+    RangeTooLong(i64, i64),
+    // spreading something other than an array into a call's arguments;
+    // holds its type
+    CannotSpread(&'static str),
+    // destructuring something other than an array in a `let`; holds its type
+    CannotDestructure(&'static str),
+    // destructuring an array with fewer elements than there are names; holds
+    // the number of names and of elements
+    TooFewElements(usize, usize),
+    // the name of a type (or hash key type) that `to_json` can't convert
+    NotSerializable(String),
+    // `from_json` was given malformed JSON; holds what was wrong with it
+    InvalidJson(String),
+    // a builtin such as `puts` failed to write its output
+    Io(ErrorKind),
+    // a named argument that isn't one of the function's parameters
+    UnknownArgument(String),
+    // a parameter given both by position and by name, or by name twice
+    DuplicateArgument(String),
+    // a parameter without a default left out before one given by name
+    MissingArgument(String),
+}
+
+impl From<ArgumentError> for VmError {
+    fn from(error: ArgumentError) -> Self {
+        match error {
+            ArgumentError::Unknown(name) => VmError::UnknownArgument(name),
+            ArgumentError::Duplicate(name) => VmError::DuplicateArgument(name),
+            ArgumentError::Missing(name) => VmError::MissingArgument(name),
+        }
+    }
+}
+
+impl From<BuiltinError> for VmError {
+    fn from(error: BuiltinError) -> Self {
+        match error {
+            BuiltinError::IncompatibleTypes => VmError::IncompatibleTypes,
+            BuiltinError::IncorrectNumberOfArgs => VmError::WrongArguments,
+            BuiltinError::IndexOutOfBounds => VmError::IndexOutOfBounds,
+            BuiltinError::NestingTooDeep => VmError::NestingTooDeep,
+            BuiltinError::UnhashableKey(type_name) => VmError::UnhashableKey(type_name),
+            BuiltinError::NotSerializable(type_name) => VmError::NotSerializable(type_name),
+            BuiltinError::InvalidJson(message) => VmError::InvalidJson(message),
+            BuiltinError::Io(kind) => VmError::Io(kind),
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use crate::object::builtins::now;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+thread_local! {
+    // the profile being recorded by the VM, if profiling is on
+    static PROFILE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Profile {
+    // the number of calls and the total time spent in each function, by name
+    pub functions: HashMap<String, (u64, Duration)>,
+    // how many calls of each function are running, so that the time spent in
+    // a recursive call isn't counted again by the call it was made from
+    active: HashMap<String, u32>,
+}
+
+/// Starts recording the function calls made by the VM on this thread.
+pub fn start() {
+    PROFILE.with(|p| *p.borrow_mut() = Some(Profile::default()));
+}
+
+/// Stops recording, returning everything recorded since `start`.
+pub fn finish() -> Option<Profile> {
+    PROFILE.with(|p| p.borrow_mut().take())
+}
+
+// called as a function is entered; the time it was entered is only read when
+// profiling is on
+pub(super) fn enter(name: &str) -> Option<Duration> {
+    PROFILE.with(|p| {
+        let mut profile = p.borrow_mut();
+        let profile = profile.as_mut()?;
+        *profile.active.entry(name.to_string()).or_default() += 1;
+        Some(now())
+    })
+}
+
+pub(super) fn exit(name: &str, entered: Option<Duration>) {
+    let Some(entered) = entered else {
+        return;
+    };
+    let elapsed = now().saturating_sub(entered);
+
+    PROFILE.with(|p| {
+        if let Some(profile) = p.borrow_mut().as_mut() {
+            let active = profile.active.entry(name.to_string()).or_default();
+            *active = active.saturating_sub(1);
+            let outermost = *active == 0;
+
+            let (calls, total) = profile.functions.entry(name.to_string()).or_default();
+            *calls += 1;
+            if outermost {
+                *total += elapsed;
+            }
+        }
+    });
+}
+
+impl Profile {
+    /// A table of every function called, the one that took longest first.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|(a_name, (_, a_total)), (b_name, (_, b_total))| {
+            b_total.cmp(a_total).then(a_name.cmp(b_name))
+        });
+
+        let mut report = format!("{:>8} {:>12}  function\n", "calls", "total_ms");
+        for (name, (calls, total)) in functions {
+            let total_ms = total.as_nanos() as f64 / 1_000_000.0;
+            report.push_str(&format!("{calls:>8} {total_ms:>12.3}  {name}\n"));
+        }
+        report
+    }
+}
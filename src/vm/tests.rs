@@ -1,11 +1,17 @@
-use crate::compiler::Compiler;
+#![cfg(test)]
+
+use crate::code::{make, OpCode};
+use crate::compiler::{ByteCode, Compiler, CompilerError};
+use crate::object::builtins::{set_clock, Builtin};
 use crate::object::{Hashable, Object};
 use crate::parser::Parser;
-use crate::vm::{VirtualMachine, VmError, STACK_SIZE};
+use crate::tests::{capture_output, with_clock, write_module};
+use crate::value;
+use crate::vm::{profile, VirtualMachine, VmError, GLOBAL_SIZE, STACK_SIZE};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-
-#[cfg(test)]
+use std::time::Duration;
 
 fn compile_and_run(input: &str) -> (Option<Rc<Object>>, Option<VmError>) {
     let mut result = None;
@@ -30,6 +36,51 @@ fn test_vm_integer_object() {
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_vm_float_arithmetic() {
+    let input = "[1.5 + 2.25, 1.5 - 2.0, 1.5 * 2.0, 1.0 / 4.0, -2.5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Float(3.75)),
+        Rc::new(Object::Float(-0.5)),
+        Rc::new(Object::Float(3.0)),
+        Rc::new(Object::Float(0.25)),
+        Rc::new(Object::Float(-2.5)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_mixed_integer_and_float_arithmetic_promotes_to_float() {
+    let input = "[1 + 0.5, 0.5 * 4, 7 / 2.0, 7 / 2]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Float(1.5)),
+        Rc::new(Object::Float(2.0)),
+        Rc::new(Object::Float(3.5)),
+        Rc::new(Object::Integer(3)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_float_comparison() {
+    let input = "[1.5 < 2, 2 <= 2.0, 0.1 > 0.2, 3.0 >= 3, 1 == 1.0, 1.5 != 1.5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
 #[test]
 fn test_vm_stack_overflow_not_dependent_on_number_of_statements() {
     let input = "1024;".repeat(STACK_SIZE + 1);
@@ -201,6 +252,34 @@ fn test_vm_boolean_expression_eight() {
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_vm_less_equal_and_greater_equal() {
+    let input = "[5 <= 5, 6 <= 5, 5 >= 5, 4 >= 5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_comparison_error_if_booleans() {
+    for input in [
+        "true > false",
+        "true < false",
+        "true >= false",
+        "true <= false",
+    ] {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, Some(VmError::IncompatibleTypes), "{input}");
+        assert_eq!(result, None, "{input}");
+    }
+}
+
 #[test]
 fn test_vm_minus_expression() {
     let input = "-10";
@@ -384,7 +463,7 @@ fn test_string_expression_three() {
 #[test]
 fn test_array_expression_one() {
     let input = "[]";
-    let expected = Rc::new(Object::Array(vec![]));
+    let expected = Rc::new(Object::Array(RefCell::new(vec![])));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
@@ -393,11 +472,11 @@ fn test_array_expression_one() {
 #[test]
 fn test_array_expression_two() {
     let input = "[1, 2, 3]";
-    let expected = Rc::new(Object::Array(vec![
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
         Rc::new(Object::Integer(1)),
         Rc::new(Object::Integer(2)),
         Rc::new(Object::Integer(3)),
-    ]));
+    ])));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
@@ -406,20 +485,29 @@ fn test_array_expression_two() {
 #[test]
 fn test_array_expression_three() {
     let input = "[1 + 2, 3 - 4, 5 * 6]";
-    let expected = Rc::new(Object::Array(vec![
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
         Rc::new(Object::Integer(3)),
         Rc::new(Object::Integer(-1)),
         Rc::new(Object::Integer(30)),
-    ]));
+    ])));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_array_expression_display() {
+    let input = "[1, 2 * 2, fn(x) { x }, [1, [2]],]";
+    let expected = "[1, 4, fn(...), [1, [2]]]";
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result.unwrap().to_string(), expected);
+}
+
 #[test]
 fn test_hash_literal_one() {
     let input = "{}";
-    let expected = Rc::new(Object::Hash(HashMap::new()));
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::new())));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
@@ -428,10 +516,10 @@ fn test_hash_literal_one() {
 #[test]
 fn test_hash_literal_two() {
     let input = "{1: 2, 3: 4}";
-    let expected = Rc::new(Object::Hash(HashMap::from([
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::from([
         (Hashable::Integer(1), Rc::new(Object::Integer(2))),
         (Hashable::Integer(3), Rc::new(Object::Integer(4))),
-    ])));
+    ]))));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
@@ -440,15 +528,24 @@ fn test_hash_literal_two() {
 #[test]
 fn test_hash_literal_three() {
     let input = "{1 + 1: 2 * 2, 4 - 3: 12 / 4}";
-    let expected = Rc::new(Object::Hash(HashMap::from([
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::from([
         (Hashable::Integer(2), Rc::new(Object::Integer(4))),
         (Hashable::Integer(1), Rc::new(Object::Integer(3))),
-    ])));
+    ]))));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_hash_literal_error_if_key_unhashable() {
+    let input = "{[1]: 2}";
+    let expected_error = VmError::UnhashableKey(String::from("array"));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
 #[test]
 fn test_index_expression_one() {
     let input = "[1, 2, 3][1]";
@@ -496,13 +593,36 @@ fn test_index_expression_five() {
 
 #[test]
 fn test_index_expression_six() {
-    let input = "[1, 2][-1]";
+    let input = "[1, 2][-3]";
     let expected = Rc::new(Object::Null);
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_negative_index() {
+    let tests = [
+        ("[1, 2, 3][-1]", Object::Integer(3)),
+        ("[1, 2, 3][-3]", Object::Integer(1)),
+        ("[][-1]", Object::Null),
+        ("[1, 2][-9223372036854775807 - 1]", Object::Null),
+        (
+            "let a = [1, 2, 3]; a[-1] = 30; a[-2] += 18; a",
+            Object::Array(RefCell::new(vec![
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Integer(20)),
+                Rc::new(Object::Integer(30)),
+            ])),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
 #[test]
 fn test_index_expression_seven() {
     let input = "{1: 2}[0]";
@@ -521,6 +641,15 @@ fn test_index_expression_eight() {
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_index_expression_error_if_not_indexable() {
+    let input = "5[0]";
+    let expected_error = VmError::IndexNotSupported;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
 #[test]
 fn test_index_expression_nine() {
     let input = "{1: 1, 2: 2}[1]";
@@ -614,6 +743,22 @@ a();
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_bare_return() {
+    let tests = [
+        ("let f = fn() { return; }; f()", Object::Null),
+        (
+            "let f = fn(x) { if (x) { return } 1 }; [f(true), f(false)]",
+            value!([null, 1]),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
 #[test]
 fn test_calling_first_class_function() {
     let input = "
@@ -902,6 +1047,378 @@ fn test_builtin_eight() {
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_builtin_print() {
+    let input = "print(\"a\"); print(\"b\");";
+    let output = capture_output(|| {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, None);
+    });
+    assert_eq!(output, "ab");
+}
+
+#[test]
+fn test_builtin_println() {
+    let input = "println(\"a\"); println(\"b\");";
+    let output = capture_output(|| {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, None);
+    });
+    assert_eq!(output, "a\nb\n");
+}
+
+#[test]
+fn test_builtin_inspect() {
+    let input = "inspect([fn(a, b) { a }, if (false) { 1 }]);";
+    let expected = Rc::new(Object::String(String::from("[<function(2 params)>, null]")));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_error_message() {
+    let input = "let e = error(\"oops\"); if (is_error(e)) { error_message(e) };";
+    let expected = Rc::new(Object::String(String::from("oops")));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_recover_calls_handler_on_error() {
+    let input =
+        "recover(fn() { error(\"oops\") }, fn(err) { \"recovered: \" + error_message(err) });";
+    let expected = Rc::new(Object::String(String::from("recovered: oops")));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_recover_returns_value_on_success() {
+    let input = "let x = 5; let f = fn() { let y = x * 2; y }; recover(f, fn(err) { 0 }) + 1;";
+    let expected = Rc::new(Object::Integer(11));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_recover_nested_inside_function() {
+    let input = "
+let inner = fn() { recover(fn() { error(\"inner\") }, fn(err) { err }) };
+let outer = fn(prefix) { recover(inner, fn(err) { prefix + error_message(err) }) };
+outer(\"outer caught \");
+";
+    let expected = Rc::new(Object::String(String::from("outer caught inner")));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_try_parse_int() {
+    let input = "[try_parse_int(\"\t42\n\"), try_parse_int(\"forty-two\")];";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Null),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_try_parse_float() {
+    let input = r#"[try_parse_float("\t2.5\n"), try_parse_float("3"), try_parse_float("two"), try_parse_float("inf")];"#;
+    let expected = Rc::new(value!([2.5, 3.0, null, null]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+
+    let (_, error) = compile_and_run("try_parse_float(true)");
+    assert_eq!(error, Some(VmError::IncompatibleTypes));
+}
+
+#[test]
+fn test_builtin_apply() {
+    let input = "
+let add = fn(a, b) { a + b };
+let args = push([3], 4);
+[apply(add, args), apply(len, [args]), apply(fn() { 1 }, [])];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(7)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(1)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_apply_error_if_wrong_number_of_args() {
+    let input = "apply(fn(a, b) { a + b }, [3, 4, 5]);";
+    let expected_error = VmError::WrongArguments;
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_builtin_partial() {
+    let input = "
+let add_three = fn(a, b, c) { a + b + c };
+let add_five = partial(fn(a, b) { a + b }, 5);
+[add_five(3), partial(push, [1])(2), partial(partial(add_three, 1), 2)(3), partial(add_five)(1)];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(8)),
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+        ]))),
+        Rc::new(Object::Integer(6)),
+        Rc::new(Object::Integer(6)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_partial_without_args_returns_function() {
+    let input = "partial(fn(a) { a * 2 });";
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert!(matches!(result.as_deref(), Some(Object::Closure(_))));
+}
+
+#[test]
+fn test_builtin_partial_error_if_too_many_args() {
+    let input = "partial(fn(a, b) { a + b }, 5)(3, 4);";
+    let expected_error = VmError::WrongArguments;
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_builtin_once_runs_function_once() {
+    let input = "
+let init = once(fn() { puts(\"initializing\"); 42 });
+[init(), init(), init()];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(42)),
+    ])));
+    let output = capture_output(|| {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None);
+        assert_eq!(result, Some(expected));
+    });
+    assert_eq!(output, "initializing\n");
+}
+
+#[test]
+fn test_builtin_once_with_argument_caches_first_result() {
+    let input = "
+let double = once(fn(x) { x * 2 });
+let f = fn() { double(5) };
+[double(1), f()];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(2)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_once_recursive_call_returns_null() {
+    let input = "let f = once(fn() { [1, f()] }); [f(), f()];";
+    let inner = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Null),
+    ])));
+    let expected = Rc::new(Object::Array(RefCell::new(vec![Rc::clone(&inner), inner])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_tap_returns_value_and_discards_result() {
+    let input = "
+let seen = [];
+let double = fn(x) { x * 2 };
+double(tap(5, fn(x) { seen = push(seen, x); 100 })) + len(seen);
+";
+    let expected = Rc::new(Object::Integer(11));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_swap() {
+    let input = "[swap([1, 2, 3], 0, -1), swap([1], -1, 0)];";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(3)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Integer(1)),
+        ]))),
+        Rc::new(Object::Array(RefCell::new(vec![Rc::new(Object::Integer(
+            1,
+        ))]))),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_swap_error_if_out_of_bounds() {
+    let input = "swap([1, 2, 3], 1, 3);";
+    let expected_error = VmError::IndexOutOfBounds;
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_builtin_insert() {
+    let input =
+        "[insert([1, 2, 4], 2, 3), insert([2], 0, 1), insert([1], 1, 2), insert([], 0, 1)];";
+    let to_array = |ints: &[i64]| {
+        Rc::new(Object::Array(RefCell::new(
+            ints.iter().map(|i| Rc::new(Object::Integer(*i))).collect(),
+        )))
+    };
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        to_array(&[1, 2, 3, 4]),
+        to_array(&[1, 2]),
+        to_array(&[1, 2]),
+        to_array(&[1]),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_insert_error_if_out_of_bounds() {
+    let input = "insert([1, 2, 3], 4, 0);";
+    let expected_error = VmError::IndexOutOfBounds;
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_builtin_remove() {
+    let input = "[remove([1, 2, 3], 1), remove([1, 2, 3], -1), remove([1], 0)];";
+    let to_array = |ints: &[i64]| {
+        Rc::new(Object::Array(RefCell::new(
+            ints.iter().map(|i| Rc::new(Object::Integer(*i))).collect(),
+        )))
+    };
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        to_array(&[1, 3]),
+        to_array(&[1, 2]),
+        to_array(&[]),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_remove_error_if_out_of_bounds() {
+    for input in ["remove([], 0);", "remove([1, 2], 2);"] {
+        let expected_error = VmError::IndexOutOfBounds;
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error));
+    }
+}
+
+#[test]
+fn test_builtin_deep_clone() {
+    let input = "deep_clone([1, [2, 3]]);";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Integer(3)),
+        ]))),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_hash_update() {
+    let input = "
+let count = fn(counts, word) { hash_update(counts, word, fn(n) { if (n) { n + 1 } else { 1 } }) };
+count(count(count({}, \"a\"), \"b\"), \"a\");
+";
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::from([
+        (
+            Hashable::String(String::from("a")),
+            Rc::new(Object::Integer(2)),
+        ),
+        (
+            Hashable::String(String::from("b")),
+            Rc::new(Object::Integer(1)),
+        ),
+    ]))));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_hash_update_error_if_not_hash() {
+    let input = "hash_update(1, 0, fn(v) { v });";
+    let expected_error = VmError::IncompatibleTypes;
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_builtin_bench() {
+    let input = "bench(fn(x) { x * 2 }, 2, fn() { 21 });";
+    let mut readings = [0, 3, 3, 4].map(Duration::from_millis).into_iter();
+    let previous = set_clock(Box::new(move || readings.next().unwrap()));
+    let (result, error) = compile_and_run(input);
+    let _ = set_clock(previous);
+
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::from(
+        [
+            ("iters", Object::Integer(2)),
+            ("total_ms", Object::Float(4.0)),
+            ("mean_ms", Object::Float(2.0)),
+            ("min_ms", Object::Float(1.0)),
+            ("max_ms", Object::Float(3.0)),
+        ]
+        .map(|(key, value)| (Hashable::String(key.to_string()), Rc::new(value))),
+    ))));
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_bench_error_if_not_callable() {
+    let input = "bench(1, 2);";
+    let expected_error = VmError::CallingNonFunction;
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
 #[test]
 fn test_builtin_nine() {
     let input = "first([1,2,3]);";
@@ -959,10 +1476,10 @@ fn test_builtin_fourteen() {
 #[test]
 fn test_builtin_fifteen() {
     let input = "rest([1,2,3]);";
-    let expected = Rc::new(Object::Array(vec![
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
         Rc::new(Object::Integer(2)),
         Rc::new(Object::Integer(3)),
-    ]));
+    ])));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
@@ -980,7 +1497,9 @@ fn test_builtin_sixteen() {
 #[test]
 fn test_builtin_seventeen() {
     let input = "push([], 1);";
-    let expected = Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))]));
+    let expected = Rc::new(Object::Array(RefCell::new(vec![Rc::new(Object::Integer(
+        1,
+    ))])));
     let (result, error) = compile_and_run(input);
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
@@ -1181,3 +1700,1344 @@ false || (1 < 0)
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
 }
+
+#[test]
+fn test_logical_and_short_circuits() {
+    // `len(1)` would be an error if it were evaluated
+    let input = "
+false && len(1)
+";
+    let expected = Rc::new(Object::Boolean(false));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_or_short_circuits() {
+    let input = "
+true || len(1)
+";
+    let expected = Rc::new(Object::Boolean(true));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_truthy_operands() {
+    let input = "
+[1 && 2, 0 || 3, 0 && 1, 1 || 0, 0 || 0]
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_and_binds_tighter_than_or() {
+    let (result, error) = compile_and_run("[true || false && false, false && true || true]");
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(true)),
+    ])));
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_evaluates_right_side_when_needed() {
+    let input = "
+false || len(1)
+";
+    let (_, error) = compile_and_run(input);
+    assert!(error.is_some());
+}
+
+#[test]
+fn test_while_loop() {
+    let input = "
+let i = 0;
+while (i < 3) {
+    let i = i + 1;
+};
+i;
+";
+    let expected = Rc::new(Object::Integer(3));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop_early_return() {
+    let input = "
+let f = fn() {
+    let i = 0;
+    while (true) {
+        i = i + 1;
+        if (i == 3) {
+            return i;
+        }
+    }
+};
+f();
+";
+    let expected = Rc::new(Object::Integer(3));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop_evaluates_to_null() {
+    let input = "while (false) { 1 }";
+    let expected = Rc::new(Object::Null);
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop() {
+    let input = "
+let sum = 0;
+for (x in [1, 2, 3]) {
+    let double = x * 2;
+    sum = sum + double;
+}
+sum;
+";
+    let expected = Rc::new(Object::Integer(12));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_evaluates_to_null() {
+    let input = "for (x in [1, 2]) { x }";
+    let expected = Rc::new(Object::Null);
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_early_return() {
+    let input = "
+let find = fn(xs) {
+    let i = 0;
+    for (x in xs) {
+        if (x > 2) {
+            return [x, i];
+        }
+        i = i + 1;
+    }
+    return -1;
+};
+[find([1, 5, 3]), find([])];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(5)),
+            Rc::new(Object::Integer(1)),
+        ]))),
+        Rc::new(Object::Integer(-1)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_nested_for_loops() {
+    let input = "
+let pairs = fn(xs, ys) {
+    let result = [];
+    for (x in xs) {
+        for (y in ys) {
+            result = push(result, x * y);
+        }
+    }
+    result
+};
+pairs([1, 2], [3, 4]);
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Integer(4)),
+        Rc::new(Object::Integer(6)),
+        Rc::new(Object::Integer(8)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_error_if_not_array() {
+    let input = "for (x in {}) { x }";
+    let expected_error = VmError::NotIterable(String::from("hash"));
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_while_break_and_continue() {
+    let input = "
+let r = [];
+let i = 0;
+while (true) {
+    i = i + 1;
+    if (i == 2) { continue; }
+    if (i > 4) { break; }
+    r = push(r, i);
+};
+[r, i];
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(3)),
+            Rc::new(Object::Integer(4)),
+        ]))),
+        Rc::new(Object::Integer(5)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_break_and_continue() {
+    let input = "
+let r = [];
+for (x in [1, 2, 3, 4, 5]) {
+    if (x == 2) { continue; }
+    if (x == 4) { break }
+    r = push(r, x);
+}
+r;
+";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(3)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_break_exits_innermost_loop() {
+    let input = "
+let pairs = fn() {
+    let r = [];
+    for (x in [1, 2, 3]) {
+        let y = 0;
+        while (true) {
+            y = y + 1;
+            if (y > 2) { break; }
+            if (x == 2) { continue; }
+            r = push(r, x * 10 + y);
+        }
+        if (x == 3) { break; }
+        r = push(r, 0);
+    }
+    r
+};
+pairs();
+";
+    let expected = Rc::new(Object::Array(RefCell::new(
+        [11, 12, 0, 0, 31, 32]
+            .iter()
+            .map(|i| Rc::new(Object::Integer(*i)))
+            .collect(),
+    )));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_continue_inside_expression_unwinds_stack() {
+    let input = "
+let n = 0;
+for (i in 0..5000) { n = n + [i, if (true) { continue; } else { 1 }][0]; }
+let m = 0;
+while (m < 5000) { m = m + 1; n = n + [m, if (true) { continue; } else { 1 }][0]; }
+n
+";
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(Rc::new(Object::Integer(0))));
+}
+
+#[test]
+fn test_break_inside_expression_unwinds_stack() {
+    let input = "
+let n = 0;
+for (i in 0..2) { for (j in [1]) { let t = [i, if (true) { break; } else { 1 }]; } n = n + 1; }
+while (true) { let t = [n, if (true) { break; } else { 1 }]; }
+n
+";
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(Rc::new(Object::Integer(2))));
+}
+
+#[test]
+fn test_assignment_closure_counter() {
+    let input = "
+let makeCounter = fn() { let count = 0; fn() { count = count + 1; count } };
+let counter = makeCounter();
+counter();
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(3));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_compound_assignment() {
+    let input = "
+let x = 10;
+let f = fn() { x -= 4; };
+f();
+x += 1 + 2;
+x *= 2;
+x /= 3;
+x;
+";
+    let expected = Rc::new(Object::Integer(6));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_conditional() {
+    let input = "let sign = fn(n) { n < 0 ? -1 : n == 0 ? 0 : 1 }; [sign(-5), sign(0), sign(5)]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(-1)),
+        Rc::new(Object::Integer(0)),
+        Rc::new(Object::Integer(1)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_bitwise_operators() {
+    let input = "[12 & 10, 12 | 10, 12 ^ 10, 1 << 2 + 3, -16 >> 2, 1 << 63 >> 63]";
+    let expected = Rc::new(Object::Array(RefCell::new(
+        [8, 14, 6, 32, -4, -1]
+            .into_iter()
+            .map(|n| Rc::new(Object::Integer(n)))
+            .collect(),
+    )));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_bitwise_error_if_not_integers() {
+    for input in ["1.0 & 1", "1 | 2.0", "true ^ false"] {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(VmError::IncompatibleTypes), "{input}");
+    }
+}
+
+#[test]
+fn test_vm_shift_error_if_amount_out_of_range() {
+    let tests = [("1 << 64", 64), ("1 >> -1", -1), ("1 << -100", -100)];
+    for (input, amount) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(VmError::InvalidShift(amount)));
+    }
+}
+
+#[test]
+fn test_vm_let_in_if_block_visible_after_block() {
+    let tests = [
+        ("fn() { if (true) { let x = 1; }; x }()", Object::Integer(1)),
+        ("if (true) { let y = 2; }; y", Object::Integer(2)),
+        ("if (true) { let y = 2; }", Object::Null),
+        ("if (false) { 1 } else { }", Object::Null),
+        ("fn() { if (true) { }; 5 }()", Object::Integer(5)),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_vm_power() {
+    let input = "[2 ** 3 ** 2, (2 ** 3) ** 2, 0 ** 0, -2 ** 3, 2.0 ** -1, 4 ** 0.5]";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(512)),
+        Rc::new(Object::Integer(64)),
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(-8)),
+        Rc::new(Object::Float(0.5)),
+        Rc::new(Object::Float(2.0)),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_power_errors() {
+    let tests = [
+        ("2 ** -1", VmError::NegativeExponent(-1)),
+        ("2 ** 63", VmError::IntegerOverflow),
+        ("true ** 2", VmError::IncompatibleTypes),
+    ];
+    for (input, expected) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_vm_reads_injected_global() {
+    // `global0 + 1` without a symbol table
+    let instructions = [
+        make(OpCode::GetGlobal, &[0]),
+        make(OpCode::Constant, &[0]),
+        make(OpCode::Add, &[]),
+        make(OpCode::Pop, &[]),
+    ];
+    let byte_code = ByteCode(
+        instructions.into_iter().flatten().collect(),
+        vec![Rc::new(Object::Integer(1))],
+    );
+    let mut vm = VirtualMachine::new(byte_code);
+    vm.set_global(0, Rc::new(Object::Integer(42)));
+    assert_eq!(vm.run(), Ok(Rc::new(Object::Integer(43))));
+}
+
+#[test]
+fn test_vm_get_global_after_run() {
+    let ast = Parser::parse_program("let a = 1; let b = [a, 2]; b").unwrap();
+    let byte_code = Compiler::new().compile(ast).unwrap();
+    let mut vm = VirtualMachine::new(byte_code);
+    assert_eq!(vm.get_global(1), Some(&Rc::new(Object::Null)));
+    vm.run().unwrap();
+
+    assert_eq!(vm.get_global(0), Some(&Rc::new(Object::Integer(1))));
+    assert_eq!(
+        vm.get_global(1),
+        Some(&Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+        ]))))
+    );
+    assert_eq!(vm.get_global(GLOBAL_SIZE as u32), None);
+}
+
+#[test]
+fn test_vm_null_literal() {
+    let input =
+        "let x = null; [x == null, x != null, 5 == null, null != \"a\", if (false) { 1 } == null]";
+    let expected = Rc::new(Object::Array(RefCell::new(
+        [true, false, false, true, true]
+            .into_iter()
+            .map(|b| Rc::new(Object::Boolean(b)))
+            .collect(),
+    )));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+
+    let (_, error) = compile_and_run("null + 1");
+    assert_eq!(error, Some(VmError::IncompatibleTypes));
+}
+
+// runs `input` and returns the VM, so that its globals can be called
+fn run_script(input: &str) -> VirtualMachine {
+    let ast = Parser::parse_program(input).unwrap();
+    let byte_code = Compiler::new().compile(ast).unwrap();
+    let mut vm = VirtualMachine::new(byte_code);
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn test_vm_call_function_from_rust() {
+    let input = "let double = fn(x) { x * 2 };
+let makeCounter = fn() { let n = 0; fn() { n = n + 1; n } };
+let counter = makeCounter();
+0";
+    let mut vm = run_script(input);
+
+    let double = Rc::clone(vm.get_global(0).unwrap());
+    let result = vm.call_function(&double, &[Rc::new(Object::Integer(5))]);
+    assert_eq!(result, Ok(Rc::new(Object::Integer(10))));
+
+    // closures keep their state between calls
+    let counter = Rc::clone(vm.get_global(2).unwrap());
+    assert_eq!(
+        vm.call_function(&counter, &[]),
+        Ok(Rc::new(Object::Integer(1)))
+    );
+    assert_eq!(
+        vm.call_function(&counter, &[]),
+        Ok(Rc::new(Object::Integer(2)))
+    );
+
+    let len = vm.call_function(
+        &Builtin::get("len").unwrap(),
+        &[Rc::new(Object::String(String::from("abc")))],
+    );
+    assert_eq!(len, Ok(Rc::new(Object::Integer(3))));
+}
+
+#[test]
+fn test_vm_call_function_error_leaves_vm_usable() {
+    let input = "let fail = fn(x) { let y = x + 1; y + true };
+let double = fn(x) { x * 2 };
+0";
+    let mut vm = run_script(input);
+    let fail = Rc::clone(vm.get_global(0).unwrap());
+    let double = Rc::clone(vm.get_global(1).unwrap());
+
+    let result = vm.call_function(&fail, &[Rc::new(Object::Integer(1))]);
+    assert_eq!(result, Err(VmError::IncompatibleTypes));
+    assert_eq!(vm.call_function(&fail, &[]), Err(VmError::WrongArguments));
+    assert_eq!(
+        vm.call_function(&Rc::new(Object::Integer(1)), &[]),
+        Err(VmError::CallingNonFunction)
+    );
+
+    let result = vm.call_function(&double, &[Rc::new(Object::Integer(21))]);
+    assert_eq!(result, Ok(Rc::new(Object::Integer(42))));
+}
+
+#[test]
+fn test_builtin_json() {
+    let input = r#"from_json(to_json({"a": [1, 2.5, "x"]}));"#;
+    let expected = Rc::new(Object::Hash(RefCell::new(HashMap::from([(
+        Hashable::String(String::from("a")),
+        Rc::new(Object::Array(RefCell::new(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Float(2.5)),
+            Rc::new(Object::String(String::from("x"))),
+        ]))),
+    )]))));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_builtin_to_json_error_if_function() {
+    let input = "to_json(fn() { 1 });";
+    let expected_error = VmError::NotSerializable(String::from("function"));
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+}
+
+#[test]
+fn test_string_error_if_unsupported_operator() {
+    let tests = [
+        ("\"foo\" - \"bar\"", "-", "string", "string"),
+        ("\"count: \" + 5", "+", "string", "integer"),
+        ("\"a\" << 1", "<<", "string", "integer"),
+        // compiled as `>` with the operands swapped
+        ("\"a\" < 1", ">", "integer", "string"),
+    ];
+    for (input, operator, left, right) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(
+            error,
+            Some(VmError::UnsupportedOperands(operator, left, right)),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_string_concatenation_in_loop() {
+    let input = "let s = \"\"; let i = 0; while (i < 3) { s += \"ab\"; i += 1; }; s";
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(
+        result,
+        Some(Rc::new(Object::String(String::from("ababab"))))
+    );
+}
+
+#[test]
+fn test_builtin_http_get_error_if_unreachable() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    drop(listener);
+    let input = format!("http_timeout(1); is_error(http_get(\"{url}\"));");
+    let (result, error) = compile_and_run(&input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(Rc::new(Object::Boolean(true))));
+}
+
+#[test]
+fn test_string_comparison() {
+    let tests = [
+        ("\"abc\" < \"abd\"", true),
+        ("\"ab\" < \"abc\"", true),
+        ("\"abc\" > \"ab\"", true),
+        ("\"\" < \"a\"", true),
+        ("\"\" <= \"\"", true),
+        ("\"b\" >= \"abc\"", true),
+        ("\"abc\" <= \"ab\"", false),
+        ("\"abc\" == \"abc\"", true),
+        ("\"\" == \"\"", true),
+        ("\"ab\" != \"abc\"", true),
+        ("let a = \"mon\"; a + \"key\" == \"monkey\"", true),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(Object::Boolean(expected))), "{input}");
+    }
+}
+
+#[test]
+fn test_string_comparison_error_if_mixed_types() {
+    let (_, error) = compile_and_run("\"1\" > 2");
+    assert_eq!(
+        error,
+        Some(VmError::UnsupportedOperands(">", "string", "integer"))
+    );
+}
+
+#[test]
+fn test_string_repetition() {
+    let tests = [
+        ("\"-\" * 5", "-----"),
+        ("3 * \"ab\"", "ababab"),
+        ("\"abc\" * 0", ""),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        let expected = Rc::new(Object::String(String::from(expected)));
+        assert_eq!(result, Some(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_string_repetition_errors() {
+    let tests = [
+        ("\"a\" * -3", VmError::InvalidRepeatCount(-3)),
+        (
+            "\"a\" * 10_000_000_000",
+            VmError::InvalidRepeatCount(10_000_000_000),
+        ),
+        (
+            "true * \"a\"",
+            VmError::UnsupportedOperands("*", "boolean", "string"),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error), "{input}");
+    }
+}
+
+#[test]
+fn test_array_concatenation() {
+    let input = "[1, 2] + [3, [4]];";
+    let expected = Rc::new(Object::Array(RefCell::new(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Array(RefCell::new(vec![Rc::new(Object::Integer(
+            4,
+        ))]))),
+    ])));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+
+    let (_, error) = compile_and_run("[1] + 2;");
+    assert_eq!(error, Some(VmError::UsePushToAppend("integer")));
+}
+
+#[test]
+fn test_deep_equality() {
+    let tests = [
+        ("[1, 2] == [1, 2]", true),
+        ("[1, 2] == [1, 2, 3]", false),
+        ("[1, 2] != [1, 3]", true),
+        ("[[1, [2]]] == [[1, [2]]]", true),
+        ("[[1, [2]]] == [[1, [3]]]", false),
+        ("[1] == [1.0]", true),
+        ("{\"a\": [1]} == {\"a\": [1]}", true),
+        ("{\"a\": [1]} != {\"a\": [2]}", true),
+        ("let f = fn() { 1 }; [f] == [f]", true),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(Object::Boolean(expected))), "{input}");
+    }
+}
+
+#[test]
+fn test_index_assignment_errors() {
+    let tests = [
+        ("let a = [1, 2]; a[2] = 0;", VmError::IndexOutOfBounds),
+        ("let a = [1, 2]; a[-3] = 0;", VmError::IndexOutOfBounds),
+        ("let s = \"ab\"; s[0] = \"c\";", VmError::IndexNotSupported),
+        (
+            "let h = {}; h[fn() { 1 }] = 0;",
+            VmError::UnhashableKey(String::from("function")),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error), "{input}");
+    }
+}
+
+#[test]
+fn test_slice() {
+    let tests = [
+        ("[1, 2, 3, 4][1:3]", value!([2, 3])),
+        ("[1, 2, 3, 4][:-1]", value!([1, 2, 3])),
+        ("[1, 2, 3, 4][-2:]", value!([3, 4])),
+        ("[1, 2, 3, 4][3:1]", value!([])),
+        ("\"hello\"[1:3]", Object::String(String::from("el"))),
+        ("let a = [1, 2]; let b = a[:]; b[0] = 5; a", value!([1, 2])),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+
+    let (_, error) = compile_and_run("5[1:2]");
+    assert_eq!(error, Some(VmError::IncompatibleTypes));
+}
+
+#[test]
+fn test_range() {
+    let tests = [
+        ("1..5", value!([1, 2, 3, 4])),
+        ("5..1", value!([])),
+        ("len(1..1)", Object::Integer(0)),
+        (
+            "let sum = 0; for (i in 1..11) { sum += i; } sum",
+            Object::Integer(55),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+
+    let tests = [
+        (
+            "true..2",
+            VmError::UnsupportedOperands("..", "boolean", "integer"),
+        ),
+        ("0..4294967296", VmError::RangeTooLong(0, 4294967296)),
+    ];
+    for (input, expected_error) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error), "{input}");
+    }
+}
+
+#[test]
+fn test_match() {
+    let tests = [
+        (r#"match (2) { 1 => "one", 2 => "two", _ => "many" }"#, value!("two")),
+        (r#"match (7) { 1 => "one", 2 => "two", _ => "many" }"#, value!("many")),
+        ("match (7) { 1 => 10 }", Object::Null),
+        ("match (1) { 1 => {} }", Object::Null),
+        ("match (2) { 1 => 0, _ => {} }", Object::Null),
+        ("match (1) { 1 => { let y = 2; y * 3 } }", Object::Integer(6)),
+        ("match (1.0) { 1 => true }", Object::Boolean(true)),
+        (r#"match ("1") { 1 => true, _ => false }"#, Object::Boolean(false)),
+        ("match ([1, [2]]) { [1, [2]] => true }", Object::Boolean(true)),
+        ("let x = match (1) { 1 => 5 } * 2; x", Object::Integer(10)),
+        (
+            "let f = fn(n) { match (n) { 0 => { return -1; } } n }; [f(0), f(4)]",
+            value!([(-1), 4]),
+        ),
+        (
+            "let s = 0; for (i in 0..5) { match (i) { 1 => { continue; }, 3 => { break; } } s += i; } s",
+            Object::Integer(2),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_function_statement() {
+    let tests = [
+        (
+            "fn fact(n) { if (n < 2) { 1 } else { n * fact(n - 1) } } fact(5)",
+            Object::Integer(120),
+        ),
+        ("fn(x) { x * 3 }(2)", Object::Integer(6)),
+        ("fn outer() { fn inner(n) { if (n == 0) { 0 } else { 1 + inner(n - 1) } } inner(3) } outer()", Object::Integer(3)),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_string_literal_is_not_looked_up() {
+    let tests = [
+        (r#"let x = "hello"; x"#, "hello"),
+        (r#"let hello = 1; "hello""#, "hello"),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(value!(expected))), "{input}");
+    }
+}
+
+#[test]
+fn test_default_parameters() {
+    let tests = [
+        (
+            r#"let greet = fn(name, greeting = "hello") { greeting + " " + name }; [greet("sam"), greet("sam", "hi")]"#,
+            value!(["hello sam", "hi sam"]),
+        ),
+        (
+            "let f = fn(a = 1, b = 2) { [a, b] }; [f(), f(5), f(5, 6)]",
+            value!([[1, 2], [5, 2], [5, 6]]),
+        ),
+        (
+            "let x = 10; let f = fn(a, b = a + x) { b }; f(1)",
+            Object::Integer(11),
+        ),
+        (
+            "let f = fn(a = []) { push(a, 1) }; [f(), f()]",
+            value!([[1], [1]]),
+        ),
+        (
+            "let f = fn(a, b = fn() { a }) { a = 2; b() }; f(1)",
+            Object::Integer(2),
+        ),
+        (
+            "fn count(n, acc = 0) { if (n == 0) { acc } else { count(n - 1, acc + 1) } } count(3)",
+            Object::Integer(3),
+        ),
+        (
+            "[apply(fn(x, y = 10) { x + y }, [1]), apply(fn(x, y = 10) { x + y }, [1, 2])]",
+            value!([11, 3]),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+
+    for input in [
+        "let f = fn(a, b = 2) { a }; f()",
+        "let f = fn(a, b = 2) { a }; f(1, 2, 3)",
+        "apply(fn(x, y, z = 1) { x }, [1])",
+    ] {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(VmError::WrongArguments), "{input}");
+    }
+}
+
+#[test]
+fn test_spread_arguments() {
+    let tests = [
+        (
+            "let add = fn(a, b, c) { a + b + c }; add(...[1, 2, 3])",
+            Object::Integer(6),
+        ),
+        (
+            "let add = fn(a, b, c) { a + b + c }; add(1, ...[2], 3)",
+            Object::Integer(6),
+        ),
+        (
+            "let add = fn(a, b, c) { a + b + c }; add(...[1], ...[], ...[2, 3])",
+            Object::Integer(6),
+        ),
+        (
+            "let f = fn(a, b = 10) { a + b }; [f(...[1]), f(...[1, 2])]",
+            value!([11, 3]),
+        ),
+        ("let xs = [1, 2]; len(...[xs])", Object::Integer(2)),
+        ("let f = fn() { 0 }; f(...[])", Object::Integer(0)),
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(...[n - 1]) } }; f(3)",
+            Object::Integer(3),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+
+    let tests = [
+        (
+            "let f = fn(a) { a }; f(...1)",
+            VmError::CannotSpread("integer"),
+        ),
+        (
+            r#"let f = fn(a) { a }; f(..."ab")"#,
+            VmError::CannotSpread("string"),
+        ),
+        ("let f = fn(a) { a }; f(...[1, 2])", VmError::WrongArguments),
+        ("let f = fn(a, b) { a }; f(...[1])", VmError::WrongArguments),
+    ];
+    for (input, expected_error) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error), "{input}");
+    }
+}
+
+#[test]
+fn test_named_arguments() {
+    let tests = [
+        ("fn(a, b) { a - b }(b: 3, a: 10)", Object::Integer(7)),
+        ("fn(a, b, c) { [a, b, c] }(1, c: 3, b: 2)", value!([1, 2, 3])),
+        ("fn(a, b) { [a, b] }(...[1], b: 2)", value!([1, 2])),
+        (
+            "fn(a, b = 2, c = 3) { [a, b, c] }(1, b: 4)",
+            value!([1, 4, 3]),
+        ),
+        ("fn(a, b = a * 2) { [a, b] }(a: 5)", value!([5, 10])),
+        (
+            "let f = fn(n, acc) { if (n == 0) { acc } else { f(acc: acc + n, n: n - 1) } }; f(3, 0)",
+            Object::Integer(6),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+
+    let tests = [
+        (
+            "fn(a) { a }(b: 1)",
+            VmError::UnknownArgument(String::from("b")),
+        ),
+        (
+            "fn(a, b) { a }(1, a: 2)",
+            VmError::DuplicateArgument(String::from("a")),
+        ),
+        (
+            "fn(a, b) { a }(a: 1, a: 2)",
+            VmError::DuplicateArgument(String::from("a")),
+        ),
+        (
+            "fn(a, b) { a }(b: 1)",
+            VmError::MissingArgument(String::from("a")),
+        ),
+        // only the parameters at the end can be left out
+        (
+            "fn(a, b = 2, c = 3) { a }(1, c: 4)",
+            VmError::MissingArgument(String::from("b")),
+        ),
+        (
+            "len(array: [])",
+            VmError::UnknownArgument(String::from("array")),
+        ),
+    ];
+    for (input, expected_error) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error), "{input}");
+    }
+}
+
+#[test]
+fn test_recursive_closure_shadowing() {
+    let tests = [
+        (
+            "let outer = fn() { let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5) }; outer()",
+            Object::Integer(120),
+        ),
+        (
+            "let f = fn(n) { if (n == 0) { \"outer\" } else { f(n - 1) } };
+             let g = fn() { let f = fn(n) { if (n == 0) { \"inner\" } else { f(n - 1) } }; f(2) };
+             [g(), f(2)]",
+            value!(["inner", "outer"]),
+        ),
+        // a function keeps calling itself after its name is rebound
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; let g = f; let f = fn(n) { 999 }; g(3)",
+            Object::Integer(0),
+        ),
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { let f = fn(m) { 100 + m }; f(n) } }; f(1)",
+            Object::Integer(101),
+        ),
+        ("let f = fn(f) { f }; f(5)", Object::Integer(5)),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_stack_is_balanced_after_run() {
+    let tests = [
+        "1; 2; 3",
+        "let x = 1; x = x + 1; let y = [x, 2]; y[0] = 3; y[1] += 1; y",
+        "let f = fn(a, b = 2) { let c = a + b; c }; f(1); f(...[1, 2])",
+        "let i = 0; while (i < 5) { i = i + 1; if (i == 2) { continue; } if (i == 4) { break; } }; i",
+        "let t = 0; for (x in 0..10) { if (x > 5) { continue; } t += x; }; t",
+        "let t = 0; for (x in 0..10) { t += [x, if (x > 5) { continue; } else { x }][1]; }; t",
+        "for (x in 0..2) { for (y in [1]) { let z = [x, if (true) { break; } else { 1 }]; } }",
+        "match (2) { 1 => \"one\", 2 => { \"two\" }, _ => \"many\" }",
+        "let h = {\"a\": 1}; h[\"b\"] = 2; h[\"a\"] + h[\"b\"]",
+        "let counter = fn() { let n = 0; fn() { n = n + 1; n } }(); counter(); counter()",
+        "let fact = fn(n) { if (n < 2) { return 1; } n * fact(n - 1) }; fact(5)",
+        "[1, 2, 3][1:] + [true ? 1 : 2]",
+    ];
+    for input in tests {
+        let program = Parser::parse_program(input).unwrap();
+        let byte_code = Compiler::new().compile(program).unwrap();
+        let mut vm = VirtualMachine::new(byte_code);
+        let result = vm.run();
+        assert!(result.is_ok(), "{input}: {result:?}");
+        assert_eq!(vm.dump_stack(), vec![], "{input}");
+    }
+}
+
+#[test]
+fn test_dump_globals() {
+    let program = Parser::parse_program("let a = 1; let b = [a]; b").unwrap();
+    let byte_code = Compiler::new().compile(program).unwrap();
+    let mut vm = VirtualMachine::new(byte_code);
+    vm.run().unwrap();
+
+    let globals = vm.dump_globals();
+    assert_eq!(globals.len(), GLOBAL_SIZE);
+    assert_eq!(globals[0], Rc::new(Object::Integer(1)));
+    assert_eq!(globals[1], Rc::new(value!([1])));
+    assert_eq!(globals[2], Rc::new(Object::Null));
+}
+
+#[test]
+fn test_const() {
+    let tests = [
+        ("const PI = 3; PI * 2", Object::Integer(6)),
+        (
+            "const f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(3)",
+            Object::Integer(0),
+        ),
+        (
+            "const PI = 3; let f = fn() { let PI = 4; PI = 5; PI }; [f(), PI]",
+            value!([5, 3]),
+        ),
+        (
+            "const n = 1; let f = fn(n) { n = 2; n }; [f(0), n]",
+            value!([2, 1]),
+        ),
+        ("const x = 1; let x = 2; x = 3; x", Object::Integer(3)),
+        ("const xs = [1]; xs[0] = 2; xs", value!([2])),
+        (
+            "let f = fn() { const a = 1; let g = fn() { let a = 2; a = 3; a }; [g(), a] }; f()",
+            value!([3, 1]),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_let_destructure() {
+    let tests = [
+        ("let [a, b, c] = [1, 2, 3]; a + b + c", Object::Integer(6)),
+        ("let [a, b] = [1, 2, 3]; [a, b]", value!([1, 2])),
+        ("let [a, b, ...rest] = [1, 2]; rest", value!([])),
+        ("let [] = [1]; 1", Object::Integer(1)),
+        (
+            "let a = 1; let b = 2; let [a, b] = [b, a]; [a, b]",
+            value!([2, 1]),
+        ),
+        (
+            "let f = fn(xs) { let [x, ...rest] = xs; [x, len(rest)] }; f([1, 2, 3])",
+            value!([1, 2]),
+        ),
+        (
+            "let f = fn(xs) { let [a, b] = xs; fn() { a - b } }; f([3, 1])()",
+            Object::Integer(2),
+        ),
+        (
+            "if (true) { let [a, b] = [1, 2]; a + b }",
+            Object::Integer(3),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(expected)), "{input}");
+    }
+
+    let tests = [
+        ("let [a, b] = [1];", VmError::TooFewElements(2, 1)),
+        ("let [a, ...rest] = [];", VmError::TooFewElements(1, 0)),
+        ("let [a] = 1;", VmError::CannotDestructure("integer")),
+        (r#"let [a] = "a";"#, VmError::CannotDestructure("string")),
+    ];
+    for (input, expected_error) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected_error), "{input}");
+    }
+}
+
+#[test]
+fn test_null_is_shared() {
+    let input = "let a = if (false) { 1 }; let b = [][0]; let c = fn() { let x = 1; }(); a";
+    let program = Parser::parse_program(input).unwrap();
+    let byte_code = Compiler::new().compile(program).unwrap();
+    let mut vm = VirtualMachine::new(byte_code);
+    vm.run().unwrap();
+
+    // each null, whether made by `OpNull`, a failed index or a function
+    // without a value, is the same one as an unbound global
+    let globals = vm.dump_globals();
+    for null in &globals[..3] {
+        assert_eq!(**null, Object::Null);
+        assert!(Rc::ptr_eq(null, &globals[3]));
+    }
+}
+
+#[test]
+fn test_string_template() {
+    let tests = [
+        (
+            r#"let name = "sam"; let age = 41; "hello {name}, you are {age + 1}""#,
+            "hello sam, you are 42",
+        ),
+        (r#""{[1, 2]} {null} {1.5} {true}""#, "[1, 2] null 1.5 true"),
+        (r#"let name = "sam"; "a {"b {name}"} c""#, "a b sam c"),
+        (r#""{len("}")} {{x}}""#, "1 {x}"),
+        (
+            r#"let f = fn(n) { "n is {if (n > 0) { "positive" } else { "not" }}" }; f(1)"#,
+            "n is positive",
+        ),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(
+            result,
+            Some(Rc::new(Object::String(String::from(expected)))),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_vm_import() {
+    let path = write_module(
+        "vm_import_bindings.mk",
+        "export let answer = 42; export let double = fn(x) { x * 2 };",
+    );
+    let (result, error) = compile_and_run(&format!("import {path:?}; double(answer)"));
+    assert_eq!(error, None);
+    assert_eq!(result, Some(Rc::new(Object::Integer(84))));
+}
+
+#[test]
+fn test_vm_import_runs_module_once() {
+    let path = write_module(
+        "vm_import_once.mk",
+        r#"puts("loading"); let count = [0]; export let bump = fn() { count[0] += 1; count[0] };"#,
+    );
+    // the import inside the function binds locals, but still shares the state
+    let input =
+        format!("import {path:?}; bump(); let f = fn() {{ import {path:?}; bump() }}; f(); f()");
+    let mut result = None;
+    let output = capture_output(|| result = compile_and_run(&input).0);
+    assert_eq!(output, "loading\n");
+    assert_eq!(result, Some(Rc::new(Object::Integer(3))));
+}
+
+#[test]
+fn test_vm_import_relative_to_importer() {
+    write_module("vm_relative/lib/inner.mk", "export let inner = 2;");
+    write_module(
+        "vm_relative/lib/outer.mk",
+        r#"import "inner.mk"; export let outer = inner * 10;"#,
+    );
+    let path = write_module(
+        "vm_relative/main.mk",
+        r#"import "lib/outer.mk"; export let total = outer + 1;"#,
+    );
+    let (result, error) = compile_and_run(&format!("import {path:?}; total"));
+    assert_eq!(error, None);
+    assert_eq!(result, Some(Rc::new(Object::Integer(21))));
+}
+
+#[test]
+fn test_vm_import_only_sees_exports() {
+    let path = write_module(
+        "vm_import_exports.mk",
+        "
+export let area = fn(r) { pi * square(r) };
+let pi = 3;
+let square = fn(x) { x * x };
+export square;
+let x = 7;
+",
+    );
+    let tests = [
+        (format!("import {path:?}; area(2)"), 12),
+        (format!("import {path:?}; square(5)"), 25),
+        // the module's `x` has a global slot of its own
+        (format!("let x = 1; import {path:?}; x"), 1),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(&input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(Object::Integer(expected))), "{input}");
+    }
+
+    let program = Parser::parse_program(&format!("import {path:?}; pi")).unwrap();
+    let error = Compiler::new().compile(program).err();
+    assert_eq!(error, Some(CompilerError::UndefinedVariable));
+}
+
+#[test]
+fn test_vm_import_errors() {
+    let canonical = |path: &str| {
+        std::fs::canonicalize(path)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let first = write_module("vm_circular_first.mk", r#"import "vm_circular_second.mk";"#);
+    let second = write_module(
+        "vm_circular_second.mk",
+        r#"export let x = 1; import "vm_circular_first.mk";"#,
+    );
+    let importer = write_module("vm_import_missing.mk", r#"import "missing.mk";"#);
+    let (first, second) = (canonical(&first), canonical(&second));
+    let tests = [
+        (
+            first.clone(),
+            CompilerError::CircularImport(format!("{first} -> {second} -> {first}")),
+        ),
+        (
+            importer.clone(),
+            CompilerError::ImportFailed(format!("missing.mk (imported from {importer}): NotFound")),
+        ),
+    ];
+    for (path, expected_error) in tests {
+        let program = Parser::parse_program(&format!("import {path:?};")).unwrap();
+        let error = Compiler::new().compile(program).err();
+        assert_eq!(error, Some(expected_error), "{path}");
+    }
+
+    // a module that fails does so when it runs
+    let failing = write_module("vm_import_failing.mk", "let x = 1 + true;");
+    let (_, error) = compile_and_run(&format!("import {failing:?};"));
+    assert_eq!(error, Some(VmError::IncompatibleTypes));
+}
+
+// runs `input` while profiling, with a clock that advances by 1ms on every reading
+fn profile_run(input: &str) -> HashMap<String, (u64, Duration)> {
+    with_clock(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], || {
+        profile::start();
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, None);
+        profile::finish().unwrap().functions
+    })
+}
+
+#[test]
+fn test_profile_counts_calls_and_time() {
+    let input = "let f = fn() { 1 }; let g = fn() { f() + f() }; g()";
+    // g is entered at 0 and left at 5, each call of f takes 1ms
+    let expected = HashMap::from([
+        (String::from("g"), (1, Duration::from_millis(5))),
+        (String::from("f"), (2, Duration::from_millis(2))),
+    ]);
+    assert_eq!(profile_run(input), expected);
+}
+
+#[test]
+fn test_profile_does_not_count_recursive_time_twice() {
+    let input = "let r = fn(n) { if (n > 0) { r(n - 1) } else { 0 } }; r(2)";
+    let expected = HashMap::from([(String::from("r"), (3, Duration::from_millis(5)))]);
+    assert_eq!(profile_run(input), expected);
+}
+
+#[test]
+fn test_profile_names_anonymous_functions_by_constant() {
+    // builtins calling back into the VM are profiled too; the same source
+    // compiles to a single constant, so is counted as a single function
+    let input = "apply(fn(x) { x * 2 }, [1]); apply(fn(x) { x * 2 }, [2]); fn() { 1 }()";
+    let expected = HashMap::from([
+        (
+            String::from("<anonymous #1>"),
+            (2, Duration::from_millis(2)),
+        ),
+        (
+            String::from("<anonymous #5>"),
+            (1, Duration::from_millis(1)),
+        ),
+    ]);
+    assert_eq!(profile_run(input), expected);
+}
+
+#[test]
+fn test_profile_report_sorted_by_total_time() {
+    let input = "let f = fn() { 1 }; let g = fn() { f() + f() }; let h = fn() { 1 }; g(); h()";
+    let report = with_clock(&[0, 1, 2, 3, 4, 5, 10, 12], || {
+        profile::start();
+        compile_and_run(input);
+        profile::finish().unwrap().report()
+    });
+    let expected = "   calls     total_ms  function
+       1        5.000  g
+       2        2.000  f
+       1        2.000  h
+";
+    assert_eq!(report, expected);
+}
+
+#[test]
+fn test_profile_off_by_default() {
+    assert_eq!(profile::finish(), None);
+    compile_and_run("let f = fn() { 1 }; f()");
+    assert_eq!(profile::finish(), None);
+}